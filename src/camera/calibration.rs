@@ -0,0 +1,187 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+use anyhow::{anyhow, Context as _};
+use serde::Deserialize;
+
+use std::path::Path;
+
+use crate::image_buffer::ThermalImage;
+
+/// The contents of a TOML calibration file. Each value is a list of rows of pixels.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CalibrationFile {
+    offset: Vec<Vec<f32>>,
+
+    #[serde(default)]
+    gain: Option<Vec<Vec<f32>>>,
+}
+
+/// A per-pixel correction applied to each camera image.
+///
+/// Each pixel is corrected as `gain * temperature + offset`. Like [`DeadPixels`], this is applied
+/// to the image as it comes from the camera, before any rotation or flipping.
+///
+/// [`DeadPixels`]: super::DeadPixels
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Calibration {
+    width: u32,
+    height: u32,
+    offset: Vec<f32>,
+    gain: Vec<f32>,
+}
+
+impl Calibration {
+    /// Load a calibration map from a file.
+    ///
+    /// Files with a `toml` extension have an `offset` and an optional `gain` key, each a list of
+    /// rows. Everything else is treated as CSV with a row of offsets on each line.
+    pub(crate) fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Unable to read calibration file {}", path.display()))?;
+        let calibration = match path.extension().and_then(|s| s.to_str()) {
+            Some("toml") => Self::from_toml(&contents),
+            _ => Self::from_csv(&contents),
+        };
+        calibration.with_context(|| format!("Invalid calibration file {}", path.display()))
+    }
+
+    fn from_toml(contents: &str) -> anyhow::Result<Self> {
+        let file: CalibrationFile = toml::from_str(contents)?;
+        let (width, height, offset) = flatten_rows(file.offset)?;
+        let gain = match file.gain {
+            Some(gain) => {
+                let (gain_width, gain_height, gain) = flatten_rows(gain)?;
+                if (gain_width, gain_height) != (width, height) {
+                    return Err(anyhow!(
+                        "The gain map is {}x{}, but the offset map is {}x{}",
+                        gain_width,
+                        gain_height,
+                        width,
+                        height
+                    ));
+                }
+                gain
+            }
+            None => vec![1.0; offset.len()],
+        };
+        Ok(Self {
+            width,
+            height,
+            offset,
+            gain,
+        })
+    }
+
+    fn from_csv(contents: &str) -> anyhow::Result<Self> {
+        let rows = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                line.split(',')
+                    .map(|value| value.trim().parse::<f32>())
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let (width, height, offset) = flatten_rows(rows)?;
+        Ok(Self {
+            width,
+            height,
+            gain: vec![1.0; offset.len()],
+            offset,
+        })
+    }
+
+    pub(crate) fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Correct the temperatures in `image`.
+    pub(crate) fn apply(&self, image: &mut ThermalImage) -> anyhow::Result<()> {
+        if image.dimensions() != self.dimensions() {
+            return Err(anyhow!(
+                "The calibration map is {}x{}, but the camera image is {}x{}",
+                self.width,
+                self.height,
+                image.width(),
+                image.height()
+            ));
+        }
+        for ((pixel, gain), offset) in image.iter_mut().zip(&self.gain).zip(&self.offset) {
+            *pixel = gain * *pixel + offset;
+        }
+        Ok(())
+    }
+}
+
+/// Flatten a list of rows into a single list, returning the width and height as well.
+fn flatten_rows(rows: Vec<Vec<f32>>) -> anyhow::Result<(u32, u32, Vec<f32>)> {
+    let width = rows.first().map_or(0, Vec::len);
+    if width == 0 {
+        return Err(anyhow!("The calibration map is empty"));
+    }
+    if let Some(row) = rows.iter().position(|row| row.len() != width) {
+        return Err(anyhow!(
+            "Row {} of the calibration map has {} values instead of {}",
+            row + 1,
+            rows[row].len(),
+            width
+        ));
+    }
+    let height = rows.len();
+    Ok((
+        width as u32,
+        height as u32,
+        rows.into_iter().flatten().collect(),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use image::ImageBuffer;
+
+    use super::Calibration;
+
+    #[test]
+    fn csv() {
+        let calibration = Calibration::from_csv("# top row\n0.0, -1.5\n\n1,2\n").unwrap();
+        assert_eq!(calibration.dimensions(), (2, 2));
+        let mut image = ImageBuffer::from_raw(2, 2, vec![20.0, 21.5, 22.0, 23.0]).unwrap();
+        calibration.apply(&mut image).unwrap();
+        assert_eq!(image.into_raw(), vec![20.0, 20.0, 23.0, 25.0]);
+        assert!(Calibration::from_csv("1,2\n3\n").is_err());
+        assert!(Calibration::from_csv("1,foo\n").is_err());
+        assert!(Calibration::from_csv("").is_err());
+    }
+
+    #[test]
+    fn toml_gain() {
+        let source = r#"
+        offset = [[1.0, 0.0, -1.0]]
+        gain = [[2.0, 1.0, 0.5]]
+        "#;
+        let calibration = Calibration::from_toml(source).unwrap();
+        assert_eq!(calibration.dimensions(), (3, 1));
+        let mut image = ImageBuffer::from_raw(3, 1, vec![10.0, 10.0, 10.0]).unwrap();
+        calibration.apply(&mut image).unwrap();
+        assert_eq!(image.into_raw(), vec![21.0, 10.0, 4.0]);
+        // Gain defaults to 1
+        let calibration = Calibration::from_toml("offset = [[1.0], [2.0]]").unwrap();
+        let mut image = ImageBuffer::from_raw(1, 2, vec![10.0, 10.0]).unwrap();
+        calibration.apply(&mut image).unwrap();
+        assert_eq!(image.into_raw(), vec![11.0, 12.0]);
+        // The gain and offset have to be the same size
+        let source = r#"
+        offset = [[1.0, 0.0]]
+        gain = [[2.0, 1.0, 0.5]]
+        "#;
+        assert!(Calibration::from_toml(source).is_err());
+    }
+
+    #[test]
+    fn size_mismatch() {
+        let calibration = Calibration::from_csv("0,0\n0,0\n").unwrap();
+        let mut image = ImageBuffer::from_raw(2, 1, vec![20.0, 20.0]).unwrap();
+        assert!(calibration.apply(&mut image).is_err());
+    }
+}
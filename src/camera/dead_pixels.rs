@@ -0,0 +1,75 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+use crate::image_buffer::ThermalImage;
+
+/// Pixels of a camera that do not report useful temperatures.
+///
+/// Each dead pixel is replaced with the average of its neighbors (ignoring any neighbors that are
+/// also dead). Coordinates are `(x, y)` in the image as it comes from the camera, before any
+/// rotation or flipping.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct DeadPixels(Vec<(u32, u32)>);
+
+impl DeadPixels {
+    pub(crate) fn new(pixels: Vec<(u32, u32)>) -> Self {
+        Self(pixels)
+    }
+
+    /// Replace the dead pixels in `image` with the average of their neighbors.
+    ///
+    /// Dead pixels outside of the image are ignored.
+    pub(crate) fn apply(&self, image: &mut ThermalImage) {
+        let (width, height) = image.dimensions();
+        for &(x, y) in &self.0 {
+            if x >= width || y >= height {
+                continue;
+            }
+            let neighbors: Vec<f32> = (y.saturating_sub(1)..=(y + 1).min(height - 1))
+                .flat_map(|ny| {
+                    (x.saturating_sub(1)..=(x + 1).min(width - 1)).map(move |nx| (nx, ny))
+                })
+                .filter(|neighbor| !self.0.contains(neighbor))
+                .map(|(nx, ny)| image.get_pixel(nx, ny)[0])
+                .collect();
+            // Leave the pixel alone if all of its neighbors are dead as well.
+            if !neighbors.is_empty() {
+                let mean = neighbors.iter().sum::<f32>() / neighbors.len() as f32;
+                image.put_pixel(x, y, [mean].into());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use image::ImageBuffer;
+
+    use super::DeadPixels;
+    use crate::image_buffer::ThermalImage;
+
+    fn test_image() -> ThermalImage {
+        ImageBuffer::from_raw(3, 3, vec![1.0, 2.0, 3.0, 4.0, 99.0, 6.0, 7.0, 8.0, 9.0]).unwrap()
+    }
+
+    #[test]
+    fn center() {
+        let mut image = test_image();
+        DeadPixels::new(vec![(1, 1)]).apply(&mut image);
+        assert_eq!(image.get_pixel(1, 1)[0], 5.0);
+    }
+
+    #[test]
+    fn corner_with_dead_neighbor() {
+        let mut image = test_image();
+        DeadPixels::new(vec![(0, 0), (1, 1)]).apply(&mut image);
+        // (1, 1) is ignored when filling in (0, 0)
+        assert_eq!(image.get_pixel(0, 0)[0], 3.0);
+        assert_eq!(image.get_pixel(1, 1)[0], 39.0 / 7.0);
+    }
+
+    #[test]
+    fn outside_image() {
+        let mut image = test_image();
+        DeadPixels::new(vec![(3, 0), (0, 3)]).apply(&mut image);
+        assert_eq!(image, test_image());
+    }
+}
@@ -1,16 +1,22 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 use std::convert::{Infallible, TryFrom};
 use std::fmt;
+use std::os::unix::io::AsRawFd;
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::Duration;
 
+use anyhow::Context as _;
+
+use embedded_hal::blocking::i2c::WriteRead;
 use i2cdev::linux::LinuxI2CError;
 use linux_embedded_hal::I2cdev;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::util::parse_int_decimal_hex;
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
 #[serde(untagged)]
 pub(crate) enum Bus {
     Number(u32),
@@ -58,6 +64,59 @@ impl TryFrom<&Bus> for I2cdev {
     }
 }
 
+mod ioctl {
+    // From include/uapi/linux/i2c-dev.h
+    nix::ioctl_write_int_bad!(i2c_timeout, 0x0702);
+}
+
+/// Convert a timeout to the units the kernel uses for I2C timeouts (10ms), rounding up.
+fn timeout_units(timeout: Duration) -> nix::libc::c_int {
+    let units = timeout.as_millis().div_ceil(10);
+    nix::libc::c_int::try_from(units).unwrap_or(nix::libc::c_int::MAX)
+}
+
+/// Set how long the kernel waits for a transfer on an I2C bus to finish before giving up.
+///
+/// Not every I2C adapter driver uses this value, in which case it is silently ignored.
+pub(crate) fn set_timeout(bus: &I2cdev, timeout: Duration) -> anyhow::Result<()> {
+    // Safe as the request only takes an integer, not a pointer.
+    unsafe { ioctl::i2c_timeout(bus.as_raw_fd(), timeout_units(timeout)) }
+        .context("Unable to set the I2C bus timeout")?;
+    Ok(())
+}
+
+/// Wraps an I2C bus so that every transaction is sent to the same address.
+///
+/// Some drivers only accept the addresses listed in the datasheet for a device, but breakout boards
+/// (and address translators) can put the device at a different address. Whatever address the
+/// driver uses is replaced with the one given here.
+pub(crate) struct FixedAddress<I2C> {
+    bus: I2C,
+    address: u8,
+}
+
+impl<I2C> FixedAddress<I2C> {
+    pub(crate) fn new(bus: I2C, address: u8) -> Self {
+        Self { bus, address }
+    }
+}
+
+impl<I2C> WriteRead for FixedAddress<I2C>
+where
+    I2C: WriteRead,
+{
+    type Error = I2C::Error;
+
+    fn write_read(
+        &mut self,
+        _address: u8,
+        bytes: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), Self::Error> {
+        self.bus.write_read(self.address, bytes, buffer)
+    }
+}
+
 impl fmt::Display for Bus {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.path().to_string_lossy())
@@ -66,8 +125,43 @@ impl fmt::Display for Bus {
 
 #[cfg(test)]
 mod test {
-    use super::Bus;
+    use super::{timeout_units, Bus, FixedAddress};
+    use embedded_hal::blocking::i2c::WriteRead;
+    use std::convert::Infallible;
     use std::path::PathBuf;
+    use std::time::Duration;
+
+    #[test]
+    fn timeout_rounding() {
+        assert_eq!(timeout_units(Duration::from_millis(10)), 1);
+        assert_eq!(timeout_units(Duration::from_millis(11)), 2);
+        assert_eq!(timeout_units(Duration::from_millis(250)), 25);
+        assert_eq!(timeout_units(Duration::from_secs(1)), 100);
+    }
+
+    #[test]
+    fn fixed_address() {
+        struct RecordingBus(Vec<u8>);
+
+        impl WriteRead for RecordingBus {
+            type Error = Infallible;
+
+            fn write_read(
+                &mut self,
+                address: u8,
+                _bytes: &[u8],
+                _buffer: &mut [u8],
+            ) -> Result<(), Self::Error> {
+                self.0.push(address);
+                Ok(())
+            }
+        }
+
+        let mut bus = FixedAddress::new(RecordingBus(Vec::new()), 0x70);
+        bus.write_read(0x68, &[0], &mut [0]).unwrap();
+        bus.write_read(0x69, &[0], &mut [0]).unwrap();
+        assert_eq!(bus.bus.0, vec![0x70, 0x70]);
+    }
 
     #[test]
     fn bus_from_num() {
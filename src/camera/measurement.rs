@@ -1,11 +1,109 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 use std::sync::Arc;
+use std::time::SystemTime;
+
+use rayon::prelude::*;
+use serde::ser::{Serialize, SerializeStruct, Serializer};
 
 use crate::image_buffer::ThermalImage;
-use crate::temperature::Temperature;
+use crate::temperature::{Temperature, TemperatureUnit};
 
 #[derive(Clone, Debug, PartialEq)]
 pub(crate) struct Measurement {
     pub(crate) image: Arc<ThermalImage>,
     pub(crate) temperature: Temperature,
+
+    /// Increases by one for every image read from the camera, so a gap means images were skipped.
+    pub(crate) frame_index: u64,
+
+    /// When the image was read from the camera.
+    pub(crate) timestamp: SystemTime,
+}
+
+/// The raw temperatures from a thermal image, for serializing as JSON.
+///
+/// The field names match those used for recorded camera data.
+#[derive(Clone, Debug)]
+pub(crate) struct RawFrame(pub(crate) Arc<ThermalImage>);
+
+impl From<&Measurement> for RawFrame {
+    fn from(measurement: &Measurement) -> Self {
+        Self(Arc::clone(&measurement.image))
+    }
+}
+
+impl Serialize for RawFrame {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut frame = serializer.serialize_struct("RawFrame", 4)?;
+        frame.serialize_field("width", &self.0.width())?;
+        frame.serialize_field("height", &self.0.height())?;
+        // Thermal images are always in Celsius.
+        frame.serialize_field("unit", &TemperatureUnit::Celsius)?;
+        frame.serialize_field("values", self.0.as_raw())?;
+        frame.end()
+    }
+}
+
+/// A summary of the temperatures of every pixel in a thermal image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum SceneStatistic {
+    Minimum,
+    Maximum,
+    Mean,
+}
+
+impl SceneStatistic {
+    pub(crate) const ALL: [Self; 3] = [Self::Minimum, Self::Maximum, Self::Mean];
+
+    /// The name of the MQTT sensor for this statistic.
+    pub(crate) fn sensor_name(&self) -> &'static str {
+        match self {
+            Self::Minimum => "minimum_temperature",
+            Self::Maximum => "maximum_temperature",
+            Self::Mean => "mean_temperature",
+        }
+    }
+
+    /// Compute this statistic for an image. Like the image, the result is in Celsius.
+    pub(crate) fn compute(&self, image: &ThermalImage) -> f32 {
+        let pixels = image.as_raw().par_iter().copied();
+        match self {
+            Self::Minimum => pixels.reduce(|| f32::INFINITY, f32::min),
+            Self::Maximum => pixels.reduce(|| f32::NEG_INFINITY, f32::max),
+            Self::Mean => pixels.sum::<f32>() / image.len() as f32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use image::ImageBuffer;
+
+    use super::{RawFrame, SceneStatistic};
+
+    #[test]
+    fn scene_statistics() {
+        let image = ImageBuffer::from_raw(2, 2, vec![20.0, 24.0, 18.5, 21.5]).unwrap();
+        assert_eq!(SceneStatistic::Minimum.compute(&image), 18.5);
+        assert_eq!(SceneStatistic::Maximum.compute(&image), 24.0);
+        assert_eq!(SceneStatistic::Mean.compute(&image), 21.0);
+    }
+
+    #[test]
+    fn raw_frame_json() {
+        let image = ImageBuffer::from_raw(2, 1, vec![20.0, 21.5]).unwrap();
+        let json = serde_json::to_value(RawFrame(Arc::new(image))).unwrap();
+        let expected = serde_json::json!({
+            "width": 2,
+            "height": 1,
+            "unit": "celsius",
+            "values": [20.0, 21.5],
+        });
+        assert_eq!(json, expected);
+    }
 }
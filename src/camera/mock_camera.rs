@@ -4,7 +4,6 @@ use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
 
-use anyhow::anyhow;
 use serde::de::{Deserialize, IntoDeserializer};
 use tracing::trace;
 
@@ -12,20 +11,39 @@ use crate::recorded_data::RecordedData;
 
 use super::thermal_camera::{CameraSample, ThermalCamera, YAxisDirection};
 
+/// The error returned once a mock camera reaches the end of the recording.
+///
+/// Unlike most camera errors, this isn't fixed by reconnecting to the camera.
+#[derive(Debug)]
+pub(crate) struct EndOfRecording;
+
+impl fmt::Display for EndOfRecording {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("No more measurements in record")
+    }
+}
+
+impl std::error::Error for EndOfRecording {}
+
 pub(crate) struct MockCamera {
     frame_rate: f32,
+    timing: PlaybackTiming,
+    speed: f32,
     measurements: Vec<RecordedData>,
     index: Box<dyn Iterator<Item = usize> + Send + Sync>,
     last_delay: Duration,
 }
 
 /// Controls how measurements are repeated by [`MockCamera`].
-#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Deserialize)]
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, serde::Deserialize, schemars::JsonSchema, serde::Serialize,
+)]
 #[serde(rename_all = "lowercase")]
 pub(crate) enum RepeatMode {
     /// Don't repeat.
     ///
-    /// Once the end of the measurements has been reached, an error is returned.
+    /// Once the end of the measurements has been reached, an [`EndOfRecording`] error is returned
+    /// and the camera stops.
     None,
 
     /// Loop over the measurements.
@@ -71,8 +89,63 @@ impl fmt::Display for RepeatMode {
     }
 }
 
+/// Controls how long [`MockCamera`] waits between frames.
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Deserialize,
+    schemars::JsonSchema,
+    serde::Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum PlaybackTiming {
+    /// Use the delays stored in the recording, so playback matches the original timing. The
+    /// configured frame rate is ignored. This is the default mode.
+    #[default]
+    Recorded,
+
+    /// Ignore the recorded delays, and play back at the configured frame rate.
+    FrameRate,
+}
+
+impl PlaybackTiming {
+    pub(crate) const KINDS: &'static [&'static str] = &["recorded", "framerate"];
+}
+
+impl FromStr for PlaybackTiming {
+    type Err = serde::de::value::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PlaybackTiming::deserialize(s.into_deserializer())
+    }
+}
+
+impl fmt::Display for PlaybackTiming {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            PlaybackTiming::Recorded => "recorded",
+            PlaybackTiming::FrameRate => "framerate",
+        };
+        write!(f, "{}", s)
+    }
+}
+
 impl MockCamera {
-    pub(crate) fn new(measurements: Vec<RecordedData>, repeat: RepeatMode) -> Self {
+    /// Create a new mock camera playing back the given measurements.
+    ///
+    /// `speed` is a multiplier for the playback rate, so 2.0 plays back twice as fast. It applies
+    /// to both playback timing modes.
+    pub(crate) fn new(
+        measurements: Vec<RecordedData>,
+        repeat: RepeatMode,
+        timing: PlaybackTiming,
+        frame_rate: f32,
+        speed: f32,
+    ) -> Self {
         let num_measurements = measurements.len();
         let index: Box<dyn Iterator<Item = usize> + Send + Sync> = match repeat {
             RepeatMode::None => Box::new(0..num_measurements),
@@ -84,7 +157,9 @@ impl MockCamera {
             }
         };
         Self {
-            frame_rate: 1.0,
+            frame_rate,
+            timing,
+            speed,
             measurements,
             index,
             last_delay: Duration::ZERO,
@@ -94,10 +169,7 @@ impl MockCamera {
 
 impl ThermalCamera for MockCamera {
     fn sample(&mut self) -> anyhow::Result<CameraSample> {
-        let index = self
-            .index
-            .next()
-            .ok_or_else(|| anyhow!("No more measurements in record"))?;
+        let index = self.index.next().ok_or(EndOfRecording)?;
         let data = self.measurements[index].clone();
         // When we loop, the first delay is 0. Instead, just repeat the previous delay. For the
         // very first frame, the delay *will* be zero, as the initial value for `last_delay` is
@@ -105,12 +177,18 @@ impl ThermalCamera for MockCamera {
         if data.delay != Duration::ZERO {
             self.last_delay = data.delay;
         }
-        let scaled_delay = self.last_delay.mul_f32(self.frame_rate);
-        // Keep the last delay around for if we loop
+        let scaled_delay = match self.timing {
+            PlaybackTiming::Recorded => self.last_delay.div_f64(self.speed.into()),
+            PlaybackTiming::FrameRate => {
+                Duration::from_secs_f64(1.0 / (f64::from(self.frame_rate) * f64::from(self.speed)))
+            }
+        };
         trace!(
             original_delay = ?data.delay,
             ?scaled_delay,
-            scale = ?self.frame_rate, "Scaled frame rate delay"
+            timing = %self.timing,
+            speed = self.speed,
+            "Scaled frame delay"
         );
         let image = Arc::try_unwrap(data.measurement.image).unwrap_or_else(|arc| {
             // If we can't take ownership of the Arc, clone the inner data instead.
@@ -124,7 +202,25 @@ impl ThermalCamera for MockCamera {
         })
     }
 
+    /// Recordings don't have a separate resolution, so the size of the first image is used.
+    fn resolution(&self) -> (u32, u32) {
+        self.measurements
+            .first()
+            .map(|data| data.measurement.image.dimensions())
+            .unwrap_or_default()
+    }
+
+    fn kind_name(&self) -> &'static str {
+        "Recorded data"
+    }
+
     fn set_frame_rate(&mut self, frame_rate: f32) -> anyhow::Result<()> {
+        // Any positive frame rate is allowed, including those below 1 FPS.
+        if !(frame_rate.is_finite() && frame_rate > 0.0) {
+            anyhow::bail!("The mock camera frame rate must be greater than 0");
+        }
+        // The frame rate is only used when not using the recorded timing, but keep track of it in
+        // all cases.
         self.frame_rate = frame_rate;
         Ok(())
     }
@@ -143,7 +239,7 @@ mod test {
     use crate::temperature::Temperature;
 
     use super::super::thermal_camera::{CameraSample, ThermalCamera};
-    use super::{MockCamera, RepeatMode};
+    use super::{MockCamera, PlaybackTiming, RepeatMode};
 
     const START_IMAGE_TEMP: f32 = 20.0;
 
@@ -162,6 +258,8 @@ mod test {
                     Measurement {
                         image: Arc::new(image),
                         temperature,
+                        frame_index: 0,
+                        timestamp: std::time::UNIX_EPOCH,
                     },
                     delay,
                 )
@@ -175,7 +273,13 @@ mod test {
             ambient_temps.len(),
             "image_temps and ambient_temps must be the same length"
         );
-        let mut cam = MockCamera::new(tiny_measurements(), repeat_mode);
+        let mut cam = MockCamera::new(
+            tiny_measurements(),
+            repeat_mode,
+            PlaybackTiming::Recorded,
+            1.0,
+            1.0,
+        );
         let measurements: Vec<CameraSample> = std::iter::from_fn(move || cam.sample().ok())
             .fuse()
             .take(30)
@@ -247,4 +351,93 @@ mod test {
             &expected_ambient[..],
         )
     }
+
+    fn frame_delays(timing: PlaybackTiming, frame_rate: f32, speed: f32) -> Vec<Duration> {
+        let mut cam = MockCamera::new(
+            tiny_measurements(),
+            RepeatMode::None,
+            timing,
+            frame_rate,
+            speed,
+        );
+        std::iter::from_fn(move || cam.sample().ok())
+            .map(|sample| sample.frame_delay)
+            .collect()
+    }
+
+    #[test]
+    fn recorded_timing() {
+        let delays = frame_delays(PlaybackTiming::Recorded, 10.0, 1.0);
+        assert_eq!(delays.len(), NUM_TINY_MEASUREMENTS);
+        assert!(delays
+            .iter()
+            .all(|delay| *delay == Duration::from_millis(25)));
+    }
+
+    #[test]
+    fn recorded_timing_speed() {
+        let delays = frame_delays(PlaybackTiming::Recorded, 10.0, 2.5);
+        assert!(delays
+            .iter()
+            .all(|delay| *delay == Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn frame_rate_timing() {
+        let delays = frame_delays(PlaybackTiming::FrameRate, 4.0, 2.0);
+        assert!(delays
+            .iter()
+            .all(|delay| *delay == Duration::from_millis(125)));
+    }
+
+    #[test]
+    fn set_frame_rate() {
+        let mut recorded = MockCamera::new(
+            tiny_measurements(),
+            RepeatMode::None,
+            PlaybackTiming::Recorded,
+            1.0,
+            1.0,
+        );
+        recorded.set_frame_rate(5.0).unwrap();
+        assert_eq!(
+            recorded.sample().unwrap().frame_delay,
+            Duration::from_millis(25)
+        );
+        let mut fixed = MockCamera::new(
+            tiny_measurements(),
+            RepeatMode::None,
+            PlaybackTiming::FrameRate,
+            1.0,
+            1.0,
+        );
+        assert!(fixed.set_frame_rate(5.0).is_ok());
+        assert_eq!(
+            fixed.sample().unwrap().frame_delay,
+            Duration::from_millis(200)
+        );
+        assert!(fixed.set_frame_rate(0.5).is_ok());
+        assert_eq!(fixed.sample().unwrap().frame_delay, Duration::from_secs(2));
+        assert!(fixed.set_frame_rate(0.0).is_err());
+    }
+
+    #[test]
+    fn resolution() {
+        let camera = MockCamera::new(
+            tiny_measurements(),
+            RepeatMode::None,
+            PlaybackTiming::Recorded,
+            1.0,
+            1.0,
+        );
+        assert_eq!(camera.resolution(), (1, 1));
+        let empty = MockCamera::new(
+            Vec::new(),
+            RepeatMode::None,
+            PlaybackTiming::Recorded,
+            1.0,
+            1.0,
+        );
+        assert_eq!(empty.resolution(), (0, 0));
+    }
 }
@@ -1,16 +1,31 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
+mod calibration;
+mod dead_pixels;
 mod i2c;
 mod measurement;
 #[cfg(feature = "mock_camera")]
 mod mock_camera;
+mod scan;
 mod settings;
 mod shared_camera;
+#[cfg(feature = "mock_camera")]
+mod synthetic_camera;
+#[cfg(feature = "mock_camera")]
+mod test_pattern_camera;
 mod thermal_camera;
+mod valid_range;
 
+pub(crate) use calibration::Calibration;
+pub(crate) use dead_pixels::DeadPixels;
 pub(crate) use i2c::Bus;
-pub(crate) use measurement::Measurement;
-pub(crate) use settings::CameraSettings;
+pub(crate) use measurement::{Measurement, RawFrame, SceneStatistic};
+pub(crate) use scan::scan;
+pub(crate) use settings::{CameraSettings, Rotation};
 pub(crate) use shared_camera::{Camera, CameraCommand};
 
 #[cfg(feature = "mock_camera")]
-pub(crate) use mock_camera::RepeatMode;
+pub(crate) use mock_camera::{EndOfRecording, PlaybackTiming, RepeatMode};
+#[cfg(feature = "mock_camera")]
+pub(crate) use synthetic_camera::SyntheticPerson;
+#[cfg(feature = "mock_camera")]
+pub(crate) use test_pattern_camera::TestPattern;
@@ -0,0 +1,151 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+use embedded_hal::blocking::i2c::WriteRead;
+use linux_embedded_hal::I2cdev;
+use tracing::{debug, trace, warn};
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A register that can be read from a kind of camera without changing any of its settings.
+struct Probe {
+    /// A description of the cameras that can be found at this address.
+    kind: &'static str,
+
+    address: u8,
+
+    /// The (big-endian) address of the register to read.
+    register: &'static [u8],
+
+    /// The number of bytes to read from the register.
+    length: usize,
+}
+
+/// The addresses known cameras can be configured to use.
+const PROBES: &[Probe] = &[
+    // The GridEYE power control register.
+    Probe {
+        kind: "GridEYE",
+        address: 0x68,
+        register: &[0x00],
+        length: 1,
+    },
+    Probe {
+        kind: "GridEYE",
+        address: 0x69,
+        register: &[0x00],
+        length: 1,
+    },
+    // The Melexis status register.
+    Probe {
+        kind: "MLX90640 or MLX90641",
+        address: 0x33,
+        register: &[0x80, 0x00],
+        length: 2,
+    },
+];
+
+/// A possible camera found by [scan].
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ScanResult {
+    pub(crate) bus: PathBuf,
+    pub(crate) address: u8,
+    pub(crate) kind: &'static str,
+}
+
+impl fmt::Display for ScanResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at address {:#04x} on {}",
+            self.kind,
+            self.address,
+            self.bus.display()
+        )
+    }
+}
+
+/// Find the I2C buses present in `dev_dir`, sorted by bus number.
+fn i2c_buses(dev_dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut buses: Vec<(u32, PathBuf)> = fs::read_dir(dev_dir)?
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            let number = name.to_str()?.strip_prefix("i2c-")?.parse().ok()?;
+            Some((number, entry.path()))
+        })
+        .collect();
+    buses.sort();
+    Ok(buses.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Scan every I2C bus for devices at the addresses known cameras use.
+///
+/// Each address is probed by reading a register that is safe to read, so this will not change the
+/// settings of any devices that respond. A device responding only means that *something* is at
+/// that address, it may not actually be a camera.
+pub(crate) fn scan() -> anyhow::Result<Vec<ScanResult>> {
+    let mut found = Vec::new();
+    for bus_path in i2c_buses(Path::new("/dev"))? {
+        debug!(bus = ?bus_path, "Scanning I2C bus");
+        let mut bus = match I2cdev::new(&bus_path) {
+            Ok(bus) => bus,
+            Err(err) => {
+                warn!(bus = ?bus_path, "Unable to open I2C bus: {}", err);
+                continue;
+            }
+        };
+        for probe in PROBES {
+            let mut buffer = vec![0u8; probe.length];
+            match bus.write_read(probe.address, probe.register, &mut buffer) {
+                Ok(_) => found.push(ScanResult {
+                    bus: bus_path.clone(),
+                    address: probe.address,
+                    kind: probe.kind,
+                }),
+                Err(err) => trace!(
+                    bus = ?bus_path,
+                    address = probe.address,
+                    "No response from I2C address: {}",
+                    err
+                ),
+            }
+        }
+    }
+    Ok(found)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{i2c_buses, ScanResult};
+
+    use std::fs::File;
+    use std::path::PathBuf;
+
+    #[test]
+    fn find_buses() {
+        let dev_dir = tempfile::tempdir().unwrap();
+        for name in ["i2c-10", "i2c-1", "i2c-foo", "spidev0.0", "i2c-2"] {
+            File::create(dev_dir.path().join(name)).unwrap();
+        }
+        let buses = i2c_buses(dev_dir.path()).unwrap();
+        let expected: Vec<PathBuf> = ["i2c-1", "i2c-2", "i2c-10"]
+            .iter()
+            .map(|name| dev_dir.path().join(name))
+            .collect();
+        assert_eq!(buses, expected);
+    }
+
+    #[test]
+    fn display() {
+        let result = ScanResult {
+            bus: PathBuf::from("/dev/i2c-1"),
+            address: 0x33,
+            kind: "MLX90640 or MLX90641",
+        };
+        assert_eq!(
+            result.to_string(),
+            "MLX90640 or MLX90641 at address 0x33 on /dev/i2c-1"
+        );
+    }
+}
@@ -3,20 +3,28 @@ use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::fmt;
 use std::marker::PhantomData;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::time::Duration;
 
-use anyhow::Context as _;
+use anyhow::{anyhow, Context as _};
 use linux_embedded_hal::I2cdev;
-use serde::de::{Deserialize, Deserializer, Error};
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, Schema, SchemaObject};
+use schemars::JsonSchema_repr;
+use serde::de::{Deserialize, Deserializer, Error, IntoDeserializer};
 use serde::ser::{Serialize, Serializer};
 use serde_repr::{Deserialize_repr, Serialize_repr};
+use tracing::warn;
 
 use super::thermal_camera::{self, ThermalCamera};
+use super::valid_range::ValidRange;
 
 /// The type for the map of extra keys found in a camera config.
 type ExtraMap = HashMap<String, toml::Value>;
 
 // This enum is purely used to restrict the acceptable values for rotation.
-#[derive(Clone, Copy, Deserialize_repr, Serialize_repr, PartialEq, Debug)]
+#[derive(Clone, Copy, Deserialize_repr, JsonSchema_repr, Serialize_repr, PartialEq, Debug)]
 #[repr(u16)]
 pub(crate) enum Rotation {
     Zero = 0,
@@ -32,7 +40,9 @@ impl Default for Rotation {
 }
 
 /// Newtype wrapper around `bool` for flipping the image.
-#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, serde::Deserialize, schemars::JsonSchema, serde::Serialize,
+)]
 #[serde(transparent)]
 struct Flip(bool);
 
@@ -54,7 +64,41 @@ impl From<Flip> for bool {
     }
 }
 
-#[derive(Clone, Default, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+/// How the camera is kept powered between measurements.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    serde::Deserialize,
+    schemars::JsonSchema,
+    serde::Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum PowerMode {
+    /// The camera is always running, and images are read at the frame rate.
+    #[default]
+    Continuous,
+
+    /// The camera is put into a low power mode between measurements, which are only taken every
+    /// `standby_interval` seconds.
+    Standby,
+}
+
+fn default_standby_interval() -> f32 {
+    10.0
+}
+
+fn default_reconnect_attempts() -> u32 {
+    5
+}
+
+fn default_reconnect_delay() -> f32 {
+    1.0
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, schemars::JsonSchema, serde::Serialize)]
 pub(crate) struct CommonCameraSettings {
     #[serde(default)]
     rotation: Rotation,
@@ -68,30 +112,207 @@ pub(crate) struct CommonCameraSettings {
     #[serde(default)]
     round_temperature: Option<f32>,
 
+    /// Pixels to replace with the average of their neighbors, as `[x, y]` pairs.
+    #[serde(default)]
+    dead_pixels: Vec<(u32, u32)>,
+
+    /// A file with per-pixel temperature corrections.
+    #[serde(default)]
+    calibration: Option<PathBuf>,
+
+    /// The temperatures the camera can plausibly report. Pixels outside of this range are
+    /// replaced.
+    #[serde(default)]
+    valid_range: Option<ValidRange>,
+
+    /// The access pattern for Melexis cameras. If not set, the camera's current pattern is used
+    /// (which is chess for the MLX90640 and interleaved for the MLX90641 by default).
+    #[serde(default, alias = "mode")]
+    read_pattern: Option<MelexisAccessPattern>,
+
+    #[serde(default)]
+    power_mode: PowerMode,
+
+    /// How long (in seconds) to wait between measurements in standby mode.
+    #[serde(default = "default_standby_interval")]
+    standby_interval: f32,
+
+    /// How many times in a row to try reconnecting to the camera after an error reading from it.
+    #[serde(default = "default_reconnect_attempts")]
+    reconnect_attempts: u32,
+
+    /// How long (in seconds) to wait before the first reconnection attempt. The delay doubles
+    /// with each failed attempt.
+    #[serde(default = "default_reconnect_delay")]
+    reconnect_delay: f32,
+
+    /// How long (in seconds) to wait for an I2C transfer to finish before giving up. If not set,
+    /// the bus's existing timeout is kept.
+    #[serde(default)]
+    i2c_timeout: Option<f32>,
+
+    /// Keep this many seconds of the most recent camera data in memory, to be written out when
+    /// triggered.
+    #[serde(default)]
+    record_ring_seconds: Option<f32>,
+
+    /// Write out the buffered camera data whenever the number of people changes.
+    #[serde(default)]
+    record_ring_on_occupancy: bool,
+
+    /// The directory buffered camera data is written to.
+    #[serde(default)]
+    record_ring_directory: Option<PathBuf>,
+
     // By annotating this field with 'flatten', any unknown keys will be collected into this map.
+    // They're left out of the schema, as they're only kept around to warn about them.
     #[serde(default, flatten)]
+    #[schemars(skip)]
     extra: ExtraMap,
 }
 
-/// Duplicating [amg88::Address] to have [serde::Deserialize] implemented for it.
-///
-/// This is used to validate that the address specified is one of the two valid addresses.
-#[derive(Clone, Copy, Debug, PartialEq, Deserialize_repr, Serialize_repr)]
-#[serde(remote = "amg88::Address")]
-#[repr(u8)]
-enum GridEyeAddress {
-    Low = 0x68,
-    High = 0x69,
+impl Default for CommonCameraSettings {
+    fn default() -> Self {
+        Self {
+            rotation: Rotation::default(),
+            flip_horizontal: Flip::default(),
+            flip_vertical: Flip::default(),
+            round_temperature: None,
+            dead_pixels: Vec::new(),
+            calibration: None,
+            valid_range: None,
+            read_pattern: None,
+            power_mode: PowerMode::default(),
+            standby_interval: default_standby_interval(),
+            reconnect_attempts: default_reconnect_attempts(),
+            reconnect_delay: default_reconnect_delay(),
+            i2c_timeout: None,
+            record_ring_seconds: None,
+            record_ring_on_occupancy: false,
+            record_ring_directory: None,
+            extra: ExtraMap::default(),
+        }
+    }
 }
 
 fn default_grideye_frame_rate() -> amg88::FrameRateValue {
     amg88::FrameRateValue::Fps10
 }
 
+/// Deserialize a GridEYE frame rate.
+///
+/// The driver maps 0 to 10 FPS (as that is the value of the register), so it's rejected here.
+fn deserialize_grideye_frame_rate<'de, D>(
+    deserializer: D,
+) -> Result<amg88::FrameRateValue, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match u8::deserialize(deserializer)? {
+        0 => Err(D::Error::custom("the frame rate must be 1 or 10")),
+        frame_rate => TryFromU8::deserialize(frame_rate.into_deserializer()),
+    }
+}
+
+/// Serialize a GridEYE frame rate as the number of frames per second.
+///
+/// This is the counterpart to [`deserialize_grideye_frame_rate`], as converting the frame rate to
+/// a `u8` gives the register value (0) for 10 FPS.
+fn serialize_grideye_frame_rate<S>(
+    frame_rate: &amg88::FrameRateValue,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match frame_rate {
+        amg88::FrameRateValue::Fps1 => 1u8,
+        amg88::FrameRateValue::Fps10 => 10u8,
+    }
+    .serialize(serializer)
+}
+
+/// The schema for a GridEYE frame rate, which can only be 1 or 10.
+fn grideye_frame_rate_schema(_: &mut SchemaGenerator) -> Schema {
+    SchemaObject {
+        instance_type: Some(InstanceType::Integer.into()),
+        enum_values: Some(vec![1.into(), 10.into()]),
+        ..SchemaObject::default()
+    }
+    .into()
+}
+
+fn default_grideye_thermistor_interval() -> NonZeroUsize {
+    NonZeroUsize::new(1).unwrap()
+}
+
+/// Defaulting to the same size as a GridEye.
+#[cfg(feature = "mock_camera")]
+fn default_synthetic_size() -> u32 {
+    8
+}
+
+#[cfg(feature = "mock_camera")]
+fn default_playback_speed() -> f32 {
+    1.0
+}
+
+#[cfg(feature = "mock_camera")]
+fn default_test_pattern_minimum() -> crate::temperature::Temperature {
+    crate::temperature::Temperature::Celsius(20.0)
+}
+
+#[cfg(feature = "mock_camera")]
+fn default_test_pattern_maximum() -> crate::temperature::Temperature {
+    crate::temperature::Temperature::Celsius(35.0)
+}
+
+#[cfg(feature = "mock_camera")]
+fn default_test_pattern_speed() -> f32 {
+    0.5
+}
+
+#[cfg(feature = "mock_camera")]
+fn default_synthetic_temperature() -> crate::temperature::Temperature {
+    crate::temperature::Temperature::Celsius(20.0)
+}
+
+/// Warn if a GridEYE address isn't one of the addresses given in the datasheet.
+fn warn_on_grideye_address(address: u8) {
+    if amg88::Address::try_from(address).is_err() {
+        warn!(
+            address = %format!("{:#04x}", address),
+            "GridEYE address is not 0x68 or 0x69, the only addresses in the datasheet"
+        );
+    }
+}
+
+/// Load recorded camera data from a file.
+///
+/// Files with a `toml` extension are parsed as TOML, everything else is treated as bincode.
+#[cfg(feature = "mock_camera")]
+fn load_recording(
+    path: &std::path::Path,
+) -> anyhow::Result<Vec<crate::recorded_data::RecordedData>> {
+    use crate::recorded_data::RecordedData;
+
+    let extension = path.extension().and_then(|s| s.to_str());
+    match extension {
+        Some("toml") => {
+            let data_string = std::fs::read_to_string(path)?;
+            toml::from_str(&data_string).map_err(anyhow::Error::from)
+        }
+        // treat everything as bincode if we don't know the extension
+        _ => {
+            let file = std::fs::File::open(path)?;
+            RecordedData::from_bincode(file)
+        }
+    }
+}
+
 struct TryFromNum<U>(PhantomData<U>);
 
 impl<U> TryFromNum<U> {
-    #[allow(dead_code)]
     pub(super) fn serialize<S, T>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
@@ -116,30 +337,27 @@ impl<U> TryFromNum<U> {
 type TryFromU8 = TryFromNum<u8>;
 type TryFromF32 = TryFromNum<f32>;
 
-#[derive(Copy, Clone, Debug, serde::Deserialize, PartialEq)]
+/// The pattern Melexis cameras use to update the pixels in each subpage.
+#[derive(
+    Copy, Clone, Debug, serde::Deserialize, schemars::JsonSchema, PartialEq, serde::Serialize,
+)]
 #[serde(rename_all = "lowercase")]
-pub(crate) enum Mlx90640AccessMode {
+pub(crate) enum MelexisAccessPattern {
     Chess,
-    #[serde(alias = "interlace")]
+    #[serde(alias = "interlace", alias = "interleaved")]
     Interleave,
 }
 
-impl Default for Mlx90640AccessMode {
-    fn default() -> Self {
-        Self::Chess
-    }
-}
-
-impl From<Mlx90640AccessMode> for mlx9064x::AccessPattern {
-    fn from(wrapped: Mlx90640AccessMode) -> Self {
+impl From<MelexisAccessPattern> for mlx9064x::AccessPattern {
+    fn from(wrapped: MelexisAccessPattern) -> Self {
         match wrapped {
-            Mlx90640AccessMode::Chess => Self::Chess,
-            Mlx90640AccessMode::Interleave => Self::Interleave,
+            MelexisAccessPattern::Chess => Self::Chess,
+            MelexisAccessPattern::Interleave => Self::Interleave,
         }
     }
 }
 
-impl From<mlx9064x::AccessPattern> for Mlx90640AccessMode {
+impl From<mlx9064x::AccessPattern> for MelexisAccessPattern {
     fn from(pattern: mlx9064x::AccessPattern) -> Self {
         match pattern {
             mlx9064x::AccessPattern::Chess => Self::Chess,
@@ -148,18 +366,33 @@ impl From<mlx9064x::AccessPattern> for Mlx90640AccessMode {
     }
 }
 
-#[derive(Clone, Debug, serde::Deserialize, PartialEq)]
+#[derive(Clone, Debug, serde::Deserialize, schemars::JsonSchema, PartialEq, serde::Serialize)]
 #[serde(rename_all = "lowercase", tag = "kind")]
 pub(crate) enum CameraSettings {
     GridEye {
         bus: super::i2c::Bus,
 
-        #[serde(with = "TryFromU8")]
-        address: amg88::Address,
+        /// The I2C address of the camera.
+        ///
+        /// The datasheet only lists 0x68 and 0x69, but any address is accepted to allow for
+        /// address translators.
+        address: u8,
 
-        #[serde(default = "default_grideye_frame_rate", with = "TryFromU8")]
+        #[serde(
+            default = "default_grideye_frame_rate",
+            deserialize_with = "deserialize_grideye_frame_rate",
+            serialize_with = "serialize_grideye_frame_rate"
+        )]
+        #[schemars(schema_with = "grideye_frame_rate_schema")]
         frame_rate: amg88::FrameRateValue,
 
+        /// Only read the thermistor every *thermistor_interval* frames.
+        ///
+        /// The GridEye thermistor does not update as often as the image at 10 FPS, so the
+        /// temperature from the last reading is reused for the frames in between.
+        #[serde(default = "default_grideye_thermistor_interval")]
+        thermistor_interval: NonZeroUsize,
+
         #[serde(flatten)]
         common: CommonCameraSettings,
     },
@@ -168,11 +401,9 @@ pub(crate) enum CameraSettings {
         address: u8,
 
         #[serde(default, with = "TryFromF32")]
+        #[schemars(with = "f32")]
         frame_rate: mlx9064x::FrameRate,
 
-        #[serde(default)]
-        mode: Mlx90640AccessMode,
-
         #[serde(flatten)]
         common: CommonCameraSettings,
     },
@@ -181,6 +412,7 @@ pub(crate) enum CameraSettings {
         address: u8,
 
         #[serde(with = "TryFromF32")]
+        #[schemars(with = "f32")]
         frame_rate: mlx9064x::FrameRate,
 
         #[serde(flatten)]
@@ -195,6 +427,68 @@ pub(crate) enum CameraSettings {
         #[serde(default)]
         repeat_mode: super::RepeatMode,
 
+        /// Whether to use the recorded frame delays, or the configured frame rate.
+        #[serde(default)]
+        playback: super::PlaybackTiming,
+
+        /// A multiplier for the playback rate, so 2.0 plays back twice as fast.
+        #[serde(default = "default_playback_speed")]
+        speed: f32,
+
+        #[serde(flatten)]
+        common: CommonCameraSettings,
+    },
+    #[cfg(feature = "mock_camera")]
+    Synthetic {
+        frame_rate: f32,
+
+        /// A recording to use the first frame of as the background image.
+        ///
+        /// If not given, a uniform background of *background_temperature* is used.
+        #[serde(default)]
+        background: Option<std::path::PathBuf>,
+
+        #[serde(default = "default_synthetic_size")]
+        width: u32,
+
+        #[serde(default = "default_synthetic_size")]
+        height: u32,
+
+        #[serde(default = "default_synthetic_temperature")]
+        background_temperature: crate::temperature::Temperature,
+
+        #[serde(default)]
+        people: Vec<super::SyntheticPerson>,
+
+        #[serde(flatten)]
+        common: CommonCameraSettings,
+    },
+    #[cfg(feature = "mock_camera")]
+    #[serde(rename = "test_pattern")]
+    TestPattern {
+        frame_rate: f32,
+
+        #[serde(default)]
+        pattern: super::TestPattern,
+
+        #[serde(default = "default_synthetic_size")]
+        width: u32,
+
+        #[serde(default = "default_synthetic_size")]
+        height: u32,
+
+        /// The coldest temperature in the pattern.
+        #[serde(default = "default_test_pattern_minimum")]
+        minimum: crate::temperature::Temperature,
+
+        /// The warmest temperature in the pattern.
+        #[serde(default = "default_test_pattern_maximum")]
+        maximum: crate::temperature::Temperature,
+
+        /// How far the pattern moves each frame, in pixels.
+        #[serde(default = "default_test_pattern_speed")]
+        speed: f32,
+
         #[serde(flatten)]
         common: CommonCameraSettings,
     },
@@ -205,10 +499,32 @@ impl CameraSettings {
     // It'd be nice at some point to have these be automatically generated, as serde sees all this
     // information.
     #[cfg(feature = "mock_camera")]
-    pub(crate) const KINDS: &'static [&'static str] = &["grideye", "mlx90640", "mlx90641", "mock"];
+    pub(crate) const KINDS: &'static [&'static str] = &[
+        "grideye",
+        "mlx90640",
+        "mlx90641",
+        "mock",
+        "synthetic",
+        "test_pattern",
+    ];
     #[cfg(not(feature = "mock_camera"))]
     pub(crate) const KINDS: &'static [&'static str] = &["grideye", "mlx90640", "mlx90641"];
 
+    /// The identifier for this kind of camera, as used for `kind` in the configuration.
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            Self::GridEye { .. } => "grideye",
+            Self::Mlx90640 { .. } => "mlx90640",
+            Self::Mlx90641 { .. } => "mlx90641",
+            #[cfg(feature = "mock_camera")]
+            Self::MockCamera { .. } => "mock",
+            #[cfg(feature = "mock_camera")]
+            Self::Synthetic { .. } => "synthetic",
+            #[cfg(feature = "mock_camera")]
+            Self::TestPattern { .. } => "test_pattern",
+        }
+    }
+
     /// Convenience method for accessing common camera settings.
     fn common(&self) -> &CommonCameraSettings {
         match self {
@@ -217,6 +533,10 @@ impl CameraSettings {
             Self::Mlx90641 { common, .. } => common,
             #[cfg(feature = "mock_camera")]
             Self::MockCamera { common, .. } => common,
+            #[cfg(feature = "mock_camera")]
+            Self::Synthetic { common, .. } => common,
+            #[cfg(feature = "mock_camera")]
+            Self::TestPattern { common, .. } => common,
         }
     }
 
@@ -239,11 +559,237 @@ impl CameraSettings {
         self.common().round_temperature
     }
 
+    pub(crate) fn dead_pixels(&self) -> super::DeadPixels {
+        super::DeadPixels::new(self.common().dead_pixels.clone())
+    }
+
+    /// Load the calibration map, if one has been configured.
+    pub(crate) fn calibration(&self) -> anyhow::Result<Option<super::Calibration>> {
+        self.common()
+            .calibration
+            .as_deref()
+            .map(super::Calibration::load)
+            .transpose()
+    }
+
+    pub(crate) fn valid_range(&self) -> Option<ValidRange> {
+        self.common().valid_range.clone()
+    }
+
+    /// How long to wait between measurements when the camera is kept in standby, or `None` if the
+    /// camera is always running.
+    pub(crate) fn standby_interval(&self) -> anyhow::Result<Option<Duration>> {
+        match self.common().power_mode {
+            PowerMode::Continuous => Ok(None),
+            PowerMode::Standby => {
+                let seconds = self.common().standby_interval;
+                if !(seconds.is_finite() && seconds > 0.0) {
+                    return Err(anyhow!(
+                        "The standby interval must be greater than 0, not {}",
+                        seconds
+                    ));
+                }
+                Ok(Some(Duration::from_secs_f32(seconds)))
+            }
+        }
+    }
+
+    /// How often measurements are taken, in frames per second.
+    ///
+    /// This is the camera's frame rate, unless the camera is put in standby between measurements,
+    /// in which case it's one measurement every standby interval.
+    pub(crate) fn measurement_rate(&self) -> f32 {
+        match self.standby_interval() {
+            Ok(Some(interval)) => self.frame_rate().min(interval.as_secs_f32().recip()),
+            // An invalid standby interval is caught by `check`.
+            Ok(None) | Err(_) => self.frame_rate(),
+        }
+    }
+
+    /// How many times in a row to try reconnecting to the camera after an error.
+    pub(crate) fn reconnect_attempts(&self) -> u32 {
+        self.common().reconnect_attempts
+    }
+
+    /// How long to wait before the first reconnection attempt.
+    pub(crate) fn reconnect_delay(&self) -> anyhow::Result<Duration> {
+        let seconds = self.common().reconnect_delay;
+        if !(seconds.is_finite() && seconds >= 0.0) {
+            return Err(anyhow!(
+                "The reconnect delay can't be negative, not {}",
+                seconds
+            ));
+        }
+        Ok(Duration::from_secs_f32(seconds))
+    }
+
+    /// How long to wait for an I2C transfer before giving up, if it's been set.
+    pub(crate) fn i2c_timeout(&self) -> anyhow::Result<Option<Duration>> {
+        self.common()
+            .i2c_timeout
+            .map(|seconds| {
+                // The kernel counts in units of 10ms, and anything over 10 seconds is going to be
+                // noticed by the watchdog well before the transfer gives up.
+                if !(0.01..=10.0).contains(&seconds) {
+                    return Err(anyhow!(
+                        "The I2C timeout must be between 0.01 and 10 seconds, not {}",
+                        seconds
+                    ));
+                }
+                Ok(Duration::from_secs_f32(seconds))
+            })
+            .transpose()
+    }
+
+    /// The size of the images from this camera after being rotated, if it's known ahead of time.
+    ///
+    /// This is the size of the images everything after the camera (like the tracker) sees.
+    pub(crate) fn rotated_resolution(&self) -> Option<(u32, u32)> {
+        self.resolution()
+            .map(|(width, height)| match self.rotation() {
+                Rotation::Zero | Rotation::OneEighty => (width, height),
+                Rotation::Ninety | Rotation::TwoSeventy => (height, width),
+            })
+    }
+
+    /// The size of the images from this camera (before any rotation), if it's known ahead of time.
+    fn resolution(&self) -> Option<(u32, u32)> {
+        match self {
+            Self::GridEye { .. } => Some((8, 8)),
+            Self::Mlx90640 { .. } => Some((32, 24)),
+            Self::Mlx90641 { .. } => Some((16, 12)),
+            #[cfg(feature = "mock_camera")]
+            Self::Synthetic {
+                background: None,
+                width,
+                height,
+                ..
+            } => Some((*width, *height)),
+            #[cfg(feature = "mock_camera")]
+            Self::TestPattern { width, height, .. } => Some((*width, *height)),
+            #[cfg(feature = "mock_camera")]
+            _ => None,
+        }
+    }
+
+    /// How much recent camera data to keep in memory, or `None` if it shouldn't be kept.
+    pub(crate) fn record_ring_duration(&self) -> Option<Duration> {
+        self.common()
+            .record_ring_seconds
+            .filter(|seconds| seconds.is_normal() && *seconds > 0.0)
+            .map(Duration::from_secs_f32)
+    }
+
+    /// Whether buffered camera data should be written out when the number of people changes.
+    #[cfg(feature = "mock_camera")]
+    pub(crate) fn record_ring_on_occupancy(&self) -> bool {
+        self.common().record_ring_on_occupancy
+    }
+
+    /// The directory to write buffered camera data to. Defaults to the temporary directory.
+    #[cfg(feature = "mock_camera")]
+    pub(crate) fn record_ring_directory(&self) -> PathBuf {
+        self.common()
+            .record_ring_directory
+            .clone()
+            .unwrap_or_else(std::env::temp_dir)
+    }
+
     /// Access any unprocessed keys from the configuration.
     pub(crate) fn extra(&self) -> &ExtraMap {
         &self.common().extra
     }
 
+    /// Check for mistakes in the settings that can be found without accessing the camera.
+    ///
+    /// Settings that will keep the camera from working are returned as errors, while settings
+    /// that are unusual but might still work are logged as warnings. This is run every time a
+    /// [`Camera`][super::Camera] is created, as well as by `--check-config`.
+    pub(crate) fn check(&self) -> anyhow::Result<()> {
+        #[allow(unreachable_patterns)]
+        let address = match self {
+            Self::GridEye { address, .. }
+            | Self::Mlx90640 { address, .. }
+            | Self::Mlx90641 { address, .. } => Some(*address),
+            _ => None,
+        };
+        if let Some(address) = address {
+            // Addresses outside of this range are reserved by the I2C specification.
+            if !(0x08..=0x77).contains(&address) {
+                return Err(anyhow!(
+                    "{:#04x} is not a valid I2C address for a camera",
+                    address
+                ));
+            }
+        }
+        if let Some((width, height)) = self.resolution() {
+            if let Some((x, y)) = self
+                .common()
+                .dead_pixels
+                .iter()
+                .find(|(x, y)| *x >= width || *y >= height)
+            {
+                return Err(anyhow!(
+                    "The dead pixel ({}, {}) is outside of the camera's {}x{} image",
+                    x,
+                    y,
+                    width,
+                    height
+                ));
+            }
+        }
+        if let Some(interval) = self.standby_interval()? {
+            if interval.as_secs_f32() * self.frame_rate() < 1.0 {
+                warn!(
+                    "The standby interval ({:?}) is shorter than a frame at {} FPS, so the camera \
+                     will never be put in standby",
+                    interval,
+                    self.frame_rate()
+                );
+            }
+        }
+        if let Some(calibration) = self.calibration()? {
+            match self.resolution() {
+                Some(resolution) if resolution != calibration.dimensions() => {
+                    return Err(anyhow!(
+                        "The calibration map is {}x{}, but the camera's image is {}x{}",
+                        calibration.dimensions().0,
+                        calibration.dimensions().1,
+                        resolution.0,
+                        resolution.1
+                    ));
+                }
+                _ => (),
+            }
+        }
+        match self {
+            Self::GridEye { address, .. } => warn_on_grideye_address(*address),
+            #[cfg(feature = "mock_camera")]
+            Self::MockCamera { path, .. } => {
+                if !path.is_file() {
+                    return Err(anyhow!(
+                        "The mock camera recording {} does not exist",
+                        path.display()
+                    ));
+                }
+            }
+            #[cfg(feature = "mock_camera")]
+            Self::Synthetic {
+                background: Some(background),
+                ..
+            } => {
+                if !background.is_file() {
+                    return Err(anyhow!(
+                        "The synthetic camera background {} does not exist",
+                        background.display()
+                    ));
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
     /// If the camera is connected over I2C, this method creates the [I2cdev] for that bus.
     ///
     /// If the camera does not use I2C, this method returns `None`.
@@ -255,9 +801,16 @@ impl CameraSettings {
             Self::Mlx90641 { bus, .. } => Some(bus),
             _ => None,
         }
-        .map(|bus| I2cdev::try_from(bus).context("Unable to connect to I2C bus"))
+        .map(|bus| {
+            let device = I2cdev::try_from(bus).context("Unable to connect to I2C bus")?;
+            if let Some(timeout) = self.i2c_timeout()? {
+                super::i2c::set_timeout(&device, timeout)?;
+            }
+            Ok(device)
+        })
     }
 
+    /// The frame rate the camera is set to, in frames per second.
     pub(crate) fn frame_rate(&self) -> f32 {
         match self {
             Self::GridEye {
@@ -272,49 +825,130 @@ impl CameraSettings {
                 f32::from(*frame_rate)
             }
             #[cfg(feature = "mock_camera")]
-            Self::MockCamera { frame_rate, .. } => *frame_rate,
+            Self::MockCamera { frame_rate, .. }
+            | Self::Synthetic { frame_rate, .. }
+            | Self::TestPattern { frame_rate, .. } => *frame_rate,
         }
     }
 
     pub(crate) fn create_camera(&self) -> anyhow::Result<Box<dyn ThermalCamera + Send>> {
+        if self.common().read_pattern.is_some()
+            && !matches!(self, Self::Mlx90640 { .. } | Self::Mlx90641 { .. })
+        {
+            warn!("read_pattern is only supported by Melexis cameras, ignoring it");
+        }
         Ok(match self {
-            Self::GridEye { address, .. } => Box::new(thermal_camera::GridEye::new(
-                self.i2c_bus().expect("GridEye uses I2C")?,
-                *address,
-            )?),
-            Self::Mlx90640 { address, mode, .. } => {
+            Self::GridEye {
+                address,
+                thermistor_interval,
+                ..
+            } => {
+                warn_on_grideye_address(*address);
+                Box::new(thermal_camera::GridEye::new(
+                    self.i2c_bus().expect("GridEye uses I2C")?,
+                    *address,
+                    *thermistor_interval,
+                )?)
+            }
+            Self::Mlx90640 { address, .. } => {
                 let bus = self.i2c_bus().expect("MLX90640 uses I2C")?;
                 let mut driver = mlx9064x::Mlx90640Driver::new(bus, *address)?;
-                driver.set_access_pattern((*mode).into())?;
+                if let Some(pattern) = self.common().read_pattern {
+                    driver.set_access_pattern(pattern.into())?;
+                }
                 Box::new(thermal_camera::Mlx90640::new(driver))
             }
             Self::Mlx90641 { address, .. } => {
                 let bus = self.i2c_bus().expect("MLX90641 uses I2C")?;
-                Box::new(thermal_camera::Mlx90641::new(
-                    mlx9064x::Mlx90641Driver::new(bus, *address)?,
-                ))
+                let mut driver = mlx9064x::Mlx90641Driver::new(bus, *address)?;
+                if let Some(pattern) = self.common().read_pattern {
+                    driver.set_access_pattern(pattern.into())?;
+                }
+                Box::new(thermal_camera::Mlx90641::new(driver))
             }
             #[cfg(feature = "mock_camera")]
             Self::MockCamera {
-                path, repeat_mode, ..
+                path,
+                frame_rate,
+                repeat_mode,
+                playback,
+                speed,
+                ..
             } => {
                 use crate::camera::mock_camera::MockCamera;
-                use crate::recorded_data::RecordedData;
 
-                let extension = path.extension().map(|s| s.to_str()).flatten();
-                let measurements: Vec<RecordedData> = match extension {
-                    Some("toml") => {
-                        let data_string = std::fs::read_to_string(path)?;
-                        toml::from_str(&data_string).map_err(anyhow::Error::from)
+                if !(speed.is_finite() && *speed > 0.0) {
+                    anyhow::bail!("The mock camera speed must be greater than 0");
+                }
+                if *playback == super::PlaybackTiming::FrameRate
+                    && !(frame_rate.is_finite() && *frame_rate > 0.0)
+                {
+                    anyhow::bail!("The mock camera frame rate must be greater than 0");
+                }
+                let measurements = load_recording(path)?;
+                let mock_cam =
+                    MockCamera::new(measurements, *repeat_mode, *playback, *frame_rate, *speed);
+                Box::new(mock_cam)
+            }
+            #[cfg(feature = "mock_camera")]
+            Self::Synthetic {
+                background,
+                width,
+                height,
+                background_temperature,
+                people,
+                ..
+            } => {
+                use crate::camera::synthetic_camera::SyntheticCamera;
+                use crate::image_buffer::ThermalImage;
+
+                let (background, temperature) = match background {
+                    Some(path) => {
+                        let measurement = load_recording(path)?
+                            .into_iter()
+                            .next()
+                            .ok_or_else(|| anyhow::anyhow!("The background recording is empty"))?
+                            .measurement;
+                        (measurement.image.as_ref().clone(), measurement.temperature)
                     }
-                    // treat everything as bincode if we don't know the extension
-                    _ => {
-                        let file = std::fs::File::open(path)?;
-                        RecordedData::from_bincode(file)
+                    None => {
+                        let pixel = [background_temperature.in_celsius()].into();
+                        (
+                            ThermalImage::from_pixel(*width, *height, pixel),
+                            *background_temperature,
+                        )
                     }
-                }?;
-                let mock_cam = MockCamera::new(measurements, *repeat_mode);
-                Box::new(mock_cam)
+                };
+                Box::new(SyntheticCamera::new(
+                    background,
+                    temperature,
+                    people.clone(),
+                ))
+            }
+            #[cfg(feature = "mock_camera")]
+            Self::TestPattern {
+                pattern,
+                width,
+                height,
+                minimum,
+                maximum,
+                speed,
+                ..
+            } => {
+                use crate::camera::test_pattern_camera::TestPatternCamera;
+
+                if *width == 0 || *height == 0 {
+                    anyhow::bail!("The test pattern must be at least 1x1");
+                }
+                if !speed.is_finite() {
+                    anyhow::bail!("The test pattern speed must be a number");
+                }
+                Box::new(TestPatternCamera::new(
+                    *pattern,
+                    (*width, *height),
+                    (*minimum, *maximum),
+                    *speed,
+                ))
             }
         })
     }
@@ -322,11 +956,15 @@ impl CameraSettings {
 
 #[cfg(test)]
 mod de_tests {
+    use std::num::NonZeroUsize;
     use std::path::PathBuf;
+    use std::time::Duration;
 
     use crate::camera::Bus;
 
-    use super::{CameraSettings, CommonCameraSettings, ExtraMap, Mlx90640AccessMode, Rotation};
+    use super::{
+        CameraSettings, CommonCameraSettings, ExtraMap, MelexisAccessPattern, PowerMode, Rotation,
+    };
 
     #[test]
     fn bus_from_num() {
@@ -458,13 +1096,160 @@ mod de_tests {
         let parsed: CameraSettings = parsed.unwrap();
         let expected = CameraSettings::GridEye {
             bus: Bus::Number(1),
-            address: amg88::Address::High,
+            address: 0x69,
             frame_rate: amg88::FrameRateValue::Fps10,
+            thermistor_interval: NonZeroUsize::new(1).unwrap(),
             common: CommonCameraSettings::default(),
         };
         assert_eq!(parsed, expected);
     }
 
+    #[test]
+    fn grideye_custom_address() {
+        let source = r#"
+        kind = "grideye"
+        bus = 1
+        address = 0x70
+        "#;
+        let parsed = toml::from_str(source);
+        assert!(parsed.is_ok(), "Unable to parse TOML: {:?}", parsed);
+        let parsed: CameraSettings = parsed.unwrap();
+        let expected = CameraSettings::GridEye {
+            bus: Bus::Number(1),
+            address: 0x70,
+            frame_rate: amg88::FrameRateValue::Fps10,
+            thermistor_interval: NonZeroUsize::new(1).unwrap(),
+            common: CommonCameraSettings::default(),
+        };
+        assert_eq!(parsed, expected);
+        let parsed: Result<CameraSettings, _> = toml::from_str(
+            r#"
+            kind = "grideye"
+            bus = 1
+            address = 0x100
+            "#,
+        );
+        assert!(parsed.is_err(), "Parsed an address larger than a u8");
+    }
+
+    #[test]
+    fn check_address() {
+        let check = |source: &str| toml::from_str::<CameraSettings>(source).unwrap().check();
+        assert!(check("kind = \"grideye\"\nbus = 1\naddress = 0x69").is_ok());
+        // Unusual, but still allowed
+        assert!(check("kind = \"grideye\"\nbus = 1\naddress = 0x70").is_ok());
+        assert!(check("kind = \"mlx90640\"\nbus = 1\naddress = 0x33\nframe_rate = 2").is_ok());
+        // Reserved addresses
+        assert!(check("kind = \"mlx90640\"\nbus = 1\naddress = 0x03\nframe_rate = 2").is_err());
+        assert!(check("kind = \"mlx90641\"\nbus = 1\naddress = 0x7f\nframe_rate = 2").is_err());
+    }
+
+    #[test]
+    fn dead_pixels() {
+        let source = r#"
+        kind = "mlx90641"
+        bus = 1
+        address = 0x33
+        frame_rate = 2
+        dead_pixels = [[3, 4], [15, 11]]
+        "#;
+        let settings: CameraSettings = toml::from_str(source).unwrap();
+        assert_eq!(settings.common().dead_pixels, vec![(3, 4), (15, 11)]);
+        assert!(settings.check().is_ok());
+        let settings: CameraSettings =
+            toml::from_str(&source.replace("[15, 11]", "[16, 11]")).unwrap();
+        assert!(settings.check().is_err());
+        let settings: CameraSettings =
+            toml::from_str(&source.replace("[15, 11]", "[15, 12]")).unwrap();
+        assert!(settings.check().is_err());
+    }
+
+    #[test]
+    fn valid_range() {
+        let source = r#"
+        kind = "mlx90640"
+        bus = 1
+        address = 0x33
+        frame_rate = 2
+        valid_range = { minimum = -40, maximum = 300 }
+        "#;
+        let settings: CameraSettings = toml::from_str(source).unwrap();
+        assert!(settings.valid_range().is_some());
+        assert!(settings.check().is_ok());
+        // Invalid ranges are rejected when parsing, so a normal start catches them too.
+        let inverted = source.replace("maximum = 300", "maximum = -50");
+        assert!(toml::from_str::<CameraSettings>(&inverted).is_err());
+        let max_invalid = source.replace("}", ", max_invalid = 2 }");
+        assert!(toml::from_str::<CameraSettings>(&max_invalid).is_err());
+    }
+
+    #[test]
+    fn power_mode() {
+        let source = r#"
+        kind = "grideye"
+        bus = 1
+        address = 0x69
+        "#;
+        let settings: CameraSettings = toml::from_str(source).unwrap();
+        assert_eq!(settings.common().power_mode, PowerMode::Continuous);
+        assert_eq!(settings.standby_interval().unwrap(), None);
+        assert_eq!(settings.measurement_rate(), settings.frame_rate());
+        let standby = format!("{}power_mode = \"standby\"", source);
+        let settings: CameraSettings = toml::from_str(&standby).unwrap();
+        assert_eq!(
+            settings.standby_interval().unwrap(),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(settings.measurement_rate(), 0.1);
+        // An interval shorter than a frame never puts the camera in standby.
+        let short: CameraSettings =
+            toml::from_str(&format!("{}\nstandby_interval = 0.01", standby)).unwrap();
+        assert_eq!(short.measurement_rate(), short.frame_rate());
+        assert!(settings.check().is_ok());
+        let settings: CameraSettings =
+            toml::from_str(&format!("{}\nstandby_interval = 0", standby)).unwrap();
+        assert!(settings.standby_interval().is_err());
+        assert!(settings.check().is_err());
+        assert!(
+            toml::from_str::<CameraSettings>(&format!("{}power_mode = \"off\"", source)).is_err()
+        );
+    }
+
+    #[test]
+    fn check_calibration() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("calibration.csv");
+        let row = vec!["0.5"; 16].join(",");
+        std::fs::write(&path, vec![row; 12].join("\n")).unwrap();
+        let source = format!(
+            "kind = \"mlx90641\"\nbus = 1\naddress = 0x33\nframe_rate = 2\ncalibration = {:?}",
+            path
+        );
+        let settings: CameraSettings = toml::from_str(&source).unwrap();
+        assert!(settings.check().is_ok());
+        // The wrong size for the camera
+        let source = source.replace("mlx90641", "mlx90640");
+        let settings: CameraSettings = toml::from_str(&source).unwrap();
+        assert!(settings.check().is_err());
+        // A missing file
+        std::fs::remove_file(&path).unwrap();
+        assert!(settings.check().is_err());
+    }
+
+    #[cfg(feature = "mock_camera")]
+    #[test]
+    fn check_mock_path() {
+        let settings = CameraSettings::MockCamera {
+            path: PathBuf::from("/not/a/real/path.bin"),
+            frame_rate: 10.0,
+            repeat_mode: crate::camera::RepeatMode::default(),
+            playback: crate::camera::PlaybackTiming::default(),
+            speed: 1.0,
+            common: CommonCameraSettings::default(),
+        };
+        assert!(settings.check().is_err());
+    }
+
     #[test]
     fn grideye_full_bus_num() {
         let source = r#"
@@ -475,20 +1260,23 @@ mod de_tests {
         flip_horizontal = true
         flip_vertical = true
         frame_rate = 1
+        thermistor_interval = 5
         "#;
         let parsed = toml::from_str(source);
         assert!(parsed.is_ok(), "Unable to parse TOML: {:?}", parsed);
         let parsed: CameraSettings = parsed.unwrap();
         let expected = CameraSettings::GridEye {
             bus: Bus::Number(3),
-            address: amg88::Address::Low,
+            address: 0x68,
             frame_rate: amg88::FrameRateValue::Fps1,
+            thermistor_interval: NonZeroUsize::new(5).unwrap(),
             common: CommonCameraSettings {
                 rotation: Rotation::OneEighty,
                 flip_horizontal: true.into(),
                 flip_vertical: true.into(),
                 round_temperature: None,
                 extra: ExtraMap::default(),
+                ..CommonCameraSettings::default()
             },
         };
         assert_eq!(parsed, expected);
@@ -511,7 +1299,6 @@ mod de_tests {
             bus: Bus::Number(1),
             address: 0x33,
             frame_rate: mlx9064x::FrameRate::Eight,
-            mode: Mlx90640AccessMode::Chess,
             common: CommonCameraSettings {
                 extra: std::iter::once(("path".to_string(), "/foo/bar/baz.bin".into())).collect(),
                 ..CommonCameraSettings::default()
@@ -535,7 +1322,6 @@ mod de_tests {
             bus: Bus::Number(1),
             address: 0x33,
             frame_rate: mlx9064x::FrameRate::Half,
-            mode: Mlx90640AccessMode::Chess,
             common: CommonCameraSettings::default(),
         };
         assert_eq!(parsed, expected);
@@ -564,6 +1350,8 @@ mod de_tests {
             path: PathBuf::from("/tmp/qux.bin"),
             frame_rate: 3.0,
             repeat_mode: crate::camera::RepeatMode::default(),
+            playback: crate::camera::PlaybackTiming::default(),
+            speed: 1.0,
             common: CommonCameraSettings {
                 extra,
                 ..CommonCameraSettings::default()
@@ -571,4 +1359,197 @@ mod de_tests {
         };
         assert_eq!(parsed, expected);
     }
+
+    #[cfg(feature = "mock_camera")]
+    #[test]
+    fn mock_camera_playback() {
+        let source = r#"
+        kind = "mock"
+        frame_rate = 5
+        path = "/tmp/qux.bin"
+        playback = "framerate"
+        speed = 2.5
+        "#;
+        let parsed = toml::from_str(source);
+        assert!(parsed.is_ok(), "Unable to parse TOML: {:?}", parsed);
+        let parsed: CameraSettings = parsed.unwrap();
+        let expected = CameraSettings::MockCamera {
+            path: PathBuf::from("/tmp/qux.bin"),
+            frame_rate: 5.0,
+            repeat_mode: crate::camera::RepeatMode::default(),
+            playback: crate::camera::PlaybackTiming::FrameRate,
+            speed: 2.5,
+            common: CommonCameraSettings::default(),
+        };
+        assert_eq!(parsed, expected);
+    }
+
+    #[cfg(feature = "mock_camera")]
+    #[test]
+    fn synthetic_camera() {
+        use crate::camera::SyntheticPerson;
+        use crate::temperature::Temperature;
+
+        let source = r#"
+        kind = "synthetic"
+        frame_rate = 10
+        width = 16
+        [[people]]
+        position = [0, 4]
+        radius = 1.5
+        temperature = 37
+        velocity = [0.5, 0]
+        [[people]]
+        position = [8, 0]
+        radius = 1
+        temperature = { fahrenheit = 98.6 }
+        "#;
+        let parsed = toml::from_str(source);
+        assert!(parsed.is_ok(), "Unable to parse TOML: {:?}", parsed);
+        let parsed: CameraSettings = parsed.unwrap();
+        let expected = CameraSettings::Synthetic {
+            frame_rate: 10.0,
+            background: None,
+            width: 16,
+            height: 8,
+            background_temperature: Temperature::Celsius(20.0),
+            people: vec![
+                SyntheticPerson {
+                    position: [0.0, 4.0],
+                    radius: 1.5,
+                    temperature: Temperature::Celsius(37.0),
+                    velocity: [0.5, 0.0],
+                },
+                SyntheticPerson {
+                    position: [8.0, 0.0],
+                    radius: 1.0,
+                    temperature: Temperature::Fahrenheit(98.6),
+                    velocity: [0.0, 0.0],
+                },
+            ],
+            common: CommonCameraSettings::default(),
+        };
+        assert_eq!(parsed, expected);
+    }
+
+    #[cfg(feature = "mock_camera")]
+    #[test]
+    fn test_pattern_camera() {
+        use crate::camera::TestPattern;
+        use crate::temperature::Temperature;
+
+        let source = r#"
+        kind = "test_pattern"
+        frame_rate = 4
+        pattern = "blob"
+        maximum = 37
+        "#;
+        let parsed: CameraSettings = toml::from_str(source).unwrap();
+        let expected = CameraSettings::TestPattern {
+            frame_rate: 4.0,
+            pattern: TestPattern::Blob,
+            width: 8,
+            height: 8,
+            minimum: Temperature::Celsius(20.0),
+            maximum: Temperature::Celsius(37.0),
+            speed: 0.5,
+            common: CommonCameraSettings::default(),
+        };
+        assert_eq!(parsed, expected);
+        assert_eq!(parsed.resolution(), Some((8, 8)));
+    }
+
+    #[test]
+    fn read_pattern() {
+        let source = r#"
+        kind = "mlx90641"
+        bus = 1
+        address = 0x33
+        frame_rate = 2
+        "#;
+        let parsed: CameraSettings = toml::from_str(source).unwrap();
+        assert_eq!(parsed.common().read_pattern, None);
+        for (value, expected) in [
+            ("chess", MelexisAccessPattern::Chess),
+            ("interleaved", MelexisAccessPattern::Interleave),
+            ("interlace", MelexisAccessPattern::Interleave),
+        ] {
+            let with_pattern = format!("{}read_pattern = \"{}\"", source, value);
+            let parsed: CameraSettings = toml::from_str(&with_pattern).unwrap();
+            assert_eq!(parsed.common().read_pattern, Some(expected));
+        }
+        // The older name for the setting still works.
+        let with_mode = format!("{}mode = \"interleave\"", source);
+        let parsed: CameraSettings = toml::from_str(&with_mode).unwrap();
+        assert_eq!(
+            parsed.common().read_pattern,
+            Some(MelexisAccessPattern::Interleave)
+        );
+        let invalid = format!("{}read_pattern = \"checkerboard\"", source);
+        assert!(toml::from_str::<CameraSettings>(&invalid).is_err());
+    }
+
+    #[test]
+    fn reconnect() {
+        let source = r#"
+        kind = "grideye"
+        bus = 1
+        address = 0x69
+        "#;
+        let settings: CameraSettings = toml::from_str(source).unwrap();
+        assert_eq!(settings.reconnect_attempts(), 5);
+        assert_eq!(settings.reconnect_delay().unwrap(), Duration::from_secs(1));
+        let custom = format!("{}reconnect_attempts = 0\nreconnect_delay = 0.5", source);
+        let settings: CameraSettings = toml::from_str(&custom).unwrap();
+        assert_eq!(settings.reconnect_attempts(), 0);
+        assert_eq!(
+            settings.reconnect_delay().unwrap(),
+            Duration::from_millis(500)
+        );
+        let negative = format!("{}reconnect_delay = -1", source);
+        let settings: CameraSettings = toml::from_str(&negative).unwrap();
+        assert!(settings.reconnect_delay().is_err());
+    }
+
+    #[test]
+    fn rotated_resolution() {
+        let source = r#"
+        kind = "mlx90640"
+        bus = 1
+        address = 0x33
+        "#;
+        let settings: CameraSettings = toml::from_str(source).unwrap();
+        assert_eq!(settings.kind(), "mlx90640");
+        assert!(CameraSettings::KINDS.contains(&settings.kind()));
+        assert_eq!(settings.rotated_resolution(), Some((32, 24)));
+        let rotated = format!("{}rotation = 270", source);
+        let settings: CameraSettings = toml::from_str(&rotated).unwrap();
+        assert_eq!(settings.rotated_resolution(), Some((24, 32)));
+    }
+
+    #[test]
+    fn i2c_timeout() {
+        let source = r#"
+        kind = "mlx90640"
+        bus = 1
+        address = 0x33
+        "#;
+        let settings: CameraSettings = toml::from_str(source).unwrap();
+        assert_eq!(settings.i2c_timeout().unwrap(), None);
+        let custom = format!("{}i2c_timeout = 0.5", source);
+        let settings: CameraSettings = toml::from_str(&custom).unwrap();
+        assert_eq!(
+            settings.i2c_timeout().unwrap(),
+            Some(Duration::from_millis(500))
+        );
+        for invalid in ["0", "0.001", "60", "nan"] {
+            let invalid = format!("{}i2c_timeout = {}", source, invalid);
+            let settings: CameraSettings = toml::from_str(&invalid).unwrap();
+            assert!(
+                settings.i2c_timeout().is_err(),
+                "Accepted invalid timeout {}",
+                invalid
+            );
+        }
+    }
 }
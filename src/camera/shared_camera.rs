@@ -1,17 +1,24 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
+use anyhow::Context as _;
 use image::imageops;
-use tokio::sync::{broadcast, oneshot};
+use tokio::sync::{broadcast, oneshot, watch};
 use tracing::{debug, info, trace, warn};
 
 use std::convert::TryFrom;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{mpsc, Arc};
 use std::thread::sleep as thread_sleep;
+use std::time::{Duration, Instant, SystemTime};
 
+use crate::image_buffer::ThermalImage;
 use crate::temperature::Temperature;
 
+use super::calibration::Calibration;
+use super::dead_pixels::DeadPixels;
 use super::measurement::Measurement;
 use super::settings::{CameraSettings, Rotation};
-use super::thermal_camera::{ThermalCamera, YAxisDirection};
+use super::thermal_camera::{CameraSample, ThermalCamera, YAxisDirection};
+use super::valid_range::RangeFilter;
 
 #[derive(Debug)]
 pub(crate) enum CameraCommand {
@@ -19,20 +26,95 @@ pub(crate) enum CameraCommand {
     Subscribe(oneshot::Sender<broadcast::Receiver<Measurement>>),
     /// Create a new command channel and return it on the provided channel.
     CreateCommandChannel(oneshot::Sender<mpsc::Sender<CameraCommand>>),
+    /// Change the frame rate the camera is being polled at.
+    SetFrameRate(f32),
     /// Gracefully stop the camera thread.
     Shutdown,
 }
 
+/// The transformations applied to every image from a camera.
+///
+/// Cameras return images with the Y-axis pointing either up or down, so images are first
+/// normalized to have the origin in the top-left corner (with the Y-axis pointing down). Then the
+/// user requested flips and rotation are applied, in that order.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Orientation {
+    rotation: Rotation,
+    flip_horizontal: bool,
+    flip_vertical: bool,
+}
+
+impl Orientation {
+    pub(crate) fn apply(
+        &self,
+        mut image: ThermalImage,
+        y_direction: YAxisDirection,
+    ) -> ThermalImage {
+        // Flipping an image with the Y-axis pointing up cancels out normalizing it.
+        if (y_direction == YAxisDirection::Up) != self.flip_vertical {
+            imageops::flip_vertical_in_place(&mut image);
+        }
+        if self.flip_horizontal {
+            imageops::flip_horizontal_in_place(&mut image);
+        }
+        match self.rotation {
+            Rotation::Zero => image,
+            Rotation::Ninety => imageops::rotate90(&image),
+            Rotation::OneEighty => {
+                imageops::rotate180_in_place(&mut image);
+                image
+            }
+            Rotation::TwoSeventy => imageops::rotate270(&image),
+        }
+    }
+
+    /// The size of an image after being reoriented.
+    fn apply_to_size(&self, (width, height): (u32, u32)) -> (u32, u32) {
+        match self.rotation {
+            Rotation::Zero | Rotation::OneEighty => (width, height),
+            Rotation::Ninety | Rotation::TwoSeventy => (height, width),
+        }
+    }
+}
+
+impl From<&CameraSettings> for Orientation {
+    fn from(settings: &CameraSettings) -> Self {
+        Self {
+            rotation: settings.rotation(),
+            flip_horizontal: settings.flip_horizontal(),
+            flip_vertical: settings.flip_vertical(),
+        }
+    }
+}
+
+/// The longest to wait between attempts to reconnect to a camera.
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
 /// Retrieve measurements from a camera.
 ///
 /// This structure runs on a separate thread in an attempt to keep the timing as close to the
 /// camera frame rate as possible.
 pub(crate) struct Camera {
     camera: Box<dyn ThermalCamera + Send>,
-    rotation: Rotation,
-    flip_vertical: bool,
-    flip_horizontal: bool,
+    /// The settings the camera was created from, used to reconnect to the camera after an error.
+    settings: CameraSettings,
+    /// The frame rate the camera is currently being polled at.
+    frame_rate: f32,
+    reconnect_attempts: u32,
+    reconnect_delay: Duration,
+    /// Whether or not measurements are being read from the camera.
+    available: watch::Sender<bool>,
+    orientation: Orientation,
     round_temperature: Option<f32>,
+    calibration: Option<Calibration>,
+    dead_pixels: DeadPixels,
+    range_filter: Option<RangeFilter>,
+    /// If set, the camera is put into standby between measurements taken this far apart.
+    standby_interval: Option<Duration>,
+    /// The number of images that have been skipped for having invalid temperatures.
+    dropped_frames: Arc<AtomicUsize>,
+    /// The index of the next image read from the camera.
+    frame_index: u64,
     measurement_channel: broadcast::Sender<Measurement>,
     command_receiver: mpsc::Receiver<CameraCommand>,
     command_sender: mpsc::Sender<CameraCommand>,
@@ -46,33 +128,37 @@ impl Camera {
     pub(crate) fn measurement_loop(mut self) -> anyhow::Result<()> {
         loop {
             // Respond to any pending commands
-            for cmd in self.command_receiver.try_iter() {
-                match cmd {
-                    CameraCommand::Subscribe(response) => {
-                        debug!("Camera loop: replying to new subscriber");
-                        warn_on_oneshot_error(response.send(self.measurement_channel.subscribe()))
-                    }
-                    CameraCommand::CreateCommandChannel(response) => {
-                        debug!("Camera loop: creating new command channel");
-                        warn_on_oneshot_error(response.send(self.command_sender.clone()))
-                    }
-                    CameraCommand::Shutdown => {
-                        info!("Terminating camera loop");
-                        return Ok(());
-                    }
+            while let Ok(cmd) = self.command_receiver.try_recv() {
+                if !self.handle_command(cmd) {
+                    return Ok(());
                 }
             }
             // Capture a measurement from the camera, apply image transformations, and wait for the
             // next frame.
-            let super::thermal_camera::CameraSample {
+            let start = Instant::now();
+            let CameraSample {
                 mut image,
                 y_direction,
                 temperature,
                 frame_delay,
-            } = self.camera.sample()?;
+            } = match self.sample_with_reconnect()? {
+                Some(sample) => sample,
+                None => return Ok(()),
+            };
+            let timestamp = SystemTime::now();
+            let frame_index = self.frame_index;
+            self.frame_index += 1;
             // If the image has a NaN value in it, skip it
             if image.iter().any(|temperature| temperature.is_nan()) {
                 warn!("Measured image has NaN, skipping");
+                self.dropped_frames.fetch_add(1, Ordering::Relaxed);
+            } else if !self
+                .range_filter
+                .as_mut()
+                .is_none_or(|filter| filter.apply(&mut image))
+            {
+                warn!("Measured image has too many implausible temperatures, skipping");
+                self.dropped_frames.fetch_add(1, Ordering::Relaxed);
             } else {
                 let temperature = self.round_temperature.map_or(temperature, |precision| {
                     let new_value = (temperature.value() / precision).round() * precision;
@@ -81,28 +167,16 @@ impl Camera {
                         Temperature::Fahrenheit(_) => Temperature::Fahrenheit(new_value),
                     }
                 });
-                // If the image returned from the camera is with the Y-Axis pointing up, or if the
-                // user has specified the image should be flipped, we need to flip the image it along
-                // the Y-axis.
-                if y_direction == YAxisDirection::Up || self.flip_vertical {
-                    imageops::flip_vertical_in_place(&mut image);
+                if let Some(calibration) = &self.calibration {
+                    calibration.apply(&mut image)?;
                 }
-                // The rest of the basic image transformations
-                if self.flip_horizontal {
-                    imageops::flip_horizontal_in_place(&mut image);
-                }
-                image = match self.rotation {
-                    Rotation::Zero => image,
-                    Rotation::Ninety => imageops::rotate90(&image),
-                    Rotation::OneEighty => {
-                        imageops::rotate180_in_place(&mut image);
-                        image
-                    }
-                    Rotation::TwoSeventy => imageops::rotate270(&image),
-                };
+                self.dead_pixels.apply(&mut image);
+                let image = self.orientation.apply(image, y_direction);
                 let channel_measurement = Measurement {
                     image: Arc::new(image),
                     temperature,
+                    frame_index,
+                    timestamp,
                 };
                 // Don't care if it fails or not, as failures are temporary.
                 #[allow(unused_must_use)]
@@ -110,30 +184,202 @@ impl Camera {
                     self.measurement_channel.send(channel_measurement);
                 }
             }
-            trace!("Waiting {}us for the next frame", frame_delay.as_micros());
-            thread_sleep(frame_delay);
+            match self.standby_interval {
+                None => {
+                    trace!("Waiting {}us for the next frame", frame_delay.as_micros());
+                    thread_sleep(frame_delay);
+                }
+                Some(interval) => {
+                    let standby = interval.saturating_sub(start.elapsed());
+                    if standby > frame_delay {
+                        trace!("Putting the camera in standby for {:?}", standby);
+                        let frame_rate = self.frame_rate;
+                        // Errors here are handled when the next sample is taken.
+                        if let Err(err) = self.camera.sleep() {
+                            warn!("Unable to put the camera in standby: {:?}", err);
+                        }
+                        if !self.wait(standby) {
+                            return Ok(());
+                        }
+                        if let Err(err) = self.camera.wake() {
+                            warn!("Unable to wake the camera from standby: {:?}", err);
+                        }
+                        // Waking up restores the frame rate from before standby, so re-apply any
+                        // change made while the camera was asleep.
+                        if self.frame_rate != frame_rate {
+                            if let Err(err) = self.camera.set_frame_rate(self.frame_rate) {
+                                warn!("Unable to change camera frame rate: {:?}", err);
+                            }
+                        }
+                    } else {
+                        thread_sleep(frame_delay);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Respond to a command, returning `false` if the camera loop should stop.
+    fn handle_command(&mut self, cmd: CameraCommand) -> bool {
+        match cmd {
+            CameraCommand::Subscribe(response) => {
+                debug!("Camera loop: replying to new subscriber");
+                warn_on_oneshot_error(response.send(self.measurement_channel.subscribe()))
+            }
+            CameraCommand::CreateCommandChannel(response) => {
+                debug!("Camera loop: creating new command channel");
+                warn_on_oneshot_error(response.send(self.command_sender.clone()))
+            }
+            CameraCommand::SetFrameRate(frame_rate) => {
+                debug!(frame_rate, "Camera loop: changing frame rate");
+                // A camera not supporting a frame rate isn't fatal, it'll just keep going
+                // at the previous rate.
+                match self.camera.set_frame_rate(frame_rate) {
+                    Ok(_) => self.frame_rate = frame_rate,
+                    Err(err) => {
+                        warn!(frame_rate, "Unable to change camera frame rate: {:?}", err)
+                    }
+                }
+            }
+            CameraCommand::Shutdown => {
+                info!("Terminating camera loop");
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Take a sample from the camera, reconnecting to the camera if there's an error.
+    ///
+    /// Each reconnection attempt waits twice as long as the last one. If the camera can't be
+    /// reconnected to in `reconnect_attempts` tries the last error is returned. Errors that
+    /// reconnecting won't fix (like the end of a recording) are returned immediately. `None` is
+    /// returned if the camera loop was shut down while waiting to reconnect.
+    fn sample_with_reconnect(&mut self) -> anyhow::Result<Option<CameraSample>> {
+        let mut attempt: u32 = 0;
+        loop {
+            match self.camera.sample() {
+                Ok(sample) => {
+                    if attempt > 0 {
+                        info!("Reconnected to the camera");
+                        self.set_available(true);
+                    }
+                    return Ok(Some(sample));
+                }
+                Err(err) if !can_reconnect(&err) => {
+                    self.set_available(false);
+                    return Err(err);
+                }
+                Err(err) if attempt < self.reconnect_attempts => {
+                    let delay = self
+                        .reconnect_delay
+                        .saturating_mul(2u32.saturating_pow(attempt))
+                        .min(MAX_RECONNECT_DELAY);
+                    attempt += 1;
+                    warn!(
+                        attempt,
+                        ?delay,
+                        "Error reading from the camera, reconnecting: {:?}",
+                        err
+                    );
+                    self.set_available(false);
+                    if !self.wait(delay) {
+                        return Ok(None);
+                    }
+                    match self.reconnect() {
+                        Ok(camera) => self.camera = camera,
+                        Err(err) => warn!("Unable to reconnect to the camera: {:?}", err),
+                    }
+                }
+                Err(err) => {
+                    self.set_available(false);
+                    return Err(err.context("Unable to reconnect to the camera"));
+                }
+            }
+        }
+    }
+
+    /// Create a new connection to the camera, with the same settings as the current one.
+    fn reconnect(&self) -> anyhow::Result<Box<dyn ThermalCamera + Send>> {
+        let mut camera = self.settings.create_camera()?;
+        camera.set_frame_rate(self.frame_rate)?;
+        Ok(camera)
+    }
+
+    /// Wait for `delay`, while still responding to commands.
+    ///
+    /// Returns `false` if the camera loop should stop.
+    fn wait(&mut self, delay: Duration) -> bool {
+        let deadline = Instant::now() + delay;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.command_receiver.recv_timeout(remaining) {
+                Ok(cmd) => {
+                    if !self.handle_command(cmd) {
+                        return false;
+                    }
+                }
+                // The command receiver can't be disconnected, as this struct has a sender.
+                Err(_) => return true,
+            }
         }
     }
 
+    fn set_available(&self, available: bool) {
+        // An error just means nobody is watching.
+        let _ = self.available.send(available);
+    }
+
+    /// A receiver for whether or not measurements are being read from the camera.
+    pub(crate) fn availability(&self) -> watch::Receiver<bool> {
+        self.available.subscribe()
+    }
+
     pub(crate) fn command_channel(&self) -> mpsc::Sender<CameraCommand> {
         self.command_sender.clone()
     }
+
+    /// The width and height of the measurements from this camera, after any rotation.
+    pub(crate) fn resolution(&self) -> (u32, u32) {
+        self.orientation.apply_to_size(self.camera.resolution())
+    }
+
+    /// A human-readable name for the kind of camera.
+    pub(crate) fn kind_name(&self) -> &'static str {
+        self.camera.kind_name()
+    }
+
+    /// A counter of the images that have been skipped for having invalid temperatures.
+    pub(crate) fn dropped_frames(&self) -> Arc<AtomicUsize> {
+        Arc::clone(&self.dropped_frames)
+    }
 }
 
 impl TryFrom<&CameraSettings> for Camera {
     type Error = anyhow::Error;
 
     fn try_from(settings: &CameraSettings) -> Result<Self, Self::Error> {
+        settings.check().context("Invalid camera settings")?;
         let mut camera = settings.create_camera()?;
         camera.set_frame_rate(settings.frame_rate())?;
         let (measurement_channel, _) = broadcast::channel(1);
         let (command_sender, command_receiver) = mpsc::channel();
+        let (available, _) = watch::channel(true);
         Ok(Self {
             camera,
-            rotation: settings.rotation(),
-            flip_vertical: settings.flip_vertical(),
-            flip_horizontal: settings.flip_horizontal(),
+            settings: settings.clone(),
+            frame_rate: settings.frame_rate(),
+            reconnect_attempts: settings.reconnect_attempts(),
+            reconnect_delay: settings.reconnect_delay()?,
+            available,
+            orientation: Orientation::from(settings),
             round_temperature: settings.round_temperature(),
+            calibration: settings.calibration()?,
+            dead_pixels: settings.dead_pixels(),
+            range_filter: settings.valid_range().map(RangeFilter::new),
+            standby_interval: settings.standby_interval()?,
+            dropped_frames: Arc::default(),
+            frame_index: 0,
             measurement_channel,
             command_receiver,
             command_sender,
@@ -141,6 +387,18 @@ impl TryFrom<&CameraSettings> for Camera {
     }
 }
 
+/// Whether reconnecting to the camera could fix `err`.
+#[cfg(feature = "mock_camera")]
+fn can_reconnect(err: &anyhow::Error) -> bool {
+    // Reconnecting to a mock camera restarts the recording from the beginning.
+    !err.is::<super::EndOfRecording>()
+}
+
+#[cfg(not(feature = "mock_camera"))]
+fn can_reconnect(_err: &anyhow::Error) -> bool {
+    true
+}
+
 /// Simply a warning function for oneshot::Sender::send() errors.
 fn warn_on_oneshot_error<T>(oneshot_send_result: Result<(), T>) {
     match oneshot_send_result {
@@ -151,3 +409,222 @@ fn warn_on_oneshot_error<T>(oneshot_send_result: Result<(), T>) {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use image::ImageBuffer;
+
+    #[cfg(feature = "mock_camera")]
+    use super::{Camera, CameraCommand, CameraSettings, ThermalCamera};
+    use super::{Orientation, Rotation, YAxisDirection};
+    use crate::image_buffer::ThermalImage;
+    #[cfg(feature = "mock_camera")]
+    use futures::FutureExt;
+    #[cfg(feature = "mock_camera")]
+    use std::convert::TryFrom;
+    #[cfg(feature = "mock_camera")]
+    use std::time::{Duration, Instant};
+
+    /// A 3x2 image, with each pixel numbered left to right, top to bottom:
+    ///
+    /// ```text
+    /// 0 1 2
+    /// 3 4 5
+    /// ```
+    fn test_image() -> ThermalImage {
+        ImageBuffer::from_raw(3, 2, (0..6).map(|n| n as f32).collect()).unwrap()
+    }
+
+    fn assert_image(image: ThermalImage, width: u32, height: u32, expected: &[f32]) {
+        assert_eq!(image.dimensions(), (width, height));
+        assert_eq!(image.into_raw(), expected);
+    }
+
+    fn rotated(rotation: Rotation) -> Orientation {
+        Orientation {
+            rotation,
+            ..Orientation::default()
+        }
+    }
+
+    #[test]
+    fn rotate_zero() {
+        let image = rotated(Rotation::Zero).apply(test_image(), YAxisDirection::Down);
+        assert_image(image, 3, 2, &[0.0, 1.0, 2.0, 3.0, 4.0, 5.0]);
+    }
+
+    #[test]
+    fn rotate_ninety() {
+        let image = rotated(Rotation::Ninety).apply(test_image(), YAxisDirection::Down);
+        assert_image(image, 2, 3, &[3.0, 0.0, 4.0, 1.0, 5.0, 2.0]);
+    }
+
+    #[test]
+    fn rotate_one_eighty() {
+        let image = rotated(Rotation::OneEighty).apply(test_image(), YAxisDirection::Down);
+        assert_image(image, 3, 2, &[5.0, 4.0, 3.0, 2.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn rotate_two_seventy() {
+        let image = rotated(Rotation::TwoSeventy).apply(test_image(), YAxisDirection::Down);
+        assert_image(image, 2, 3, &[2.0, 5.0, 1.0, 4.0, 0.0, 3.0]);
+    }
+
+    #[test]
+    fn y_axis_up() {
+        let image = Orientation::default().apply(test_image(), YAxisDirection::Up);
+        assert_image(image, 3, 2, &[3.0, 4.0, 5.0, 0.0, 1.0, 2.0]);
+        // Flipping vertically cancels out the normalization
+        let flipped = Orientation {
+            flip_vertical: true,
+            ..Orientation::default()
+        };
+        let image = flipped.apply(test_image(), YAxisDirection::Up);
+        assert_image(image, 3, 2, &[0.0, 1.0, 2.0, 3.0, 4.0, 5.0]);
+    }
+
+    #[test]
+    fn flips_before_rotation() {
+        let orientation = Orientation {
+            rotation: Rotation::Ninety,
+            flip_horizontal: true,
+            flip_vertical: false,
+        };
+        let image = orientation.apply(test_image(), YAxisDirection::Down);
+        // Flipped horizontally:
+        // 2 1 0
+        // 5 4 3
+        assert_image(image, 2, 3, &[5.0, 2.0, 4.0, 1.0, 3.0, 0.0]);
+    }
+
+    #[cfg(feature = "mock_camera")]
+    #[test]
+    fn resolution() {
+        let source = "kind = \"test_pattern\"\nframe_rate = 10\nwidth = 4\nheight = 2\n";
+        let settings: CameraSettings = toml::from_str(source).unwrap();
+        let camera = Camera::try_from(&settings).unwrap();
+        assert_eq!(camera.resolution(), (4, 2));
+        assert_eq!(camera.kind_name(), "Test pattern");
+        let rotated = format!("{}rotation = 90", source);
+        let settings: CameraSettings = toml::from_str(&rotated).unwrap();
+        let camera = Camera::try_from(&settings).unwrap();
+        assert_eq!(camera.resolution(), (2, 4));
+    }
+
+    #[cfg(feature = "mock_camera")]
+    #[test]
+    fn invalid_settings() {
+        let source = "kind = \"test_pattern\"\nframe_rate = 10\nwidth = 4\nheight = 2\n";
+        let dead_pixel = format!("{}dead_pixels = [[4, 0]]", source);
+        let settings: CameraSettings = toml::from_str(&dead_pixel).unwrap();
+        assert!(Camera::try_from(&settings).is_err());
+    }
+
+    #[cfg(feature = "mock_camera")]
+    #[test]
+    fn shutdown_in_standby() {
+        let source = "kind = \"test_pattern\"\nframe_rate = 10\npower_mode = \"standby\"\n";
+        let standby = format!("{}standby_interval = 600", source);
+        let settings: CameraSettings = toml::from_str(&standby).unwrap();
+        let camera = Camera::try_from(&settings).unwrap();
+        let commands = camera.command_channel();
+        let mut measurements = camera.measurement_channel.subscribe();
+        let camera_thread = std::thread::spawn(move || camera.measurement_loop());
+        // Wait for the first measurement, after which the camera goes into standby.
+        while measurements.try_recv().is_err() {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let start = Instant::now();
+        commands.send(CameraCommand::Shutdown).unwrap();
+        camera_thread.join().unwrap().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(60));
+    }
+
+    /// A camera that has been disconnected.
+    #[cfg(feature = "mock_camera")]
+    struct DisconnectedCamera;
+
+    #[cfg(feature = "mock_camera")]
+    impl ThermalCamera for DisconnectedCamera {
+        fn sample(&mut self) -> anyhow::Result<super::CameraSample> {
+            Err(anyhow::anyhow!("No such device"))
+        }
+
+        fn resolution(&self) -> (u32, u32) {
+            (0, 0)
+        }
+
+        fn kind_name(&self) -> &'static str {
+            "Disconnected camera"
+        }
+
+        fn set_frame_rate(&mut self, _frame_rate: f32) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "mock_camera")]
+    fn disconnected_camera(reconnect_attempts: u32) -> Camera {
+        let source = format!(
+            "kind = \"test_pattern\"\nframe_rate = 10\nreconnect_attempts = {}\nreconnect_delay = 0",
+            reconnect_attempts
+        );
+        let settings: CameraSettings = toml::from_str(&source).unwrap();
+        let mut camera = Camera::try_from(&settings).unwrap();
+        camera.camera = Box::new(DisconnectedCamera);
+        camera
+    }
+
+    #[cfg(feature = "mock_camera")]
+    #[test]
+    fn reconnect() {
+        let mut camera = disconnected_camera(1);
+        let mut availability = camera.availability();
+        assert!(camera.sample_with_reconnect().unwrap().is_some());
+        // The camera was unavailable, then available again.
+        assert!(availability.changed().now_or_never().is_some());
+        assert!(*availability.borrow());
+    }
+
+    #[cfg(feature = "mock_camera")]
+    #[test]
+    fn reconnect_disabled() {
+        let mut camera = disconnected_camera(0);
+        let availability = camera.availability();
+        assert!(camera.sample_with_reconnect().is_err());
+        assert!(!*availability.borrow());
+    }
+
+    #[cfg(feature = "mock_camera")]
+    #[test]
+    fn shutdown_while_reconnecting() {
+        let mut camera = disconnected_camera(1);
+        camera.reconnect_delay = std::time::Duration::from_secs(60);
+        camera
+            .command_channel()
+            .send(CameraCommand::Shutdown)
+            .unwrap();
+        assert!(camera.sample_with_reconnect().unwrap().is_none());
+    }
+
+    #[cfg(feature = "mock_camera")]
+    #[test]
+    fn end_of_recording() {
+        use crate::camera::mock_camera::MockCamera;
+        use crate::camera::{EndOfRecording, PlaybackTiming, RepeatMode};
+        let mut camera = disconnected_camera(5);
+        camera.camera = Box::new(MockCamera::new(
+            Vec::new(),
+            RepeatMode::None,
+            PlaybackTiming::Recorded,
+            1.0,
+            1.0,
+        ));
+        let availability = camera.availability();
+        // Reconnecting would restart the recording, so the error is returned straight away.
+        let err = camera.sample_with_reconnect().err().unwrap();
+        assert!(err.is::<EndOfRecording>());
+        assert!(!*availability.borrow());
+    }
+}
@@ -0,0 +1,245 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+use std::time::Duration;
+
+use tracing::trace;
+
+use crate::image_buffer::ThermalImage;
+use crate::temperature::Temperature;
+
+use super::thermal_camera::{CameraSample, ThermalCamera, YAxisDirection};
+
+/// A warm blob standing in for a person, moving across the image at a constant velocity.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, schemars::JsonSchema, serde::Serialize)]
+pub(crate) struct SyntheticPerson {
+    /// The center of the blob in the first frame, as `[x, y]` in pixels.
+    pub(crate) position: [f32; 2],
+
+    /// The radius of the blob in pixels.
+    pub(crate) radius: f32,
+
+    /// The temperature of the blob.
+    pub(crate) temperature: Temperature,
+
+    /// How far the blob moves each frame, as `[x, y]` in pixels.
+    #[serde(default)]
+    pub(crate) velocity: [f32; 2],
+}
+
+impl SyntheticPerson {
+    /// The center of the blob for the given frame.
+    fn position_at(&self, frame_number: usize) -> [f32; 2] {
+        let frame_number = frame_number as f32;
+        [
+            self.position[0] + self.velocity[0] * frame_number,
+            self.position[1] + self.velocity[1] * frame_number,
+        ]
+    }
+
+    /// Whether the given pixel is covered by this blob in the given frame.
+    ///
+    /// A pixel is covered if its center is within the radius of the blob.
+    fn covers(&self, frame_number: usize, x: u32, y: u32) -> bool {
+        let [center_x, center_y] = self.position_at(frame_number);
+        let delta_x = x as f32 + 0.5 - center_x;
+        let delta_y = y as f32 + 0.5 - center_y;
+        delta_x.hypot(delta_y) <= self.radius
+    }
+}
+
+/// A camera generating frames from a static background and a list of moving people.
+///
+/// As the generated frames are deterministic, the number of people visible in each frame is known
+/// ahead of time, which makes this camera useful for testing the occupancy tracker.
+pub(crate) struct SyntheticCamera {
+    background: ThermalImage,
+    temperature: Temperature,
+    people: Vec<SyntheticPerson>,
+    frame_rate: f32,
+    frame_number: usize,
+}
+
+impl SyntheticCamera {
+    pub(crate) fn new(
+        background: ThermalImage,
+        temperature: Temperature,
+        people: Vec<SyntheticPerson>,
+    ) -> Self {
+        Self {
+            background,
+            temperature,
+            people,
+            frame_rate: 1.0,
+            frame_number: 0,
+        }
+    }
+
+    /// Generate the image for the given frame.
+    pub(crate) fn render(&self, frame_number: usize) -> ThermalImage {
+        let mut image = self.background.clone();
+        for (x, y, pixel) in image.enumerate_pixels_mut() {
+            let covering = self
+                .people
+                .iter()
+                .filter(|person| person.covers(frame_number, x, y))
+                .map(|person| person.temperature.in_celsius());
+            for temperature in covering {
+                pixel[0] = pixel[0].max(temperature);
+            }
+        }
+        image
+    }
+
+    /// The number of people visible in the given frame.
+    pub(crate) fn visible_people(&self, frame_number: usize) -> usize {
+        self.people
+            .iter()
+            .filter(|person| {
+                self.background
+                    .enumerate_pixels()
+                    .any(|(x, y, _)| person.covers(frame_number, x, y))
+            })
+            .count()
+    }
+}
+
+impl ThermalCamera for SyntheticCamera {
+    fn sample(&mut self) -> anyhow::Result<CameraSample> {
+        let image = self.render(self.frame_number);
+        trace!(
+            frame_number = self.frame_number,
+            visible_people = self.visible_people(self.frame_number),
+            "Generated synthetic frame"
+        );
+        self.frame_number += 1;
+        Ok(CameraSample {
+            image,
+            y_direction: YAxisDirection::Down,
+            temperature: self.temperature,
+            frame_delay: Duration::from_secs_f32(self.frame_rate.recip()),
+        })
+    }
+
+    fn resolution(&self) -> (u32, u32) {
+        self.background.dimensions()
+    }
+
+    fn kind_name(&self) -> &'static str {
+        "Synthetic camera"
+    }
+
+    fn set_frame_rate(&mut self, frame_rate: f32) -> anyhow::Result<()> {
+        // Any positive frame rate is allowed, including those below 1 FPS.
+        if !(frame_rate.is_finite() && frame_rate > 0.0) {
+            anyhow::bail!("The synthetic camera frame rate must be greater than 0");
+        }
+        self.frame_rate = frame_rate;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::image_buffer::ThermalImage;
+    use crate::occupancy::{Tracker, TrackerSettings};
+    use crate::temperature::Temperature;
+
+    use super::super::thermal_camera::ThermalCamera;
+    use super::{SyntheticCamera, SyntheticPerson};
+
+    const BACKGROUND_TEMP: f32 = 20.0;
+
+    const PERSON_TEMP: f32 = 37.0;
+
+    fn empty_background() -> ThermalImage {
+        ThermalImage::from_pixel(8, 8, [BACKGROUND_TEMP].into())
+    }
+
+    fn walking_person() -> SyntheticPerson {
+        SyntheticPerson {
+            position: [-2.0, 4.0],
+            radius: 1.0,
+            temperature: Temperature::Celsius(PERSON_TEMP),
+            velocity: [0.5, 0.0],
+        }
+    }
+
+    #[test]
+    fn static_background() {
+        let mut camera =
+            SyntheticCamera::new(empty_background(), Temperature::Celsius(25.0), Vec::new());
+        for _ in 0..5 {
+            let sample = camera.sample().unwrap();
+            assert_eq!(sample.image, empty_background());
+            assert_eq!(sample.temperature, Temperature::Celsius(25.0));
+        }
+        assert_eq!(camera.visible_people(0), 0);
+    }
+
+    #[test]
+    fn moving_person() {
+        let camera = SyntheticCamera::new(
+            empty_background(),
+            Temperature::Celsius(25.0),
+            vec![walking_person()],
+        );
+        // Starts out of frame
+        assert_eq!(camera.visible_people(0), 0);
+        assert_eq!(camera.render(0), empty_background());
+        // Frame 6 has the person centered at (1, 4), covering the pixels around that point
+        assert_eq!(camera.visible_people(6), 1);
+        let image = camera.render(6);
+        assert_eq!(image[(0, 3)][0], PERSON_TEMP);
+        assert_eq!(image[(0, 4)][0], PERSON_TEMP);
+        assert_eq!(image[(3, 4)][0], BACKGROUND_TEMP);
+        // And eventually leaves the frame on the other side
+        assert_eq!(camera.visible_people(30), 0);
+    }
+
+    #[test]
+    fn frame_delay() {
+        let mut camera =
+            SyntheticCamera::new(empty_background(), Temperature::Celsius(25.0), Vec::new());
+        camera.set_frame_rate(4.0).unwrap();
+        let sample = camera.sample().unwrap();
+        assert_eq!(sample.frame_delay, std::time::Duration::from_millis(250));
+        camera.set_frame_rate(0.5).unwrap();
+        let sample = camera.sample().unwrap();
+        assert_eq!(sample.frame_delay, std::time::Duration::from_secs(2));
+        assert!(camera.set_frame_rate(0.0).is_err());
+    }
+
+    /// An example of driving the tracker with synthetic frames.
+    #[test]
+    fn tracker_counts_person() {
+        let camera = SyntheticCamera::new(
+            empty_background(),
+            Temperature::Celsius(25.0),
+            vec![SyntheticPerson {
+                radius: 2.0,
+                velocity: [0.25, 0.0],
+                ..walking_person()
+            }],
+        );
+        let mut tracker = Tracker::new(&TrackerSettings::default());
+        // Let the background model train on an empty room first.
+        for _ in 0..100 {
+            tracker.update(&empty_background());
+        }
+        assert_eq!(tracker.count(), 0);
+        // Then walk the person into the middle of the room.
+        const LAST_FRAME: usize = 24;
+        for frame_number in 0..=LAST_FRAME {
+            tracker.update(&camera.render(frame_number));
+        }
+        assert_eq!(camera.visible_people(LAST_FRAME), 1);
+        assert_eq!(tracker.count(), camera.visible_people(LAST_FRAME));
+    }
+
+    #[test]
+    fn resolution() {
+        let background = ThermalImage::from_pixel(4, 2, [BACKGROUND_TEMP].into());
+        let camera = SyntheticCamera::new(background, Temperature::Celsius(25.0), Vec::new());
+        assert_eq!(camera.resolution(), (4, 2));
+        assert_eq!(camera.kind_name(), "Synthetic camera");
+    }
+}
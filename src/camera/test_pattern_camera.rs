@@ -0,0 +1,209 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+use std::time::Duration;
+
+use crate::image_buffer::ThermalImage;
+use crate::temperature::Temperature;
+
+use super::thermal_camera::{CameraSample, ThermalCamera, YAxisDirection};
+
+/// The kinds of images a [`TestPatternCamera`] can generate.
+#[derive(
+    Clone, Copy, Debug, PartialEq, serde::Deserialize, schemars::JsonSchema, serde::Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum TestPattern {
+    /// A diagonal gradient from the minimum to the maximum temperature, moving towards the top
+    /// left corner.
+    Gradient,
+
+    /// A hot blob sweeping from left to right across the middle of the image.
+    Blob,
+
+    /// Each pixel is a random temperature between the minimum and maximum.
+    Noise,
+}
+
+impl Default for TestPattern {
+    fn default() -> Self {
+        Self::Gradient
+    }
+}
+
+/// A camera generating test patterns, for working on the rendering and streaming without a
+/// camera.
+pub(crate) struct TestPatternCamera {
+    pattern: TestPattern,
+    width: u32,
+    height: u32,
+    minimum: f32,
+    maximum: f32,
+    /// How far the pattern moves each frame, in pixels.
+    speed: f32,
+    frame_rate: f32,
+    frame_number: usize,
+}
+
+impl TestPatternCamera {
+    pub(crate) fn new(
+        pattern: TestPattern,
+        (width, height): (u32, u32),
+        (minimum, maximum): (Temperature, Temperature),
+        speed: f32,
+    ) -> Self {
+        Self {
+            pattern,
+            width,
+            height,
+            minimum: minimum.in_celsius(),
+            maximum: maximum.in_celsius(),
+            speed,
+            frame_rate: 1.0,
+            frame_number: 0,
+        }
+    }
+
+    /// Map a value from 0 to 1 to a temperature in Celsius.
+    fn temperature(&self, fraction: f32) -> f32 {
+        self.minimum + (self.maximum - self.minimum) * fraction.clamp(0.0, 1.0)
+    }
+
+    /// Generate the image for the given frame.
+    pub(crate) fn render(&self, frame_number: usize) -> ThermalImage {
+        let offset = self.speed * frame_number as f32;
+        ThermalImage::from_fn(self.width, self.height, |x, y| {
+            let fraction = match self.pattern {
+                TestPattern::Gradient => {
+                    let period = (self.width + self.height) as f32;
+                    ((x + y) as f32 + offset).rem_euclid(period) / period
+                }
+                TestPattern::Blob => {
+                    // The blob is a quarter of the image tall, and starts and ends just outside of
+                    // the image.
+                    let radius = (self.height as f32 / 4.0).max(1.0);
+                    let center_x = offset.rem_euclid(self.width as f32 + 2.0 * radius) - radius;
+                    let center_y = self.height as f32 / 2.0;
+                    let distance = (x as f32 + 0.5 - center_x).hypot(y as f32 + 0.5 - center_y);
+                    1.0 - distance / radius
+                }
+                TestPattern::Noise => {
+                    let index = (frame_number as u64) << 32 | u64::from(y * self.width + x);
+                    (splitmix64(index) >> 40) as f32 / (1u64 << 24) as f32
+                }
+            };
+            [self.temperature(fraction)].into()
+        })
+    }
+}
+
+/// A quick (and deterministic) way to get random-looking numbers.
+///
+/// See <https://prng.di.unimi.it/splitmix64.c> for the original.
+fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+impl ThermalCamera for TestPatternCamera {
+    fn sample(&mut self) -> anyhow::Result<CameraSample> {
+        let image = self.render(self.frame_number);
+        self.frame_number += 1;
+        Ok(CameraSample {
+            image,
+            y_direction: YAxisDirection::Down,
+            temperature: Temperature::Celsius(self.minimum),
+            frame_delay: Duration::from_secs_f32(self.frame_rate.recip()),
+        })
+    }
+
+    fn resolution(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn kind_name(&self) -> &'static str {
+        "Test pattern"
+    }
+
+    fn set_frame_rate(&mut self, frame_rate: f32) -> anyhow::Result<()> {
+        if !(frame_rate.is_finite() && frame_rate > 0.0) {
+            anyhow::bail!("The test pattern camera frame rate must be greater than 0");
+        }
+        self.frame_rate = frame_rate;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::temperature::Temperature;
+
+    use super::super::thermal_camera::ThermalCamera;
+    use super::{TestPattern, TestPatternCamera};
+
+    const MINIMUM: f32 = 20.0;
+
+    const MAXIMUM: f32 = 36.0;
+
+    fn camera(pattern: TestPattern) -> TestPatternCamera {
+        TestPatternCamera::new(
+            pattern,
+            (8, 8),
+            (Temperature::Celsius(MINIMUM), Temperature::Celsius(MAXIMUM)),
+            1.0,
+        )
+    }
+
+    #[test]
+    fn gradient() {
+        let camera = camera(TestPattern::Gradient);
+        let image = camera.render(0);
+        assert_eq!(image[(0, 0)][0], MINIMUM);
+        assert_eq!(image[(4, 4)][0], (MINIMUM + MAXIMUM) / 2.0);
+        // The gradient moves by a pixel each frame
+        assert_eq!(camera.render(1)[(0, 0)][0], image[(1, 0)][0]);
+    }
+
+    #[test]
+    fn blob() {
+        let camera = camera(TestPattern::Blob);
+        // The blob starts out just outside of the image
+        assert!(camera.render(0).iter().all(|t| *t == MINIMUM));
+        let image = camera.render(6);
+        assert!(image[(4, 4)][0] > MINIMUM);
+        assert_eq!(image[(4, 0)][0], MINIMUM);
+        assert_eq!(image[(0, 4)][0], MINIMUM);
+        // And comes back around after crossing the image
+        assert_eq!(camera.render(12 + 6), image);
+    }
+
+    #[test]
+    fn noise() {
+        let camera = camera(TestPattern::Noise);
+        let image = camera.render(0);
+        assert!(image.iter().all(|t| (MINIMUM..=MAXIMUM).contains(t)));
+        assert!(image.iter().any(|t| *t != image[(0, 0)][0]));
+        assert_ne!(camera.render(1), image);
+    }
+
+    #[test]
+    fn frame_delay() {
+        let mut camera = camera(TestPattern::Gradient);
+        camera.set_frame_rate(4.0).unwrap();
+        let sample = camera.sample().unwrap();
+        assert_eq!(sample.frame_delay, std::time::Duration::from_millis(250));
+        assert!(camera.set_frame_rate(0.0).is_err());
+    }
+
+    #[test]
+    fn resolution() {
+        let camera = TestPatternCamera::new(
+            TestPattern::Gradient,
+            (4, 2),
+            (Temperature::Celsius(MINIMUM), Temperature::Celsius(MAXIMUM)),
+            1.0,
+        );
+        assert_eq!(camera.resolution(), (4, 2));
+        assert_eq!(camera.render(0).dimensions(), camera.resolution());
+    }
+}
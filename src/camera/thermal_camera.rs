@@ -1,5 +1,6 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 use std::convert::TryFrom;
+use std::num::{NonZeroU16, NonZeroUsize};
 use std::ops::RangeInclusive;
 use std::time::{Duration, Instant};
 
@@ -8,6 +9,7 @@ use image::flat::{FlatSamples, SampleLayout};
 use linux_embedded_hal::I2cdev;
 use tracing::{debug, trace};
 
+use super::i2c::FixedAddress;
 use crate::image_buffer;
 use crate::temperature::Temperature;
 use crate::util::{Filter, MovingAverage};
@@ -44,36 +46,99 @@ pub(crate) trait ThermalCamera {
     /// until the next measurement should be taken is also included in the returned value.
     fn sample(&mut self) -> anyhow::Result<CameraSample>;
 
+    /// The width and height of the images from [`sample`][ThermalCamera::sample].
+    ///
+    /// This is the size of the images straight from the camera, before any rotation is applied.
+    fn resolution(&self) -> (u32, u32);
+
+    /// A human-readable name for the kind of camera, like "Panasonic GridEYE".
+    fn kind_name(&self) -> &'static str;
+
     /// Set the camera frame rate.
     fn set_frame_rate(&mut self, frame_rate: f32) -> anyhow::Result<()>;
+
+    /// Put the camera into a low power mode until [`wake`][ThermalCamera::wake] is called.
+    ///
+    /// Cameras without a low power mode can leave this as a no-op.
+    fn sleep(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Return the camera to normal operation after [`sleep`][ThermalCamera::sleep].
+    ///
+    /// The next call to [`sample`][ThermalCamera::sample] should return a fresh image.
+    fn wake(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
 }
 
 pub(crate) struct GridEye {
-    camera: amg88::GridEye<I2cdev>,
+    camera: amg88::GridEye<FixedAddress<I2cdev>>,
     frame_rate: amg88::FrameRateValue,
+    thermistor_interval: NonZeroUsize,
+    thermistor_cache: Option<(Temperature, usize)>,
 }
 
 impl GridEye {
     const DEFAULT_FRAME_RATE: amg88::FrameRateValue = amg88::FrameRateValue::Fps10;
 
-    pub(crate) fn new(bus: I2cdev, address: amg88::Address) -> anyhow::Result<Self> {
-        let mut camera = amg88::GridEye::new(bus, address);
+    /// Create a new GridEYE camera.
+    ///
+    /// The driver only accepts the two addresses from the datasheet, so the bus is wrapped to allow
+    /// any address to be used.
+    pub(crate) fn new(
+        bus: I2cdev,
+        address: u8,
+        thermistor_interval: NonZeroUsize,
+    ) -> anyhow::Result<Self> {
+        let bus = FixedAddress::new(bus, address);
+        // The address given to the driver is ignored by FixedAddress.
+        let mut camera = amg88::GridEye::new(bus, amg88::Address::Low);
         camera.set_frame_rate(Self::DEFAULT_FRAME_RATE)?;
         Ok(Self {
             camera,
             frame_rate: Self::DEFAULT_FRAME_RATE,
+            thermistor_interval,
+            thermistor_cache: None,
         })
     }
+
+    /// How long it takes the camera to capture a single frame.
+    fn frame_duration(&self) -> Duration {
+        match self.frame_rate {
+            amg88::FrameRateValue::Fps1 => Duration::from_secs(1),
+            amg88::FrameRateValue::Fps10 => Duration::from_millis(100),
+        }
+    }
+
+    /// Read the temperature of the camera.
+    ///
+    /// The thermistor is only read every `thermistor_interval` frames, with the previous value
+    /// being reused for the frames in between.
+    fn temperature(&mut self) -> anyhow::Result<Temperature> {
+        match self.thermistor_cache {
+            Some((temperature, age)) if age + 1 < self.thermistor_interval.get() => {
+                self.thermistor_cache = Some((temperature, age + 1));
+                trace!(frames = %(age + 1), "Reusing cached thermistor value");
+                Ok(temperature)
+            }
+            _ => {
+                let temperature = self
+                    .camera
+                    .thermistor()
+                    .context("Error retrieving temperature from camera")
+                    .map(Temperature::Celsius)?;
+                self.thermistor_cache = Some((temperature, 0));
+                Ok(temperature)
+            }
+        }
+    }
 }
 
 impl ThermalCamera for GridEye {
     fn sample(&mut self) -> anyhow::Result<CameraSample> {
         let start = Instant::now();
-        let temperature = self
-            .camera
-            .thermistor()
-            .context("Error retrieving temperature from camera")
-            .map(Temperature::Celsius)?;
+        let temperature = self.temperature()?;
         let grid = self.camera.image()?;
         let (row_count, col_count) = grid.dim();
         let height = row_count as u32;
@@ -90,11 +155,8 @@ impl ThermalCamera for GridEye {
             // first item in the tuple.
             .map_err(|e| e.0)
             .context("Unable to convert 2D array into an ImageBuffer")?;
-        let frame_duration = match self.frame_rate {
-            amg88::FrameRateValue::Fps1 => Duration::from_secs(1),
-            amg88::FrameRateValue::Fps10 => Duration::from_millis(100),
-        };
-        let frame_delay = frame_duration
+        let frame_delay = self
+            .frame_duration()
             .checked_sub(start.elapsed())
             .unwrap_or_default();
         Ok(CameraSample {
@@ -105,6 +167,14 @@ impl ThermalCamera for GridEye {
         })
     }
 
+    fn resolution(&self) -> (u32, u32) {
+        (8, 8)
+    }
+
+    fn kind_name(&self) -> &'static str {
+        "Panasonic GridEYE"
+    }
+
     fn set_frame_rate(&mut self, frame_rate: f32) -> anyhow::Result<()> {
         // Truncate the frame rate as the Grideye only has integer frame rates
         let frame_rate = frame_rate.trunc().clamp(0.0, u8::MAX as f32) as u8;
@@ -116,6 +186,33 @@ impl ThermalCamera for GridEye {
         self.frame_rate = grideye_frame_rate;
         Ok(())
     }
+
+    fn sleep(&mut self) -> anyhow::Result<()> {
+        self.camera
+            .set_power_mode(amg88::PowerControlValue::Sleep)
+            .context("Error putting GridEYE to sleep")
+    }
+
+    /// Wake the GridEYE following the procedure from the datasheet.
+    ///
+    /// The other registers are undefined after leaving sleep mode, so the camera is reset and the
+    /// frame rate set again. The thermistor is read again as well, as it may have drifted.
+    fn wake(&mut self) -> anyhow::Result<()> {
+        self.camera
+            .set_power_mode(amg88::PowerControlValue::Normal)
+            .context("Error waking GridEYE")?;
+        std::thread::sleep(Duration::from_millis(50));
+        self.camera.reset_initial()?;
+        std::thread::sleep(Duration::from_millis(2));
+        self.camera.reset_flags()?;
+        self.camera
+            .set_frame_rate(self.frame_rate)
+            .context("Error setting GridEYE frame rate")?;
+        self.thermistor_cache = None;
+        // Wait for a full frame to be captured before it's read.
+        std::thread::sleep(self.frame_duration());
+        Ok(())
+    }
 }
 
 /// The result of polling for a new frame of data to be available from a Melexis camera.
@@ -134,13 +231,13 @@ struct MelexisFramePoll {
     subpage: mlx9064x::Subpage,
 }
 
-const MELEXIS_MOVING_AVERAGE_LEN: usize = 10;
+const MELEXIS_MOVING_AVERAGE_LEN: NonZeroU16 = NonZeroU16::new(10).unwrap();
 
 // This is a dirty hack. I was having trouble implementing ThermalCamera while being generic over
 // the underlying mlx9064x::CameraDriver. When GATs are stabilized, there's a 'gat' branch on
 // mlx9064x and 'mlx9064x-gat' branch for r-u-still-there that are much simpler.
 macro_rules! melexis_camera {
-    ($name:ident, $driver:path) => {
+    ($name:ident, $driver:path, $kind_name:literal) => {
 
 /// A wrapper over Melexis cameras to implement [`ThermalCamera`]
 ///
@@ -156,9 +253,12 @@ pub(crate) struct $name {
 
     previous_frame_start: Option<Instant>,
 
-    average_frame_duration: MovingAverage<Duration, MELEXIS_MOVING_AVERAGE_LEN>,
+    average_frame_duration: MovingAverage<Duration>,
+
+    average_check_duration: MovingAverage<Duration>,
 
-    average_check_duration: MovingAverage<Duration, MELEXIS_MOVING_AVERAGE_LEN>,
+    /// The frame rate to restore when waking up, if the camera is asleep.
+    awake_frame_rate: Option<mlx9064x::FrameRate>,
 }
 
 impl $name {
@@ -179,8 +279,9 @@ impl $name {
             camera,
             temperature_buffer: vec![0f32; num_pixels],
             previous_frame_start: None,
-            average_frame_duration: MovingAverage::new(),
-            average_check_duration: MovingAverage::new(),
+            average_frame_duration: MovingAverage::new(MELEXIS_MOVING_AVERAGE_LEN),
+            average_check_duration: MovingAverage::new(MELEXIS_MOVING_AVERAGE_LEN),
+            awake_frame_rate: None,
         }
     }
 
@@ -319,6 +420,14 @@ impl ThermalCamera for $name {
         })
     }
 
+    fn resolution(&self) -> (u32, u32) {
+        (self.camera.width() as u32, self.camera.height() as u32)
+    }
+
+    fn kind_name(&self) -> &'static str {
+        $kind_name
+    }
+
     fn set_frame_rate(&mut self, frame_rate: f32) -> anyhow::Result<()> {
         let mlx_frame_rate = mlx9064x::FrameRate::try_from(frame_rate)
             .context("Invalid frame rate, only 0.5, 1, 2, 4, 8, 16, 32, or 64 are valid for MLX9064* cameras")?;
@@ -326,12 +435,45 @@ impl ThermalCamera for $name {
             .set_frame_rate(mlx_frame_rate)
             .context("Error setting MLX9064x frame rate")?;
         // Reset the frame duration average.
-        self.average_frame_duration = MovingAverage::new();
+        self.average_frame_duration = MovingAverage::new(MELEXIS_MOVING_AVERAGE_LEN);
+        Ok(())
+    }
+
+    /// Slow the camera down to its lowest frame rate.
+    ///
+    /// The driver doesn't expose a sleep mode for Melexis cameras (the step mode the datasheets
+    /// used to describe isn't calibrated), so the best that can be done is measuring less often.
+    fn sleep(&mut self) -> anyhow::Result<()> {
+        if self.awake_frame_rate.is_none() {
+            self.awake_frame_rate = Some(self.camera.frame_rate()?);
+            self.camera
+                .set_frame_rate(mlx9064x::FrameRate::Half)
+                .context("Error setting MLX9064x frame rate")?;
+        }
+        Ok(())
+    }
+
+    fn wake(&mut self) -> anyhow::Result<()> {
+        if let Some(frame_rate) = self.awake_frame_rate.take() {
+            self.camera
+                .set_frame_rate(frame_rate)
+                .context("Error setting MLX9064x frame rate")?;
+            self.average_frame_duration = MovingAverage::new(MELEXIS_MOVING_AVERAGE_LEN);
+            self.previous_frame_start = None;
+        }
         Ok(())
     }
 }
     };
 }
 
-melexis_camera!(Mlx90640, mlx9064x::Mlx90640Driver<I2cdev>);
-melexis_camera!(Mlx90641, mlx9064x::Mlx90641Driver<I2cdev>);
+melexis_camera!(
+    Mlx90640,
+    mlx9064x::Mlx90640Driver<I2cdev>,
+    "Melexis MLX90640"
+);
+melexis_camera!(
+    Mlx90641,
+    mlx9064x::Mlx90641Driver<I2cdev>,
+    "Melexis MLX90641"
+);
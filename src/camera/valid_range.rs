@@ -0,0 +1,175 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+use std::convert::TryFrom;
+
+use anyhow::anyhow;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::image_buffer::ThermalImage;
+use crate::temperature::Temperature;
+
+use super::dead_pixels::DeadPixels;
+
+fn default_max_invalid() -> f32 {
+    0.25
+}
+
+/// The range of temperatures a camera can plausibly report.
+///
+/// Glitches when reading from a camera can give temperatures far outside of what the camera can
+/// measure (like -273°C). These would throw off both the occupancy tracker and the color scale, so
+/// they're replaced before anything else sees them.
+#[derive(Clone, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(deny_unknown_fields, try_from = "UncheckedValidRange")]
+pub(crate) struct ValidRange {
+    pub(crate) minimum: Temperature,
+
+    pub(crate) maximum: Temperature,
+
+    /// The fraction of the pixels (from 0 to 1) that can be invalid before the entire image is
+    /// dropped.
+    #[serde(default = "default_max_invalid")]
+    pub(crate) max_invalid: f32,
+}
+
+/// A [`ValidRange`] that hasn't been checked yet.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct UncheckedValidRange {
+    minimum: Temperature,
+
+    maximum: Temperature,
+
+    #[serde(default = "default_max_invalid")]
+    max_invalid: f32,
+}
+
+impl TryFrom<UncheckedValidRange> for ValidRange {
+    type Error = anyhow::Error;
+
+    fn try_from(range: UncheckedValidRange) -> anyhow::Result<Self> {
+        if range.minimum.as_celsius() >= range.maximum.as_celsius() {
+            return Err(anyhow!(
+                "The valid range minimum ({}) must be less than the maximum ({})",
+                range.minimum,
+                range.maximum
+            ));
+        }
+        if !(0.0..=1.0).contains(&range.max_invalid) {
+            return Err(anyhow!(
+                "The valid range max_invalid must be between 0 and 1, not {}",
+                range.max_invalid
+            ));
+        }
+        Ok(Self {
+            minimum: range.minimum,
+            maximum: range.maximum,
+            max_invalid: range.max_invalid,
+        })
+    }
+}
+
+/// Replaces the pixels outside of a [`ValidRange`].
+#[derive(Clone, Debug)]
+pub(crate) struct RangeFilter {
+    range: ValidRange,
+    previous: Option<ThermalImage>,
+}
+
+impl RangeFilter {
+    pub(crate) fn new(range: ValidRange) -> Self {
+        Self {
+            range,
+            previous: None,
+        }
+    }
+
+    /// Replace the invalid pixels in `image`.
+    ///
+    /// Invalid pixels are replaced with their value from the previous image if there is one, and
+    /// otherwise with the average of their valid neighbors. If there are too many invalid pixels,
+    /// `image` is left alone and `false` is returned.
+    pub(crate) fn apply(&mut self, image: &mut ThermalImage) -> bool {
+        let (minimum, maximum) = (
+            self.range.minimum.in_celsius(),
+            self.range.maximum.in_celsius(),
+        );
+        let invalid: Vec<(u32, u32)> = image
+            .enumerate_pixels()
+            .filter(|(_, _, pixel)| !(minimum..=maximum).contains(&pixel[0]))
+            .map(|(x, y, _)| (x, y))
+            .collect();
+        let pixel_count = (image.width() * image.height()) as f32;
+        if invalid.len() as f32 > pixel_count * self.range.max_invalid {
+            return false;
+        }
+        match &self.previous {
+            Some(previous) if previous.dimensions() == image.dimensions() => {
+                for &(x, y) in &invalid {
+                    image.put_pixel(x, y, *previous.get_pixel(x, y));
+                }
+            }
+            _ => DeadPixels::new(invalid).apply(image),
+        }
+        self.previous = Some(image.clone());
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use image::ImageBuffer;
+
+    use super::{RangeFilter, ValidRange};
+    use crate::image_buffer::ThermalImage;
+    use crate::temperature::Temperature;
+
+    fn filter() -> RangeFilter {
+        RangeFilter::new(ValidRange {
+            minimum: Temperature::Celsius(-40.0),
+            maximum: Temperature::Celsius(300.0),
+            max_invalid: 0.25,
+        })
+    }
+
+    fn image(pixels: [f32; 4]) -> ThermalImage {
+        ImageBuffer::from_raw(2, 2, pixels.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn neighbors_then_previous() {
+        let mut filter = filter();
+        // Without a previous image, the neighbors are used.
+        let mut first = image([20.0, 21.0, -273.0, 22.0]);
+        assert!(filter.apply(&mut first));
+        assert_eq!(first, image([20.0, 21.0, 21.0, 22.0]));
+        let mut second = image([25.0, 1000.0, 25.0, 25.0]);
+        assert!(filter.apply(&mut second));
+        assert_eq!(second, image([25.0, 21.0, 25.0, 25.0]));
+    }
+
+    #[test]
+    fn too_many_invalid() {
+        let mut filter = filter();
+        let mut glitched = image([20.0, 1000.0, -273.0, 22.0]);
+        assert!(!filter.apply(&mut glitched));
+        assert_eq!(glitched, image([20.0, 1000.0, -273.0, 22.0]));
+    }
+
+    #[test]
+    fn deserialize() {
+        let parsed: ValidRange = toml::from_str("minimum = -40\nmaximum = 300").unwrap();
+        assert_eq!(parsed, filter().range);
+        assert!(toml::from_str::<ValidRange>("minimum = -40").is_err());
+        // The minimum has to be below the maximum.
+        assert!(toml::from_str::<ValidRange>("minimum = 300\nmaximum = -40").is_err());
+        assert!(toml::from_str::<ValidRange>("minimum = 20\nmaximum = 20").is_err());
+        assert!(
+            toml::from_str::<ValidRange>("minimum = -40\nmaximum = 300\nmax_invalid = 2").is_err()
+        );
+        assert!(
+            toml::from_str::<ValidRange>("minimum = -40\nmaximum = 300\nmax_invalid = nan")
+                .is_err()
+        );
+    }
+}
@@ -1,13 +1,16 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
-use anyhow::anyhow;
+use anyhow::{anyhow, Context as _};
+use futures::future::Future;
+use futures::stream::{unfold, Stream, StreamExt};
 use structopt::StructOpt;
-use tracing::{debug, error, info_span, instrument, trace, warn, Instrument};
+use tokio::signal::unix::{signal, SignalKind};
+use tracing::{debug, error, info, info_span, instrument, trace, warn, Instrument};
 use tracing_subscriber::fmt::format::FmtSpan;
 use tracing_subscriber::prelude::*;
 use tracing_subscriber::{fmt as tracing_fmt, EnvFilter, Registry};
 
+use std::convert::TryFrom;
 use std::env;
-use std::fs::read_to_string;
 use std::path::PathBuf;
 
 mod camera;
@@ -21,60 +24,44 @@ mod recorded_data;
 mod render;
 mod settings;
 mod stream;
+#[cfg(feature = "systemd")]
+mod systemd;
 mod temperature;
+mod upload;
 mod util;
 
+use crate::mqtt::MqttClient;
 use crate::pipeline::Pipeline;
 use crate::pubsub::spmc;
-use crate::settings::{Args, Settings};
+use crate::settings::{Args, ConfigSource, Settings, CONFIG_TOML_VAR};
 
-/// Select a configuration file to use.
+/// Select where the configuration is read from.
 ///
-/// If there's a path present in the provided [Args], it will be used. Otherwise, `config.toml`
-/// will be searched for within the configuration directory. If a `CONFIGURATION_DIRECTORY`
-/// environment variable exists (ex: systemd sets it in some cases), that is searched. If that
-/// variable isn't set, `/etc/r-u-still-there/` is used. If no config file is found, `Ok(None)` is
-/// returned.
+/// See [ConfigSource::find] for the order the possible sources are checked in. The configuration
+/// directory is taken from the `CONFIGURATION_DIRECTORY` environment variable if it exists (ex:
+/// systemd sets it in some cases), otherwise `/etc/r-u-still-there/` is used.
 #[instrument(level = "debug", err)]
-fn find_config_file(args: &Args) -> anyhow::Result<Option<PathBuf>> {
-    if let Some(cli_config_path) = &args.config_path {
-        debug!(?cli_config_path, "using config path from CLI argument");
-        let path = cli_config_path.clone();
-        if path.exists() {
-            return Ok(Some(path));
-        } else {
-            return Err(anyhow!("Non-existant config file given: {:?}", path));
-        }
-    }
-    // Check for $CONFIGURATION_DIRECTORY, which can be set by systemd. Otherwise use
-    // /etc/r-u-still-there
-    let prefix = env::var("CONFIGURATION_DIRECTORY")
+fn config_source(args: &Args) -> anyhow::Result<ConfigSource> {
+    let config_dir = env::var("CONFIGURATION_DIRECTORY")
         .map_or(PathBuf::from("/etc/r-u-still-there"), PathBuf::from);
-
-    // Only supporting TOML
-    let path = prefix.join("config.toml");
-    debug!("checking for file {:?}", path);
-    if path.exists() {
-        return Ok(Some(path));
-    }
-    Ok(None)
+    let config_toml = env::var(CONFIG_TOML_VAR).ok();
+    let source = ConfigSource::find(args.config_path.as_deref(), config_toml, &config_dir)?;
+    debug!(?source, "selected configuration source");
+    Ok(source)
 }
 
 /// Find and create the final configuration for the application.
 #[instrument(level = "debug", err)]
-fn create_config() -> anyhow::Result<Settings> {
+fn create_config(args: &Args) -> anyhow::Result<Settings> {
     // Configuration priority is as follows from least to greatest:
-    // Defaults -> Config file -> CLI flag
-    let args = Args::from_args();
-    // Find a config file
-    let config_data = if let Some(path) = find_config_file(&args)? {
-        read_to_string(path)?
-    } else {
-        "".to_string()
-    };
+    // Defaults -> Config file (or stdin, or RUSTILLTHERE_CONFIG_TOML) -> CLI flag
+    let config_data = config_source(args)?.read(std::io::stdin())?;
     args.apply_to_config_str(&config_data)
 }
 
+/// How long to wait for the MQTT broker when checking the configuration.
+const CHECK_CONNECTION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(15);
+
 // Just picking values for these.
 #[repr(i32)]
 enum ExitCode {
@@ -158,12 +145,157 @@ fn set_up_logging() {
     };
 }
 
+/// Create a future that resolves when either SIGTERM or SIGINT is received.
+fn shutdown_signal() -> anyhow::Result<impl Future<Output = ()>> {
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    Ok(async move {
+        tokio::select! {
+            _ = terminate.recv() => info!("Received SIGTERM"),
+            _ = interrupt.recv() => info!("Received SIGINT"),
+        }
+    })
+}
+
+/// Create a stream of configurations, re-read from the config file each time SIGHUP is received.
+///
+/// If the new configuration has errors, it is logged and skipped.
+fn reload_signal(args: Args) -> anyhow::Result<impl Stream<Item = Settings>> {
+    let hangup = signal(SignalKind::hangup())?;
+    let hangups = unfold(hangup, |mut hangup| async move {
+        hangup.recv().await.map(|_| ((), hangup))
+    });
+    let reloadable = config_source(&args)?.is_reloadable();
+    Ok(hangups.filter_map(move |_| {
+        if !reloadable {
+            warn!("Received SIGHUP, but a configuration read from stdin can't be reloaded");
+            return std::future::ready(None);
+        }
+        info!("Received SIGHUP, reloading configuration");
+        let config = match create_config(&args) {
+            Ok(config) => {
+                debug!(?config, "reloaded config");
+                Some(config)
+            }
+            Err(err) => {
+                error!("Unable to reload configuration: {:?}", err);
+                None
+            }
+        };
+        std::future::ready(config)
+    }))
+}
+
+/// Check that the camera can be opened and the MQTT broker connected to.
+async fn check_connections(config: &Settings) -> anyhow::Result<()> {
+    let camera_settings = config.camera.clone();
+    tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+        let mut camera = camera_settings.create_camera()?;
+        camera.set_frame_rate(camera_settings.frame_rate())?;
+        camera.sample()?;
+        Ok(())
+    })
+    .await?
+    .context("Unable to read from the camera")?;
+    info!("Camera is working");
+    tokio::time::timeout(
+        CHECK_CONNECTION_TIMEOUT,
+        MqttClient::check_connection(&config.mqtt),
+    )
+    .await
+    .map_err(|_| anyhow!("Timed out connecting to the MQTT broker"))??;
+    info!("Connected to the MQTT broker");
+    Ok(())
+}
+
+/// Run the checks requested by `--check-config`.
+async fn check_config(config: &Settings, connections: bool) -> ExitCode {
+    if let Err(err) = config.camera.check() {
+        error!("Configuration error: {:?}", err);
+        return ExitCode::Config;
+    }
+    if let Err(err) = config.tracker.check(config.camera.rotated_resolution()) {
+        error!("Configuration error: {:?}", err);
+        return ExitCode::Config;
+    }
+    // Creating the MQTT options also loads any TLS certificates.
+    if let Err(err) = rumqttc::MqttOptions::try_from(&config.mqtt) {
+        error!("Configuration error: {:?}", err);
+        return ExitCode::Config;
+    }
+    if connections {
+        if let Err(err) = check_connections(config).await {
+            error!("Configuration error: {:?}", err);
+            return ExitCode::Config;
+        }
+    }
+    info!("Configuration is valid");
+    ExitCode::Success
+}
+
+/// Print the combined configuration, for `--dump-config`.
+fn dump_config(config: &Settings) -> ExitCode {
+    match config.to_toml() {
+        Ok(dumped) => {
+            print!("{}", dumped);
+            ExitCode::Success
+        }
+        Err(err) => {
+            error!("Unable to serialize the configuration: {:?}", err);
+            ExitCode::Other
+        }
+    }
+}
+
+/// Print the cameras found by scanning the I2C buses, for `--list-cameras`.
+fn list_cameras() -> ExitCode {
+    match camera::scan() {
+        Err(err) => {
+            error!("Unable to scan for cameras: {:?}", err);
+            ExitCode::Other
+        }
+        Ok(found) if found.is_empty() => {
+            println!("No cameras found");
+            ExitCode::Success
+        }
+        Ok(found) => {
+            for camera in found {
+                println!("{}", camera);
+            }
+            ExitCode::Success
+        }
+    }
+}
+
+/// Print the JSON Schema for the configuration, for `--print-schema`.
+fn print_schema() -> ExitCode {
+    let schema = schemars::schema_for!(Settings);
+    match serde_json::to_string_pretty(&schema) {
+        Ok(schema) => {
+            println!("{}", schema);
+            ExitCode::Success
+        }
+        Err(err) => {
+            error!("Unable to serialize the configuration schema: {:?}", err);
+            ExitCode::Other
+        }
+    }
+}
+
 async fn inner_main() -> ExitCode {
     set_up_logging();
     let setup_span = info_span!("setup");
+    let args = Args::from_args();
+    // Scanning doesn't need a configuration, as it's meant to help write one.
+    if args.list_cameras {
+        return info_span!("list_cameras").in_scope(list_cameras);
+    }
+    if args.print_schema {
+        return print_schema();
+    }
     let config = {
         let _enter = setup_span.enter();
-        match create_config() {
+        match create_config(&args) {
             Err(err) => {
                 trace!("Full error chain: {:#?}", err);
                 // Walk the error chain, looking for toml errors
@@ -175,7 +307,12 @@ async fn inner_main() -> ExitCode {
                 }
                 // Not a toml error if we reach here.
                 error!("Error combining configuration: {:?}", err);
-                return ExitCode::Setup;
+                // When only checking the configuration, every problem is a configuration problem.
+                return if args.check_config {
+                    ExitCode::Config
+                } else {
+                    ExitCode::Setup
+                };
             }
             Ok(config) => {
                 debug!(?config, "final config");
@@ -183,16 +320,67 @@ async fn inner_main() -> ExitCode {
             }
         }
     };
+    if args.dump_config {
+        return dump_config(&config);
+    }
+    if args.check_config {
+        return check_config(&config, args.check_connections)
+            .instrument(info_span!("check_config"))
+            .await;
+    }
+    if args.once {
+        return match Pipeline::capture_once(config, args.output)
+            .instrument(info_span!("once"))
+            .await
+        {
+            Err(err) => {
+                error!("Capture error: {:?}", err);
+                ExitCode::Other
+            }
+            Ok(_) => ExitCode::Success,
+        };
+    }
+    // Calibration runs before the signal handlers are installed so that it can still be
+    // interrupted normally.
+    #[cfg(feature = "mock_camera")]
+    if let Some(seconds) = args.calibrate {
+        let calibrate_span = info_span!("calibrate");
+        let duration = std::time::Duration::from_secs(seconds);
+        return match Pipeline::calibrate(
+            config,
+            duration,
+            args.calibration_path,
+            args.calibration_model_path,
+        )
+        .instrument(calibrate_span)
+        .await
+        {
+            Err(err) => {
+                error!("Calibration error: {:?}", err);
+                ExitCode::Other
+            }
+            Ok(_) => ExitCode::Success,
+        };
+    }
+    let signals = shutdown_signal().and_then(|shutdown| Ok((shutdown, reload_signal(args)?)));
+    let (shutdown, reloads) = match signals {
+        Err(err) => {
+            error!("Unable to set up signal handlers: {:?}", err);
+            return ExitCode::Setup;
+        }
+        Ok(signals) => signals,
+    };
     let config_span = info_span!("config");
-    let app = match Pipeline::new(config).instrument(config_span).await {
+    let mut app = match Pipeline::new(config.clone()).instrument(config_span).await {
         Err(err) => {
             error!("Setup error: {:?}", err);
             return ExitCode::Setup;
         }
         Ok(app) => app,
     };
+    app.reload_from(config, reloads);
     let pipeline_span = info_span!("pipeline");
-    if let Err(err) = app.instrument(pipeline_span).await {
+    if let Err(err) = app.run_until(shutdown).instrument(pipeline_span).await {
         error!("{:?}", err);
         ExitCode::Other
     } else {
@@ -1,30 +1,44 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::error::Error;
-use std::io::ErrorKind;
+use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::{anyhow, Context as _};
+use bytes::Bytes;
 use rumqttc::{
-    AsyncClient, ConnectReturnCode, Event, EventLoop, LastWill, MqttOptions as RuMqttOptions,
+    AsyncClient, ConnectReturnCode, Event, EventLoop, MqttOptions as RuMqttOptions, Outgoing,
     Packet, QoS,
 };
 use serde::Serialize;
-use tokio::sync::watch;
-use tracing::{debug, error, trace, warn};
+use tokio::sync::{broadcast, watch, Mutex as AsyncMutex};
+use tracing::{debug, error, info, trace, warn};
 
 use crate::mqtt::Status;
 
 use super::serialize::serialize;
 use super::MqttSettings;
 
+/// The most recent retained message for each topic, as `(QoS, payload)`.
+type RetainedMessages = Arc<AsyncMutex<HashMap<String, (QoS, Vec<u8>)>>>;
+
+/// The subscribed topics, as `(QoS, channel for the payloads)`.
+type Subscriptions = Arc<AsyncMutex<HashMap<String, (QoS, broadcast::Sender<Bytes>)>>>;
+
 #[derive(Clone, Debug)]
 pub(crate) struct MqttSender {
     sender: rumqttc::Sender<rumqttc::Request>,
     connected: watch::Receiver<bool>,
+    retained: RetainedMessages,
+    subscriptions: Subscriptions,
 }
 
 impl MqttSender {
+    /// The number of messages for a subscription that are buffered for each receiver.
+    const SUBSCRIPTION_CAPACITY: usize = 4;
+
     pub(crate) async fn enqueue_publish<T: Serialize>(
         &mut self,
         topic: String,
@@ -32,8 +46,47 @@ impl MqttSender {
         payload: &T,
         retain: bool,
     ) -> anyhow::Result<()> {
-        trace!("Enqueuing MQTT publish");
         let payload = serialize(payload)?;
+        self.enqueue_publish_bytes(topic, qos, payload, retain)
+            .await
+    }
+
+    /// Enqueue a message with a raw payload, skipping serialization.
+    ///
+    /// Retained messages are also kept so they can be published again after reconnecting. If the
+    /// client isn't connected, retained messages are *only* kept, and will be published once the
+    /// connection is established.
+    pub(crate) async fn enqueue_publish_bytes(
+        &mut self,
+        topic: String,
+        qos: QoS,
+        payload: Vec<u8>,
+        retain: bool,
+    ) -> anyhow::Result<()> {
+        if retain {
+            // Hold the lock until the message has been enqueued so that this message can't be
+            // sent after an older one being republished.
+            let retained = Arc::clone(&self.retained);
+            let mut retained = retained.lock().await;
+            retained.insert(topic.clone(), (qos, payload.clone()));
+            if !self.is_connected() {
+                trace!(?topic, "Deferring retained MQTT publish until connected");
+                return Ok(());
+            }
+            self.send_publish(topic, qos, payload, retain).await
+        } else {
+            self.send_publish(topic, qos, payload, retain).await
+        }
+    }
+
+    async fn send_publish(
+        &mut self,
+        topic: String,
+        qos: QoS,
+        payload: Vec<u8>,
+        retain: bool,
+    ) -> anyhow::Result<()> {
+        trace!("Enqueuing MQTT publish");
         let mut message = rumqttc::Publish::new(topic, qos, payload);
         message.retain = retain;
         self.sender
@@ -43,6 +96,83 @@ impl MqttSender {
         Ok(())
     }
 
+    /// Publish every retained message again.
+    ///
+    /// This covers the discovery configs and retained states in case the broker lost them (for
+    /// example if it was restarted without persistence) while the client was disconnected.
+    async fn republish_retained(&mut self) -> anyhow::Result<()> {
+        let retained = self.retained.lock().await;
+        debug!(
+            count = retained.len(),
+            "Republishing retained MQTT messages"
+        );
+        for (topic, (qos, payload)) in retained.iter() {
+            let mut message = rumqttc::Publish::new(topic, *qos, payload.clone());
+            message.retain = true;
+            self.sender
+                .send(message.into())
+                .await
+                .context("Sending publish message to internal MQTT client")?;
+        }
+        Ok(())
+    }
+
+    /// Subscribe to a topic, returning a receiver for the payloads of the messages published to
+    /// it.
+    ///
+    /// Subscriptions are renewed whenever the client reconnects. Retained messages are ignored so
+    /// that old commands aren't repeated every time the client connects.
+    pub(crate) async fn subscribe(
+        &mut self,
+        topic: String,
+        qos: QoS,
+    ) -> anyhow::Result<broadcast::Receiver<Bytes>> {
+        let mut subscriptions = self.subscriptions.lock().await;
+        if let Some((_, sender)) = subscriptions.get(&topic) {
+            return Ok(sender.subscribe());
+        }
+        let (sender, receiver) = broadcast::channel(Self::SUBSCRIPTION_CAPACITY);
+        subscriptions.insert(topic.clone(), (qos, sender));
+        // If the client isn't connected yet, the subscription is sent once it is.
+        if self.is_connected() {
+            debug!(?topic, "Subscribing to MQTT topic");
+            self.sender
+                .send(rumqttc::Subscribe::new(topic, qos).into())
+                .await
+                .context("Sending subscribe message to internal MQTT client")?;
+        }
+        Ok(receiver)
+    }
+
+    /// Subscribe to every subscribed topic again, as the subscriptions are lost when
+    /// reconnecting.
+    async fn resubscribe(&mut self) -> anyhow::Result<()> {
+        let subscriptions = self.subscriptions.lock().await;
+        for (topic, (qos, _)) in subscriptions.iter() {
+            debug!(?topic, "Subscribing to MQTT topic");
+            self.sender
+                .send(rumqttc::Subscribe::new(topic, *qos).into())
+                .await
+                .context("Sending subscribe message to internal MQTT client")?;
+        }
+        Ok(())
+    }
+
+    /// The payloads of the retained messages, by topic.
+    #[cfg(test)]
+    pub(crate) async fn retained_payloads(&self) -> HashMap<String, Vec<u8>> {
+        let retained = self.retained.lock().await;
+        retained
+            .iter()
+            .map(|(topic, (_, payload))| (topic.clone(), payload.clone()))
+            .collect()
+    }
+
+    /// Whether the client is currently connected to the MQTT broker.
+    pub(crate) fn is_connected(&self) -> bool {
+        *self.connected.borrow()
+    }
+
     pub(crate) async fn publish_if_connected<T: Serialize>(
         &mut self,
         topic: String,
@@ -58,6 +188,31 @@ impl MqttSender {
         Ok(connected)
     }
 
+    pub(crate) async fn publish_bytes_if_connected(
+        &mut self,
+        topic: String,
+        qos: QoS,
+        payload: Vec<u8>,
+        retain: bool,
+    ) -> anyhow::Result<bool> {
+        let connected = *self.connected.borrow_and_update();
+        if connected {
+            self.enqueue_publish_bytes(topic, qos, payload, retain)
+                .await?;
+        }
+        Ok(connected)
+    }
+
+    /// Disconnect from the MQTT broker once all previously enqueued messages have been sent.
+    ///
+    /// The client loop ([`MqttClient::run_loop`]) exits after the disconnection.
+    pub(crate) async fn disconnect(&mut self) -> anyhow::Result<()> {
+        self.sender
+            .send(rumqttc::Request::Disconnect)
+            .await
+            .context("Sending disconnect message to internal MQTT client")
+    }
+
     pub(crate) async fn publish_when_connected<T: Serialize>(
         &mut self,
         topic: String,
@@ -83,22 +238,23 @@ pub(crate) struct MqttClient {
     event_loop: EventLoop,
     connected: watch::Sender<bool>,
     sender: rumqttc::Sender<rumqttc::Request>,
+    retained: RetainedMessages,
+    subscriptions: Subscriptions,
 }
 
 impl MqttClient {
     const EVENT_LOOP_CAPACITY: usize = 20;
 
+    /// The delay before the first reconnection attempt.
+    const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+    /// The longest delay between reconnection attempts.
+    const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
     pub(crate) fn new(settings: &MqttSettings) -> anyhow::Result<Self> {
-        let status_topic = [&settings.base_topic, &settings.name, "status"].join("/");
+        let status_topic = settings.status_topic();
         let mut client_options = RuMqttOptions::try_from(settings)?;
-        client_options
-            .set_last_will(LastWill::new(
-                &status_topic,
-                Status::Offline.to_string().as_bytes(),
-                QoS::AtLeastOnce,
-                true,
-            ))
-            .set_connection_timeout(10);
+        client_options.set_connection_timeout(10);
         let (connected, _) = watch::channel(false);
         let (_, event_loop) = AsyncClient::new(client_options, Self::EVENT_LOOP_CAPACITY);
         let sender = event_loop.handle();
@@ -107,6 +263,8 @@ impl MqttClient {
             event_loop,
             connected,
             sender,
+            retained: RetainedMessages::default(),
+            subscriptions: Subscriptions::default(),
         })
     }
 
@@ -114,41 +272,147 @@ impl MqttClient {
         &self.status_topic
     }
 
+    /// Connect to the MQTT broker once, then disconnect.
+    ///
+    /// This checks that the broker can be reached and that it accepts the configured credentials.
+    /// A different client ID is used (and no last will is set) so that a running instance isn't
+    /// disturbed.
+    pub(crate) async fn check_connection(settings: &MqttSettings) -> anyhow::Result<()> {
+        let options = RuMqttOptions::try_from(settings)?;
+        let (host, port) = options.broker_address();
+        let mut check_options = RuMqttOptions::new(settings.check_client_id()?, host, port);
+        check_options
+            .set_transport(options.transport())
+            .set_connection_timeout(10);
+        if let Some((username, password)) = options.credentials() {
+            check_options.set_credentials(username, password);
+        }
+        let (client, mut event_loop) = AsyncClient::new(check_options, Self::EVENT_LOOP_CAPACITY);
+        loop {
+            match event_loop
+                .poll()
+                .await
+                .context("Unable to connect to the MQTT broker")?
+            {
+                Event::Incoming(Packet::ConnAck(conn_ack)) => {
+                    if conn_ack.code != ConnectReturnCode::Success {
+                        return Err(anyhow!(
+                            "Connection to MQTT broker refused: {:?}",
+                            conn_ack.code
+                        ));
+                    }
+                    debug!("Connected to MQTT broker, disconnecting");
+                    client.disconnect().await?;
+                }
+                Event::Outgoing(Outgoing::Disconnect) => return Ok(()),
+                event => trace!(?event, "MQTT event processed"),
+            }
+        }
+    }
+
     pub(crate) fn new_sender(&self) -> MqttSender {
         MqttSender {
             sender: self.sender.clone(),
             connected: self.connected.subscribe(),
+            retained: Arc::clone(&self.retained),
+            subscriptions: Arc::clone(&self.subscriptions),
         }
     }
 
+    /// Pass the payload of a message on to the subscribers for its topic.
+    ///
+    /// This takes the subscriptions instead of `&self` as the event loop isn't `Sync`.
+    async fn dispatch(subscriptions: &Subscriptions, publish: rumqttc::Publish) {
+        if publish.retain {
+            debug!(topic = ?publish.topic, "Ignoring retained MQTT message");
+            return;
+        }
+        let subscriptions = subscriptions.lock().await;
+        match subscriptions.get(&publish.topic) {
+            // There being no receivers isn't an error, there might be some in the future.
+            Some((_, sender)) => {
+                let _ = sender.send(publish.payload);
+            }
+            None => trace!(topic = ?publish.topic, "Received message for unknown topic"),
+        }
+    }
+
+    // Ignoring the error is fine, as it'll only error if all receivers are dropped. If there are
+    // no receivers, there might be some in the future.
+    fn set_connected(&self, connected: bool) {
+        #[allow(unused_must_use)]
+        {
+            self.connected.send(connected);
+        }
+    }
+
+    /// Mark the client as disconnected, and wait before trying to connect again.
+    ///
+    /// The delay doubles after each consecutive failure, up to [`MqttClient::MAX_BACKOFF`].
+    ///
+    /// This takes the connection state instead of `&self` as the event loop isn't `Sync`.
+    async fn back_off(connected: &watch::Sender<bool>, backoff: &mut Duration) {
+        if *connected.borrow() {
+            warn!("Lost connection to MQTT broker");
+            #[allow(unused_must_use)]
+            {
+                connected.send(false);
+            }
+        }
+        warn!(delay = ?backoff, "Retrying connection to MQTT broker");
+        tokio::time::sleep(*backoff).await;
+        *backoff = std::cmp::min(*backoff * 2, Self::MAX_BACKOFF);
+    }
+
     pub(crate) async fn run_loop(mut self) -> anyhow::Result<()> {
+        let mut backoff = Self::INITIAL_BACKOFF;
+        let mut has_connected = false;
         loop {
             match self.event_loop.poll().await {
                 Ok(Event::Incoming(Packet::ConnAck(conn_ack))) => {
                     if conn_ack.code == ConnectReturnCode::Success {
-                        debug!("Connected to MQTT broker");
-                        // Ignoring the error is fine, as it'll only error if all receivers
-                        // are dropped. If there are no receivers, there might be some in
-                        // the future
-                        #[allow(unused_must_use)]
-                        {
-                            self.connected.send(true);
+                        if has_connected {
+                            info!("Reconnected to MQTT broker");
+                        } else {
+                            info!("Connected to MQTT broker");
                         }
-                        // Set the online status immediately after we connect.
+                        has_connected = true;
+                        backoff = Self::INITIAL_BACKOFF;
+                        self.set_connected(true);
+                        // Set the online status immediately after we connect, then publish the
+                        // retained messages. This is done in a separate task as the event loop
+                        // needs to be polled for the messages to be sent.
                         let mut sender = self.new_sender();
-                        sender
-                            .enqueue_publish(
-                                self.status_topic.clone(),
-                                QoS::AtLeastOnce,
-                                &Status::Online,
-                                true,
-                            )
-                            .await?;
+                        let status_topic = self.status_topic.clone();
+                        tokio::spawn(async move {
+                            let res = sender
+                                .enqueue_publish(
+                                    status_topic,
+                                    QoS::AtLeastOnce,
+                                    &Status::Online,
+                                    true,
+                                )
+                                .await;
+                            if let Err(err) = res.and(sender.republish_retained().await) {
+                                error!(error = ?err, "Unable to publish retained MQTT messages");
+                            }
+                            if let Err(err) = sender.resubscribe().await {
+                                error!(error = ?err, "Unable to subscribe to MQTT topics");
+                            }
+                        });
                     } else {
                         error!(response_code = ?conn_ack.code, "Connection to MQTT broker refused.");
                         return Err(anyhow!("Connection to MQTT broker refused"));
                     }
                 }
+                Ok(Event::Incoming(Packet::Publish(publish))) => {
+                    Self::dispatch(&self.subscriptions, publish).await
+                }
+                Ok(Event::Outgoing(Outgoing::Disconnect)) => {
+                    debug!("Disconnected from MQTT broker");
+                    self.set_connected(false);
+                    return Ok(());
+                }
                 Ok(event) => {
                     trace!(?event, "MQTT event processed")
                 }
@@ -158,17 +422,16 @@ impl MqttClient {
                     // If the rumqttc::tls module (or the Error enum within it) are ever made
                     // accessible, this should get a bit simpler to handle instead of the
                     // dynamic downcasting mess we have here.
-                    error!(error = ?net_err, "Encountered a network connection error");
-                    if let Some(net_err_src) = net_err.source() {
-                        // IO errors can be recoverable, treat everything else as unrecoverable
-                        if net_err_src.is::<std::io::Error>() {
-                            warn!(error = ?net_err, "MQTT client I/O error, retrying connection");
-                            self.connected.send(false).unwrap();
-                        } else {
-                            return Err(net_err).context("MQTT network error");
-                        }
+                    // IO errors can be recoverable, treat everything else as unrecoverable
+                    if net_err
+                        .source()
+                        .is_some_and(|source| source.is::<std::io::Error>())
+                    {
+                        warn!(error = ?net_err, "MQTT client network I/O error");
+                        Self::back_off(&self.connected, &mut backoff).await;
                     } else {
-                        return Err(net_err).context("MQTT network error of unknown kind");
+                        error!(error = ?net_err, "Encountered a network connection error");
+                        return Err(net_err).context("MQTT network error");
                     }
                 }
                 Err(err @ rumqttc::ConnectionError::MqttState(rumqttc::StateError::Connect(_))) => {
@@ -177,25 +440,17 @@ impl MqttClient {
                     error!(error = ?err, "Error connecting to MQTT broker");
                     return Err(err).context("Error connecting to MQTT broker");
                 }
-                Err(err @ rumqttc::ConnectionError::Io(_)) => {
-                    error!(error = ?err, "Connection I/O error");
-                    if let rumqttc::ConnectionError::Io(ref io_err) = err {
-                        match io_err.kind() {
-                            ErrorKind::ConnectionReset
-                            | ErrorKind::ConnectionAborted
-                            | ErrorKind::BrokenPipe
-                            | ErrorKind::TimedOut
-                            | ErrorKind::Interrupted => {
-                                self.connected.send(false).unwrap();
-                                warn!(error = ?err, "Ignoring MQTT I/O error");
-                            }
-                            _ => {
-                                return Err(err).context("MQTT I/O error");
-                            }
-                        }
-                    } else {
-                        return Err(err).context("Somehow an I/O error wasn't an I/O error");
-                    }
+                // I/O errors (including the broker being unreachable), timeouts and missed pings
+                // are all retried.
+                Err(
+                    err @ (rumqttc::ConnectionError::Io(_)
+                    | rumqttc::ConnectionError::Timeout(_)
+                    | rumqttc::ConnectionError::MqttState(
+                        rumqttc::StateError::Io(_) | rumqttc::StateError::AwaitPingResp,
+                    )),
+                ) => {
+                    warn!(error = ?err, "MQTT connection error");
+                    Self::back_off(&self.connected, &mut backoff).await;
                 }
                 Err(err) => {
                     // Treat all other errors as non-recoverable
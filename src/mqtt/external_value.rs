@@ -3,25 +3,43 @@ use std::convert::TryFrom;
 use std::fmt;
 use std::path::PathBuf;
 
-use serde::Deserialize;
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize, Serializer};
 use tracing::debug;
-/// A type that can either be deserialized either from a string or a path to a file.
+/// A type that can either be deserialized either from a string, a path to a file, or the name of
+/// an environment variable.
 ///
 /// If just a plain string is present, that value is used. If a map with a key 'file' with a string
 /// value is provided, the inner string value is taken as a path to a file, the contents of which
-/// will be read and used ad the final value.
-#[derive(Deserialize, PartialEq)]
+/// will be read and used ad the final value. If a map with a key 'env' is provided instead, the
+/// value of the named environment variable is used.
+#[derive(Clone, Deserialize, PartialEq)]
 #[serde(try_from = "InnerExternalValue")]
 pub struct ExternalValue(pub String);
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
 #[serde(untagged)]
 enum InnerExternalValue {
     File { file: PathBuf },
 
+    Env { env: String },
+
     String(String),
 }
 
+impl JsonSchema for ExternalValue {
+    fn schema_name() -> String {
+        "ExternalValue".to_string()
+    }
+
+    /// External values are deserialized through [`InnerExternalValue`], so they share its schema.
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        InnerExternalValue::json_schema(gen)
+    }
+}
+
 impl fmt::Debug for ExternalValue {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_tuple("ExternalValue")
@@ -39,6 +57,16 @@ impl fmt::Display for ExternalValue {
     }
 }
 
+impl Serialize for ExternalValue {
+    /// Secrets are censored when serialized, the same as when they're displayed.
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
 impl From<&str> for ExternalValue {
     fn from(s: &str) -> Self {
         Self(s.to_string())
@@ -60,6 +88,15 @@ impl TryFrom<InnerExternalValue> for ExternalValue {
                 debug!("Reading secret from {:?}", file);
                 std::fs::read_to_string(file).map(|s| Self(s.trim().to_string()))
             }
+            InnerExternalValue::Env { env } => {
+                debug!("Reading secret from ${}", env);
+                std::env::var(&env).map(Self).map_err(|err| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::NotFound,
+                        format!("Unable to read environment variable {}: {}", env, err),
+                    )
+                })
+            }
             InnerExternalValue::String(s) => {
                 debug!("Using secret directly");
                 Ok(Self(s))
@@ -123,6 +160,34 @@ mod test {
         assert_eq!(parsed.field.0, file_value.to_string());
     }
 
+    #[test]
+    fn read_env() {
+        std::env::set_var("RUSTILLTHERE_TEST_READ_ENV", "foo bar baz");
+        let parsed: Result<Wrapper, _> = toml::from_str(
+            r#"
+        field = { env = "RUSTILLTHERE_TEST_READ_ENV" }
+        "#,
+        );
+        assert!(
+            parsed.is_ok(),
+            "unable to read from the environment: {:?}",
+            parsed
+        );
+        let parsed = parsed.unwrap();
+        assert_eq!(parsed.field.0, "foo bar baz".to_string());
+        assert_eq!(format!("{:?}", parsed.field), r#"ExternalValue("*******")"#);
+    }
+
+    #[test]
+    fn missing_env() {
+        let parsed: Result<Wrapper, _> = toml::from_str(
+            r#"
+        field = { env = "RUSTILLTHERE_TEST_NOT_A_REAL_VARIABLE" }
+        "#,
+        );
+        assert!(parsed.is_err());
+    }
+
     #[test]
     fn read_file_trailing_newline() {
         let mut file = NamedTempFile::new().expect("to be able to create a temp file");
@@ -83,6 +83,10 @@ where
     #[serde(alias = "json_attr_t", default, skip_serializing_if = "is_default")]
     pub json_attributes_topic: Option<String>,
 
+    // Home Assistant uses this to generate the entity ID, instead of the name.
+    #[serde(alias = "obj_id", default, skip_serializing_if = "is_default")]
+    pub object_id: Option<String>,
+
     // Not including 'name', as the default value for that is specific to the type of device
     #[serde(alias = "pl_avail", default, skip_serializing_if = "is_default")]
     pub payload_available: PayloadAvailable,
@@ -93,7 +97,8 @@ where
     #[serde(default, skip_serializing_if = "is_default")]
     pub qos: SensorQoS,
 
-    #[serde(alias = "stat_t")]
+    // Cameras use `topic` instead of `state_topic`, so this is skipped when empty.
+    #[serde(alias = "stat_t", default, skip_serializing_if = "is_default")]
     pub state_topic: String,
 
     #[serde(alias = "uniq_id", default, skip_serializing_if = "is_default")]
@@ -121,6 +126,7 @@ where
             icon: None,
             json_attributes_template: None,
             json_attributes_topic: None,
+            object_id: None,
             payload_available: PayloadAvailable::default(),
             payload_not_available: PayloadNotAvailable::default(),
             qos: SensorQoS::default(),
@@ -2,7 +2,12 @@
 mod common;
 mod device;
 mod sensor;
+mod trigger;
 mod util;
 
 pub use device::{Connection, Device};
-pub use sensor::{AnalogSensor, AnalogSensorClass, BinarySensor, BinarySensorClass, Component};
+pub use sensor::{
+    AnalogSensor, AnalogSensorClass, AnalogSensorStateClass, BinarySensor, BinarySensorClass,
+    Camera, Component,
+};
+pub use trigger::DeviceTrigger;
@@ -21,8 +21,11 @@ pub enum Component {
     /// Non-binary sensors, with many values
     Sensor,
 
-    /// Cameras. Not available yet.
+    /// Cameras
     Camera,
+
+    /// Device automations (only triggers for now)
+    DeviceAutomation,
 }
 
 impl std::string::ToString for Component {
@@ -32,6 +35,7 @@ impl std::string::ToString for Component {
             Component::BinarySensor => "binary_sensor",
             Component::Sensor => "sensor",
             Component::Camera => "camera",
+            Component::DeviceAutomation => "device_automation",
         }
         .to_string()
     }
@@ -105,6 +109,7 @@ macro_rules! expose_common {
         expose_mqtt_config!(icon, Option<String>);
         expose_mqtt_config!(json_attributes_template, Option<String>);
         expose_mqtt_config!(json_attributes_topic, Option<String>);
+        expose_mqtt_config!(object_id, Option<String>);
         expose_mqtt_config!(payload_available, PayloadAvailable);
         expose_mqtt_config!(payload_not_available, PayloadNotAvailable);
         expose_mqtt_config!(qos, SensorQoS);
@@ -123,6 +128,7 @@ pub enum BinarySensorClass {
     Battery,
     Connectivity,
     Occupancy,
+    Problem,
 }
 
 impl Default for BinarySensorClass {
@@ -210,6 +216,7 @@ where
 #[serde(rename_all = "snake_case")]
 pub enum AnalogSensorClass {
     None,
+    Duration,
     SignalStrength,
     Temperature,
     Timestamp,
@@ -221,6 +228,23 @@ impl Default for AnalogSensorClass {
     }
 }
 
+/// How Home Assistant should keep long-term statistics for a sensor.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnalogSensorStateClass {
+    None,
+    Measurement,
+    Total,
+    /// A total that only ever increases, except when it's reset back to zero.
+    TotalIncreasing,
+}
+
+impl Default for AnalogSensorStateClass {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
 default_string!(AnalogSensorName, "MQTT Sensor");
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
@@ -237,6 +261,9 @@ where
     #[serde(default, skip_serializing_if = "is_default")]
     name: AnalogSensorName,
 
+    #[serde(alias = "stat_cla", default, skip_serializing_if = "is_default")]
+    state_class: AnalogSensorStateClass,
+
     #[serde(alias = "unit_of_meas", default, skip_serializing_if = "is_default")]
     unit_of_measurement: Option<String>,
 }
@@ -248,6 +275,7 @@ where
 {
     expose_common!();
     expose_inner!(device_class, AnalogSensorClass);
+    expose_inner!(state_class, AnalogSensorStateClass);
     expose_inner!(unit_of_measurement, Option<String>);
 
     pub fn new_with_state_topic_and_device<S>(state_topic: S, device: P) -> Self
@@ -258,6 +286,7 @@ where
             mqtt: EntityConfig::new_with_state_and_device(state_topic, device),
             device_class: AnalogSensorClass::default(),
             name: AnalogSensorName::default(),
+            state_class: AnalogSensorStateClass::default(),
             unit_of_measurement: None,
         }
     }
@@ -279,3 +308,59 @@ where
         Self::Sensor
     }
 }
+
+default_string!(CameraName, "MQTT Camera");
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Camera<P>
+where
+    P: Borrow<Device> + Default + PartialEq,
+{
+    #[serde(flatten)]
+    mqtt: EntityConfig<P>,
+
+    #[serde(default, skip_serializing_if = "is_default")]
+    name: CameraName,
+
+    /// The topic images are published to.
+    #[serde(alias = "t")]
+    topic: String,
+}
+
+#[allow(dead_code)]
+impl<P> Camera<P>
+where
+    P: Borrow<Device> + Default + PartialEq,
+{
+    expose_common!();
+    expose_inner!(topic, String);
+
+    pub fn new_with_topic_and_device<S>(topic: S, device: P) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            // Cameras don't have a state topic, just the image topic.
+            mqtt: EntityConfig::new_with_state_and_device(String::new(), device),
+            name: CameraName::default(),
+            topic: topic.into(),
+        }
+    }
+
+    pub fn name(&self) -> &String {
+        &self.name.0
+    }
+
+    pub fn set_name(&mut self, new_name: String) {
+        self.name.0 = new_name;
+    }
+}
+
+impl<P> From<&Camera<P>> for Component
+where
+    P: Borrow<Device> + Default + PartialEq,
+{
+    fn from(_: &Camera<P>) -> Self {
+        Self::Camera
+    }
+}
@@ -0,0 +1,106 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+use std::borrow::Borrow;
+
+use paste::paste;
+use serde::{Deserialize, Serialize};
+
+use super::common::SensorQoS;
+use super::device::Device;
+use super::sensor::Component;
+use super::util::is_default;
+use crate::{default_newtype, default_string, expose_inner};
+
+default_string!(AutomationType, "trigger");
+
+/// A device trigger, for event-style integrations.
+///
+/// Unlike the other components, device triggers are not entities. Instead they show up as triggers
+/// in the Home Assistant automation editor, and need to be attached to a device.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct DeviceTrigger<P>
+where
+    P: Borrow<Device> + Default + PartialEq,
+{
+    /// The only supported value is "trigger".
+    #[serde(alias = "atype")]
+    automation_type: AutomationType,
+
+    #[serde(alias = "dev")]
+    device: P,
+
+    /// The payload that fires the trigger. If not set, any payload fires the trigger.
+    #[serde(alias = "pl", default, skip_serializing_if = "is_default")]
+    payload: Option<String>,
+
+    #[serde(default, skip_serializing_if = "is_default")]
+    qos: SensorQoS,
+
+    #[serde(alias = "stype")]
+    subtype: String,
+
+    #[serde(alias = "t")]
+    topic: String,
+
+    #[serde(rename = "type")]
+    trigger_type: String,
+
+    #[serde(alias = "val_tpl", default, skip_serializing_if = "is_default")]
+    value_template: Option<String>,
+}
+
+#[allow(dead_code)]
+impl<P> DeviceTrigger<P>
+where
+    P: Borrow<Device> + Default + PartialEq,
+{
+    expose_inner!(payload, Option<String>);
+    expose_inner!(qos, SensorQoS);
+    expose_inner!(subtype, String);
+    expose_inner!(topic, String);
+    expose_inner!(trigger_type, String);
+    expose_inner!(value_template, Option<String>);
+
+    pub fn new_with_topic_and_device<S, T, U>(
+        topic: S,
+        trigger_type: T,
+        subtype: U,
+        device: P,
+    ) -> Self
+    where
+        S: Into<String>,
+        T: Into<String>,
+        U: Into<String>,
+    {
+        Self {
+            automation_type: AutomationType::default(),
+            device,
+            payload: None,
+            qos: SensorQoS::default(),
+            subtype: subtype.into(),
+            topic: topic.into(),
+            trigger_type: trigger_type.into(),
+            value_template: None,
+        }
+    }
+
+    pub fn component() -> Component {
+        Component::DeviceAutomation
+    }
+
+    pub fn device(&self) -> &Device {
+        self.device.borrow()
+    }
+
+    pub fn set_device(&mut self, device: P) {
+        self.device = device;
+    }
+}
+
+impl<P> From<&DeviceTrigger<P>> for Component
+where
+    P: Borrow<Device> + Default + PartialEq,
+{
+    fn from(_: &DeviceTrigger<P>) -> Self {
+        Self::DeviceAutomation
+    }
+}
@@ -8,6 +8,10 @@ mod state;
 mod state_values;
 
 pub(crate) use client::{MqttClient, MqttSender};
+pub(crate) use external_value::ExternalValue;
 pub(crate) use settings::{MqttSettings, MqttUrl};
 pub(crate) use state::{DiscoveryValue, State};
-pub(crate) use state_values::{Occupancy, OccupancyCount, Status};
+pub(crate) use state_values::{
+    CameraImage, Capacity, CrossingCount, Loitering, Occupancy, OccupancyCount, OccupancyDuration,
+    PersonEntered, PersonExited, Status, TrackedObjects,
+};
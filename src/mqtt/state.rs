@@ -3,6 +3,7 @@ use std::borrow::Borrow;
 use std::fmt;
 use std::ops::Deref;
 
+use bytes::Bytes;
 use futures::sink::{unfold, Sink};
 use rumqttc::QoS;
 use serde::Serialize;
@@ -62,6 +63,17 @@ impl InnerState {
             .publish_when_connected(self.topic.clone(), self.qos, &value, self.retain)
             .await
     }
+
+    /// Publish a raw payload, skipping serialization.
+    ///
+    /// Unlike [`InnerState::publish`], the payload is dropped if the client isn't connected.
+    async fn publish_bytes(&mut self, payload: Bytes) -> anyhow::Result<()> {
+        debug!(length = payload.len(), ?self.topic, "Publishing raw payload to topic");
+        self.sender
+            .publish_bytes_if_connected(self.topic.clone(), self.qos, payload.to_vec(), self.retain)
+            .await
+            .map(|_| ())
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -190,6 +202,15 @@ where
         })
     }
 
+    /// A sink for publishing raw payloads (like images) to this state's topic.
+    pub(crate) fn bytes_sink(&self) -> impl Sink<Bytes, Error = anyhow::Error> {
+        let unfold_state = self.inner().clone();
+        unfold(unfold_state, move |mut inner, payload| async move {
+            inner.publish_bytes(payload).await?;
+            Ok(inner)
+        })
+    }
+
     pub(crate) fn discovery_config<T>(&self, availability_topic: &str) -> Option<T::Config>
     where
         T: DiscoveryValue<D> + fmt::Debug,
@@ -239,26 +260,76 @@ where
 
     pub(crate) async fn publish_home_assistant_discovery<T>(
         &mut self,
-        home_assistant_prefix: &str,
+        home_assistant_prefixes: &[String],
+        availability_topic: &str,
+    ) -> anyhow::Result<()>
+    where
+        T: DiscoveryValue<D> + fmt::Debug,
+        <T as DiscoveryValue<D>>::Config: fmt::Debug,
+    {
+        self.publish_home_assistant_discovery_with::<T, _>(
+            home_assistant_prefixes,
+            availability_topic,
+            |_| (),
+        )
+        .await
+    }
+
+    /// Publish the Home Assistant discovery configuration, after it's been modified by `configure`.
+    ///
+    /// The same configuration is published under each of the discovery prefixes, so the state and
+    /// availability topics are shared between all of them.
+    pub(crate) async fn publish_home_assistant_discovery_with<T, F>(
+        &mut self,
+        home_assistant_prefixes: &[String],
         availability_topic: &str,
+        configure: F,
+    ) -> anyhow::Result<()>
+    where
+        T: DiscoveryValue<D> + fmt::Debug,
+        <T as DiscoveryValue<D>>::Config: fmt::Debug,
+        F: FnOnce(&mut T::Config),
+    {
+        let mut config = match self.discovery_config::<T>(availability_topic) {
+            Some(config) => config,
+            None => return Ok(()),
+        };
+        configure(&mut config);
+        for prefix in home_assistant_prefixes {
+            if let Some(config_topic) = self.discovery_topic::<T>(prefix) {
+                debug!(?config, "Publishing Home Assistant discovery config");
+                self.inner_mut()
+                    .sender
+                    // Discovery messages should be retained
+                    .enqueue_publish(config_topic, QoS::AtLeastOnce, &config, true)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the Home Assistant discovery configuration from each of the discovery prefixes.
+    ///
+    /// An empty retained message on a config topic removes the entity from Home Assistant, so an
+    /// entity that has been disabled doesn't linger from when it was last enabled.
+    pub(crate) async fn remove_home_assistant_discovery<T>(
+        &mut self,
+        home_assistant_prefixes: &[String],
     ) -> anyhow::Result<()>
     where
         T: DiscoveryValue<D> + fmt::Debug,
         <T as DiscoveryValue<D>>::Config: fmt::Debug,
     {
-        let discovery_topic = self.discovery_topic::<T>(home_assistant_prefix);
-        let discovery_config = self.discovery_config::<T>(availability_topic);
-        if let Some((config_topic, config)) = discovery_topic.zip(discovery_config) {
-            debug!(?config, "Publishing Home Assistant discovery config");
-            self.inner_mut()
-                .sender
-                // Discovery messages should be retained
-                .enqueue_publish(config_topic, QoS::AtLeastOnce, &config, true)
-                .await
-                .map_err(anyhow::Error::from)
-        } else {
-            Ok(())
+        for prefix in home_assistant_prefixes {
+            if let Some(config_topic) = self.discovery_topic::<T>(prefix) {
+                debug!(?config_topic, "Removing Home Assistant discovery config");
+                self.inner_mut()
+                    .sender
+                    .enqueue_publish_bytes(config_topic, QoS::AtLeastOnce, Vec::new(), true)
+                    .await?;
+            }
         }
+        Ok(())
     }
 
     fn unique_id(&self) -> Option<String> {
@@ -1,9 +1,13 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 use std::borrow::Borrow;
+use std::fmt;
 use std::string::ToString;
+use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
 
+use crate::occupancy::TrackedObject;
+
 use super::home_assistant as hass;
 use super::state::DiscoveryValue;
 
@@ -156,6 +160,130 @@ impl ToString for Occupancy {
     }
 }
 
+/// Whether there are more people in a location than it's meant to hold.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Capacity {
+    Over,
+    #[default]
+    Normal,
+}
+
+impl<D> DiscoveryValue<D> for Capacity
+where
+    D: Borrow<hass::Device>,
+    D: Default + PartialEq,
+    D: Serialize,
+{
+    type Config = hass::BinarySensor<D>;
+
+    fn retained() -> bool {
+        true
+    }
+
+    fn component_type() -> hass::Component {
+        hass::Component::BinarySensor
+    }
+
+    fn home_assistant_config(
+        device: D,
+        state_topic: String,
+        availability_topic: String,
+        name: String,
+        unique_id: String,
+    ) -> Self::Config {
+        let mut config = hass::BinarySensor::new_with_state_topic_and_device(state_topic, device);
+        config.add_availability_topic(availability_topic);
+        config.set_device_class(hass::BinarySensorClass::Problem);
+        config.set_name(name);
+        config.set_unique_id(Some(unique_id));
+        config.set_payload_on(Self::Over.to_string().into());
+        config.set_payload_off(Self::Normal.to_string().into());
+        config
+    }
+}
+
+impl From<bool> for Capacity {
+    fn from(over: bool) -> Self {
+        if over {
+            Self::Over
+        } else {
+            Self::Normal
+        }
+    }
+}
+
+impl fmt::Display for Capacity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Capacity::Over => "over",
+            Capacity::Normal => "normal",
+        })
+    }
+}
+
+/// Whether someone has stayed in the same spot for too long.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Loitering {
+    Loitering,
+    #[default]
+    Clear,
+}
+
+impl<D> DiscoveryValue<D> for Loitering
+where
+    D: Borrow<hass::Device>,
+    D: Default + PartialEq,
+    D: Serialize,
+{
+    type Config = hass::BinarySensor<D>;
+
+    fn retained() -> bool {
+        true
+    }
+
+    fn component_type() -> hass::Component {
+        hass::Component::BinarySensor
+    }
+
+    fn home_assistant_config(
+        device: D,
+        state_topic: String,
+        availability_topic: String,
+        name: String,
+        unique_id: String,
+    ) -> Self::Config {
+        let mut config = hass::BinarySensor::new_with_state_topic_and_device(state_topic, device);
+        config.add_availability_topic(availability_topic);
+        config.set_device_class(hass::BinarySensorClass::Problem);
+        config.set_name(name);
+        config.set_unique_id(Some(unique_id));
+        config.set_payload_on(Self::Loitering.to_string().into());
+        config.set_payload_off(Self::Clear.to_string().into());
+        config
+    }
+}
+
+impl From<bool> for Loitering {
+    fn from(loitering: bool) -> Self {
+        if loitering {
+            Self::Loitering
+        } else {
+            Self::Clear
+        }
+    }
+}
+
+impl fmt::Display for Loitering {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Loitering::Loitering => "loitering",
+            Loitering::Clear => "clear",
+        })
+    }
+}
+
 #[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub(crate) struct OccupancyCount(usize);
 
@@ -209,6 +337,257 @@ impl From<OccupancyCount> for usize {
     }
 }
 
+/// The number of people that have crossed a counting line in one direction.
+///
+/// The count starts from zero every time r-u-still-there starts, so it's marked as a
+/// `total_increasing` sensor, which Home Assistant treats as a counter that may be reset.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(transparent)]
+pub(crate) struct CrossingCount(usize);
+
+impl<D> DiscoveryValue<D> for CrossingCount
+where
+    D: Borrow<hass::Device>,
+    D: Default + PartialEq,
+    D: Serialize,
+{
+    type Config = hass::AnalogSensor<D>;
+
+    fn retained() -> bool {
+        true
+    }
+
+    fn component_type() -> hass::Component {
+        hass::Component::Sensor
+    }
+
+    fn home_assistant_config(
+        device: D,
+        state_topic: String,
+        availability_topic: String,
+        name: String,
+        unique_id: String,
+    ) -> Self::Config {
+        let mut config = hass::AnalogSensor::new_with_state_topic_and_device(state_topic, device);
+        config.add_availability_topic(availability_topic);
+        config.set_state_class(hass::AnalogSensorStateClass::TotalIncreasing);
+        config.set_unit_of_measurement(Some("people".to_string()));
+        config.set_name(name);
+        config.set_unique_id(Some(unique_id));
+        config
+    }
+}
+
+impl From<usize> for CrossingCount {
+    fn from(count: usize) -> Self {
+        Self(count)
+    }
+}
+
+/// A length of time, in whole seconds.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(transparent)]
+pub(crate) struct OccupancyDuration(u64);
+
+impl<D> DiscoveryValue<D> for OccupancyDuration
+where
+    D: Borrow<hass::Device>,
+    D: Default + PartialEq,
+    D: Serialize,
+{
+    type Config = hass::AnalogSensor<D>;
+
+    fn retained() -> bool {
+        true
+    }
+
+    fn component_type() -> hass::Component {
+        hass::Component::Sensor
+    }
+
+    fn home_assistant_config(
+        device: D,
+        state_topic: String,
+        availability_topic: String,
+        name: String,
+        unique_id: String,
+    ) -> Self::Config {
+        let mut config = hass::AnalogSensor::new_with_state_topic_and_device(state_topic, device);
+        config.add_availability_topic(availability_topic);
+        config.set_device_class(hass::AnalogSensorClass::Duration);
+        config.set_unit_of_measurement(Some("s".to_string()));
+        config.set_name(name);
+        config.set_unique_id(Some(unique_id));
+        config
+    }
+}
+
+impl From<Duration> for OccupancyDuration {
+    fn from(duration: Duration) -> Self {
+        Self(duration.as_secs())
+    }
+}
+
+/// The objects currently being tracked.
+///
+/// The state of the Home Assistant sensor is the number of objects, with the full list of objects
+/// available as the `objects` attribute.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(transparent)]
+pub(crate) struct TrackedObjects(Vec<TrackedObject>);
+
+impl<D> DiscoveryValue<D> for TrackedObjects
+where
+    D: Borrow<hass::Device>,
+    D: Default + PartialEq,
+    D: Serialize,
+{
+    type Config = hass::AnalogSensor<D>;
+
+    fn retained() -> bool {
+        false
+    }
+
+    fn component_type() -> hass::Component {
+        hass::Component::Sensor
+    }
+
+    fn home_assistant_config(
+        device: D,
+        state_topic: String,
+        availability_topic: String,
+        name: String,
+        unique_id: String,
+    ) -> Self::Config {
+        let mut config =
+            hass::AnalogSensor::new_with_state_topic_and_device(state_topic.clone(), device);
+        config.add_availability_topic(availability_topic);
+        config.set_name(name);
+        config.set_unique_id(Some(unique_id));
+        config.set_value_template(Some("{{ value_json | count }}".to_string()));
+        config.set_json_attributes_topic(Some(state_topic));
+        config.set_json_attributes_template(Some(
+            "{{ {'objects': value_json} | tojson }}".to_string(),
+        ));
+        config
+    }
+}
+
+impl From<Vec<TrackedObject>> for TrackedObjects {
+    fn from(objects: Vec<TrackedObject>) -> Self {
+        Self(objects)
+    }
+}
+
+/// A camera image.
+///
+/// Images are published as raw JPEG bytes with [`State::bytes_sink`], so this type is only used
+/// for Home Assistant discovery.
+///
+/// [`State::bytes_sink`]: super::State::bytes_sink
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub(crate) struct CameraImage;
+
+impl<D> DiscoveryValue<D> for CameraImage
+where
+    D: Borrow<hass::Device>,
+    D: Default + PartialEq,
+    D: Serialize,
+{
+    type Config = hass::Camera<D>;
+
+    fn retained() -> bool {
+        false
+    }
+
+    fn component_type() -> hass::Component {
+        hass::Component::Camera
+    }
+
+    fn home_assistant_config(
+        device: D,
+        state_topic: String,
+        availability_topic: String,
+        name: String,
+        unique_id: String,
+    ) -> Self::Config {
+        let mut config = hass::Camera::new_with_topic_and_device(state_topic, device);
+        config.add_availability_topic(availability_topic);
+        config.set_name(name);
+        config.set_unique_id(Some(unique_id));
+        config
+    }
+}
+
+/// Define a unit type for an event published as a Home Assistant device trigger.
+///
+/// The trigger type is `$trigger_type`, the subtype is always "occupancy", and the event payload is
+/// `$payload`.
+macro_rules! trigger_event {
+    ($(#[$meta:meta])* $name:ident, $trigger_type:literal, $payload:literal) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+        #[serde(into = "&'static str")]
+        pub(crate) struct $name;
+
+        impl From<$name> for &'static str {
+            fn from(_: $name) -> Self {
+                $payload
+            }
+        }
+
+        impl<D> DiscoveryValue<D> for $name
+        where
+            D: Borrow<hass::Device>,
+            D: Default + PartialEq,
+            D: Serialize,
+        {
+            type Config = hass::DeviceTrigger<D>;
+
+            fn retained() -> bool {
+                false
+            }
+
+            fn component_type() -> hass::Component {
+                hass::Component::DeviceAutomation
+            }
+
+            // Device triggers aren't entities, so they don't have names, unique IDs, or
+            // availability.
+            fn home_assistant_config(
+                device: D,
+                state_topic: String,
+                _availability_topic: String,
+                _name: String,
+                _unique_id: String,
+            ) -> Self::Config {
+                let mut config = hass::DeviceTrigger::new_with_topic_and_device(
+                    state_topic,
+                    $trigger_type,
+                    "occupancy",
+                    device,
+                );
+                config.set_payload(Some($payload.to_string()));
+                config
+            }
+        }
+    };
+}
+
+trigger_event!(
+    /// Someone entering the monitored area.
+    PersonEntered,
+    "person_entered",
+    "entered"
+);
+
+trigger_event!(
+    /// Someone leaving the monitored area.
+    PersonExited,
+    "person_exited",
+    "exited"
+);
+
 // Fallback implementations for primitives
 
 impl<D> DiscoveryValue<D> for bool
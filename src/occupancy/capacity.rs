@@ -0,0 +1,58 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+use futures::{Stream, StreamExt};
+
+/// Convert a stream of occupancy counts to whether the space is over its capacity.
+///
+/// The space is over capacity as soon as the count goes above `limit`, but it isn't back under
+/// capacity until the count is at or below `limit - hysteresis`. There is one value for each
+/// count.
+pub(crate) fn over_capacity<S>(
+    counts: S,
+    limit: usize,
+    hysteresis: usize,
+) -> impl Stream<Item = bool>
+where
+    S: Stream<Item = usize>,
+{
+    let lower = limit.saturating_sub(hysteresis);
+    counts.scan(false, move |over, count| {
+        if count > limit {
+            *over = true;
+        } else if count <= lower {
+            *over = false;
+        }
+        std::future::ready(Some(*over))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use futures::{stream, StreamExt};
+
+    use super::over_capacity;
+
+    #[tokio::test]
+    async fn hysteresis() {
+        let counts = stream::iter([2, 3, 4, 3, 4, 3, 2, 3, 4]);
+        let collected: Vec<bool> = over_capacity(counts, 3, 1).collect().await;
+        assert_eq!(
+            collected,
+            [false, false, true, true, true, true, false, false, true]
+        );
+    }
+
+    #[tokio::test]
+    async fn no_hysteresis() {
+        let counts = stream::iter([3, 4, 3, 4]);
+        let collected: Vec<bool> = over_capacity(counts, 3, 0).collect().await;
+        assert_eq!(collected, [false, true, false, true]);
+    }
+
+    #[tokio::test]
+    async fn large_hysteresis() {
+        // The hysteresis can't go below 0 people.
+        let counts = stream::iter([2, 1, 0]);
+        let collected: Vec<bool> = over_capacity(counts, 1, 5).collect().await;
+        assert_eq!(collected, [true, true, false]);
+    }
+}
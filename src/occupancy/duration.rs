@@ -0,0 +1,134 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+use std::time::{Duration, Instant};
+
+use futures::stream::{self, Stream, StreamExt};
+use tokio_stream::wrappers::IntervalStream;
+
+/// Tracks how long a space has been continuously occupied or vacant.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct OccupancyTimer {
+    occupied: bool,
+    since: Instant,
+}
+
+impl OccupancyTimer {
+    pub(crate) fn new(occupied: bool, now: Instant) -> Self {
+        Self {
+            occupied,
+            since: now,
+        }
+    }
+
+    /// Update the occupancy state, resetting the timer if the state changed.
+    pub(crate) fn update(&mut self, occupied: bool, now: Instant) {
+        if occupied != self.occupied {
+            self.occupied = occupied;
+            self.since = now;
+        }
+    }
+
+    /// How long the space has been continuously occupied, or zero if it's vacant.
+    pub(crate) fn occupied_for(&self, now: Instant) -> Duration {
+        if self.occupied {
+            now.saturating_duration_since(self.since)
+        } else {
+            Duration::ZERO
+        }
+    }
+
+    /// How long the space has been continuously vacant, or zero if it's occupied.
+    pub(crate) fn vacant_for(&self, now: Instant) -> Duration {
+        if self.occupied {
+            Duration::ZERO
+        } else {
+            now.saturating_duration_since(self.since)
+        }
+    }
+}
+
+/// Create a stream of occupancy durations from a stream of occupancy states.
+///
+/// The returned stream yields `(occupied_for, vacant_for)` whenever the occupancy changes, and
+/// every `interval` in between. An `interval` of zero only yields durations when the occupancy
+/// changes.
+pub(crate) fn occupancy_durations<S>(
+    occupancy: S,
+    interval: Duration,
+) -> impl Stream<Item = (Duration, Duration)>
+where
+    S: Stream<Item = bool> + Send + 'static,
+{
+    // tokio::time::interval panics with a zero period.
+    let ticks = if interval.is_zero() {
+        stream::empty().left_stream()
+    } else {
+        IntervalStream::new(tokio::time::interval(interval))
+            .map(|_| None)
+            .right_stream()
+    };
+    let timer = OccupancyTimer::new(false, Instant::now());
+    stream::select(occupancy.map(Some), ticks).scan(timer, |timer, occupied| {
+        let now = Instant::now();
+        if let Some(occupied) = occupied {
+            timer.update(occupied, now);
+        }
+        futures::future::ready(Some((timer.occupied_for(now), timer.vacant_for(now))))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use futures::stream::{self, StreamExt};
+
+    use super::{occupancy_durations, OccupancyTimer};
+
+    #[test]
+    fn timer_transitions() {
+        let start = Instant::now();
+        let mut timer = OccupancyTimer::new(false, start);
+        let later = start + Duration::from_secs(30);
+        assert_eq!(timer.vacant_for(later), Duration::from_secs(30));
+        assert_eq!(timer.occupied_for(later), Duration::ZERO);
+        // Becoming occupied resets the timer
+        timer.update(true, later);
+        let even_later = later + Duration::from_secs(10);
+        assert_eq!(timer.occupied_for(even_later), Duration::from_secs(10));
+        assert_eq!(timer.vacant_for(even_later), Duration::ZERO);
+        // Repeated updates with the same state don't reset it
+        timer.update(true, even_later);
+        let latest = even_later + Duration::from_secs(5);
+        assert_eq!(timer.occupied_for(latest), Duration::from_secs(15));
+        // And going vacant starts the vacant timer from zero again
+        timer.update(false, latest);
+        assert_eq!(timer.vacant_for(latest), Duration::ZERO);
+        assert_eq!(timer.occupied_for(latest), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn durations_on_change() {
+        // Use a long interval so only the first tick is seen along with the changes.
+        let changes = stream::iter([true, false]);
+        let durations: Vec<(Duration, Duration)> =
+            occupancy_durations(changes, Duration::from_secs(3600))
+                .take(3)
+                .collect()
+                .await;
+        assert_eq!(durations.len(), 3);
+        // Everything happens nearly instantly, so the durations are all close to zero.
+        for (occupied, vacant) in durations {
+            assert!(occupied < Duration::from_secs(1));
+            assert!(vacant < Duration::from_secs(1));
+        }
+    }
+
+    #[tokio::test]
+    async fn zero_interval_only_on_change() {
+        let changes = stream::iter([true, false]);
+        let durations: Vec<(Duration, Duration)> =
+            occupancy_durations(changes, Duration::ZERO).collect().await;
+        // No ticks, just the two changes.
+        assert_eq!(durations.len(), 2);
+    }
+}
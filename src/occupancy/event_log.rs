@@ -0,0 +1,236 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{sync_channel, Receiver, TrySendError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Context as _;
+use futures::{Future, Stream, StreamExt};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::task::spawn_blocking;
+use tracing::{debug, warn};
+
+use super::transition::{count_changes, CountChange};
+
+/// How many events can be waiting to be written before new events are dropped.
+const EVENT_BUFFER_SIZE: usize = 64;
+
+/// Settings for the occupancy event log.
+#[derive(Clone, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
+pub(crate) struct EventLogSettings {
+    /// The file to append events to.
+    pub(crate) path: PathBuf,
+
+    /// The size (in bytes) the log can grow to before it is rotated.
+    ///
+    /// When rotated, the current log is renamed with a `.1` suffix (replacing any previous rotated
+    /// log), and a new log is started. Defaults to 1 MiB.
+    #[serde(default = "EventLogSettings::default_max_size")]
+    pub(crate) max_size: u64,
+}
+
+impl EventLogSettings {
+    const fn default_max_size() -> u64 {
+        1024 * 1024
+    }
+}
+
+/// A change in the occupancy count, written as a single line of JSON.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+struct OccupancyEvent {
+    /// When the change happened, as seconds since the Unix epoch.
+    ts: f64,
+
+    /// The new occupancy count.
+    count: usize,
+
+    /// The difference from the previous count.
+    delta: i64,
+}
+
+impl OccupancyEvent {
+    fn new(change: CountChange) -> Self {
+        let ts = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        Self {
+            ts,
+            count: change.count,
+            delta: change.delta(),
+        }
+    }
+}
+
+/// Appends events to a file, rotating it once it grows too large.
+struct EventWriter {
+    path: PathBuf,
+    max_size: u64,
+    file: File,
+    size: u64,
+}
+
+impl EventWriter {
+    fn open(settings: &EventLogSettings) -> io::Result<Self> {
+        let file = open_append(&settings.path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path: settings.path.clone(),
+            max_size: settings.max_size,
+            file,
+            size,
+        })
+    }
+
+    fn rotated_path(&self) -> PathBuf {
+        let mut rotated = self.path.clone().into_os_string();
+        rotated.push(".1");
+        rotated.into()
+    }
+
+    fn write_event(&mut self, event: &OccupancyEvent) -> anyhow::Result<()> {
+        let mut line = serde_json::to_vec(event)?;
+        line.push(b'\n');
+        let line_size = line.len() as u64;
+        if self.size > 0 && self.size + line_size > self.max_size {
+            let rotated = self.rotated_path();
+            debug!(path = ?self.path, ?rotated, "Rotating occupancy event log");
+            fs::rename(&self.path, &rotated).context("Unable to rotate occupancy event log")?;
+            self.file = open_append(&self.path)?;
+            self.size = 0;
+        }
+        self.file.write_all(&line)?;
+        self.size += line_size;
+        Ok(())
+    }
+
+    /// Write events until every sender has been dropped.
+    fn run(mut self, events: Receiver<OccupancyEvent>) {
+        for event in events {
+            if let Err(err) = self.write_event(&event) {
+                warn!("Unable to write occupancy event: {:?}", err);
+            }
+        }
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Log every change in the occupancy count to a file.
+///
+/// The file is written on a separate thread, and if it falls too far behind events are dropped
+/// instead of holding up the rest of the pipeline. The returned future completes when the count
+/// stream ends and every event has been written.
+pub(crate) fn log_occupancy_events<S>(
+    counts: S,
+    settings: &EventLogSettings,
+) -> anyhow::Result<impl Future<Output = anyhow::Result<()>>>
+where
+    S: Stream<Item = usize>,
+{
+    let writer = EventWriter::open(settings).with_context(|| {
+        format!(
+            "Unable to open occupancy event log {}",
+            settings.path.display()
+        )
+    })?;
+    let (sender, receiver) = sync_channel(EVENT_BUFFER_SIZE);
+    let writer_task = spawn_blocking(move || writer.run(receiver));
+    let events = count_changes(counts).map(OccupancyEvent::new);
+    Ok(async move {
+        futures::pin_mut!(events);
+        while let Some(event) = events.next().await {
+            match sender.try_send(event) {
+                Ok(()) => (),
+                Err(TrySendError::Full(event)) => {
+                    warn!(
+                        ?event,
+                        "Occupancy event log is falling behind, dropping event"
+                    );
+                }
+                Err(TrySendError::Disconnected(_)) => {
+                    anyhow::bail!("Occupancy event log writer stopped unexpectedly");
+                }
+            }
+        }
+        drop(sender);
+        writer_task.await?;
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use futures::stream;
+
+    use super::{log_occupancy_events, EventLogSettings, EventWriter, OccupancyEvent};
+
+    #[test]
+    fn settings_defaults() {
+        let settings: EventLogSettings = toml::from_str(r#"path = "/tmp/events.jsonl""#).unwrap();
+        assert_eq!(settings.path, std::path::PathBuf::from("/tmp/events.jsonl"));
+        assert_eq!(settings.max_size, 1024 * 1024);
+    }
+
+    #[tokio::test]
+    async fn logs_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.jsonl");
+        let settings = EventLogSettings {
+            path: path.clone(),
+            max_size: 1024,
+        };
+        let counts = stream::iter([0, 0, 1, 2, 2, 0]);
+        log_occupancy_events(counts, &settings)
+            .unwrap()
+            .await
+            .unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        let events: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(events.len(), 3);
+        let summary: Vec<(u64, i64)> = events
+            .iter()
+            .map(|event| {
+                assert!(event["ts"].as_f64().unwrap() > 0.0);
+                (
+                    event["count"].as_u64().unwrap(),
+                    event["delta"].as_i64().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(summary, [(1, 1), (2, 1), (0, -2)]);
+    }
+
+    #[test]
+    fn rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.jsonl");
+        let settings = EventLogSettings {
+            path: path.clone(),
+            max_size: 100,
+        };
+        let mut writer = EventWriter::open(&settings).unwrap();
+        let rotated = writer.rotated_path();
+        assert_eq!(rotated, dir.path().join("events.jsonl.1"));
+        let event = OccupancyEvent {
+            ts: 1_640_000_000.0,
+            count: 1,
+            delta: 1,
+        };
+        // Each line is 40 bytes (with the newline), so the third line causes a rotation.
+        for _ in 0..3 {
+            writer.write_event(&event).unwrap();
+        }
+        assert_eq!(fs::read_to_string(&rotated).unwrap().lines().count(), 2);
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 1);
+    }
+}
@@ -5,11 +5,13 @@
 //! Zivkovic, Z., & van der Heijden, F. (2006). Efficient adaptive density estimation per image
 //! pixel for the task of background subtraction. In Pattern Recognition Letters (Vol. 27, Issue 7,
 //! pp. 773–780). Elsevier BV. https://doi.org/10.1016/j.patrec.2005.11.005
+use std::io;
 use std::iter;
 
 use bitvec::prelude::*;
 use rayon::prelude::*;
-use serde::Deserialize;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use tracing::trace;
 
 use super::learning_rate::LearningRate;
@@ -18,7 +20,7 @@ use super::learning_rate::LearningRate;
 ///
 /// Each pixel's model can be made up of multiple distributions, and the number of distributions in
 /// each model can vary of the lifetime of the model.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 struct GaussianComponent {
     /// The *squared* variance.
     variance: f32,
@@ -50,7 +52,7 @@ impl GaussianComponent {
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, JsonSchema, Serialize)]
 pub(crate) struct GmmParameters {
     /// The rate at which new values are incorporated into the model.
     ///
@@ -58,6 +60,7 @@ pub(crate) struct GmmParameters {
     /// value during the training of a fresh model (by starting from 1, and decreasing until the
     /// final learning rate is reached), but it doesn't seriously impact performance.
     #[serde(default = "GmmParameters::default_learning_rate")]
+    #[schemars(with = "f32")]
     pub(crate) learning_rate: LearningRate,
 
     /// A hard limit on the number of gaussians used to model each pixel.
@@ -132,7 +135,7 @@ impl Default for GmmParameters {
         }
     }
 }
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub(super) struct GaussianMixtureModel(Vec<GaussianComponent>);
 
 // As a precondition to this implementation, `components` *must* always be sorted by component
@@ -274,6 +277,12 @@ impl<Container> BackgroundModel<Container> {
         self.parameters = params;
     }
 
+    /// Whether the learning rate has decayed to its final value.
+    #[cfg(any(feature = "mock_camera", test))]
+    pub(super) fn is_trained(&self) -> bool {
+        self.parameters.learning_rate.is_trained()
+    }
+
     #[inline]
     fn set_all_state(&mut self, state: bool) {
         self.frozen_pixels.set_all(state)
@@ -332,6 +341,39 @@ impl BackgroundModel<Vec<GaussianMixtureModel>> {
             .map(|(sample, model)| model.background_probability(*sample, params))
             .collect()
     }
+
+    /// The number of pixels this model covers.
+    pub(super) fn len(&self) -> usize {
+        self.pixel_models.len()
+    }
+
+    /// Write the model for each pixel out as JSON.
+    ///
+    /// Only the pixel models are saved, the parameters come from the settings when the model is
+    /// [loaded][Self::load].
+    #[cfg(any(feature = "mock_camera", test))]
+    pub(super) fn save<W: io::Write>(&self, writer: W) -> serde_json::Result<()> {
+        serde_json::to_writer(writer, &self.pixel_models)
+    }
+
+    /// Load a model previously written with [`save`][Self::save].
+    ///
+    /// A saved model has already been trained, so the learning rate starts at its final value
+    /// instead of going through the initialization period again.
+    pub(super) fn load<R: io::Read>(
+        reader: R,
+        parameters: GmmParameters,
+    ) -> serde_json::Result<Self> {
+        let pixel_models: Vec<GaussianMixtureModel> = serde_json::from_reader(reader)?;
+        let frozen_pixels = bitvec![0; pixel_models.len()];
+        let mut parameters = parameters;
+        parameters.learning_rate = LearningRate::Trained(parameters.learning_rate.into());
+        Ok(Self {
+            pixel_models,
+            parameters,
+            frozen_pixels,
+        })
+    }
 }
 
 impl<Container> BackgroundModel<Container>
@@ -355,7 +397,7 @@ mod test {
     use rand_core::SeedableRng;
     use rand_distr::{DistIter, Distribution, Normal};
 
-    use super::{BackgroundModel, GaussianMixtureModel};
+    use super::{BackgroundModel, GaussianMixtureModel, GmmParameters};
 
     type NormalSamples = DistIter<Normal<f32>, ChaCha8Rng, f32>;
     type GmmBackground = BackgroundModel<Vec<GaussianMixtureModel>>;
@@ -477,4 +519,21 @@ mod test {
         // modeling the room temperature pixels and be treated as the background.
         check_model(model, &mut bg_samples, &mut &mut fg_samples);
     }
+
+    // Test that a saved model classifies samples the same after being loaded.
+    #[test]
+    fn save_and_load() {
+        let (mut bg_samples, mut fg_samples) = random_samples();
+        let mut model: GmmBackground = BackgroundModel::new(LENGTH);
+        for _ in 0..TRAINING_SIZE {
+            let samples = generate_image(&mut bg_samples, LENGTH);
+            model.update(&samples)
+        }
+        let mut saved = Vec::new();
+        model.save(&mut saved).unwrap();
+        let loaded = GmmBackground::load(saved.as_slice(), GmmParameters::default()).unwrap();
+        assert_eq!(loaded.len(), LENGTH);
+        assert!(loaded.is_trained());
+        check_model(loaded, &mut bg_samples, &mut fg_samples);
+    }
 }
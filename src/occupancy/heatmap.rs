@@ -0,0 +1,234 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+use std::num::NonZeroU32;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use image::imageops::{resize, FilterType};
+use image::{ImageBuffer, Luma, Rgba, RgbaImage};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use tracing::{debug, info};
+
+use crate::image_buffer::BytesImage;
+use crate::settings::gradient::Gradient;
+
+pub(crate) type SharedHeatmap = Arc<Mutex<Heatmap>>;
+
+/// Settings for the occupancy heatmap.
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
+pub(crate) struct HeatmapSettings {
+    /// The amount of time the heatmap covers.
+    ///
+    /// Older frames fade out of the heatmap exponentially, with frames from *window* seconds ago
+    /// having about a third of the weight of the current frame. Defaults to a day.
+    #[serde_as(as = "serde_with::DurationSeconds<u64>")]
+    #[schemars(with = "u64")]
+    #[serde(default = "HeatmapSettings::default_window")]
+    pub(crate) window: Duration,
+
+    /// The gradient used to render the heatmap. Defaults to "inferno".
+    #[serde(default = "HeatmapSettings::default_colors")]
+    pub(crate) colors: Gradient,
+
+    /// How many pixels wide (and tall) each camera pixel is in the rendered heatmap. Defaults to
+    /// 10.
+    #[serde(default = "HeatmapSettings::default_scale")]
+    pub(crate) scale: NonZeroU32,
+}
+
+impl HeatmapSettings {
+    const fn default_window() -> Duration {
+        Duration::from_secs(24 * 60 * 60)
+    }
+
+    fn default_colors() -> Gradient {
+        Gradient::Inferno
+    }
+
+    fn default_scale() -> NonZeroU32 {
+        NonZeroU32::new(10).unwrap()
+    }
+}
+
+impl Default for HeatmapSettings {
+    fn default() -> Self {
+        Self {
+            window: Self::default_window(),
+            colors: Self::default_colors(),
+            scale: Self::default_scale(),
+        }
+    }
+}
+
+/// How often each pixel has been in the foreground, decaying over time.
+#[derive(Clone, Debug)]
+pub(crate) struct Heatmap {
+    settings: HeatmapSettings,
+    width: u32,
+    height: u32,
+    /// The (exponentially weighted) fraction of time each pixel has been in the foreground.
+    values: Vec<f32>,
+    last_update: Option<Instant>,
+}
+
+impl Heatmap {
+    pub(crate) fn new(settings: &HeatmapSettings) -> Self {
+        Self {
+            settings: settings.clone(),
+            width: 0,
+            height: 0,
+            values: Vec::new(),
+            last_update: None,
+        }
+    }
+
+    pub(crate) fn shared(settings: &HeatmapSettings) -> SharedHeatmap {
+        Arc::new(Mutex::new(Self::new(settings)))
+    }
+
+    /// Add a foreground mask (where any non-zero pixel is in the foreground) captured at `now`.
+    ///
+    /// Each frame is weighted by how long it's been since the previous frame, so skipped or
+    /// dropped frames don't skew the heatmap.
+    pub(crate) fn update(&mut self, foreground: &ImageBuffer<Luma<u8>, Vec<u8>>, now: Instant) {
+        if foreground.dimensions() != (self.width, self.height) {
+            debug!(dimensions = ?foreground.dimensions(), "Starting new heatmap");
+            self.width = foreground.width();
+            self.height = foreground.height();
+            self.values = vec![0.0; foreground.len()];
+            self.last_update = None;
+        }
+        let weight = match self.last_update {
+            // The first frame only marks the start of the heatmap.
+            None => 0.0,
+            Some(_) if self.settings.window.is_zero() => 1.0,
+            Some(last_update) => {
+                let elapsed = now.saturating_duration_since(last_update);
+                1.0 - (-elapsed.as_secs_f32() / self.settings.window.as_secs_f32()).exp()
+            }
+        };
+        self.last_update = Some(now);
+        for (value, pixel) in self.values.iter_mut().zip(foreground.iter()) {
+            let sample = if *pixel != 0 { 1.0 } else { 0.0 };
+            *value += (sample - *value) * weight;
+        }
+    }
+
+    /// Clear the heatmap, starting over with the next frame.
+    pub(crate) fn reset(&mut self) {
+        info!("Resetting the occupancy heatmap");
+        self.values.iter_mut().for_each(|value| *value = 0.0);
+        self.last_update = None;
+    }
+
+    /// The fraction of time each pixel has been in the foreground, or `None` if nothing has been
+    /// added to the heatmap yet.
+    pub(crate) fn image(&self) -> Option<ImageBuffer<Luma<f32>, Vec<f32>>> {
+        if self.values.is_empty() {
+            None
+        } else {
+            ImageBuffer::from_raw(self.width, self.height, self.values.clone())
+        }
+    }
+
+    /// Render the heatmap with the configured gradient.
+    ///
+    /// The colors are scaled so that the most used pixel is at the top of the gradient.
+    pub(crate) fn render(&self) -> Option<BytesImage> {
+        let image = self.image()?;
+        let maximum = image.iter().copied().fold(0.0f32, f32::max);
+        let colored = RgbaImage::from_fn(image.width(), image.height(), |x, y| {
+            let value = image[(x, y)][0];
+            let t = if maximum > 0.0 { value / maximum } else { 0.0 };
+            let color = self.settings.colors.eval_continuous(t as f64);
+            Rgba([color.r, color.g, color.b, u8::MAX])
+        });
+        let scale = self.settings.scale.get();
+        let enlarged = resize(
+            &colored,
+            image.width() * scale,
+            image.height() * scale,
+            FilterType::Nearest,
+        );
+        let (width, height) = enlarged.dimensions();
+        BytesImage::from_raw(width, height, Bytes::from(enlarged.into_raw()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::num::NonZeroU32;
+    use std::time::{Duration, Instant};
+
+    use float_cmp::assert_approx_eq;
+    use image::{ImageBuffer, Luma};
+
+    use crate::settings::gradient::Gradient;
+
+    use super::{Heatmap, HeatmapSettings};
+
+    fn foreground(pixels: [u8; 4]) -> ImageBuffer<Luma<u8>, Vec<u8>> {
+        ImageBuffer::from_raw(2, 2, pixels.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn defaults() {
+        let settings: HeatmapSettings = toml::from_str("").unwrap();
+        assert_eq!(settings, HeatmapSettings::default());
+        assert_eq!(settings.window, Duration::from_secs(86400));
+        assert_eq!(settings.colors, Gradient::Inferno);
+        assert_eq!(settings.scale.get(), 10);
+    }
+
+    #[test]
+    fn accumulates() {
+        let settings = HeatmapSettings {
+            window: Duration::from_secs(10),
+            ..HeatmapSettings::default()
+        };
+        let mut heatmap = Heatmap::new(&settings);
+        assert!(heatmap.image().is_none());
+        let start = Instant::now();
+        heatmap.update(&foreground([u8::MAX, 0, 0, 0]), start);
+        // The first frame has no weight.
+        assert!(heatmap.image().unwrap().iter().all(|value| *value == 0.0));
+        heatmap.update(
+            &foreground([u8::MAX, u8::MAX, 0, 0]),
+            start + settings.window,
+        );
+        let image = heatmap.image().unwrap();
+        let expected = 1.0 - (-1.0f32).exp();
+        assert_approx_eq!(f32, image[(0, 0)][0], expected);
+        assert_approx_eq!(f32, image[(1, 0)][0], expected);
+        assert_eq!(image[(0, 1)][0], 0.0);
+        // And then it decays.
+        heatmap.update(&foreground([0, 0, 0, 0]), start + settings.window * 2);
+        let image = heatmap.image().unwrap();
+        assert_approx_eq!(f32, image[(0, 0)][0], expected * (-1.0f32).exp());
+        heatmap.reset();
+        assert!(heatmap.image().unwrap().iter().all(|value| *value == 0.0));
+    }
+
+    #[test]
+    fn render() {
+        let settings = HeatmapSettings {
+            window: Duration::ZERO,
+            colors: Gradient::Grayscale,
+            scale: NonZeroU32::new(3).unwrap(),
+        };
+        let mut heatmap = Heatmap::new(&settings);
+        let start = Instant::now();
+        heatmap.update(&foreground([0, 0, 0, 0]), start);
+        heatmap.update(
+            &foreground([u8::MAX, 0, 0, 0]),
+            start + Duration::from_secs(1),
+        );
+        let rendered = heatmap.render().unwrap();
+        assert_eq!(rendered.dimensions(), (6, 6));
+        assert_eq!(rendered[(2, 2)].0, [u8::MAX, u8::MAX, u8::MAX, u8::MAX]);
+        assert_eq!(rendered[(3, 0)].0, [0, 0, 0, u8::MAX]);
+    }
+}
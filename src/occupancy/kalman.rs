@@ -0,0 +1,171 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+use super::point::Point;
+
+/// How much an object's velocity is expected to change between frames (as a variance, in square
+/// pixels).
+const PROCESS_NOISE: f32 = 0.25;
+
+/// How noisy the measured center of an object is (as a variance, in square pixels).
+const MEASUREMENT_NOISE: f32 = 1.0;
+
+/// The initial uncertainty of a new object's velocity, as a variance.
+const INITIAL_VELOCITY_VARIANCE: f32 = 4.0;
+
+/// The squared Mahalanobis distance a measurement needs to be within to be associated with a
+/// prediction. This is the 99% point of a chi-squared distribution with two degrees of freedom.
+const GATE_DISTANCE_2: f32 = 9.21;
+
+/// A constant velocity Kalman filter for a single axis.
+///
+/// The state is the position and velocity along the axis, with time measured in frames.
+#[derive(Clone, Debug, PartialEq)]
+struct AxisFilter {
+    position: f32,
+    velocity: f32,
+    /// Covariance of the state, as `[[pos-pos, pos-vel], [vel-pos, vel-vel]]`.
+    covariance: [[f32; 2]; 2],
+}
+
+impl AxisFilter {
+    fn new(position: f32) -> Self {
+        Self {
+            position,
+            velocity: 0.0,
+            covariance: [[MEASUREMENT_NOISE, 0.0], [0.0, INITIAL_VELOCITY_VARIANCE]],
+        }
+    }
+
+    /// Advance the state by a single frame.
+    fn predict(&mut self) {
+        let [[p00, p01], [p10, p11]] = self.covariance;
+        self.position += self.velocity;
+        // P = F * P * F^T + Q, with F = [[1, 1], [0, 1]] and Q modeling a random acceleration
+        // (G * G^T * q, with G = [1/2, 1]).
+        self.covariance = [
+            [
+                p00 + p01 + p10 + p11 + PROCESS_NOISE / 4.0,
+                p01 + p11 + PROCESS_NOISE / 2.0,
+            ],
+            [p10 + p11 + PROCESS_NOISE / 2.0, p11 + PROCESS_NOISE],
+        ];
+    }
+
+    /// The variance of the difference between a measurement and the predicted position.
+    fn innovation_variance(&self) -> f32 {
+        self.covariance[0][0] + MEASUREMENT_NOISE
+    }
+
+    /// Incorporate a measured position.
+    fn update(&mut self, measured: f32) {
+        let [[p00, p01], [p10, p11]] = self.covariance;
+        let innovation = measured - self.position;
+        let innovation_variance = self.innovation_variance();
+        let position_gain = p00 / innovation_variance;
+        let velocity_gain = p10 / innovation_variance;
+        self.position += position_gain * innovation;
+        self.velocity += velocity_gain * innovation;
+        self.covariance = [
+            [(1.0 - position_gain) * p00, (1.0 - position_gain) * p01],
+            [p10 - velocity_gain * p00, p11 - velocity_gain * p01],
+        ];
+    }
+}
+
+/// Smooths and predicts the position of a tracked object.
+///
+/// The horizontal and vertical axes are modeled independently with a constant velocity model.
+#[derive(Clone, Debug, PartialEq)]
+pub(super) struct KalmanFilter {
+    x: AxisFilter,
+    y: AxisFilter,
+}
+
+impl KalmanFilter {
+    pub(super) fn new(position: Point<f32>) -> Self {
+        Self {
+            x: AxisFilter::new(position.x),
+            y: AxisFilter::new(position.y),
+        }
+    }
+
+    /// The current estimate of the position.
+    pub(super) fn position(&self) -> Point<f32> {
+        Point::new(self.x.position, self.y.position)
+    }
+
+    /// Advance the filter by one frame, returning the predicted position.
+    pub(super) fn predict(&mut self) -> Point<f32> {
+        self.x.predict();
+        self.y.predict();
+        self.position()
+    }
+
+    /// The squared Mahalanobis distance from the current estimate to a measured position, or
+    /// `None` if the measurement is too unlikely to be of the same object.
+    pub(super) fn gated_distance_2(&self, measured: Point<f32>) -> Option<f32> {
+        let distance_2 = (measured.x - self.x.position).powi(2) / self.x.innovation_variance()
+            + (measured.y - self.y.position).powi(2) / self.y.innovation_variance();
+        if distance_2 <= GATE_DISTANCE_2 {
+            Some(distance_2)
+        } else {
+            None
+        }
+    }
+
+    /// Incorporate a measured position.
+    pub(super) fn update(&mut self, measured: Point<f32>) {
+        self.x.update(measured.x);
+        self.y.update(measured.y);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use float_cmp::assert_approx_eq;
+
+    use super::{KalmanFilter, Point};
+
+    #[test]
+    fn stationary() {
+        let mut filter = KalmanFilter::new(Point::new(3.0, 4.0));
+        for _ in 0..10 {
+            let predicted = filter.predict();
+            assert_approx_eq!(f32, predicted.x, 3.0);
+            assert_approx_eq!(f32, predicted.y, 4.0);
+            filter.update(Point::new(3.0, 4.0));
+        }
+    }
+
+    #[test]
+    fn follows_constant_velocity() {
+        let mut filter = KalmanFilter::new(Point::new(0.0, 5.0));
+        for frame in 1..=20 {
+            filter.predict();
+            filter.update(Point::new(frame as f32, 5.0));
+        }
+        // After settling, the prediction should be close to the next position.
+        let predicted = filter.predict();
+        assert_approx_eq!(f32, predicted.x, 21.0, epsilon = 0.1);
+        assert_approx_eq!(f32, predicted.y, 5.0, epsilon = 0.01);
+    }
+
+    #[test]
+    fn gate() {
+        let mut filter = KalmanFilter::new(Point::new(2.0, 2.0));
+        filter.predict();
+        assert!(filter.gated_distance_2(Point::new(3.0, 2.0)).is_some());
+        assert!(filter.gated_distance_2(Point::new(12.0, 2.0)).is_none());
+    }
+
+    #[test]
+    fn smooths_noise() {
+        let mut filter = KalmanFilter::new(Point::new(5.0, 5.0));
+        for frame in 0..20 {
+            filter.predict();
+            let offset = if frame % 2 == 0 { 1.0 } else { -1.0 };
+            filter.update(Point::new(5.0 + offset, 5.0));
+        }
+        let position = filter.position();
+        assert!((position.x - 5.0).abs() < 1.0);
+    }
+}
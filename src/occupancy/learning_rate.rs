@@ -1,9 +1,9 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use tracing::debug;
 
-#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
-#[serde(from = "f32")]
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(from = "f32", into = "f32")]
 pub(crate) enum LearningRate {
     Initializing {
         sample_count: i32,
@@ -52,6 +52,27 @@ impl LearningRate {
     pub(super) const fn is_trained(&self) -> bool {
         matches!(self, Self::Trained(..))
     }
+
+    /// Scale this learning rate for a model that is only updated every `interval` samples.
+    ///
+    /// The scaled rate keeps the same effective adaptation time as the original rate applied to
+    /// every sample.
+    pub(super) fn decimated(self, interval: usize) -> Self {
+        if interval <= 1 {
+            return self;
+        }
+        let scale = |value: f32| 1.0 - (1.0 - value).powi(interval as i32);
+        match self {
+            LearningRate::Initializing {
+                sample_count,
+                target_value,
+            } => LearningRate::Initializing {
+                sample_count,
+                target_value: scale(target_value),
+            },
+            LearningRate::Trained(value) => LearningRate::Trained(scale(value)),
+        }
+    }
 }
 
 impl From<f32> for LearningRate {
@@ -59,3 +80,13 @@ impl From<f32> for LearningRate {
         Self::new(target)
     }
 }
+
+impl From<LearningRate> for f32 {
+    /// The target learning rate, regardless of how far along the initialization period is.
+    fn from(learning_rate: LearningRate) -> Self {
+        match learning_rate {
+            LearningRate::Initializing { target_value, .. } => target_value,
+            LearningRate::Trained(value) => value,
+        }
+    }
+}
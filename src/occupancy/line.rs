@@ -0,0 +1,287 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use anyhow::anyhow;
+use futures::{Stream, StreamExt};
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use super::zone::{check_unique_names, sensor_name};
+use super::TrackedObject;
+
+/// A virtual line across the camera's view, counting the people that cross it.
+///
+/// Crossing from the left side of the line to the right side (as seen when looking from `start`
+/// towards `end`) is counted as an entry, and crossing the other way is counted as an exit.
+#[derive(Clone, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(try_from = "UncheckedCountingLine")]
+pub(crate) struct CountingLine {
+    /// The name of the line, used in the MQTT topic and entity names.
+    ///
+    /// Only ASCII letters, numbers, `-` and `_` are allowed.
+    #[serde(deserialize_with = "sensor_name")]
+    pub(crate) name: String,
+
+    /// One end of the line, as `[x, y]` in sensor pixels.
+    pub(crate) start: [f32; 2],
+
+    /// The other end of the line, as `[x, y]` in sensor pixels.
+    pub(crate) end: [f32; 2],
+}
+
+/// A [`CountingLine`] that hasn't been checked yet.
+#[derive(Deserialize)]
+struct UncheckedCountingLine {
+    #[serde(deserialize_with = "sensor_name")]
+    name: String,
+
+    start: [f32; 2],
+
+    end: [f32; 2],
+}
+
+impl TryFrom<UncheckedCountingLine> for CountingLine {
+    type Error = anyhow::Error;
+
+    fn try_from(line: UncheckedCountingLine) -> anyhow::Result<Self> {
+        if line.start == line.end {
+            return Err(anyhow!(
+                "The start and end of counting line \"{}\" must be different points",
+                line.name
+            ));
+        }
+        Ok(Self {
+            name: line.name,
+            start: line.start,
+            end: line.end,
+        })
+    }
+}
+
+/// Deserialize a list of counting lines, rejecting names that are used more than once.
+pub(crate) fn unique_lines<'de, D>(deserializer: D) -> Result<Vec<CountingLine>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let lines = Vec::<CountingLine>::deserialize(deserializer)?;
+    check_unique_names("line", lines.iter().map(|line| line.name.as_str()))
+        .map_err(serde::de::Error::custom)?;
+    Ok(lines)
+}
+
+/// The number of people that have crossed a [`CountingLine`] in each direction.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct LineCounts {
+    pub(crate) entries: usize,
+    pub(crate) exits: usize,
+}
+
+/// The z-component of the cross product of `(b - a)` and `(c - a)`.
+///
+/// This is positive when `c` is to the right of the line from `a` to `b` (with the Y-axis pointing
+/// down, like image coordinates), negative when it's to the left, and zero when it's on the line.
+fn side(a: [f32; 2], b: [f32; 2], c: [f32; 2]) -> f32 {
+    (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+}
+
+impl CountingLine {
+    /// Whether moving from `from` to `to` crosses this line, and in which direction.
+    ///
+    /// `Some(true)` is an entry, `Some(false)` is an exit, and `None` means the line wasn't
+    /// crossed. Starting or ending exactly on the line doesn't count as crossing it.
+    fn crossing(&self, from: [f32; 2], to: [f32; 2]) -> Option<bool> {
+        let from_side = side(self.start, self.end, from);
+        let to_side = side(self.start, self.end, to);
+        if from_side * to_side >= 0.0 {
+            return None;
+        }
+        // The path crossed the infinite line, now check that it crossed between the end points.
+        let start_side = side(from, to, self.start);
+        let end_side = side(from, to, self.end);
+        if start_side * end_side > 0.0 {
+            return None;
+        }
+        Some(to_side > 0.0)
+    }
+
+    /// Count the people crossing this line, producing the running totals for each list of
+    /// objects.
+    ///
+    /// Objects are followed by their ID, and only the objects that are people when they cross the
+    /// line are counted. The position where each object was last seen off of the line is used, so
+    /// stopping right on the line and then continuing on is still counted.
+    pub(crate) fn crossings<S>(self, objects: S) -> impl Stream<Item = LineCounts>
+    where
+        S: Stream<Item = Vec<TrackedObject>>,
+    {
+        let mut positions: HashMap<u64, [f32; 2]> = HashMap::new();
+        let mut counts = LineCounts::default();
+        objects.map(move |objects| {
+            let mut new_positions = HashMap::with_capacity(objects.len());
+            for object in objects {
+                let previous = positions.get(&object.id).copied();
+                if object.person {
+                    match previous.and_then(|previous| self.crossing(previous, object.center)) {
+                        Some(true) => counts.entries += 1,
+                        Some(false) => counts.exits += 1,
+                        None => (),
+                    }
+                }
+                let on_line = side(self.start, self.end, object.center) == 0.0;
+                let position = match previous {
+                    Some(previous) if on_line => previous,
+                    _ => object.center,
+                };
+                new_positions.insert(object.id, position);
+            }
+            // Objects that are no longer tracked are dropped.
+            positions = new_positions;
+            counts
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::{stream, StreamExt};
+    use serde::Deserialize;
+
+    use super::{CountingLine, LineCounts, TrackedObject};
+
+    /// A horizontal line across the middle of an 8x8 camera.
+    fn line() -> CountingLine {
+        CountingLine {
+            name: "door".to_string(),
+            start: [0.0, 4.0],
+            end: [8.0, 4.0],
+        }
+    }
+
+    fn object(id: u64, center: [f32; 2]) -> TrackedObject {
+        TrackedObject {
+            id,
+            center,
+            bounding_box: [0, 0, 0, 0],
+            temperature: 30.0,
+            person: true,
+            dwell_time: 0.0,
+        }
+    }
+
+    async fn counts(frames: Vec<Vec<TrackedObject>>) -> Vec<LineCounts> {
+        line().crossings(stream::iter(frames)).collect().await
+    }
+
+    fn counted(entries: usize, exits: usize) -> LineCounts {
+        LineCounts { entries, exits }
+    }
+
+    #[test]
+    fn parse() {
+        #[derive(Debug, Deserialize)]
+        struct Lines {
+            #[serde(deserialize_with = "super::unique_lines")]
+            lines: Vec<CountingLine>,
+        }
+        let source = r#"
+        [[lines]]
+        name = "door"
+        start = [0, 4]
+        end = [8, 4]
+        "#;
+        let parsed: Lines = toml::from_str(source).unwrap();
+        assert_eq!(parsed.lines, vec![line()]);
+        let bad_name = r#"
+        [[lines]]
+        name = "front door"
+        start = [0, 4]
+        end = [8, 4]
+        "#;
+        assert!(toml::from_str::<Lines>(bad_name).is_err());
+        let duplicate = r#"
+        [[lines]]
+        name = "door"
+        start = [0, 4]
+        end = [8, 4]
+        [[lines]]
+        name = "door"
+        start = [0, 2]
+        end = [8, 2]
+        "#;
+        assert!(toml::from_str::<Lines>(duplicate).is_err());
+        let degenerate = r#"
+        [[lines]]
+        name = "door"
+        start = [4, 4]
+        end = [4, 4]
+        "#;
+        assert!(toml::from_str::<Lines>(degenerate).is_err());
+    }
+
+    #[test]
+    fn crossing_direction() {
+        let line = line();
+        // Looking from the start to the end is looking to the right, so down is the right side.
+        assert_eq!(line.crossing([2.0, 3.0], [2.0, 5.0]), Some(true));
+        assert_eq!(line.crossing([2.0, 5.0], [2.0, 3.0]), Some(false));
+        assert_eq!(line.crossing([2.0, 3.0], [3.0, 3.5]), None);
+        // Touching the line isn't crossing it.
+        assert_eq!(line.crossing([2.0, 3.0], [2.0, 4.0]), None);
+    }
+
+    #[test]
+    fn past_the_end() {
+        let line = CountingLine {
+            end: [4.0, 4.0],
+            ..line()
+        };
+        assert_eq!(line.crossing([6.0, 3.0], [6.0, 5.0]), None);
+        assert_eq!(line.crossing([3.0, 3.0], [3.0, 5.0]), Some(true));
+    }
+
+    #[tokio::test]
+    async fn entry_and_exit() {
+        let frames = vec![
+            vec![object(1, [2.0, 2.0])],
+            vec![object(1, [2.0, 5.0])],
+            vec![object(1, [2.0, 6.0])],
+            vec![object(1, [2.0, 3.0])],
+        ];
+        assert_eq!(
+            counts(frames).await,
+            [counted(0, 0), counted(1, 0), counted(1, 0), counted(1, 1)]
+        );
+    }
+
+    #[tokio::test]
+    async fn pause_on_line() {
+        let frames = vec![
+            vec![object(1, [2.0, 2.0])],
+            vec![object(1, [2.0, 4.0])],
+            vec![object(1, [2.0, 6.0])],
+        ];
+        assert_eq!(
+            counts(frames).await,
+            [counted(0, 0), counted(0, 0), counted(1, 0)]
+        );
+    }
+
+    #[tokio::test]
+    async fn new_objects() {
+        // An object that appears on the other side of the line (or a different object taking
+        // its place) hasn't crossed it.
+        let frames = vec![vec![object(1, [2.0, 2.0])], vec![object(2, [2.0, 6.0])]];
+        assert_eq!(counts(frames).await, [counted(0, 0), counted(0, 0)]);
+    }
+
+    #[tokio::test]
+    async fn only_people() {
+        let not_person = |center| TrackedObject {
+            person: false,
+            ..object(1, center)
+        };
+        let frames = vec![vec![not_person([2.0, 2.0])], vec![not_person([2.0, 6.0])]];
+        assert_eq!(counts(frames).await, [counted(0, 0), counted(0, 0)]);
+    }
+}
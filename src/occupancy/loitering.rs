@@ -0,0 +1,62 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+use std::time::Duration;
+
+use futures::{Stream, StreamExt};
+
+use super::TrackedObject;
+
+/// Convert a stream of tracked objects to whether anyone is loitering.
+///
+/// Someone is loitering when a person has stayed in the same spot for at least `threshold`. There
+/// is one value for each list of objects.
+pub(crate) fn loitering<S>(objects: S, threshold: Duration) -> impl Stream<Item = bool>
+where
+    S: Stream<Item = Vec<TrackedObject>>,
+{
+    let threshold = threshold.as_secs_f32();
+    objects.map(move |objects| {
+        objects
+            .iter()
+            .any(|object| object.person && object.dwell_time >= threshold)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use futures::{stream, StreamExt};
+
+    use super::{loitering, TrackedObject};
+
+    fn object(person: bool, dwell_time: f32) -> TrackedObject {
+        TrackedObject {
+            id: 0,
+            center: [0.0, 0.0],
+            bounding_box: [0, 0, 0, 0],
+            temperature: 30.0,
+            person,
+            dwell_time,
+        }
+    }
+
+    #[tokio::test]
+    async fn threshold() {
+        let objects = stream::iter([
+            vec![],
+            vec![object(true, 10.0)],
+            vec![object(true, 10.0), object(true, 60.0)],
+            vec![object(true, 30.0)],
+        ]);
+        let collected: Vec<bool> = loitering(objects, Duration::from_secs(30)).collect().await;
+        assert_eq!(collected, [false, false, true, true]);
+    }
+
+    #[tokio::test]
+    async fn only_people() {
+        // Something warm that isn't a person (like a radiator) doesn't count as loitering.
+        let objects = stream::iter([vec![object(false, 600.0)]]);
+        let collected: Vec<bool> = loitering(objects, Duration::from_secs(30)).collect().await;
+        assert_eq!(collected, [false]);
+    }
+}
@@ -1,10 +1,31 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
+mod capacity;
+mod duration;
+mod event_log;
 mod gmm;
+mod heatmap;
+mod kalman;
 mod learning_rate;
+mod line;
+mod loitering;
 mod moments;
 mod point;
 mod settings;
 mod tracker;
+mod transition;
+mod zone;
 
+pub(crate) use capacity::over_capacity;
+pub(crate) use duration::occupancy_durations;
+pub(crate) use event_log::log_occupancy_events;
+pub(crate) use heatmap::{Heatmap, SharedHeatmap};
+pub(crate) use line::{unique_lines, CountingLine};
+pub(crate) use loitering::loitering;
+#[cfg(feature = "mock_camera")]
+pub(crate) use settings::TrackerMode;
 pub(crate) use settings::TrackerSettings;
-pub(crate) use tracker::Tracker;
+pub(crate) use tracker::{TrackedObject, Tracker};
+#[cfg(feature = "mock_camera")]
+pub(crate) use transition::count_changes;
+pub(crate) use transition::{transitions, Transition};
+pub(crate) use zone::{unique_zones, Zone};
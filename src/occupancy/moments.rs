@@ -187,6 +187,20 @@ impl Index<(i32, i32)> for ScaleInvariantMoments {
     }
 }
 
+/// Log-scale Hu moments, so that each moment is of a similar magnitude.
+///
+/// Each moment `h` becomes `sign(h) * log10(|h|)`. Moments that are exactly zero (as they are for
+/// very small objects) are left as zero.
+pub(super) fn log_scale(hu_moments: [f32; 7]) -> [f32; 7] {
+    hu_moments.map(|moment| {
+        if moment == 0.0 {
+            0.0
+        } else {
+            moment.signum() * moment.abs().log10()
+        }
+    })
+}
+
 pub(super) fn hu_moments(point_temperatures: &[PointTemperature]) -> [f32; 7] {
     let raw_moments = RawMoments::new(point_temperatures);
     let central_moments = CentralMoments::new(&raw_moments);
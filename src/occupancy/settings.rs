@@ -1,46 +1,574 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
+use std::convert::TryFrom;
+use std::num::{NonZeroU32, NonZeroUsize};
+use std::path::PathBuf;
 use std::time::Duration;
 
-use serde::Deserialize;
+use anyhow::anyhow;
+
+use rayon::prelude::*;
+use schemars::JsonSchema;
+use serde::{de, Deserialize, Deserializer, Serialize};
 use serde_with::serde_as;
 
+use crate::image_buffer::ThermalImage;
+use crate::temperature::Temperature;
+
+use super::event_log::EventLogSettings;
 use super::gmm::GmmParameters;
+use super::heatmap::HeatmapSettings;
+use super::moments;
+
+/// How pixels are separated into foreground (people) and background.
+#[derive(Clone, Copy, Debug, Default, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum TrackerMode {
+    /// Use a Gaussian mixture model of each pixel's temperature to find the background.
+    #[default]
+    Gmm,
+
+    /// Consider every pixel above a [`Threshold`] to be in the foreground.
+    ///
+    /// This is much lighter than [`TrackerMode::Gmm`], but anything warm enough (like a radiator)
+    /// will be counted.
+    Threshold,
+}
+
+/// The default maximum movement when using log-scaled Hu moments.
+///
+/// The higher order moments of small objects are close to zero, and their signs change from frame
+/// to frame. Each sign change adds a few hundred to the distance, so this is high enough to still
+/// match the same object when that happens (in the recorded test data, about 97% of objects are
+/// within this distance of their match in the previous frame).
+const LOG_HU_MAXIMUM_MOVEMENT: f32 = 2000.0;
+
+/// How the difference in shape between objects in subsequent frames is measured.
+#[derive(Clone, Copy, Debug, Default, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ShapeDistance {
+    /// The squared Euclidean distance between the Hu moments of each object.
+    ///
+    /// The Hu moments span many orders of magnitude, so this is mostly the difference in the
+    /// first moment.
+    #[default]
+    Euclidean,
+
+    /// The squared Euclidean distance between the log-scaled Hu moments of each object.
+    LogHu,
+}
+
+impl ShapeDistance {
+    /// Transform Hu moments so that the squared Euclidean distance can be used to compare them.
+    pub(crate) fn transform(&self, hu_moments: [f32; 7]) -> [f32; 7] {
+        match self {
+            Self::Euclidean => hu_moments,
+            Self::LogHu => moments::log_scale(hu_moments),
+        }
+    }
+
+    const fn default_maximum_movement(&self) -> f32 {
+        match self {
+            Self::Euclidean => 16.0,
+            Self::LogHu => LOG_HU_MAXIMUM_MOVEMENT,
+        }
+    }
+}
+
+/// The temperature a pixel must exceed to be considered foreground in [`TrackerMode::Threshold`].
+#[derive(Clone, Copy, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Threshold {
+    /// A fixed temperature.
+    Static(Temperature),
+
+    /// A number of degrees Celsius above the median temperature of the current image.
+    Dynamic(f32),
+
+    /// A number of standard deviations above the mean temperature of the current image.
+    #[serde(deserialize_with = "non_negative")]
+    Deviation(f32),
+
+    /// A percentile (from 0 to 100) of the temperatures in the current image.
+    ///
+    /// A fixed share of the pixels will always be above this cutoff, so this works best when
+    /// paired with [`TrackerSettings::minimum_size`].
+    #[serde(deserialize_with = "percentile")]
+    Percentile(f32),
+}
+
+impl Threshold {
+    const DEFAULT_DYNAMIC_OFFSET: f32 = 3.0;
+
+    /// The temperature (in Celsius) above which pixels in this image are in the foreground.
+    pub(crate) fn cutoff(&self, image: &ThermalImage) -> f32 {
+        match self {
+            Self::Static(temperature) => temperature.in_celsius(),
+            Self::Dynamic(offset) => Self::nth_smallest(image, image.len() / 2) + offset,
+            Self::Deviation(deviations) => {
+                let pixels = image.as_raw();
+                let count = pixels.len() as f32;
+                let (sum, sum_squares) = pixels
+                    .par_iter()
+                    .map(|&temperature| (temperature, temperature * temperature))
+                    .reduce(
+                        || (0.0, 0.0),
+                        |(sum_a, squares_a), (sum_b, squares_b)| {
+                            (sum_a + sum_b, squares_a + squares_b)
+                        },
+                    );
+                let mean = sum / count;
+                // Clamp to 0 in case rounding makes the variance slightly negative.
+                let variance = (sum_squares / count - mean * mean).max(0.0);
+                mean + deviations * variance.sqrt()
+            }
+            Self::Percentile(percentile) => {
+                // Nearest-rank percentile
+                let rank = ((percentile / 100.0) * image.len() as f32).ceil() as usize;
+                Self::nth_smallest(image, rank.clamp(1, image.len()) - 1)
+            }
+        }
+    }
+
+    /// Find the `index`th smallest temperature (counting from 0) in an image.
+    fn nth_smallest(image: &ThermalImage, index: usize) -> f32 {
+        let mut pixels: Vec<f32> = image.iter().copied().collect();
+        let (_, value, _) = pixels.select_nth_unstable_by(index, f32::total_cmp);
+        *value
+    }
+}
+
+/// Deserialize a [`Threshold`] given either as an inline table or as its own `[tracker.threshold]`
+/// table.
+///
+/// The TOML deserializer only accepts the second form for enum variants that are tables
+/// themselves, but that's the form used when writing out the settings. Going through a JSON value
+/// accepts both.
+fn deserialize_threshold<'de, D>(deserializer: D) -> Result<Threshold, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = serde_json::Value::deserialize(deserializer)?;
+    Threshold::deserialize(value).map_err(de::Error::custom)
+}
+
+/// Ensure a value is finite and not negative.
+fn non_negative<'de, D>(deserializer: D) -> Result<f32, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = f32::deserialize(deserializer)?;
+    if value.is_finite() && value >= 0.0 {
+        Ok(value)
+    } else {
+        Err(de::Error::invalid_value(
+            de::Unexpected::Float(value.into()),
+            &"a non-negative number",
+        ))
+    }
+}
+
+/// Ensure the frame budget (if given) is a positive number.
+fn frame_budget<'de, D>(deserializer: D) -> Result<Option<f32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<f32>::deserialize(deserializer)? {
+        Some(value) if !(value.is_finite() && value > 0.0) => Err(de::Error::invalid_value(
+            de::Unexpected::Float(value.into()),
+            &"a number greater than 0",
+        )),
+        budget => Ok(budget),
+    }
+}
+
+/// Ensure a value is a valid percentile.
+fn percentile<'de, D>(deserializer: D) -> Result<f32, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = f32::deserialize(deserializer)?;
+    if (0.0..=100.0).contains(&value) {
+        Ok(value)
+    } else {
+        Err(de::Error::invalid_value(
+            de::Unexpected::Float(value.into()),
+            &"a number from 0 to 100",
+        ))
+    }
+}
+
+impl Default for Threshold {
+    fn default() -> Self {
+        Self::Dynamic(Self::DEFAULT_DYNAMIC_OFFSET)
+    }
+}
+
+/// Named combinations of tracker settings, from least to most likely to detect a person.
+///
+/// The values for each preset were chosen by running the tracker over the recorded datasets used
+/// in the tracker tests. `Low` has the fewest false detections, `High` has the fewest missed
+/// people, and `Medium` has the fewest errors overall.
+#[derive(Clone, Copy, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Sensitivity {
+    Low,
+    Medium,
+    High,
+}
+
+impl Sensitivity {
+    const fn background_confidence_threshold(&self) -> f32 {
+        match self {
+            Self::Low | Self::Medium => 0.00001,
+            Self::High => 0.0001,
+        }
+    }
+
+    const fn center_closeness(&self) -> f32 {
+        match self {
+            Self::Low => 1.0,
+            Self::Medium => 0.5,
+            Self::High => 0.25,
+        }
+    }
+
+    const fn overlap_threshold(&self) -> f32 {
+        match self {
+            Self::Low | Self::High => 0.9,
+            Self::Medium => 0.8,
+        }
+    }
+}
+
+/// The range of temperatures an object's mean temperature must be within to be a person.
+#[derive(Clone, Copy, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(try_from = "UncheckedTemperatureRange")]
+pub(crate) struct TemperatureRange {
+    /// The coldest a person can be. Defaults to 28°C.
+    #[serde(default = "TemperatureRange::default_min")]
+    pub(crate) min: Temperature,
+
+    /// The warmest a person can be. Defaults to 37°C.
+    #[serde(default = "TemperatureRange::default_max")]
+    pub(crate) max: Temperature,
+}
+
+/// A [`TemperatureRange`] that hasn't been checked yet.
+#[derive(Deserialize)]
+struct UncheckedTemperatureRange {
+    #[serde(default = "TemperatureRange::default_min")]
+    min: Temperature,
+
+    #[serde(default = "TemperatureRange::default_max")]
+    max: Temperature,
+}
+
+impl TryFrom<UncheckedTemperatureRange> for TemperatureRange {
+    type Error = anyhow::Error;
+
+    fn try_from(range: UncheckedTemperatureRange) -> anyhow::Result<Self> {
+        if range.min.as_celsius() > range.max.as_celsius() {
+            return Err(anyhow!(
+                "The person temperature range minimum ({}) must not be above the maximum ({})",
+                range.min,
+                range.max
+            ));
+        }
+        Ok(Self {
+            min: range.min,
+            max: range.max,
+        })
+    }
+}
+
+impl TemperatureRange {
+    fn default_min() -> Temperature {
+        Temperature::Celsius(28.0)
+    }
+
+    fn default_max() -> Temperature {
+        Temperature::Celsius(37.0)
+    }
+
+    /// Whether a temperature is within this range, regardless of the units used.
+    pub(crate) fn contains(&self, temperature: Temperature) -> bool {
+        // Comparing everything in the same unit so that a temperature on a boundary is included
+        // even when it was given in the other unit.
+        (self.min.as_celsius()..=self.max.as_celsius()).contains(&temperature.as_celsius())
+    }
+}
+
+impl Default for TemperatureRange {
+    fn default() -> Self {
+        Self {
+            min: Self::default_min(),
+            max: Self::default_max(),
+        }
+    }
+}
+
+/// The part of the camera's view people are tracked in.
+#[derive(Clone, Copy, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(try_from = "UncheckedRegionOfInterest")]
+pub(crate) struct RegionOfInterest {
+    /// The column of the left edge of the region, in sensor pixels.
+    pub(crate) x: u32,
+
+    /// The row of the top edge of the region, in sensor pixels.
+    pub(crate) y: u32,
+
+    /// The width of the region, in sensor pixels.
+    pub(crate) width: NonZeroU32,
+
+    /// The height of the region, in sensor pixels.
+    pub(crate) height: NonZeroU32,
+}
+
+/// A [`RegionOfInterest`] that hasn't been checked yet.
+#[derive(Deserialize)]
+struct UncheckedRegionOfInterest {
+    x: u32,
+    y: u32,
+    width: NonZeroU32,
+    height: NonZeroU32,
+}
+
+impl TryFrom<UncheckedRegionOfInterest> for RegionOfInterest {
+    type Error = anyhow::Error;
+
+    fn try_from(region: UncheckedRegionOfInterest) -> anyhow::Result<Self> {
+        let region = Self {
+            x: region.x,
+            y: region.y,
+            width: region.width,
+            height: region.height,
+        };
+        if region.end().is_none() {
+            return Err(anyhow!(
+                "The region of interest ({}x{} at ({}, {})) is too large",
+                region.width,
+                region.height,
+                region.x,
+                region.y
+            ));
+        }
+        Ok(region)
+    }
+}
+
+impl RegionOfInterest {
+    /// The column and row just past the right and bottom edges of the region, or `None` if they
+    /// don't fit in a `u32`.
+    fn end(&self) -> Option<(u32, u32)> {
+        Some((
+            self.x.checked_add(self.width.get())?,
+            self.y.checked_add(self.height.get())?,
+        ))
+    }
+
+    /// Whether a pixel is within this region.
+    pub(crate) fn contains_pixel(&self, x: u32, y: u32) -> bool {
+        match self.end() {
+            Some((right, bottom)) => (self.x..right).contains(&x) && (self.y..bottom).contains(&y),
+            None => false,
+        }
+    }
+}
 
 /// Settings for the people tracker.
 #[serde_as]
-#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+#[derive(Clone, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
 pub(crate) struct TrackerSettings {
+    /// How the foreground is separated from the background.
+    #[serde(default)]
+    pub(crate) mode: TrackerMode,
+
+    /// The threshold used when [`mode`][TrackerSettings::mode] is [`TrackerMode::Threshold`].
+    #[serde(default, deserialize_with = "deserialize_threshold")]
+    pub(crate) threshold: Threshold,
+
+    /// A preset for the detection settings.
+    ///
+    /// This sets [`background_confidence_threshold`], [`center_closeness`] and
+    /// [`overlap_threshold`], but any of those that are given explicitly take precedence over the
+    /// preset. If not set, the individual defaults for each of those settings are used.
+    ///
+    /// [`background_confidence_threshold`]: TrackerSettings::background_confidence_threshold
+    /// [`center_closeness`]: TrackerSettings::center_closeness
+    /// [`overlap_threshold`]: TrackerSettings::overlap_threshold
+    #[serde(default)]
+    pub(crate) sensitivity: Option<Sensitivity>,
+
     /// Background subtraction settings.
     ///
     /// The defaults are usually sufficient for most use cases.
     #[serde(default)]
     pub(crate) background_model_parameters: GmmParameters,
 
+    /// A background model saved when calibrating, to start from instead of training a new one.
+    ///
+    /// If the model was saved for a camera with a different resolution, it is ignored.
+    #[serde(default)]
+    pub(crate) background_model: Option<PathBuf>,
+
     /// Background confidence threshold.
-    #[serde(default = "TrackerSettings::default_confidence_threshold")]
-    pub(crate) background_confidence_threshold: f32,
+    #[serde(default)]
+    pub(crate) background_confidence_threshold: Option<f32>,
 
-    #[serde(default = "TrackerSettings::default_maximum_movement")]
-    pub(crate) maximum_movement: f32,
+    /// How the shapes of objects are compared when matching them between frames.
+    #[serde(default)]
+    pub(crate) shape_distance: ShapeDistance,
+
+    /// The largest difference in shape (as measured by [`shape_distance`]) for an object to be
+    /// considered the same object in the next frame.
+    ///
+    /// The default depends on [`shape_distance`].
+    ///
+    /// [`shape_distance`]: TrackerSettings::shape_distance
+    #[serde(default)]
+    pub(crate) maximum_movement: Option<f32>,
+
+    /// Follow objects between frames with a Kalman filter over their centers.
+    ///
+    /// Objects are matched to their predicted position instead of by shape alone, which keeps
+    /// objects tracked when their shape changes from one frame to the next.
+    #[serde(default)]
+    pub(crate) use_kalman: bool,
 
     /// The minimum size for an object to be considered a person.
     #[serde(default)]
     pub(crate) minimum_size: Option<usize>,
 
+    /// The range of mean temperatures for an object to be considered a person.
+    ///
+    /// Objects outside of this range (like electronics or cold drinks) are still tracked, but are
+    /// never counted. If not set, any temperature is allowed.
+    #[serde(default)]
+    pub(crate) person_temperature_range: Option<TemperatureRange>,
+
+    /// Only track people within this part of the camera's view.
+    ///
+    /// Pixels outside of the region are always treated as background, so objects (and their
+    /// centers) are always within it. If not set, the entire view is used.
+    #[serde(default)]
+    pub(crate) region_of_interest: Option<RegionOfInterest>,
+
     /// How long before a stationary object is ignored.
     ///
     /// Whenever an object moves, its stationary timeout is reset. After *stationary_timeout*
     /// seconds of not moving, an object that was previously marked as a person is no longer
-    /// considered on (until they move again).
+    /// considered on (until they move again). This is measured in wall-clock time, so it is not
+    /// affected by [`decimation`][TrackerSettings::decimation].
     #[serde_as(as = "serde_with::DurationSeconds<u64>")]
+    #[schemars(with = "u64")]
     #[serde(default = "TrackerSettings::default_stationary_timeout")]
     pub(crate) stationary_timeout: Duration,
 
-    #[serde(default = "TrackerSettings::default_overlap_threshold")]
-    pub(crate) overlap_threshold: f32,
+    /// How long a person is still counted after they disappear.
+    ///
+    /// People sitting still can blend into the background for a moment, which would otherwise
+    /// drop the count to zero and back. A person that isn't matched with anything in a new frame
+    /// is kept (and counted) for up to *presence_decay* seconds, and if a new object shows up
+    /// nearby in that time it's matched with the person. The default (0) drops people right
+    /// away.
+    #[serde_as(as = "serde_with::DurationSeconds<u64>")]
+    #[schemars(with = "u64")]
+    #[serde(default)]
+    pub(crate) presence_decay: Duration,
+
+    #[serde(default)]
+    pub(crate) overlap_threshold: Option<f32>,
+
+    #[serde(default)]
+    pub(crate) center_closeness: Option<f32>,
+
+    /// The number of frames to process before occupancy counts are published.
+    ///
+    /// The background model is essentially untrained when first started, so every warm object in
+    /// view looks like a person. During the warm-up period the background model is still updated,
+    /// but the count is held at 0.
+    #[serde(default)]
+    pub(crate) warmup_frames: usize,
+
+    /// The fraction of a frame interval the tracker may spend processing a single frame.
+    ///
+    /// If processing a frame takes longer than this, the next frame (or frames) are skipped so
+    /// that the tracker does not fall behind the camera. If not set, every frame is processed.
+    #[serde(default, deserialize_with = "frame_budget")]
+    pub(crate) frame_budget: Option<f32>,
+
+    /// Only process every *decimation*th frame from the camera.
+    ///
+    /// Slow-moving occupancy doesn't need every frame to be tracked, and skipping frames
+    /// significantly reduces the cost of updating the background model. The background model's
+    /// learning rate is scaled so that it adapts over the same amount of time as if every frame
+    /// were processed.
+    #[serde(default = "TrackerSettings::default_decimation")]
+    pub(crate) decimation: NonZeroUsize,
+
+    /// How often the occupied and vacant durations are published.
+    ///
+    /// The durations are also published whenever the occupancy changes. Setting this to 0 only
+    /// publishes the durations when the occupancy changes.
+    #[serde_as(as = "serde_with::DurationSeconds<u64>")]
+    #[schemars(with = "u64")]
+    #[serde(default = "TrackerSettings::default_duration_interval")]
+    pub(crate) duration_interval: Duration,
+
+    /// How long there must be people present before the space is marked as occupied.
+    ///
+    /// This only affects the occupied sensor, the count is always published immediately.
+    #[serde_as(as = "serde_with::DurationSeconds<u64>")]
+    #[schemars(with = "u64")]
+    #[serde(default)]
+    pub(crate) occupied_on_delay: Duration,
 
-    #[serde(default = "TrackerSettings::default_center_closeness")]
-    pub(crate) center_closeness: f32,
+    /// How long the space must be empty before it is no longer marked as occupied.
+    ///
+    /// This only affects the occupied sensor, the count is always published immediately.
+    #[serde_as(as = "serde_with::DurationSeconds<u64>")]
+    #[schemars(with = "u64")]
+    #[serde(default)]
+    pub(crate) occupied_off_delay: Duration,
+
+    /// The most people allowed in the space before the over capacity sensor is turned on.
+    #[serde(default)]
+    pub(crate) capacity_limit: Option<usize>,
+
+    /// How far below [`capacity_limit`][TrackerSettings::capacity_limit] the count must drop
+    /// before the over capacity sensor is turned off again.
+    ///
+    /// This keeps the sensor from flapping when the count goes back and forth right at the limit.
+    #[serde(default = "TrackerSettings::default_capacity_hysteresis")]
+    pub(crate) capacity_hysteresis: usize,
+
+    /// How long a person can stay in one spot before the loitering sensor is turned on.
+    ///
+    /// This is measured from when the person last moved, the same way as
+    /// [`stationary_timeout`][TrackerSettings::stationary_timeout]. If not set, there is no
+    /// loitering sensor.
+    #[serde_as(as = "Option<serde_with::DurationSeconds<u64>>")]
+    #[schemars(with = "Option<u64>")]
+    #[serde(default)]
+    pub(crate) loitering_threshold: Option<Duration>,
+
+    /// Track people using the filtered images instead of the raw camera images.
+    ///
+    /// Only has an effect if [`smoothing`][crate::render::RenderSettings::smoothing] or
+    /// [`spatial_filter`][crate::render::RenderSettings::spatial_filter] are set. Filtering
+    /// reduces the noise from the camera, but also blurs movement.
+    #[serde(default)]
+    pub(crate) smoothed_input: bool,
+
+    /// Append every change in the occupancy count to a file as a line of JSON.
+    #[serde(default)]
+    pub(crate) event_log: Option<EventLogSettings>,
+
+    /// Keep a heatmap of how often each pixel is in the foreground.
+    ///
+    /// The heatmap is served as `/api/heatmap.png` by the HTTP server.
+    #[serde(default)]
+    pub(crate) heatmap: Option<HeatmapSettings>,
 }
 
 impl TrackerSettings {
@@ -53,10 +581,6 @@ impl TrackerSettings {
         Duration::from_secs(60 * 60 * 3)
     }
 
-    const fn default_maximum_movement() -> f32 {
-        16.0
-    }
-
     const fn default_overlap_threshold() -> f32 {
         0.9
     }
@@ -64,45 +588,563 @@ impl TrackerSettings {
     const fn default_center_closeness() -> f32 {
         1.0
     }
+
+    const fn default_duration_interval() -> Duration {
+        Duration::from_secs(60)
+    }
+
+    const fn default_capacity_hysteresis() -> usize {
+        1
+    }
+
+    fn default_decimation() -> NonZeroUsize {
+        NonZeroUsize::new(1).unwrap()
+    }
+
+    /// The background confidence threshold, taking [`sensitivity`] into account.
+    ///
+    /// [`sensitivity`]: TrackerSettings::sensitivity
+    pub(crate) fn background_confidence_threshold(&self) -> f32 {
+        self.background_confidence_threshold.unwrap_or_else(|| {
+            self.sensitivity
+                .map_or_else(Self::default_confidence_threshold, |sensitivity| {
+                    sensitivity.background_confidence_threshold()
+                })
+        })
+    }
+
+    /// The overlap threshold, taking [`sensitivity`] into account.
+    ///
+    /// [`sensitivity`]: TrackerSettings::sensitivity
+    pub(crate) fn overlap_threshold(&self) -> f32 {
+        self.overlap_threshold.unwrap_or_else(|| {
+            self.sensitivity
+                .map_or_else(Self::default_overlap_threshold, |sensitivity| {
+                    sensitivity.overlap_threshold()
+                })
+        })
+    }
+
+    /// The maximum difference in shape for objects to be matched, taking [`shape_distance`] into
+    /// account.
+    ///
+    /// [`shape_distance`]: TrackerSettings::shape_distance
+    pub(crate) fn maximum_movement(&self) -> f32 {
+        self.maximum_movement
+            .unwrap_or_else(|| self.shape_distance.default_maximum_movement())
+    }
+
+    /// The center closeness, taking [`sensitivity`] into account.
+    ///
+    /// [`sensitivity`]: TrackerSettings::sensitivity
+    pub(crate) fn center_closeness(&self) -> f32 {
+        self.center_closeness.unwrap_or_else(|| {
+            self.sensitivity
+                .map_or_else(Self::default_center_closeness, |sensitivity| {
+                    sensitivity.center_closeness()
+                })
+        })
+    }
+
+    /// Check for mistakes in the settings, given the size of the camera's images (if known).
+    pub(crate) fn check(&self, resolution: Option<(u32, u32)>) -> anyhow::Result<()> {
+        if let (Some(region), Some((width, height))) = (self.region_of_interest, resolution) {
+            let fits = matches!(
+                region.end(),
+                Some((right, bottom)) if right <= width && bottom <= height
+            );
+            if !fits {
+                return Err(anyhow!(
+                    "The region of interest ({}x{} at ({}, {})) extends past the camera's {}x{} image",
+                    region.width,
+                    region.height,
+                    region.x,
+                    region.y,
+                    width,
+                    height
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// The background model parameters, adjusted for the configured decimation.
+    pub(crate) fn model_parameters(&self) -> GmmParameters {
+        let mut parameters = self.background_model_parameters;
+        parameters.learning_rate = parameters.learning_rate.decimated(self.decimation.get());
+        parameters
+    }
 }
 
 impl Default for TrackerSettings {
     fn default() -> Self {
         Self {
+            mode: TrackerMode::default(),
+            threshold: Threshold::default(),
+            sensitivity: None,
             background_model_parameters: GmmParameters::default(),
-            background_confidence_threshold: Self::default_confidence_threshold(),
-            maximum_movement: Self::default_maximum_movement(),
+            background_model: None,
+            background_confidence_threshold: None,
+            shape_distance: ShapeDistance::default(),
+            maximum_movement: None,
+            use_kalman: false,
             minimum_size: None,
+            person_temperature_range: None,
+            region_of_interest: None,
             stationary_timeout: Self::default_stationary_timeout(),
-            overlap_threshold: Self::default_overlap_threshold(),
-            center_closeness: Self::default_center_closeness(),
+            presence_decay: Duration::ZERO,
+            overlap_threshold: None,
+            center_closeness: None,
+            warmup_frames: 0,
+            frame_budget: None,
+            decimation: Self::default_decimation(),
+            duration_interval: Self::default_duration_interval(),
+            occupied_on_delay: Duration::ZERO,
+            occupied_off_delay: Duration::ZERO,
+            capacity_limit: None,
+            capacity_hysteresis: Self::default_capacity_hysteresis(),
+            loitering_threshold: None,
+            smoothed_input: false,
+            event_log: None,
+            heatmap: None,
         }
     }
 }
 
 #[cfg(test)]
 mod test {
+    use std::num::NonZeroUsize;
     use std::time::Duration;
 
-    use super::{GmmParameters, TrackerSettings};
+    use float_cmp::assert_approx_eq;
+
+    use crate::image_buffer::ThermalImage;
+    use crate::temperature::Temperature;
+
+    use super::{
+        EventLogSettings, GmmParameters, Sensitivity, ShapeDistance, Threshold, TrackerMode,
+        TrackerSettings,
+    };
 
     #[test]
     fn defaults() -> anyhow::Result<()> {
         let source = "";
         let config: TrackerSettings = toml::from_str(source)?;
         let expected = TrackerSettings {
+            mode: TrackerMode::Gmm,
+            threshold: Threshold::Dynamic(3.0),
+            sensitivity: None,
             background_model_parameters: GmmParameters::default(),
-            background_confidence_threshold: TrackerSettings::default_confidence_threshold(),
-            maximum_movement: TrackerSettings::default_maximum_movement(),
+            background_model: None,
+            background_confidence_threshold: None,
+            shape_distance: ShapeDistance::default(),
+            maximum_movement: None,
+            use_kalman: false,
             minimum_size: None,
+            person_temperature_range: None,
+            region_of_interest: None,
             stationary_timeout: TrackerSettings::default_stationary_timeout(),
-            overlap_threshold: TrackerSettings::default_overlap_threshold(),
-            center_closeness: TrackerSettings::default_center_closeness(),
+            presence_decay: Duration::ZERO,
+            overlap_threshold: None,
+            center_closeness: None,
+            warmup_frames: 0,
+            frame_budget: None,
+            decimation: TrackerSettings::default_decimation(),
+            duration_interval: TrackerSettings::default_duration_interval(),
+            occupied_on_delay: Duration::ZERO,
+            occupied_off_delay: Duration::ZERO,
+            capacity_limit: None,
+            capacity_hysteresis: TrackerSettings::default_capacity_hysteresis(),
+            loitering_threshold: None,
+            smoothed_input: false,
+            event_log: None,
+            heatmap: None,
+        };
+        assert_eq!(config, expected);
+        assert_approx_eq!(f32, config.background_confidence_threshold(), 0.0001);
+        assert_approx_eq!(f32, config.overlap_threshold(), 0.9);
+        assert_approx_eq!(f32, config.center_closeness(), 1.0);
+        Ok(())
+    }
+
+    #[test]
+    fn sensitivity_presets() -> anyhow::Result<()> {
+        let config: TrackerSettings = toml::from_str(r#"sensitivity = "high""#)?;
+        assert_eq!(config.sensitivity, Some(Sensitivity::High));
+        assert_approx_eq!(f32, config.background_confidence_threshold(), 0.0001);
+        assert_approx_eq!(f32, config.overlap_threshold(), 0.9);
+        assert_approx_eq!(f32, config.center_closeness(), 0.25);
+        let config: TrackerSettings = toml::from_str(r#"sensitivity = "medium""#)?;
+        assert_approx_eq!(f32, config.background_confidence_threshold(), 0.00001);
+        assert_approx_eq!(f32, config.overlap_threshold(), 0.8);
+        assert_approx_eq!(f32, config.center_closeness(), 0.5);
+        // Individual settings override the preset
+        let source = r#"
+        sensitivity = "low"
+        center_closeness = 2.0
+        "#;
+        let config: TrackerSettings = toml::from_str(source)?;
+        assert_approx_eq!(f32, config.background_confidence_threshold(), 0.00001);
+        assert_approx_eq!(f32, config.overlap_threshold(), 0.9);
+        assert_approx_eq!(f32, config.center_closeness(), 2.0);
+        assert!(toml::from_str::<TrackerSettings>(r#"sensitivity = "extreme""#).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn warmup_frames() -> anyhow::Result<()> {
+        let source = r#"
+        warmup_frames = 100
+        "#;
+        let config: TrackerSettings = toml::from_str(source)?;
+        let expected = TrackerSettings {
+            warmup_frames: 100,
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn frame_budget() -> anyhow::Result<()> {
+        let source = r#"
+        frame_budget = 0.8
+        "#;
+        let config: TrackerSettings = toml::from_str(source)?;
+        let expected = TrackerSettings {
+            frame_budget: Some(0.8),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+        for invalid in ["0", "-0.5", "nan", "inf"] {
+            let source = format!("frame_budget = {}", invalid);
+            assert!(
+                toml::from_str::<TrackerSettings>(&source).is_err(),
+                "frame_budget = {} should be rejected",
+                invalid
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn decimation() -> anyhow::Result<()> {
+        let source = r#"
+        decimation = 5
+        "#;
+        let config: TrackerSettings = toml::from_str(source)?;
+        let expected = TrackerSettings {
+            decimation: NonZeroUsize::new(5).unwrap(),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn decimation_zero() {
+        let source = r#"
+        decimation = 0
+        "#;
+        let config: Result<TrackerSettings, _> = toml::from_str(source);
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn decimated_learning_rate() {
+        let source = r#"
+        decimation = 10
+        background_model_parameters = { learning_rate = 0.1 }
+        "#;
+        let config: TrackerSettings = toml::from_str(source).unwrap();
+        let mut learning_rate = config.model_parameters().learning_rate;
+        while !learning_rate.is_trained() {
+            learning_rate.increment();
+        }
+        // 1 - (1 - 0.1)^10
+        assert_approx_eq!(
+            f32,
+            learning_rate.current_value(),
+            0.651_322_3,
+            epsilon = 1e-6
+        );
+        // No decimation leaves the learning rate alone
+        let config = TrackerSettings::default();
+        assert_eq!(
+            config.model_parameters(),
+            config.background_model_parameters
+        );
+    }
+
+    #[test]
+    fn duration_interval() -> anyhow::Result<()> {
+        let source = r#"
+        duration_interval = 15
+        "#;
+        let config: TrackerSettings = toml::from_str(source)?;
+        let expected = TrackerSettings {
+            duration_interval: Duration::from_secs(15),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn occupied_delays() -> anyhow::Result<()> {
+        let source = r#"
+        occupied_on_delay = 2
+        occupied_off_delay = 30
+        "#;
+        let config: TrackerSettings = toml::from_str(source)?;
+        let expected = TrackerSettings {
+            occupied_on_delay: Duration::from_secs(2),
+            occupied_off_delay: Duration::from_secs(30),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn capacity_limit() -> anyhow::Result<()> {
+        let config: TrackerSettings = toml::from_str("capacity_limit = 12")?;
+        let expected = TrackerSettings {
+            capacity_limit: Some(12),
+            capacity_hysteresis: 1,
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+        let config: TrackerSettings =
+            toml::from_str("capacity_limit = 12\ncapacity_hysteresis = 0")?;
+        assert_eq!(config.capacity_hysteresis, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn loitering_threshold() -> anyhow::Result<()> {
+        let config: TrackerSettings = toml::from_str("loitering_threshold = 300")?;
+        let expected = TrackerSettings {
+            loitering_threshold: Some(Duration::from_secs(300)),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn presence_decay() -> anyhow::Result<()> {
+        let config: TrackerSettings = toml::from_str("presence_decay = 5")?;
+        let expected = TrackerSettings {
+            presence_decay: Duration::from_secs(5),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn person_temperature_range() -> anyhow::Result<()> {
+        let source = r#"
+        [person_temperature_range]
+        max = { fahrenheit = 100 }
+        "#;
+        let config: TrackerSettings = toml::from_str(source)?;
+        let range = config
+            .person_temperature_range
+            .expect("person_temperature_range to be set");
+        assert_eq!(range.min, Temperature::Celsius(28.0));
+        assert_eq!(range.max, Temperature::Fahrenheit(100.0));
+        assert!(range.contains(Temperature::Celsius(28.0)));
+        assert!(range.contains(Temperature::Celsius(37.7)));
+        assert!(range.contains(Temperature::Fahrenheit(100.0)));
+        assert!(!range.contains(Temperature::Celsius(27.9)));
+        assert!(!range.contains(Temperature::Celsius(38.0)));
+        assert!(!range.contains(Temperature::Fahrenheit(82.0)));
+        Ok(())
+    }
+
+    #[test]
+    fn inverted_person_temperature_range() {
+        let inverted = "person_temperature_range = { min = 37, max = 28 }";
+        assert!(toml::from_str::<TrackerSettings>(inverted).is_err());
+        // The default maximum is below this minimum.
+        let inverted_default = "person_temperature_range = { min = { fahrenheit = 100 } }";
+        assert!(toml::from_str::<TrackerSettings>(inverted_default).is_err());
+        let single = "person_temperature_range = { min = 30, max = { fahrenheit = 86 } }";
+        assert!(toml::from_str::<TrackerSettings>(single).is_ok());
+    }
+
+    #[test]
+    fn region_of_interest() -> anyhow::Result<()> {
+        let config: TrackerSettings =
+            toml::from_str("region_of_interest = { x = 2, y = 1, width = 4, height = 3 }")?;
+        let region = config
+            .region_of_interest
+            .expect("region_of_interest to be set");
+        assert!(region.contains_pixel(2, 1));
+        assert!(region.contains_pixel(5, 3));
+        assert!(!region.contains_pixel(6, 3));
+        assert!(!region.contains_pixel(5, 4));
+        assert!(!region.contains_pixel(1, 1));
+        assert!(config.check(Some((8, 8))).is_ok());
+        assert!(config.check(Some((6, 4))).is_ok());
+        assert!(config.check(Some((5, 8))).is_err());
+        assert!(config.check(Some((8, 3))).is_err());
+        // The resolution isn't known ahead of time for some cameras.
+        assert!(config.check(None).is_ok());
+        assert!(toml::from_str::<TrackerSettings>(
+            "region_of_interest = { x = 0, y = 0, width = 0, height = 3 }"
+        )
+        .is_err());
+        // The right edge would overflow.
+        assert!(toml::from_str::<TrackerSettings>(
+            "region_of_interest = { x = 4294967295, y = 0, width = 1, height = 3 }"
+        )
+        .is_err());
+        assert!(toml::from_str::<TrackerSettings>(
+            "region_of_interest = { x = 4294967294, y = 0, width = 1, height = 3 }"
+        )
+        .is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn use_kalman() -> anyhow::Result<()> {
+        let config: TrackerSettings = toml::from_str("use_kalman = true")?;
+        let expected = TrackerSettings {
+            use_kalman: true,
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn shape_distance() -> anyhow::Result<()> {
+        let config: TrackerSettings = toml::from_str(r#"shape_distance = "log_hu""#)?;
+        assert_eq!(config.shape_distance, ShapeDistance::LogHu);
+        assert_eq!(config.maximum_movement, None);
+        assert_approx_eq!(f32, config.maximum_movement(), 2000.0);
+        let config: TrackerSettings = toml::from_str("maximum_movement = 2.5")?;
+        assert_eq!(config.shape_distance, ShapeDistance::Euclidean);
+        assert_approx_eq!(f32, config.maximum_movement(), 2.5);
+        assert_approx_eq!(f32, TrackerSettings::default().maximum_movement(), 16.0);
+        Ok(())
+    }
+
+    #[test]
+    fn log_hu_transform() {
+        let moments = [0.1, -0.01, 0.0, 1000.0, -1.0, 1.0, 0.5];
+        assert_eq!(ShapeDistance::Euclidean.transform(moments), moments);
+        let transformed = ShapeDistance::LogHu.transform(moments);
+        let expected = [-1.0, 2.0, 0.0, 3.0, -0.0, 0.0, -std::f32::consts::LOG10_2];
+        for (actual, expected) in transformed.iter().zip(expected.iter()) {
+            assert_approx_eq!(f32, *actual, *expected, epsilon = 0.00001);
+        }
+    }
+
+    #[test]
+    fn event_log() -> anyhow::Result<()> {
+        let source = r#"
+        [event_log]
+        path = "/var/log/occupancy.jsonl"
+        max_size = 4096
+        "#;
+        let config: TrackerSettings = toml::from_str(source)?;
+        let expected = TrackerSettings {
+            event_log: Some(EventLogSettings {
+                path: "/var/log/occupancy.jsonl".into(),
+                max_size: 4096,
+            }),
+            ..Default::default()
         };
         assert_eq!(config, expected);
         Ok(())
     }
 
+    #[test]
+    fn smoothed_input() -> anyhow::Result<()> {
+        let config: TrackerSettings = toml::from_str("smoothed_input = true")?;
+        let expected = TrackerSettings {
+            smoothed_input: true,
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn threshold_mode() -> anyhow::Result<()> {
+        let source = r#"
+        mode = "threshold"
+        threshold = { static = { fahrenheit = 86.0 } }
+        "#;
+        let config: TrackerSettings = toml::from_str(source)?;
+        let expected = TrackerSettings {
+            mode: TrackerMode::Threshold,
+            threshold: Threshold::Static(Temperature::Fahrenheit(86.0)),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+        let config: TrackerSettings = toml::from_str("threshold = { dynamic = 5.0 }")?;
+        assert_eq!(config.threshold, Threshold::Dynamic(5.0));
+        let config: TrackerSettings = toml::from_str("threshold = { deviation = 2.5 }")?;
+        assert_eq!(config.threshold, Threshold::Deviation(2.5));
+        let config: TrackerSettings = toml::from_str("threshold = { percentile = 95 }")?;
+        assert_eq!(config.threshold, Threshold::Percentile(95.0));
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_threshold() {
+        for source in [
+            "threshold = { deviation = -1.0 }",
+            "threshold = { deviation = nan }",
+            "threshold = { percentile = 101 }",
+            "threshold = { percentile = -5 }",
+        ] {
+            assert!(
+                toml::from_str::<TrackerSettings>(source).is_err(),
+                "'{}' was accepted",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn threshold_cutoff() {
+        let mut image = ThermalImage::from_pixel(3, 3, [20.0].into());
+        image[(1, 1)] = [37.0].into();
+        assert_approx_eq!(
+            f32,
+            Threshold::Static(Temperature::Fahrenheit(86.0)).cutoff(&image),
+            30.0,
+            epsilon = 1e-4
+        );
+        // The single warm pixel doesn't shift the median.
+        assert_approx_eq!(f32, Threshold::Dynamic(3.0).cutoff(&image), 23.0);
+        // The mean is 21.8889 and the standard deviation is 5.3426
+        assert_approx_eq!(
+            f32,
+            Threshold::Deviation(2.0).cutoff(&image),
+            32.5741,
+            epsilon = 1e-3
+        );
+        assert_approx_eq!(f32, Threshold::Percentile(50.0).cutoff(&image), 20.0);
+        assert_approx_eq!(f32, Threshold::Percentile(100.0).cutoff(&image), 37.0);
+        assert_approx_eq!(f32, Threshold::Percentile(0.0).cutoff(&image), 20.0);
+    }
+
+    #[test]
+    fn threshold_cutoff_nan() {
+        // A NaN pixel (from a glitched read) shouldn't panic when ranking the pixels.
+        let mut image = ThermalImage::from_pixel(3, 3, [20.0].into());
+        image[(1, 1)] = [f32::NAN].into();
+        assert_approx_eq!(f32, Threshold::Dynamic(3.0).cutoff(&image), 23.0);
+        assert_approx_eq!(f32, Threshold::Percentile(50.0).cutoff(&image), 20.0);
+    }
+
     #[test]
     fn minimum_size() -> anyhow::Result<()> {
         let source = r#"
@@ -1,28 +1,37 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
+use anyhow::Context as _;
 use futures::{Sink, Stream};
 use image::{ImageBuffer, Luma};
 use imageproc::region_labelling::{connected_components, Connectivity};
 use rayon::prelude::*;
 use rstar::{Envelope, PointDistance, RTree, RTreeObject};
+use serde::Serialize;
 use tokio::sync::watch;
+use tokio::time::Instant;
 use tokio_stream::wrappers::WatchStream;
-use tracing::{debug, debug_span, instrument, trace};
+use tracing::{debug, debug_span, info, instrument, trace, warn};
 
 use std::collections::{HashMap, HashSet};
 use std::convert::Infallible;
+use std::fs::File;
+use std::io::BufReader;
 use std::iter;
+use std::path::Path;
 use std::pin::Pin;
 use std::sync::{Arc, RwLock};
 use std::task::{Context, Poll};
-use std::time::Instant;
+use std::time::Duration;
 
 use crate::camera::Measurement;
 use crate::image_buffer::ThermalImage;
+use crate::temperature::Temperature;
 
 use super::gmm::{BackgroundModel, GaussianMixtureModel};
+use super::heatmap::SharedHeatmap;
+use super::kalman::KalmanFilter;
 use super::moments::hu_moments;
 use super::point::{Point, PointTemperature};
-use super::settings::TrackerSettings;
+use super::settings::{ShapeDistance, TrackerMode, TrackerSettings};
 
 type GmmBackground = BackgroundModel<Vec<GaussianMixtureModel>>;
 
@@ -31,54 +40,239 @@ pub(crate) struct Tracker {
     settings: TrackerSettings,
     background: Arc<RwLock<Option<GmmBackground>>>,
     objects: Arc<RwLock<RTree<Object>>>,
+    /// People that have recently disappeared, along with when they were last seen.
+    lost_objects: Arc<RwLock<Vec<(Instant, Object)>>>,
     count_sender: Arc<watch::Sender<usize>>,
     count_receiver: watch::Receiver<usize>,
+    objects_sender: Arc<watch::Sender<Vec<TrackedObject>>>,
+    objects_receiver: watch::Receiver<Vec<TrackedObject>>,
+    next_object_id: u64,
+    frame_count: usize,
+    frame_interval: Option<Duration>,
+    skip_frames: usize,
+    heatmap: Option<SharedHeatmap>,
 }
 
 impl Tracker {
     pub(crate) fn new(settings: &TrackerSettings) -> Self {
         debug!(params=?settings.background_model_parameters, "GMM parameters");
         let (sender, receiver) = watch::channel(0);
+        let (objects_sender, objects_receiver) = watch::channel(Vec::new());
         Self {
-            settings: *settings,
+            settings: settings.clone(),
             background: Arc::new(RwLock::new(None)),
             objects: Arc::new(RwLock::new(RTree::default())),
+            lost_objects: Arc::new(RwLock::new(Vec::new())),
             count_sender: Arc::new(sender),
             count_receiver: receiver,
+            objects_sender: Arc::new(objects_sender),
+            objects_receiver,
+            next_object_id: 0,
+            frame_count: 0,
+            frame_interval: None,
+            skip_frames: 0,
+            heatmap: None,
         }
     }
 
+    /// Set the rate frames are expected to arrive at.
+    ///
+    /// This is used with [`TrackerSettings::frame_budget`] to decide when frames should be skipped.
+    pub(crate) fn set_frame_rate(&mut self, frame_rate: f32) {
+        self.frame_interval = if frame_rate.is_normal() && frame_rate > 0.0 {
+            Some(Duration::from_secs_f32(frame_rate.recip()))
+        } else {
+            None
+        };
+    }
+
+    /// Add the foreground from every frame (after warming up) to `heatmap`.
+    pub(crate) fn set_heatmap(&mut self, heatmap: SharedHeatmap) {
+        self.heatmap = Some(heatmap);
+    }
+
+    /// The number of frames to skip after a frame took `elapsed` to process.
+    fn frames_over_budget(&self, elapsed: Duration) -> usize {
+        let budget = match (self.frame_interval, self.settings.frame_budget) {
+            (Some(interval), Some(fraction)) => interval.mul_f32(fraction),
+            _ => return 0,
+        };
+        if elapsed <= budget {
+            0
+        } else {
+            // Skip enough frames to cover the time spent over budget.
+            (elapsed.as_secs_f32() / budget.as_secs_f32()).ceil() as usize - 1
+        }
+    }
+
+    /// Throw away the background model, so that a new one is built starting with the next frame.
+    ///
+    /// This is useful after the scene changes in a way the model can't adapt to quickly, like
+    /// furniture being moved.
+    pub(crate) fn reset_background(&self) {
+        info!("Resetting the background model");
+        *self.background.write().unwrap() = None;
+    }
+
+    /// Start from a background model saved by [`save_background`][Self::save_background] instead
+    /// of training a new one.
+    ///
+    /// If the model doesn't match the size of the images from the camera, it's thrown away and a
+    /// new one is trained like normal.
+    pub(crate) fn load_background(&self, path: &Path) -> anyhow::Result<()> {
+        let file = File::open(path)
+            .with_context(|| format!("Unable to open background model {}", path.display()))?;
+        let model = GmmBackground::load(BufReader::new(file), self.settings.model_parameters())
+            .with_context(|| format!("Unable to read background model {}", path.display()))?;
+        info!(path = %path.display(), pixels = model.len(), "Loaded background model");
+        *self.background.write().unwrap() = Some(model);
+        Ok(())
+    }
+
+    /// Save the current background model so that it can be loaded later with
+    /// [`load_background`][Self::load_background].
+    #[cfg(feature = "mock_camera")]
+    pub(crate) fn save_background(&self, path: &Path) -> anyhow::Result<()> {
+        use std::io::{BufWriter, Write};
+
+        let background = self.background.read().unwrap();
+        let model = background
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("There is no background model to save"))?;
+        let file = File::create(path)
+            .with_context(|| format!("Unable to create background model {}", path.display()))?;
+        let mut writer = BufWriter::new(file);
+        model
+            .save(&mut writer)
+            .with_context(|| format!("Unable to write background model {}", path.display()))?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Whether the tracker is still within the warm-up period.
+    pub(crate) fn is_warming_up(&self) -> bool {
+        self.frame_count < self.settings.warmup_frames
+    }
+
+    /// Whether the background has stopped changing, assuming the scene is empty.
+    ///
+    /// The tracker needs to be out of the warm-up period, the background model (if used) needs to
+    /// be fully trained, and nothing can currently be tracked.
+    #[cfg(any(feature = "mock_camera", test))]
+    pub(crate) fn is_stable(&self) -> bool {
+        let background_trained = match self.settings.mode {
+            TrackerMode::Gmm => self
+                .background
+                .read()
+                .unwrap()
+                .as_ref()
+                .is_some_and(GmmBackground::is_trained),
+            TrackerMode::Threshold => true,
+        };
+        !self.is_warming_up()
+            && background_trained
+            && self.objects.read().unwrap().size() == 0
+            && self.lost_objects.read().unwrap().is_empty()
+    }
+
     pub(crate) fn count(&self) -> usize {
+        let lost_count = self.lost_objects.read().unwrap().len();
         self.objects
             .read()
             .unwrap()
             .iter()
             .filter(|o| o.is_person)
             .count()
+            + lost_count
+    }
+
+    /// A summary of every object currently being tracked, ordered by ID.
+    ///
+    /// People that have recently disappeared (see [`TrackerSettings::presence_decay`]) are
+    /// included where they were last seen.
+    pub(crate) fn objects(&self) -> Vec<TrackedObject> {
+        let lost_objects = self.lost_objects.read().unwrap();
+        let mut objects: Vec<TrackedObject> = self
+            .objects
+            .read()
+            .unwrap()
+            .iter()
+            .chain(lost_objects.iter().map(|(_, object)| object))
+            .map(TrackedObject::from)
+            .collect();
+        objects.sort_unstable_by_key(|object| object.id);
+        objects
     }
 
     #[instrument(level = "trace", skip(self, image))]
     pub(crate) fn update(&mut self, image: &ThermalImage) {
         let mut background_option = self.background.write().unwrap();
-        let background = background_option.get_or_insert_with(|| {
-            let mut model = GmmBackground::new(image.len());
-            model.set_parameters(self.settings.background_model_parameters);
-            model
-        });
-        let foreground: Vec<u8> = background
-            .background_probability::<Vec<f32>>(image)
-            .into_iter()
-            .map(|p| {
-                if p < self.settings.background_confidence_threshold {
-                    u8::MAX
-                } else {
-                    0u8
-                }
-            })
-            .collect();
-        let foreground: ImageBuffer<Luma<u8>, Vec<u8>> =
+        // A loaded background model might be for a different camera.
+        if background_option
+            .as_ref()
+            .is_some_and(|background| background.len() != image.len())
+        {
+            warn!("The background model doesn't match the camera, training a new one");
+            *background_option = None;
+        }
+        // Threshold mode skips the background model entirely.
+        let mut background = match self.settings.mode {
+            TrackerMode::Gmm => Some(background_option.get_or_insert_with(|| {
+                let mut model = GmmBackground::new(image.len());
+                model.set_parameters(self.settings.model_parameters());
+                model
+            })),
+            TrackerMode::Threshold => None,
+        };
+        let foreground: Vec<u8> = match &background {
+            Some(background) => background
+                .background_probability::<Vec<f32>>(image)
+                .into_iter()
+                .map(|p| {
+                    if p < self.settings.background_confidence_threshold() {
+                        u8::MAX
+                    } else {
+                        0u8
+                    }
+                })
+                .collect(),
+            None => {
+                let cutoff = self.settings.threshold.cutoff(image);
+                image
+                    .iter()
+                    .map(
+                        |temperature| {
+                            if *temperature > cutoff {
+                                u8::MAX
+                            } else {
+                                0u8
+                            }
+                        },
+                    )
+                    .collect()
+            }
+        };
+        let mut foreground: ImageBuffer<Luma<u8>, Vec<u8>> =
             ImageBuffer::from_raw(image.width(), image.height(), foreground)
                 .expect("A mapped Vec should be able to be used for a new ImageBuffer");
+        // Anything outside of the region of interest is background, no matter how warm it is. As
+        // objects are then made up of pixels within the region, their centers are always within it
+        // as well.
+        if let Some(region) = self.settings.region_of_interest {
+            for (x, y, pixel) in foreground.enumerate_pixels_mut() {
+                if !region.contains_pixel(x, y) {
+                    pixel[0] = 0;
+                }
+            }
+        }
+        // The foreground is meaningless until the background model has had a chance to learn the
+        // scene.
+        if let Some(heatmap) = self.heatmap.as_ref().filter(|_| !self.is_warming_up()) {
+            heatmap
+                .lock()
+                .unwrap()
+                .update(&foreground, Instant::now().into_std());
+        }
         let components = connected_components(&foreground, Connectivity::Eight, Luma([0u8]));
         // We only care about the foreground pixels, so skip the background (label == 0).
         let filtered_pixels = components
@@ -93,29 +287,62 @@ impl Tracker {
                 .push((Point::new(x, y), temperature));
         }
         let now = Instant::now();
-        let new_objects: Vec<Object> = object_points
+        let mut new_objects: Vec<Object> = object_points
             .into_values()
             .filter_map(|points| {
                 // Filter out any blobs smaller than the minimum size
                 if points.len() >= self.settings.minimum_size.unwrap_or_default() {
-                    Some(Object::new(points, now))
+                    Some(Object::new(points, now, self.settings.shape_distance))
                 } else {
                     debug!(point_count = %points.len(), "Skipping object because of size");
                     None
                 }
             })
             .collect();
+        // Every object starts out with a new ID, and inherits the ID of the object it's
+        // correlated with (if any) in update_tracked_objects.
+        for object in new_objects.iter_mut() {
+            object.id = self.next_object_id;
+            self.next_object_id = self.next_object_id.wrapping_add(1);
+        }
         let mut new_objects: RTree<Object> = RTree::bulk_load(new_objects);
         let mut old_objects = self.objects.write().unwrap();
+        let mut lost_objects = self.lost_objects.write().unwrap();
+        // People that recently disappeared get another chance to be matched.
+        let lost_since: HashMap<u64, Instant> = lost_objects
+            .drain(..)
+            .map(|(since, object)| {
+                let id = object.id;
+                old_objects.insert(object);
+                (id, since)
+            })
+            .collect();
         self.update_tracked_objects(&mut old_objects, &mut new_objects);
-        // Mark any new people, and unmark any objects that have been stationary too long.
-        background.thaw_all();
+        *lost_objects = self.retain_lost_objects(&old_objects, &new_objects, &lost_since, now);
+        // Mark any new people, and unmark any objects that have been stationary too long. While
+        // warming up, nothing is considered a person so that the background model can learn the
+        // entire scene.
+        if let Some(background) = background.as_mut() {
+            background.thaw_all();
+        }
         let image_width = image.width();
+        let warming_up = self.is_warming_up();
+        let person_temperature_range = self.settings.person_temperature_range;
         for object in new_objects.iter_mut() {
-            if object.is_person {
+            if warming_up {
+                object.is_person = false;
+            } else if person_temperature_range.is_some_and(|range| {
+                !range.contains(Temperature::Celsius(object.temperature_mean()))
+            }) {
+                // Objects that are too hot or cold are tracked, but never counted.
+                if object.is_person {
+                    debug!(object = %object.summary(), "Object is outside the person temperature range");
+                }
+                object.is_person = false;
+            } else if object.is_person {
                 if object.last_movement.elapsed() > self.settings.stationary_timeout {
                     object.is_person = false;
-                } else {
+                } else if let Some(background) = background.as_mut() {
                     let pixel_numbers = object
                         .points()
                         .map(|point| point.pixel_number(image_width) as usize)
@@ -127,15 +354,30 @@ impl Tracker {
         // Update the background model, save the new objects for the next frame and broadcast the
         // new count of persons in view.
         *old_objects = new_objects;
-        background.update(image);
+        if let Some(background) = background {
+            background.update(image);
+        }
         // Need to release locks before count() will work
         drop(background_option);
         drop(old_objects);
+        drop(lost_objects);
+        self.frame_count = self.frame_count.saturating_add(1);
+        if warming_up {
+            if self.is_warming_up() {
+                trace!(frame_count = %self.frame_count, "Warming up, skipping occupancy count");
+            } else {
+                debug!(frame_count = %self.frame_count, "Tracker warm-up complete");
+            }
+            return;
+        }
         let new_count = self.count();
         trace!(count = %new_count, "Current occupancy count");
         self.count_sender
             .send(new_count)
             .expect("There's a receiver also stored on the Tracker, so all sends should succeed.");
+        self.objects_sender
+            .send(self.objects())
+            .expect("There's a receiver also stored on the Tracker, so all sends should succeed.");
     }
 
     #[instrument(
@@ -148,7 +390,11 @@ impl Tracker {
         old_objects: &mut RTree<Object>,
         new_objects: &mut RTree<Object>,
     ) {
-        let max_distance = self.settings.maximum_movement;
+        if self.settings.use_kalman {
+            self.update_tracked_objects_kalman(old_objects, new_objects);
+            return;
+        }
+        let max_distance = self.settings.maximum_movement();
         for new_object in new_objects.iter_mut() {
             let neighbor = old_objects.pop_nearest_neighbor(&new_object.hu_moments);
             if let Some(old_object) = neighbor {
@@ -162,23 +408,7 @@ impl Tracker {
                         new_object = %new_object.summary(),
                         %distance_2,
                     );
-                    let old_center = old_object.center();
-                    let new_center = new_object.center();
-                    let center_difference = old_center.squared_distance(new_center);
-                    let overlap_coefficient = old_object.overlap_coefficient(new_object);
-                    // If the object hasn't moved, keep the old update time and person marking
-                    trace!(%center_difference, %overlap_coefficient);
-                    if center_difference < self.settings.center_closeness
-                        && overlap_coefficient >= self.settings.overlap_threshold
-                    {
-                        new_object.last_movement = old_object.last_movement;
-                        new_object.is_person = old_object.is_person;
-                        debug!("Ignoring movement for object");
-                    } else {
-                        // Conversely, if an object has moved, make sure it's marked as a person
-                        new_object.is_person = true;
-                        debug!("Marking object as person");
-                    }
+                    self.correlate_objects(&old_object, new_object);
                 } else {
                     // Put the old object back in if it's too far away.
                     old_objects.insert(old_object);
@@ -187,9 +417,114 @@ impl Tracker {
         }
     }
 
+    /// Find the people from the previous frame that weren't matched with a new object, and are
+    /// still within the [presence decay][TrackerSettings::presence_decay].
+    fn retain_lost_objects(
+        &self,
+        old_objects: &RTree<Object>,
+        new_objects: &RTree<Object>,
+        lost_since: &HashMap<u64, Instant>,
+        now: Instant,
+    ) -> Vec<(Instant, Object)> {
+        let matched: HashSet<u64> = new_objects.iter().map(|object| object.id).collect();
+        old_objects
+            .iter()
+            .filter(|object| object.is_person && !matched.contains(&object.id))
+            .filter_map(|object| {
+                let since = lost_since.get(&object.id).copied().unwrap_or(now);
+                if now.duration_since(since) < self.settings.presence_decay {
+                    trace!(object = %object.summary(), "Keeping lost person");
+                    Some((since, object.clone()))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Match objects using the position predicted by each old object's Kalman filter.
+    ///
+    /// Each new object is matched with the closest old object whose predicted position is within
+    /// the filter's gate, and the new object inherits (and updates) that object's filter. Objects
+    /// that aren't matched get a new filter.
+    fn update_tracked_objects_kalman(
+        &self,
+        old_objects: &mut RTree<Object>,
+        new_objects: &mut RTree<Object>,
+    ) {
+        let mut candidates: Vec<(Object, KalmanFilter)> = old_objects
+            .iter()
+            .map(|old_object| {
+                let mut filter = old_object
+                    .kalman
+                    .clone()
+                    .unwrap_or_else(|| KalmanFilter::new(old_object.bounding_box_center()));
+                filter.predict();
+                (old_object.clone(), filter)
+            })
+            .collect();
+        for new_object in new_objects.iter_mut() {
+            let measured = new_object.bounding_box_center();
+            let nearest = candidates
+                .iter()
+                .enumerate()
+                .filter_map(|(index, (_, filter))| {
+                    filter
+                        .gated_distance_2(measured)
+                        .map(|distance_2| (index, distance_2))
+                })
+                .min_by(|(_, left), (_, right)| left.total_cmp(right));
+            let mut filter = match nearest {
+                Some((index, distance_2)) => {
+                    let (old_object, filter) = candidates.swap_remove(index);
+                    let object_pair_span = debug_span!("Correlated objects");
+                    let _pair_span = object_pair_span.enter();
+                    let predicted = filter.position();
+                    debug!(
+                        old_object = %old_object.summary(),
+                        new_object = %new_object.summary(),
+                        predicted = ?(predicted.x, predicted.y),
+                        %distance_2,
+                    );
+                    self.correlate_objects(&old_object, new_object);
+                    filter
+                }
+                None => KalmanFilter::new(measured),
+            };
+            filter.update(measured);
+            new_object.kalman = Some(filter);
+        }
+    }
+
+    /// Carry the identity of an object over to the object it was matched with in a new frame.
+    fn correlate_objects(&self, old_object: &Object, new_object: &mut Object) {
+        new_object.id = old_object.id;
+        let new_center = new_object.center();
+        let center_difference = old_object.moved_from.squared_distance(new_center);
+        let overlap_coefficient = old_object.overlap_coefficient(new_object);
+        // If the object hasn't moved, keep the old update time and person marking
+        trace!(%center_difference, %overlap_coefficient);
+        if center_difference < self.settings.center_closeness()
+            && overlap_coefficient >= self.settings.overlap_threshold()
+        {
+            new_object.last_movement = old_object.last_movement;
+            new_object.moved_from = old_object.moved_from;
+            new_object.is_person = old_object.is_person;
+            debug!("Ignoring movement for object");
+        } else {
+            // Conversely, if an object has moved, make sure it's marked as a person
+            new_object.is_person = true;
+            debug!("Marking object as person");
+        }
+    }
+
     pub(crate) fn count_stream(&self) -> impl Stream<Item = usize> {
         WatchStream::new(self.count_receiver.clone())
     }
+
+    pub(crate) fn objects_stream(&self) -> impl Stream<Item = Vec<TrackedObject>> {
+        WatchStream::new(self.objects_receiver.clone())
+    }
 }
 
 impl Sink<Measurement> for Tracker {
@@ -201,7 +536,22 @@ impl Sink<Measurement> for Tracker {
     }
 
     fn start_send(mut self: Pin<&mut Self>, measurement: Measurement) -> Result<(), Self::Error> {
+        if self.skip_frames > 0 {
+            self.skip_frames -= 1;
+            trace!(remaining = %self.skip_frames, "Skipping frame to stay within frame budget");
+            return Ok(());
+        }
+        let start = Instant::now();
         self.update(&measurement.image);
+        let elapsed = start.elapsed();
+        self.skip_frames = self.frames_over_budget(elapsed);
+        if self.skip_frames > 0 {
+            debug!(
+                ?elapsed,
+                skip_frames = %self.skip_frames,
+                "Frame processing exceeded budget"
+            );
+        }
         Ok(())
     }
 
@@ -216,35 +566,48 @@ impl Sink<Measurement> for Tracker {
 
 #[derive(Clone, Debug)]
 struct Object {
+    id: u64,
     point_temperatures: Vec<PointTemperature>,
     hu_moments: [f32; 7],
     last_movement: Instant,
+    /// The center of this object when it last moved.
+    ///
+    /// Movement is measured from here instead of from the previous frame, so that an object moving
+    /// slowly enough to never cross the closeness threshold between two frames is still noticed.
+    moved_from: Point<f32>,
     is_person: bool,
+    kalman: Option<KalmanFilter>,
 }
 
 impl Object {
-    fn new<I>(point_temperatures: I, when: Instant) -> Self
+    fn new<I>(point_temperatures: I, when: Instant, shape_distance: ShapeDistance) -> Self
     where
         I: IntoIterator<Item = PointTemperature>,
     {
         let point_temperatures: Vec<PointTemperature> = point_temperatures.into_iter().collect();
-        let hu_moments = hu_moments(&point_temperatures);
+        let hu_moments = shape_distance.transform(hu_moments(&point_temperatures));
         assert!(
             !point_temperatures.is_empty(),
             "An object must have at least one point"
         );
-        Self {
+        let mut object = Self {
+            id: 0,
             point_temperatures,
             hu_moments,
             last_movement: when,
+            moved_from: Point::new(0.0, 0.0),
             is_person: false,
-        }
+            kalman: None,
+        };
+        object.moved_from = object.center();
+        object
     }
 
     fn summary(&self) -> String {
         let center = self.center();
         format!(
-            "Point(center: ({:5.2}, {:5.2}), human: {:3}, last_movement: {:5.1}s ago)",
+            "Point(id: {}, center: ({:5.2}, {:5.2}), human: {:3}, last_movement: {:5.1}s ago)",
+            self.id,
             center.x,
             center.y,
             if self.is_person { "yes" } else { "no" },
@@ -260,28 +623,40 @@ impl Object {
         self.point_temperatures.iter().map(|(p, _)| p)
     }
 
+    /// The smallest and largest corners of the box enclosing every point in this object.
+    fn bounding_box(&self) -> (Point<u32>, Point<u32>) {
+        let mut points = self.points();
+        let first = *points
+            .next()
+            .expect("There must always be at least one point in an object");
+        points.fold((first, first), |(min, max), point| {
+            (
+                Point::new(min.x.min(point.x), min.y.min(point.y)),
+                Point::new(max.x.max(point.x), max.y.max(point.y)),
+            )
+        })
+    }
+
+    /// The center of the bounding box of this object.
+    fn bounding_box_center(&self) -> Point<f32> {
+        let (min, max) = self.bounding_box();
+        Point::new((min.x + max.x) as f32 / 2.0, (min.y + max.y) as f32 / 2.0)
+    }
+
+    /// The centroid of this object, the mean position of all of its points.
+    ///
+    /// Unlike [`bounding_box_center`][Self::bounding_box_center], this isn't thrown off by a few
+    /// stray points at the edges of an object.
     pub(crate) fn center(&self) -> Point<f32> {
-        let mut points = self
-            .points()
-            .map(|point| Point::new(point.x as f32, point.y as f32));
-        // Short circuit the easy cases
-        match self.len() {
-            0 => unreachable!("There must always be at least one point in an object"),
-            1 => points.next().unwrap(),
-            _ => {
-                let mut min_x = f32::MAX;
-                let mut min_y = f32::MAX;
-                let mut max_x = f32::MIN;
-                let mut max_y = f32::MIN;
-                for point in points {
-                    min_y = point.y.min(min_y);
-                    min_x = point.x.min(min_x);
-                    max_y = point.y.max(max_y);
-                    max_x = point.x.max(max_x);
-                }
-                Point::new((max_x - min_x) / 2.0, (max_y - min_y) / 2.0)
-            }
-        }
+        let count = self.len();
+        assert!(
+            count > 0,
+            "There must always be at least one point in an object"
+        );
+        let (sum_x, sum_y) = self.points().fold((0.0, 0.0), |(sum_x, sum_y), point| {
+            (sum_x + point.x as f32, sum_y + point.y as f32)
+        });
+        Point::new(sum_x / count as f32, sum_y / count as f32)
     }
 
     fn sum_temperatures(&self) -> f32 {
@@ -324,6 +699,51 @@ impl Object {
     }
 }
 
+/// A summary of a tracked object, suitable for publishing.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct TrackedObject {
+    /// An ID that stays the same for an object as long as it is tracked across frames.
+    pub(crate) id: u64,
+
+    /// The center of the object (the average position of its pixels), as `[x, y]`.
+    ///
+    /// When [`use_kalman`][TrackerSettings::use_kalman] is enabled, this is the smoothed position
+    /// from the object's Kalman filter instead.
+    pub(crate) center: [f32; 2],
+
+    /// The bounding box of the object, as `[min_x, min_y, max_x, max_y]` (inclusive).
+    pub(crate) bounding_box: [u32; 4],
+
+    /// The mean temperature of the object, in degrees Celsius.
+    pub(crate) temperature: f32,
+
+    /// Whether or not this object is considered a person.
+    pub(crate) person: bool,
+
+    /// How long (in seconds) the object has stayed in the same spot, measured from when it last
+    /// moved.
+    pub(crate) dwell_time: f32,
+}
+
+impl From<&Object> for TrackedObject {
+    fn from(object: &Object) -> Self {
+        let (min, max) = object.bounding_box();
+        // Use the smoothed position if the object is being followed with a Kalman filter.
+        let center = object
+            .kalman
+            .as_ref()
+            .map_or_else(|| object.center(), KalmanFilter::position);
+        Self {
+            id: object.id,
+            center: [center.x, center.y],
+            bounding_box: [min.x, min.y, max.x, max.y],
+            temperature: object.temperature_mean(),
+            person: object.is_person,
+            dwell_time: object.last_movement.elapsed().as_secs_f32(),
+        }
+    }
+}
+
 impl RTreeObject for Object {
     type Envelope = rstar::AABB<[f32; 7]>;
 
@@ -357,14 +777,26 @@ impl PointDistance for Object {
 #[cfg(test)]
 mod test {
     use std::io::Cursor;
-    use std::time::Instant;
+    use std::num::NonZeroU32;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use tokio::time::Instant;
 
     use float_cmp::assert_approx_eq;
 
+    use crate::image_buffer::ThermalImage;
+    use crate::occupancy::gmm::GmmParameters;
+    use crate::occupancy::heatmap::{Heatmap, HeatmapSettings};
+    use crate::occupancy::learning_rate::LearningRate;
+    use crate::occupancy::settings::{
+        RegionOfInterest, ShapeDistance, TemperatureRange, Threshold, TrackerMode,
+    };
     use crate::occupancy::TrackerSettings;
     use crate::recorded_data::RecordedData;
+    use crate::temperature::Temperature;
 
-    use super::{Object, Point, PointTemperature, Tracker};
+    use super::{Object, Point, PointTemperature, TrackedObject, Tracker};
 
     const EMPTY_ROOM_DATA: &[u8] = include_bytes!("empty-room.bin");
     const WALK_IN_DATA: &[u8] = include_bytes!("walk-in.bin");
@@ -374,7 +806,7 @@ mod test {
     #[test]
     fn single_object_stats() {
         let points: [PointTemperature; 1] = [(Point::new(3, 9), 37.0)];
-        let object = Object::new(points, Instant::now());
+        let object = Object::new(points, Instant::now(), ShapeDistance::default());
         assert_eq!(
             object.center(),
             Point::new(3.0, 9.0),
@@ -395,9 +827,9 @@ mod test {
     #[test]
     fn multi_point_object_stats() {
         let points: [PointTemperature; 6] = [
-            // A rectangle, but with extra points that're within the box to ensure it's not just
-            // averaging all points. A rectangle is used to ensure both dimensions are being
-            // looked at separately.
+            // A rectangle, but with extra points that're within the box to ensure the center is the
+            // average of all points, not the center of the bounding box. A rectangle is used to
+            // ensure both dimensions are being looked at separately.
             (Point::new(0, 0), 37.26),
             (Point::new(0, 10), 36.71),
             (Point::new(1, 1), 36.98),
@@ -405,21 +837,68 @@ mod test {
             (Point::new(4, 0), 36.88),
             (Point::new(4, 10), 36.71),
         ];
-        let object = Object::new(points, Instant::now());
+        let object = Object::new(points, Instant::now(), ShapeDistance::default());
         // Manually calculated (well, in Excel)
         const MEAN: f32 = 36.98;
         const VARIANCE: f32 = 0.0606;
-        assert_eq!(
-            object.center(),
-            Point::new(2.0, 5.0),
-            "Incorrect center for a rectangle with bounding box ((0, 0), (4, 10)"
-        );
+        let center = object.center();
+        assert_approx_eq!(f32, center.x, 12.0 / 6.0);
+        assert_approx_eq!(f32, center.y, 23.0 / 6.0);
+        assert_eq!(object.bounding_box(), (Point::new(0, 0), Point::new(4, 10)));
+        assert_eq!(object.bounding_box_center(), Point::new(2.0, 5.0));
         let mean = object.temperature_mean();
         assert_approx_eq!(f32, mean, MEAN, epsilon = 0.01);
         let variance = object.temperature_variance();
         assert_approx_eq!(f32, variance, VARIANCE, epsilon = 0.0001);
     }
 
+    #[test]
+    fn offset_object_center() {
+        // An object away from the origin, so the center isn't just half of the object's size.
+        let points: [PointTemperature; 4] = [
+            (Point::new(5, 6), 37.0),
+            (Point::new(6, 6), 37.0),
+            (Point::new(5, 7), 37.0),
+            (Point::new(6, 7), 37.0),
+        ];
+        let object = Object::new(points, Instant::now(), ShapeDistance::default());
+        assert_eq!(object.center(), Point::new(5.5, 6.5));
+        assert_eq!(object.bounding_box_center(), Point::new(5.5, 6.5));
+    }
+
+    /// An object creeping along by less than the closeness threshold each frame is still noticed
+    /// once it has moved far enough from where it last moved.
+    #[test]
+    fn slow_drift() {
+        let tracker = Tracker::new(&TrackerSettings::default());
+        // A 4x4 square, with one pixel at a time moving from the left column to the right column,
+        // moving the center a quarter pixel to the right each frame.
+        let square = |moved: u32| -> Vec<PointTemperature> {
+            (0..4)
+                .flat_map(|y| (0..4).map(move |x| (x, y)))
+                .map(|(x, y)| if y < moved && x == 0 { (4, y) } else { (x, y) })
+                .map(|(x, y)| (Point::new(x, y), 37.0))
+                .collect()
+        };
+        let start = Instant::now();
+        let mut previous = Object::new(square(0), start, ShapeDistance::default());
+        let mut reset_at = None;
+        for moved in 1..=4 {
+            let when = start + Duration::from_secs(moved as u64);
+            let mut object = Object::new(square(moved), when, ShapeDistance::default());
+            tracker.correlate_objects(&previous, &mut object);
+            if object.last_movement != start && reset_at.is_none() {
+                reset_at = Some(moved);
+                assert_eq!(object.moved_from, object.center());
+            }
+            previous = object;
+        }
+        // Each step is (0.25 pixels)², well under the default closeness of 1, but after four
+        // steps the center is a whole pixel away from where it started.
+        assert_eq!(reset_at, Some(4));
+        assert!(previous.is_person);
+    }
+
     struct OccupancyCount {
         count: usize,
         start_frame: Option<usize>,
@@ -608,6 +1087,389 @@ mod test {
         !failed
     }
 
+    /// Create a room temperature image with a 2x2 body temperature blob at the given column.
+    fn synthetic_frame(blob_column: Option<u32>) -> ThermalImage {
+        let mut image = ThermalImage::from_pixel(8, 8, [20.0].into());
+        if let Some(column) = blob_column {
+            for x in column..(column + 2) {
+                for y in 3..5 {
+                    image[(x, y)] = [37.0].into();
+                }
+            }
+        }
+        image
+    }
+
+    #[test]
+    fn warmup_suppresses_count() {
+        const WARMUP_FRAMES: usize = 50;
+        let settings = TrackerSettings {
+            warmup_frames: WARMUP_FRAMES,
+            ..TrackerSettings::default()
+        };
+        let mut tracker = Tracker::new(&settings);
+        // A person walking back and forth the entire time.
+        let frames = (0..WARMUP_FRAMES).map(|n| synthetic_frame(Some((n % 6) as u32)));
+        for (frame_number, frame) in frames.enumerate() {
+            assert!(tracker.is_warming_up());
+            tracker.update(&frame);
+            assert_eq!(
+                *tracker.count_receiver.borrow(),
+                0,
+                "Occupancy published during warm-up (frame #{})",
+                frame_number
+            );
+        }
+        assert!(!tracker.is_warming_up());
+    }
+
+    #[test]
+    fn empty_scene_stabilizes() {
+        let settings = TrackerSettings {
+            warmup_frames: 10,
+            background_model_parameters: GmmParameters {
+                learning_rate: LearningRate::new(0.05),
+                ..GmmParameters::default()
+            },
+            ..TrackerSettings::default()
+        };
+        let mut tracker = Tracker::new(&settings);
+        assert!(!tracker.is_stable());
+        // Past the warm-up period, but the learning rate is still decaying.
+        for _ in 0..10 {
+            tracker.update(&synthetic_frame(None));
+        }
+        assert!(!tracker.is_warming_up());
+        assert!(!tracker.is_stable());
+        for _ in 0..20 {
+            tracker.update(&synthetic_frame(None));
+        }
+        assert!(tracker.is_stable());
+    }
+
+    #[test]
+    fn threshold_mode() {
+        let settings = TrackerSettings {
+            mode: TrackerMode::Threshold,
+            threshold: Threshold::Static(Temperature::Celsius(30.0)),
+            ..TrackerSettings::default()
+        };
+        let mut tracker = Tracker::new(&settings);
+        // No training is needed for threshold mode.
+        tracker.update(&synthetic_frame(None));
+        assert_eq!(tracker.count(), 0);
+        for column in 0..4 {
+            tracker.update(&synthetic_frame(Some(column)));
+        }
+        assert_eq!(tracker.count(), 1);
+        assert!(
+            tracker.background.read().unwrap().is_none(),
+            "Background model created in threshold mode"
+        );
+        tracker.update(&synthetic_frame(None));
+        assert_eq!(tracker.count(), 0);
+    }
+
+    #[test]
+    fn reset_background() {
+        let mut tracker = Tracker::new(&TrackerSettings::default());
+        tracker.update(&synthetic_frame(None));
+        assert!(tracker.background.read().unwrap().is_some());
+        // Clones share the background model, like the copies used by the pipeline.
+        tracker.clone().reset_background();
+        assert!(tracker.background.read().unwrap().is_none());
+        // And it's rebuilt with the next frame
+        tracker.update(&synthetic_frame(None));
+        assert!(tracker.background.read().unwrap().is_some());
+    }
+
+    #[cfg(feature = "mock_camera")]
+    #[test]
+    fn save_and_load_background() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("background-model.json");
+        let tracker = Tracker::new(&TrackerSettings::default());
+        assert!(tracker.save_background(&path).is_err());
+        let mut tracker = tracker;
+        for _ in 0..100 {
+            tracker.update(&synthetic_frame(None));
+        }
+        tracker.save_background(&path).unwrap();
+        let mut loaded = Tracker::new(&TrackerSettings::default());
+        loaded.load_background(&path).unwrap();
+        assert!(loaded
+            .background
+            .read()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|background| background.is_trained()));
+        // A model for a different size of image is replaced.
+        loaded.update(&ThermalImage::from_pixel(2, 2, [20.0].into()));
+        assert_eq!(
+            loaded.background.read().unwrap().as_ref().map(|b| b.len()),
+            Some(4)
+        );
+    }
+
+    #[test]
+    fn person_temperature_range() {
+        let settings = TrackerSettings {
+            mode: TrackerMode::Threshold,
+            threshold: Threshold::Static(Temperature::Celsius(30.0)),
+            person_temperature_range: Some(TemperatureRange {
+                min: Temperature::Celsius(28.0),
+                max: Temperature::Celsius(35.0),
+            }),
+            ..TrackerSettings::default()
+        };
+        let mut tracker = Tracker::new(&settings);
+        // The synthetic blob is 37°C, too warm to be a person here.
+        for column in 0..4 {
+            tracker.update(&synthetic_frame(Some(column)));
+        }
+        assert_eq!(tracker.count(), 0);
+        let objects = tracker.objects();
+        assert_eq!(objects.len(), 1, "The object should still be tracked");
+        assert!(!objects[0].person);
+    }
+
+    #[test]
+    fn tracked_object_summary() {
+        let points: [PointTemperature; 3] = [
+            (Point::new(3, 2), 37.0),
+            (Point::new(5, 2), 37.0),
+            (Point::new(5, 6), 37.0),
+        ];
+        let object = Object::new(points, Instant::now(), ShapeDistance::default());
+        assert_eq!(object.bounding_box(), (Point::new(3, 2), Point::new(5, 6)));
+        let summary = TrackedObject::from(&object);
+        assert_eq!(summary.center, [13.0 / 3.0, 10.0 / 3.0]);
+        assert_eq!(summary.bounding_box, [3, 2, 5, 6]);
+    }
+
+    #[test]
+    fn tracked_object_centroid() {
+        // An L-shaped object, with most of the points along the bottom.
+        // X . .
+        // X . .
+        // X X X
+        let points: [PointTemperature; 5] = [
+            (Point::new(0, 0), 37.0),
+            (Point::new(0, 1), 37.0),
+            (Point::new(0, 2), 37.0),
+            (Point::new(1, 2), 37.0),
+            (Point::new(2, 2), 37.0),
+        ];
+        let object = Object::new(points, Instant::now(), ShapeDistance::default());
+        assert_eq!(object.bounding_box_center(), Point::new(1.0, 1.0));
+        // The published center is the centroid, not the center of the bounding box.
+        let summary = TrackedObject::from(&object);
+        assert_eq!(summary.center, [0.6, 1.4]);
+    }
+
+    #[test]
+    fn persistent_object_ids() {
+        let settings = TrackerSettings {
+            mode: TrackerMode::Threshold,
+            threshold: Threshold::Static(Temperature::Celsius(30.0)),
+            ..TrackerSettings::default()
+        };
+        let mut tracker = Tracker::new(&settings);
+        tracker.update(&synthetic_frame(Some(0)));
+        let first = tracker.objects();
+        assert_eq!(first.len(), 1);
+        for column in 1..4 {
+            tracker.update(&synthetic_frame(Some(column)));
+            let objects = tracker.objects_receiver.borrow().clone();
+            assert_eq!(objects.len(), 1);
+            assert_eq!(objects[0].id, first[0].id, "Object ID changed while moving");
+            assert_eq!(objects[0].bounding_box, [column, 3, column + 1, 4]);
+            assert_eq!(objects[0].center, [column as f32 + 0.5, 3.5]);
+            assert_approx_eq!(f32, objects[0].temperature, 37.0);
+            assert!(objects[0].person);
+        }
+        // An object appearing after the previous one left gets a new ID.
+        tracker.update(&synthetic_frame(None));
+        assert!(tracker.objects().is_empty());
+        tracker.update(&synthetic_frame(Some(4)));
+        let objects = tracker.objects();
+        assert_eq!(objects.len(), 1);
+        assert_ne!(objects[0].id, first[0].id);
+    }
+
+    #[test]
+    fn region_of_interest() {
+        let settings = TrackerSettings {
+            mode: TrackerMode::Threshold,
+            threshold: Threshold::Static(Temperature::Celsius(30.0)),
+            region_of_interest: Some(RegionOfInterest {
+                x: 4,
+                y: 0,
+                width: NonZeroU32::new(4).unwrap(),
+                height: NonZeroU32::new(8).unwrap(),
+            }),
+            ..TrackerSettings::default()
+        };
+        let mut tracker = Tracker::new(&settings);
+        // Someone moving around outside of the region is never seen.
+        for column in 0..3 {
+            tracker.update(&synthetic_frame(Some(column)));
+            assert!(tracker.objects().is_empty());
+        }
+        // Someone straddling the edge is cut down to the part within the region.
+        tracker.update(&synthetic_frame(Some(3)));
+        let objects = tracker.objects();
+        assert_eq!(objects.len(), 1);
+        assert_eq!(objects[0].bounding_box, [4, 3, 4, 4]);
+        for column in 4..7 {
+            tracker.update(&synthetic_frame(Some(column)));
+        }
+        assert_eq!(tracker.count(), 1);
+    }
+
+    #[test]
+    fn heatmap() {
+        let settings = TrackerSettings {
+            mode: TrackerMode::Threshold,
+            threshold: Threshold::Static(Temperature::Celsius(30.0)),
+            warmup_frames: 2,
+            ..TrackerSettings::default()
+        };
+        let heatmap = Heatmap::shared(&HeatmapSettings {
+            window: Duration::ZERO,
+            ..HeatmapSettings::default()
+        });
+        let mut tracker = Tracker::new(&settings);
+        tracker.set_heatmap(Arc::clone(&heatmap));
+        // Nothing is added while warming up.
+        for _ in 0..2 {
+            tracker.update(&synthetic_frame(Some(0)));
+        }
+        assert!(heatmap.lock().unwrap().image().is_none());
+        // With no window, the heatmap is just the most recent foreground.
+        tracker.update(&synthetic_frame(Some(0)));
+        tracker.update(&synthetic_frame(Some(2)));
+        let image = heatmap.lock().unwrap().image().unwrap();
+        for (x, y, value) in image.enumerate_pixels() {
+            let expected = if (2..4).contains(&x) && (3..5).contains(&y) {
+                1.0
+            } else {
+                0.0
+            };
+            assert_eq!(value[0], expected, "pixel ({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn presence_decay() {
+        let settings = TrackerSettings {
+            mode: TrackerMode::Threshold,
+            threshold: Threshold::Static(Temperature::Celsius(30.0)),
+            presence_decay: Duration::from_secs(60 * 60),
+            ..TrackerSettings::default()
+        };
+        let mut tracker = Tracker::new(&settings);
+        for column in 0..4 {
+            tracker.update(&synthetic_frame(Some(column)));
+        }
+        let before = tracker.objects();
+        assert_eq!(tracker.count(), 1);
+        // The person disappears for a few frames, but is still counted.
+        for _ in 0..3 {
+            tracker.update(&synthetic_frame(None));
+            assert_eq!(tracker.count(), 1);
+            assert_eq!(*tracker.count_receiver.borrow(), 1);
+            let objects = tracker.objects();
+            assert_eq!(objects.len(), 1);
+            assert_eq!(objects[0].id, before[0].id);
+            assert_eq!(objects[0].bounding_box, before[0].bounding_box);
+        }
+        assert!(!tracker.is_stable());
+        // And is matched back up when they reappear.
+        tracker.update(&synthetic_frame(Some(4)));
+        assert_eq!(tracker.count(), 1);
+        let after = tracker.objects();
+        assert_eq!(after.len(), 1);
+        assert_eq!(after[0].id, before[0].id);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn presence_decay_expires() {
+        let settings = TrackerSettings {
+            mode: TrackerMode::Threshold,
+            threshold: Threshold::Static(Temperature::Celsius(30.0)),
+            presence_decay: Duration::from_millis(20),
+            ..TrackerSettings::default()
+        };
+        let mut tracker = Tracker::new(&settings);
+        for column in 0..4 {
+            tracker.update(&synthetic_frame(Some(column)));
+        }
+        tracker.update(&synthetic_frame(None));
+        assert_eq!(tracker.count(), 1);
+        tokio::time::advance(Duration::from_millis(30)).await;
+        tracker.update(&synthetic_frame(None));
+        assert_eq!(tracker.count(), 0);
+        assert!(tracker.objects().is_empty());
+    }
+
+    #[test]
+    fn kalman_tracking() {
+        let settings = TrackerSettings {
+            mode: TrackerMode::Threshold,
+            threshold: Threshold::Static(Temperature::Celsius(30.0)),
+            use_kalman: true,
+            ..TrackerSettings::default()
+        };
+        let mut tracker = Tracker::new(&settings);
+        // A blob that changes shape as it moves to the right, one pixel per frame.
+        let shapes: [&[(u32, u32)]; 3] = [
+            &[(0, 0), (1, 0), (0, 1), (1, 1)],
+            &[(0, 0), (1, 0), (2, 0)],
+            &[(1, 0), (1, 1), (1, 2)],
+        ];
+        let mut first_id = None;
+        for column in 0..9 {
+            let mut image = ThermalImage::from_pixel(16, 8, [20.0].into());
+            for (x, y) in shapes[column as usize % shapes.len()] {
+                image[(column + x, 3 + y)] = [37.0].into();
+            }
+            tracker.update(&image);
+            let objects = tracker.objects();
+            assert_eq!(objects.len(), 1);
+            let id = *first_id.get_or_insert(objects[0].id);
+            assert_eq!(objects[0].id, id, "Object ID changed while moving");
+        }
+        // An object on the other side of the image is a different object.
+        let mut image = ThermalImage::from_pixel(16, 8, [20.0].into());
+        image[(0, 0)] = [37.0].into();
+        tracker.update(&image);
+        let objects = tracker.objects();
+        assert_eq!(objects.len(), 1);
+        assert_ne!(Some(objects[0].id), first_id);
+    }
+
+    #[test]
+    fn frame_budget() {
+        let settings = TrackerSettings {
+            frame_budget: Some(0.5),
+            ..TrackerSettings::default()
+        };
+        let mut tracker = Tracker::new(&settings);
+        // Without a frame rate, frames are never skipped
+        assert_eq!(tracker.frames_over_budget(Duration::from_secs(10)), 0);
+        // 10 FPS with a 50% budget is 50ms
+        tracker.set_frame_rate(10.0);
+        assert_eq!(tracker.frames_over_budget(Duration::from_millis(40)), 0);
+        assert_eq!(tracker.frames_over_budget(Duration::from_millis(50)), 0);
+        assert_eq!(tracker.frames_over_budget(Duration::from_millis(60)), 1);
+        assert_eq!(tracker.frames_over_budget(Duration::from_millis(120)), 2);
+        // No budget means no skipping
+        let mut tracker = Tracker::new(&TrackerSettings::default());
+        tracker.set_frame_rate(10.0);
+        assert_eq!(tracker.frames_over_budget(Duration::from_secs(10)), 0);
+    }
+
     #[test]
     fn empty_room() {
         let recorded_data = RecordedData::from_bincode(Cursor::new(EMPTY_ROOM_DATA))
@@ -0,0 +1,128 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+use std::future;
+
+use futures::{stream, Stream, StreamExt};
+use serde::Serialize;
+
+/// Someone entering or leaving, serialized like `{"type": "enter"}`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub(crate) enum Transition {
+    Enter,
+    Exit,
+}
+
+/// A change in the occupancy count.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct CountChange {
+    /// The count before the change.
+    pub(crate) previous: usize,
+
+    /// The count after the change.
+    pub(crate) count: usize,
+}
+
+impl CountChange {
+    /// The difference from the previous count.
+    pub(crate) fn delta(&self) -> i64 {
+        self.count as i64 - self.previous as i64
+    }
+
+    /// The people entering or leaving for this change, one [`Transition`] for each person.
+    pub(crate) fn transitions(&self) -> Vec<Transition> {
+        let transition = if self.count > self.previous {
+            Transition::Enter
+        } else {
+            Transition::Exit
+        };
+        vec![transition; self.count.abs_diff(self.previous)]
+    }
+}
+
+/// Convert a stream of occupancy counts to a stream of the changes in that count.
+///
+/// Repeated counts are skipped, and the first count is only used as the starting point.
+pub(crate) fn count_changes<S>(counts: S) -> impl Stream<Item = CountChange>
+where
+    S: Stream<Item = usize>,
+{
+    counts
+        .scan(None, |previous: &mut Option<usize>, count| {
+            let change = previous
+                .replace(count)
+                .filter(|previous| *previous != count)
+                .map(|previous| CountChange { previous, count });
+            future::ready(Some(change))
+        })
+        .filter_map(future::ready)
+}
+
+/// Convert a stream of occupancy counts to a stream of people entering and leaving.
+///
+/// There is one transition for each person, so a count going from 0 to 2 is two
+/// [`Transition::Enter`]s. The first count is only used as the starting point.
+pub(crate) fn transitions<S>(counts: S) -> impl Stream<Item = Transition>
+where
+    S: Stream<Item = usize>,
+{
+    count_changes(counts).flat_map(|change| stream::iter(change.transitions()))
+}
+
+#[cfg(test)]
+mod test {
+    use futures::{stream, StreamExt};
+
+    use super::{count_changes, transitions, CountChange, Transition};
+
+    #[tokio::test]
+    async fn changes() {
+        let counts = stream::iter([0, 0, 1, 3, 3, 0]);
+        let collected: Vec<CountChange> = count_changes(counts).collect().await;
+        assert_eq!(
+            collected,
+            [
+                CountChange {
+                    previous: 0,
+                    count: 1
+                },
+                CountChange {
+                    previous: 1,
+                    count: 3
+                },
+                CountChange {
+                    previous: 3,
+                    count: 0
+                },
+            ]
+        );
+        assert_eq!(collected[2].delta(), -3);
+    }
+
+    #[tokio::test]
+    async fn from_counts() {
+        let counts = stream::iter([1, 1, 3, 2, 2, 0]);
+        let collected: Vec<Transition> = transitions(counts).collect().await;
+        assert_eq!(
+            collected,
+            [
+                Transition::Enter,
+                Transition::Enter,
+                Transition::Exit,
+                Transition::Exit,
+                Transition::Exit,
+            ]
+        );
+    }
+
+    #[test]
+    fn json() {
+        assert_eq!(
+            serde_json::to_string(&Transition::Enter).unwrap(),
+            r#"{"type":"enter"}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&Transition::Exit).unwrap(),
+            r#"{"type":"exit"}"#
+        );
+    }
+}
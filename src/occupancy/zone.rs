@@ -0,0 +1,218 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+use std::collections::HashSet;
+use std::num::NonZeroU32;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use super::TrackedObject;
+
+/// A named rectangular area of the camera's view, with its own occupancy count.
+#[derive(Clone, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
+pub(crate) struct Zone {
+    /// The name of the zone, used in the MQTT topic and entity names.
+    ///
+    /// Only ASCII letters, numbers, `-` and `_` are allowed.
+    #[serde(deserialize_with = "sensor_name")]
+    pub(crate) name: String,
+
+    /// The column of the left edge of the zone, in sensor pixels.
+    pub(crate) x: u32,
+
+    /// The row of the top edge of the zone, in sensor pixels.
+    pub(crate) y: u32,
+
+    /// The width of the zone, in sensor pixels.
+    pub(crate) width: NonZeroU32,
+
+    /// The height of the zone, in sensor pixels.
+    pub(crate) height: NonZeroU32,
+}
+
+impl Zone {
+    /// Whether a point (in sensor coordinates) is within this zone.
+    ///
+    /// The top and left edges are part of the zone, while the bottom and right edges are not, so
+    /// that a point on the boundary between two adjacent zones is only in one of them.
+    pub(crate) fn contains(&self, [x, y]: [f32; 2]) -> bool {
+        let left = self.x as f32;
+        let top = self.y as f32;
+        let right = left + self.width.get() as f32;
+        let bottom = top + self.height.get() as f32;
+        (left..right).contains(&x) && (top..bottom).contains(&y)
+    }
+
+    /// The number of people in this zone.
+    ///
+    /// People are assigned to a zone by their center, so someone straddling the edge of a zone is
+    /// only counted in one zone.
+    pub(crate) fn count(&self, objects: &[TrackedObject]) -> usize {
+        objects
+            .iter()
+            .filter(|object| object.person && self.contains(object.center))
+            .count()
+    }
+}
+
+/// Deserialize a name that is used as part of an MQTT topic and entity name.
+pub(super) fn sensor_name<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let name = String::deserialize(deserializer)?;
+    let valid_char = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+    if !name.is_empty() && name.chars().all(valid_char) {
+        Ok(name)
+    } else {
+        Err(serde::de::Error::custom(format!(
+            "invalid name '{}' (only ASCII letters, numbers, '-' and '_' are allowed)",
+            name
+        )))
+    }
+}
+
+/// Return an error naming the first name given more than once.
+pub(super) fn check_unique_names<'a, I>(kind: &str, names: I) -> Result<(), String>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut seen = HashSet::new();
+    match names.into_iter().find(|name| !seen.insert(*name)) {
+        Some(duplicate) => Err(format!(
+            "the {} name '{}' is used more than once",
+            kind, duplicate
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Deserialize a list of zones, rejecting names that are used more than once.
+///
+/// Each zone's name is used for its MQTT topic and entity, so duplicate names would publish over
+/// each other.
+pub(crate) fn unique_zones<'de, D>(deserializer: D) -> Result<Vec<Zone>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let zones = Vec::<Zone>::deserialize(deserializer)?;
+    check_unique_names("zone", zones.iter().map(|zone| zone.name.as_str()))
+        .map_err(serde::de::Error::custom)?;
+    Ok(zones)
+}
+
+#[cfg(test)]
+mod test {
+    use std::num::NonZeroU32;
+
+    use serde::Deserialize;
+
+    use crate::occupancy::TrackedObject;
+
+    use super::Zone;
+
+    #[derive(Debug, Deserialize)]
+    struct Zones {
+        #[serde(deserialize_with = "super::unique_zones")]
+        zones: Vec<Zone>,
+    }
+
+    fn object(center: [f32; 2], person: bool) -> TrackedObject {
+        TrackedObject {
+            id: 0,
+            center,
+            bounding_box: [0, 0, 0, 0],
+            temperature: 37.0,
+            person,
+            dwell_time: 0.0,
+        }
+    }
+
+    #[test]
+    fn parse() -> anyhow::Result<()> {
+        let source = r#"
+        [[zones]]
+        name = "desk"
+        x = 0
+        y = 0
+        width = 4
+        height = 8
+        "#;
+        let parsed: Zones = toml::from_str(source)?;
+        assert_eq!(
+            parsed.zones,
+            vec![Zone {
+                name: "desk".to_string(),
+                x: 0,
+                y: 0,
+                width: NonZeroU32::new(4).unwrap(),
+                height: NonZeroU32::new(8).unwrap(),
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn invalid() {
+        let bad_name = r#"
+        [[zones]]
+        name = "front door"
+        x = 0
+        y = 0
+        width = 4
+        height = 8
+        "#;
+        assert!(toml::from_str::<Zones>(bad_name).is_err());
+        let duplicate = r#"
+        [[zones]]
+        name = "desk"
+        x = 0
+        y = 0
+        width = 4
+        height = 8
+        [[zones]]
+        name = "desk"
+        x = 4
+        y = 0
+        width = 4
+        height = 8
+        "#;
+        assert!(toml::from_str::<Zones>(duplicate).is_err());
+        let empty = r#"
+        [[zones]]
+        name = "desk"
+        x = 0
+        y = 0
+        width = 0
+        height = 8
+        "#;
+        assert!(toml::from_str::<Zones>(empty).is_err());
+    }
+
+    #[test]
+    fn counts() {
+        let left = Zone {
+            name: "left".to_string(),
+            x: 0,
+            y: 0,
+            width: NonZeroU32::new(4).unwrap(),
+            height: NonZeroU32::new(8).unwrap(),
+        };
+        let right = Zone {
+            name: "right".to_string(),
+            x: 4,
+            ..left.clone()
+        };
+        let objects = [
+            object([1.5, 3.5], true),
+            // Spanning both zones, but centered in the right one
+            object([4.0, 3.5], true),
+            object([6.5, 7.0], true),
+            // Not a person
+            object([2.0, 2.0], false),
+            // Outside of both zones
+            object([4.0, 8.5], true),
+        ];
+        assert_eq!(left.count(&objects), 1);
+        assert_eq!(right.count(&objects), 2);
+    }
+}
@@ -1,48 +1,81 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 use anyhow::{anyhow, Context as _};
+use bytes::Bytes;
 use futures::future::{Future, FutureExt, TryFutureExt};
 use futures::ready;
 use futures::stream::{BoxStream, FuturesUnordered, Stream, StreamExt};
 use http::Response;
 use pin_project::pin_project;
 use rumqttc::QoS;
-use tokio::sync::{oneshot, Mutex as AsyncMutex};
+#[cfg(feature = "mock_camera")]
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::{oneshot, watch, Mutex as AsyncMutex, Notify};
 use tokio::task::spawn_blocking;
 use tokio::time::Duration;
-use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+use tokio_stream::wrappers::{
+    errors::BroadcastStreamRecvError, BroadcastStream, IntervalStream, WatchStream,
+};
 use tracing::{debug, info, info_span, trace, trace_span, warn};
 use tracing_futures::Instrument;
 use warp::Filter;
 
 use std::convert::{TryFrom, TryInto};
+use std::num::NonZeroU16;
 use std::path::PathBuf;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{mpsc, Arc};
 use std::task::{Context, Poll};
 
-use crate::camera::{Camera, CameraCommand, Measurement};
-use crate::image_buffer::BytesImage;
+use crate::camera::{Camera, CameraCommand, CameraSettings, Measurement, RawFrame, SceneStatistic};
+use crate::image_buffer::{BytesImage, ThermalImage};
 use crate::mqtt::{
-    home_assistant as hass, MqttClient, MqttSender, MqttSettings, Occupancy, OccupancyCount, State,
+    home_assistant as hass, CameraImage, Capacity, CrossingCount, Loitering, MqttClient,
+    MqttSender, MqttSettings, Occupancy, OccupancyCount, OccupancyDuration, PersonEntered,
+    PersonExited, State, Status, TrackedObjects,
+};
+use crate::occupancy::{
+    log_occupancy_events, loitering, occupancy_durations, over_capacity, transitions, CountingLine,
+    Heatmap, SharedHeatmap, TrackedObject, Tracker, TrackerSettings, Transition, Zone,
 };
-use crate::occupancy::{Tracker, TrackerSettings};
+use crate::pubsub::TreeCount;
 use crate::settings::Settings;
-use crate::util::{flatten_join_result, StreamExt as _};
+use crate::temperature::{Temperature, TemperatureUnit};
+use crate::upload::UploadSettings;
+use crate::util::{
+    flatten_join_result, ExponentialMovingAverage, Filter as _, MovingAverage, StreamExt as _,
+};
 use crate::{render, spmc, stream};
 
 type ArcDevice = Arc<hass::Device>;
 type InnerTask = Pin<Box<dyn Future<Output = anyhow::Result<()>>>>;
 type TaskList = FuturesUnordered<InnerTask>;
 type MeasurementStream<'a> = BoxStream<'a, Measurement>;
+type SharedRenderer = Arc<AsyncMutex<render::layer::ImageLayers>>;
+type HttpRoute = warp::filters::BoxedFilter<(Result<Response<hyper::Body>, http::Error>,)>;
+
+/// How long to wait for the camera and MQTT client to stop when shutting down.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[pin_project]
 pub(crate) struct Pipeline {
     camera_command_channel: mpsc::Sender<CameraCommand>,
+    /// The number of camera images skipped for having invalid temperatures.
+    dropped_frames: Arc<AtomicUsize>,
+    /// Notified when the background model should be reset.
+    background_reset: Arc<Notify>,
+    /// The occupancy heatmap, if enabled. The tracker adds to it, and the HTTP server serves it.
+    heatmap: Option<SharedHeatmap>,
     rendered_source: spmc::Sender<BytesImage>,
+    renderer: SharedRenderer,
     mqtt_sender: MqttSender,
     mqtt_config: MqttSettings,
     status_topic: String,
     hass_device: ArcDevice,
+    // The camera and MQTT client are kept separate from the other tasks so that they can be
+    // stopped cleanly when shutting down.
+    camera_task: InnerTask,
+    mqtt_task: InnerTask,
     #[pin]
     tasks: TaskList,
 }
@@ -50,10 +83,18 @@ pub(crate) struct Pipeline {
 impl Pipeline {
     pub(crate) async fn new(config: Settings) -> anyhow::Result<Self> {
         let camera_settings = &config.camera;
+        config
+            .tracker
+            .check(camera_settings.rotated_resolution())
+            .context("Invalid tracker settings")?;
         let camera: Camera = camera_settings
             .try_into()
             .context("Error configuring camera")?;
+        let camera_model = camera.kind_name();
+        let camera_resolution = camera.resolution();
         let camera_command_channel = camera.command_channel();
+        let dropped_frames = camera.dropped_frames();
+        let camera_availability = camera.availability();
         let camera_task = spawn_blocking(move || {
             camera
                 .measurement_loop()
@@ -65,30 +106,63 @@ impl Pipeline {
         let measurement_stream = Self::create_measurement_stream(&camera_command_channel)
             .await
             .context("Error requesting measurement stream from camera")?;
-        let (rendered_source, render_task) =
-            create_renderer(measurement_stream, config.render, frame_rate_limit)?;
+        let spatial_filter = config.render.spatial_filter;
+        let smoothing = config.render.smoothing;
+        let measurement_stream = filter_measurements(measurement_stream, spatial_filter, smoothing);
+        // The tracker is created later, so the tracked objects (for the overlay and outlines) are
+        // forwarded to the renderer once it's been set up.
+        let (objects_sender, objects_receiver) = watch::channel(Vec::new());
+        let (rendered_source, renderer, render_task) = create_renderer(
+            measurement_stream,
+            config.render,
+            frame_rate_limit,
+            objects_receiver.clone(),
+        )?;
         let mqtt_client = MqttClient::new(&config.mqtt)?;
         let mqtt_sender = mqtt_client.new_sender();
         let status_topic = mqtt_client.status_topic().to_string();
         let mqtt_client = tokio::spawn(mqtt_client.run_loop())
             .map(flatten_join_result)
             .boxed();
-        // Once IntoIterator is implemented for arrays, this line can be simplified
-        let tasks: TaskList =
-            std::array::IntoIter::new([render_task, camera_task, mqtt_client]).collect();
+        let tasks: TaskList = std::iter::once(render_task).collect();
         debug!("Opening connection to MQTT broker");
         // Create a device for HAss integration. It's still used even if the HAss messages aren;t
         // being sent.
-        let hass_device = Self::create_device(&config.mqtt.name, config.mqtt.unique_id());
+        let unique_id = config.mqtt.unique_id();
+        // Summarize the setup in a single line to make it easier to ask for help. Only the host and
+        // port of the MQTT server are logged, as the URL can have credentials in it.
+        let mqtt_url = config.mqtt.server_url();
+        let streams = &config.streams;
+        info!(
+            camera.kind = config.camera.kind(),
+            camera.model = camera_model,
+            camera.resolution = ?camera_resolution,
+            camera.frame_rate = config.camera.frame_rate(),
+            listen_address = ?streams
+                .http_streams_enabled()
+                .then(|| std::net::SocketAddr::from(streams.clone())),
+            mqtt.host = mqtt_url.host_str().unwrap_or_default(),
+            mqtt.port = ?mqtt_url.port(),
+            %unique_id,
+            "Starting r-u-still-there"
+        );
+        let hass_device = Self::create_device(&config.mqtt.name, unique_id);
         let mut app = Self {
             camera_command_channel,
+            dropped_frames,
+            background_reset: Arc::default(),
+            heatmap: config.tracker.heatmap.as_ref().map(Heatmap::shared),
             rendered_source,
+            renderer,
             mqtt_sender,
             mqtt_config: config.mqtt,
             status_topic,
             hass_device,
+            camera_task,
+            mqtt_task: mqtt_client,
             tasks,
         };
+        app.create_camera_availability(camera_availability);
         app.record_measurements(
             config
                 .camera
@@ -99,17 +173,275 @@ impl Pipeline {
         )
         .await
         .context("Error configuring camera frame recording")?;
-        app.create_streams(config.streams)
+        app.record_ring(&config.camera, objects_receiver.clone())
+            .await
+            .context("Error configuring buffered camera frame recording")?;
+        app.create_uploader(config.upload)
+            .await
+            .context("Error configuring recorded data uploads")?;
+        if let Some(idle_frame_rate) = config.streams.idle_frame_rate() {
+            app.create_idle_throttle(idle_frame_rate, config.camera.frame_rate());
+        }
+        app.create_streams(config.streams, objects_receiver)
             .context("Error creating video streams")?;
-        app.create_tracker(config.tracker)
+        app.create_camera_entity()
             .await
-            .context("Error creating occupancy tracker")?;
+            .context("Error creating Home Assistant camera")?;
+        app.create_raw_frame_publisher()
+            .await
+            .context("Error creating raw frame publisher")?;
+        let tracker_filters = if config.tracker.smoothed_input {
+            (spatial_filter, smoothing)
+        } else {
+            (render::SpatialFilter::None, None)
+        };
+        app.create_tracker(
+            config.tracker,
+            config.zones,
+            config.lines,
+            config.camera.measurement_rate(),
+            tracker_filters,
+            objects_sender,
+        )
+        .await
+        .context("Error creating occupancy tracker")?;
         app.create_thermometer()
             .await
             .context("Error creating ambient temperature monitor")?;
+        app.notify_systemd()
+            .await
+            .context("Error notifying systemd")?;
         Ok(app)
     }
 
+    /// Apply new configurations as they're received from `configs`.
+    ///
+    /// Only the render settings are changed; the camera, tracker and everything else keep
+    /// running as they are, and any changes to their settings are logged and ignored.
+    pub(crate) fn reload_from<S>(&mut self, current: Settings, configs: S)
+    where
+        S: Stream<Item = Settings> + Send + 'static,
+    {
+        let renderer = Arc::clone(&self.renderer);
+        let task = configs
+            .fold(current, move |current, new| {
+                let renderer = Arc::clone(&renderer);
+                async move { reload_render_settings(&renderer, current, new).await }
+            })
+            .map(|_| Ok(()))
+            .instrument(info_span!("reload"))
+            .boxed();
+        self.tasks.push(task);
+    }
+
+    /// Run the pipeline until either it finishes or `shutdown` resolves.
+    ///
+    /// When shutting down, every task besides the camera and MQTT client is dropped. Then the
+    /// offline status is published, the MQTT client disconnects, and the camera thread is stopped.
+    pub(crate) async fn run_until<F>(mut self, shutdown: F) -> anyhow::Result<()>
+    where
+        F: Future<Output = ()>,
+    {
+        tokio::select! {
+            res = &mut self => return res,
+            _ = shutdown => (),
+        }
+        info!("Shutting down");
+        self.tasks = TaskList::new();
+        if self
+            .camera_command_channel
+            .send(CameraCommand::Shutdown)
+            .is_err()
+        {
+            warn!("Camera thread already stopped");
+        }
+        self.mqtt_sender
+            .enqueue_publish(
+                self.status_topic.clone(),
+                QoS::AtLeastOnce,
+                &Status::Offline,
+                true,
+            )
+            .await?;
+        self.mqtt_sender.disconnect().await?;
+        let stopped = futures::future::try_join(self.camera_task, self.mqtt_task);
+        match tokio::time::timeout(SHUTDOWN_TIMEOUT, stopped).await {
+            Ok(res) => res.map(|_| ()),
+            Err(_) => {
+                warn!("Timed out waiting for the camera and MQTT client to stop");
+                Ok(())
+            }
+        }
+    }
+
+    /// Capture a single frame from the camera, then write it to `path`.
+    ///
+    /// The format is chosen by the file extension. "json" writes the raw temperatures in the same
+    /// format as the API, while "jpg", "png" (and "webp", if enabled) write the rendered image.
+    /// Only the camera is started; nothing is published.
+    pub(crate) async fn capture_once(config: Settings, path: PathBuf) -> anyhow::Result<()> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        // Check the format before the camera is started, so that typos fail quickly.
+        let encoder: Option<stream::Encoder> = match extension.as_deref() {
+            Some("json") => None,
+            Some("jpg" | "jpeg") => Some(stream::encode_jpeg),
+            Some("png") => Some(stream::encode_png),
+            #[cfg(feature = "webp")]
+            Some("webp") => Some(stream::encode_webp),
+            _ => return Err(anyhow!("Unsupported output format for {:?}", path)),
+        };
+        let renderer = render::layer::ImageLayers::try_from(config.render.clone())
+            .context("Error configuring renderer")?;
+        let camera: Camera = (&config.camera)
+            .try_into()
+            .context("Error configuring camera")?;
+        let camera_command_channel = camera.command_channel();
+        let camera_task = spawn_blocking(move || {
+            camera
+                .measurement_loop()
+                .context("Error within camera frame thread")
+        })
+        .map(flatten_join_result);
+        tokio::pin!(camera_task);
+        let measurement_stream = Self::create_measurement_stream(&camera_command_channel)
+            .await
+            .context("Error requesting measurement stream from camera")?;
+        let mut measurement_stream =
+            filter_measurements(measurement_stream, config.render.spatial_filter, None);
+        let measurement = tokio::select! {
+            res = &mut camera_task => {
+                res?;
+                return Err(anyhow!("The camera stopped before a frame was captured"));
+            }
+            measurement = measurement_stream.next() => measurement,
+        }
+        .ok_or_else(|| anyhow!("The camera stopped before a frame was captured"))?;
+        info!(frame_index = measurement.frame_index, "Captured frame");
+        if camera_command_channel
+            .send(CameraCommand::Shutdown)
+            .is_err()
+        {
+            warn!("Camera thread already stopped");
+        }
+        if tokio::time::timeout(SHUTDOWN_TIMEOUT, camera_task)
+            .await
+            .is_err()
+        {
+            warn!("Timed out waiting for the camera to stop");
+        }
+        let data = match encoder {
+            None => Bytes::from(serde_json::to_vec(&RawFrame::from(&measurement))?),
+            Some(encoder) => {
+                let rendered = renderer.render(measurement, Vec::new()).await?;
+                encoder(&rendered)?
+            }
+        };
+        std::fs::write(&path, data)
+            .with_context(|| format!("Error writing capture to {:?}", path))?;
+        info!(?path, "Capture saved");
+        Ok(())
+    }
+
+    /// Capture an empty-room reference recording for `duration`, then write it to `path`.
+    ///
+    /// A tracker is trained on the frames as they're captured so that it can be reported when
+    /// the background model has stabilized, and the trained model is written to `model_path`.
+    /// Only the camera is started; nothing is published.
+    #[cfg(feature = "mock_camera")]
+    pub(crate) async fn calibrate(
+        config: Settings,
+        duration: Duration,
+        path: PathBuf,
+        model_path: PathBuf,
+    ) -> anyhow::Result<()> {
+        let camera: Camera = (&config.camera)
+            .try_into()
+            .context("Error configuring camera")?;
+        let camera_command_channel = camera.command_channel();
+        let camera_task = spawn_blocking(move || {
+            camera
+                .measurement_loop()
+                .context("Error within camera frame thread")
+        })
+        .map(flatten_join_result);
+        tokio::pin!(camera_task);
+        let measurement_stream = Self::create_measurement_stream(&camera_command_channel)
+            .await
+            .context("Error requesting measurement stream from camera")?;
+        let mut tracker = Tracker::new(&config.tracker);
+        tracker.set_frame_rate(config.camera.measurement_rate());
+        info!(
+            ?duration,
+            "Capturing empty-room reference, the room must stay empty"
+        );
+        let capture =
+            timed_measurements(measurement_stream).take_until(tokio::time::sleep(duration));
+        tokio::pin!(capture);
+        let mut records = Vec::new();
+        let mut was_stable = false;
+        loop {
+            let record = tokio::select! {
+                res = &mut camera_task => {
+                    res?;
+                    return Err(anyhow!("The camera stopped before calibration finished"));
+                }
+                record = capture.next() => record,
+            };
+            let record = match record {
+                Some(record) => record,
+                None => break,
+            };
+            tracker.update(&record.measurement.image);
+            records.push(record);
+            let is_stable = tracker.is_stable();
+            if is_stable && !was_stable {
+                info!(
+                    frame_count = records.len(),
+                    "Background model has stabilized"
+                );
+            } else if was_stable && !is_stable {
+                warn!(
+                    frame_count = records.len(),
+                    "Object detected during calibration"
+                );
+            }
+            was_stable = is_stable;
+        }
+        info!(frame_count = records.len(), "Calibration capture complete");
+        if !was_stable {
+            warn!("Background model did not stabilize, try calibrating for longer");
+        }
+        if camera_command_channel
+            .send(CameraCommand::Shutdown)
+            .is_err()
+        {
+            warn!("Camera thread already stopped");
+        }
+        // The capture also ends if the camera stops, so check for errors before saving anything.
+        match tokio::time::timeout(SHUTDOWN_TIMEOUT, camera_task).await {
+            Ok(res) => res?,
+            Err(_) => warn!("Timed out waiting for the camera to stop"),
+        }
+        let data = crate::recorded_data::RecordedData::to_bincode(&records)?;
+        tokio::fs::write(&path, data)
+            .await
+            .with_context(|| format!("Error writing calibration data to {:?}", path))?;
+        info!(?path, "Empty-room reference saved");
+        match config.tracker.mode {
+            crate::occupancy::TrackerMode::Gmm => {
+                tracker.save_background(&model_path)?;
+                info!(path = ?model_path, "Background model saved");
+            }
+            crate::occupancy::TrackerMode::Threshold => {
+                info!("The threshold tracker has no background model to save");
+            }
+        }
+        Ok(())
+    }
+
     // Get a Stream of Measurements from the camera.
     async fn create_measurement_stream(
         command_channel: &mpsc::Sender<CameraCommand>,
@@ -155,7 +487,80 @@ impl Pipeline {
         Arc::new(device)
     }
 
-    fn create_streams(&mut self, settings: stream::StreamSettings) -> anyhow::Result<()> {
+    /// Mark the device as unavailable over MQTT while the camera is being reconnected to.
+    fn create_camera_availability(&mut self, mut availability: watch::Receiver<bool>) {
+        let mut mqtt_sender = self.mqtt_sender.clone();
+        let status_topic = self.status_topic.clone();
+        let availability_task = async move {
+            while availability.changed().await.is_ok() {
+                let status = if *availability.borrow() {
+                    Status::Online
+                } else {
+                    Status::Offline
+                };
+                debug!(?status, "Camera availability changed");
+                mqtt_sender
+                    .enqueue_publish(status_topic.clone(), QoS::AtLeastOnce, &status, true)
+                    .await?;
+            }
+            Ok(())
+        };
+        self.tasks.push(
+            availability_task
+                .instrument(info_span!("camera_availability"))
+                .boxed(),
+        );
+    }
+
+    /// Poll the camera at a lower frame rate while there are no stream clients connected.
+    ///
+    /// Only the rendered image subscribers are watched. The tracker subscribes to the camera
+    /// directly, so it keeps receiving frames (albeit at the idle rate).
+    fn create_idle_throttle(&mut self, idle_frame_rate: f32, frame_rate: f32) {
+        let subscribers = self.rendered_source.subscribers();
+        let command_channel = self.camera_command_channel.clone();
+        self.tasks.push(
+            throttle_idle_camera(command_channel, subscribers, idle_frame_rate, frame_rate)
+                .instrument(info_span!("idle_throttle"))
+                .boxed(),
+        );
+    }
+
+    // No-op version for when the systemd feature isn't enabled.
+    #[cfg(not(feature = "systemd"))]
+    async fn notify_systemd(&mut self) -> anyhow::Result<()> {
+        if std::env::var_os("NOTIFY_SOCKET").is_some() {
+            warn!("Started by systemd, but systemd support has not been enabled.");
+        }
+        Ok(())
+    }
+
+    /// Tell systemd that startup is complete, and start pinging the watchdog if it's enabled.
+    #[cfg(feature = "systemd")]
+    async fn notify_systemd(&mut self) -> anyhow::Result<()> {
+        if !crate::systemd::notify("READY=1")? {
+            return Ok(());
+        }
+        debug!("Notified systemd that startup is complete");
+        if let Some(timeout) = crate::systemd::watchdog_interval() {
+            let interval = timeout / 2;
+            info!(?interval, "Pinging systemd watchdog");
+            let measurements =
+                Self::create_measurement_stream(&self.camera_command_channel).await?;
+            self.tasks.push(
+                ping_watchdog(measurements, interval)
+                    .instrument(info_span!("systemd_watchdog"))
+                    .boxed(),
+            );
+        }
+        Ok(())
+    }
+
+    fn create_streams(
+        &mut self,
+        settings: stream::StreamSettings,
+        objects: watch::Receiver<Vec<TrackedObject>>,
+    ) -> anyhow::Result<()> {
         // Bail out if there aren't any stream sources enabled.
         // For now there's just MJPEG, but HLS is planned for the future.
         if !settings.any_streams_enabled() {
@@ -165,20 +570,46 @@ impl Pipeline {
         }
         let mut routes = Vec::new();
         if settings.mjpeg.enabled {
-            debug!("creating JPEG encoder");
+            let format = settings.mjpeg.format;
+            let encode = format.encoder()?;
+            debug!(?format, "creating stream encoder");
             let jpeg_sender = self.rendered_source.new_child();
-            let encoder_stream = self
-                .rendered_source
-                .uncounted_stream()
-                .then(|image| async move {
-                    let res = spawn_blocking(move || stream::encode_jpeg(&image))
-                        .map(flatten_join_result)
-                        .await;
-                    // Map the JoinError to an anyhow::Error
-                    res.map_err(|err| anyhow!("Error with JPEG encoding thread: {:?}", err))
-                });
+            let rendered_stream = self.rendered_source.uncounted_stream().boxed();
+            // Only throttle the encoder here, rendering may already be throttled by
+            // `frame_rate_limit` in `create_renderer`.
+            let rendered_stream = match settings.mjpeg.encoder_delay() {
+                None => rendered_stream,
+                Some(delay) => {
+                    debug!(?delay, "Limiting JPEG encoder frame rate");
+                    tokio_stream::StreamExt::throttle(rendered_stream, delay).boxed()
+                }
+            };
+            let max_dimension = settings.mjpeg.max_dimension;
+            if let Some(max_dimension) = max_dimension {
+                debug!(max_dimension, "Shrinking stream frames");
+            }
+            let encoder_stream = rendered_stream.then(move |image| async move {
+                let res = spawn_blocking(move || match max_dimension {
+                    None => stream::timed_encode(format.content_type(), encode, &image),
+                    Some(max_dimension) => stream::timed_encode(
+                        format.content_type(),
+                        encode,
+                        &render::ImageResize::shrink_to_fit(&image, max_dimension.get()),
+                    ),
+                })
+                .map(flatten_join_result)
+                .await;
+                // Map the JoinError to an anyhow::Error
+                res.map_err(|err| anyhow!("Error with image encoding thread: {:?}", err))
+            });
             // MJPEG sink
-            let mjpeg = stream::MjpegStream::new(&jpeg_sender);
+            let mjpeg = stream::MjpegStream::new(
+                &jpeg_sender,
+                format.content_type(),
+                settings.mjpeg.boundary.clone(),
+                settings.mjpeg.content_length,
+                settings.encode_time_header,
+            );
             let mjpeg_output = mjpeg.clone();
             let mjpeg_route = warp::path("mjpeg")
                 .and(warp::path::end())
@@ -199,11 +630,40 @@ impl Pipeline {
                     .boxed(),
             );
         }
+        if let Some(v4l2_settings) = &settings.v4l2 {
+            self.create_v4l2_output(v4l2_settings)?;
+        }
+        if let Some(frame_feed_settings) = &settings.frame_feed {
+            self.create_frame_feed(frame_feed_settings)?;
+        }
         if settings.http_streams_enabled() {
-            let combined_route = routes
-                .into_iter()
-                .reduce(|combined, next| combined.or(next).unify().boxed())
-                .ok_or_else(|| anyhow!("problem creating streaming routes"))?;
+            let health_route = self.create_health_route();
+            let json_routes = [
+                self.create_raw_frame_route(),
+                Self::create_objects_route(objects),
+            ];
+            #[cfg(feature = "compression")]
+            let json_routes = json_routes.map(stream::compressed);
+            routes.extend(json_routes);
+            routes.push(self.create_reset_background_route());
+            if let Some(heatmap) = &self.heatmap {
+                routes.push(Self::create_heatmap_route(Arc::clone(heatmap)));
+                routes.push(Self::create_reset_heatmap_route(Arc::clone(heatmap)));
+            }
+            #[cfg(feature = "webp")]
+            routes.push(self.create_webp_snapshot_route(settings.encode_time_header));
+            let combined_route =
+                Self::combine_http_routes(health_route, routes, settings.auth.clone())?;
+            let combined_route = match stream::cors(&settings.cors_allowed_origins) {
+                Some(cors) => {
+                    debug!(origins = ?settings.cors_allowed_origins, "allowing CORS requests");
+                    combined_route
+                        .with(cors)
+                        .map(warp::Reply::into_response)
+                        .boxed()
+                }
+                None => combined_route,
+            };
             let bind_address: std::net::SocketAddr = settings.into();
             debug!(address = ?bind_address, "creating warp server");
             let server = warp::serve(combined_route).bind(bind_address);
@@ -213,59 +673,570 @@ impl Pipeline {
         Ok(())
     }
 
-    /// Create an occupancy tracker with the given settings and an expected frame duration.
-    async fn create_tracker(&mut self, settings: TrackerSettings) -> anyhow::Result<()> {
-        let tracker = Tracker::new(&settings);
-        let mut count = State::new_discoverable(
-            self.mqtt_sender.clone(),
-            Arc::clone(&self.hass_device),
-            &self.mqtt_config.base_topic,
-            "count",
-            true,
-            QoS::AtLeastOnce,
+    /// Write the rendered images to a V4L2 output device.
+    #[cfg(target_os = "linux")]
+    fn create_v4l2_output(&mut self, settings: &stream::V4l2Settings) -> anyhow::Result<()> {
+        let mut output = stream::V4l2Output::open(&settings.device, settings.pixel_format)?;
+        debug!(device = ?settings.device, "writing rendered images to V4L2 device");
+        // There's no way to tell if anything is reading from the device, so keep rendering
+        // images the entire time.
+        let mut rendered_stream = Box::pin(self.rendered_source.stream());
+        let output_task = async move {
+            while let Some(image) = rendered_stream.next().await {
+                output = spawn_blocking(move || -> anyhow::Result<_> {
+                    output.write_frame(&image)?;
+                    Ok(output)
+                })
+                .map(flatten_join_result)
+                .await?;
+            }
+            Ok(())
+        };
+        self.tasks
+            .push(output_task.instrument(info_span!("v4l2_output")).boxed());
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn create_v4l2_output(&mut self, _settings: &stream::V4l2Settings) -> anyhow::Result<()> {
+        Err(anyhow!("V4L2 output is only supported on Linux"))
+    }
+
+    /// Stream the raw camera measurements to clients connecting over TCP.
+    #[cfg(feature = "frame_feed")]
+    fn create_frame_feed(&mut self, settings: &stream::FrameFeedSettings) -> anyhow::Result<()> {
+        let address = settings.socket_address();
+        let listener = std::net::TcpListener::bind(address)
+            .with_context(|| format!("Unable to bind frame feed server to {}", address))?;
+        listener.set_nonblocking(true)?;
+        let listener = tokio::net::TcpListener::from_std(listener)?;
+        debug!(?address, "creating frame feed server");
+        // Frame feed clients are counted as stream clients, so the camera isn't throttled while
+        // they're connected.
+        let frame_sender = self.rendered_source.new_child();
+        let command_channel = self.camera_command_channel.clone();
+        let encode_sender = frame_sender.clone();
+        let encode_task = async move {
+            let measurements = Self::create_measurement_stream(&command_channel).await?;
+            stream::frame_feed::encode_frames(measurements)
+                .never_error()
+                .forward(encode_sender)
+                .await
+        };
+        self.tasks.push(
+            encode_task
+                .instrument(info_span!("frame_feed_encoder"))
+                .boxed(),
         );
-        let mut occupied = State::new_discoverable(
+        self.tasks.push(
+            stream::frame_feed::serve(listener, frame_sender)
+                .instrument(info_span!("frame_feed_server"))
+                .boxed(),
+        );
+        Ok(())
+    }
+
+    #[cfg(not(feature = "frame_feed"))]
+    fn create_frame_feed(&mut self, _settings: &stream::FrameFeedSettings) -> anyhow::Result<()> {
+        Err(anyhow!(
+            "The frame feed requires r-u-still-there to be built with the 'frame_feed' feature"
+        ))
+    }
+
+    /// A health check route, reporting whether the MQTT client is connected to the broker, along
+    /// with the number of camera images that have been dropped.
+    fn create_health_route(&self) -> HttpRoute {
+        let mqtt_sender = self.mqtt_sender.clone();
+        let dropped_frames = Arc::clone(&self.dropped_frames);
+        warp::path("healthz")
+            .and(warp::path::end())
+            .map(move || {
+                let (status, body) = if mqtt_sender.is_connected() {
+                    (200, "OK")
+                } else {
+                    (503, "MQTT broker disconnected")
+                };
+                Response::builder()
+                    .status(status)
+                    .header(
+                        "X-Dropped-Frames",
+                        dropped_frames.load(Ordering::Relaxed).to_string(),
+                    )
+                    .body(hyper::Body::from(body))
+            })
+            .boxed()
+    }
+
+    /// Combine the HTTP routes into one, requiring `auth` (if given) for everything but the
+    /// health check.
+    fn combine_http_routes(
+        health_route: HttpRoute,
+        routes: Vec<HttpRoute>,
+        auth: Option<stream::StreamAuth>,
+    ) -> anyhow::Result<warp::filters::BoxedFilter<(warp::reply::Response,)>> {
+        let protected_routes = routes
+            .into_iter()
+            .reduce(|combined, next| combined.or(next).unify().boxed())
+            .ok_or_else(|| anyhow!("problem creating streaming routes"))?;
+        if auth.is_some() {
+            debug!("requiring authorization for the HTTP server");
+        }
+        // The health check is left public so that it can be used by monitoring tools.
+        Ok(health_route
+            .or(stream::authorized(auth).and(protected_routes))
+            .unify()
+            .recover(stream::recover_unauthorized)
+            .map(warp::Reply::into_response)
+            .boxed())
+    }
+
+    /// A route serving the temperatures from the next camera measurement as JSON.
+    fn create_raw_frame_route(
+        &self,
+    ) -> warp::filters::BoxedFilter<(Result<Response<hyper::Body>, http::Error>,)> {
+        let command_channel = self.camera_command_channel.clone();
+        warp::path!("api" / "frame.json")
+            .and_then(move || {
+                let command_channel = command_channel.clone();
+                async move {
+                    let frame = match Self::create_measurement_stream(&command_channel).await {
+                        Ok(mut measurement_stream) => measurement_stream
+                            .next()
+                            .await
+                            .map(|measurement| RawFrame::from(&measurement)),
+                        Err(err) => {
+                            warn!("Unable to subscribe to camera measurements: {:?}", err);
+                            None
+                        }
+                    };
+                    let response = match frame.map(|frame| serde_json::to_vec(&frame)) {
+                        Some(Ok(json)) => Response::builder()
+                            .status(200)
+                            .header("Content-Type", "application/json")
+                            .body(hyper::Body::from(json)),
+                        Some(Err(err)) => {
+                            warn!("Error serializing frame: {:?}", err);
+                            Response::builder()
+                                .status(500)
+                                .body(hyper::Body::from("Unable to serialize frame"))
+                        }
+                        None => Response::builder()
+                            .status(500)
+                            .body(hyper::Body::from("Unable to get a frame")),
+                    };
+                    Ok::<_, warp::Rejection>(response)
+                }
+            })
+            .boxed()
+    }
+
+    /// A route serving the objects currently being tracked as JSON.
+    fn create_objects_route(
+        objects: watch::Receiver<Vec<TrackedObject>>,
+    ) -> warp::filters::BoxedFilter<(Result<Response<hyper::Body>, http::Error>,)> {
+        warp::path!("api" / "objects")
+            .map(move || match serde_json::to_vec(&*objects.borrow()) {
+                Ok(json) => Response::builder()
+                    .status(200)
+                    .header("Content-Type", "application/json")
+                    .body(hyper::Body::from(json)),
+                Err(err) => {
+                    warn!("Error serializing tracked objects: {:?}", err);
+                    Response::builder()
+                        .status(500)
+                        .body(hyper::Body::from("Unable to serialize objects"))
+                }
+            })
+            .boxed()
+    }
+
+    /// A route for resetting the background model.
+    fn create_reset_background_route(
+        &self,
+    ) -> warp::filters::BoxedFilter<(Result<Response<hyper::Body>, http::Error>,)> {
+        let background_reset = Arc::clone(&self.background_reset);
+        warp::post()
+            .and(warp::path!("api" / "reset-background"))
+            .map(move || {
+                info!("Background reset requested over HTTP");
+                background_reset.notify_one();
+                Response::builder()
+                    .status(200)
+                    .body(hyper::Body::from("Background reset"))
+            })
+            .boxed()
+    }
+
+    /// A route serving the occupancy heatmap as a PNG.
+    fn create_heatmap_route(
+        heatmap: SharedHeatmap,
+    ) -> warp::filters::BoxedFilter<(Result<Response<hyper::Body>, http::Error>,)> {
+        warp::path!("api" / "heatmap.png")
+            .map(move || {
+                // The heatmap is small, so it's rendered and encoded in place.
+                let rendered = heatmap.lock().unwrap().render();
+                match rendered.map(|image| stream::encode_png(&image)) {
+                    Some(Ok(png)) => Response::builder()
+                        .status(200)
+                        .header("Content-Type", "image/png")
+                        .body(hyper::Body::from(png)),
+                    Some(Err(err)) => {
+                        warn!("Error encoding heatmap: {:?}", err);
+                        Response::builder()
+                            .status(500)
+                            .body(hyper::Body::from("Unable to encode heatmap"))
+                    }
+                    None => Response::builder()
+                        .status(503)
+                        .body(hyper::Body::from("The heatmap is not available yet")),
+                }
+            })
+            .boxed()
+    }
+
+    /// A route for clearing the occupancy heatmap.
+    fn create_reset_heatmap_route(
+        heatmap: SharedHeatmap,
+    ) -> warp::filters::BoxedFilter<(Result<Response<hyper::Body>, http::Error>,)> {
+        warp::post()
+            .and(warp::path!("api" / "reset-heatmap"))
+            .map(move || {
+                info!("Heatmap reset requested over HTTP");
+                heatmap.lock().unwrap().reset();
+                Response::builder()
+                    .status(200)
+                    .body(hyper::Body::from("Heatmap reset"))
+            })
+            .boxed()
+    }
+
+    /// A route serving the next rendered image, encoded as WebP.
+    ///
+    /// If `encode_time_header` is set, the time taken to encode the image is given in the
+    /// `X-Encode-Time-Ms` header.
+    #[cfg(feature = "webp")]
+    fn create_webp_snapshot_route(
+        &self,
+        encode_time_header: bool,
+    ) -> warp::filters::BoxedFilter<(Result<Response<hyper::Body>, http::Error>,)> {
+        let rendered_source = self.rendered_source.clone();
+        warp::path("snapshot.webp")
+            .and(warp::path::end())
+            .and_then(move || {
+                // Rendering only happens while there are subscribers, so subscribe just long
+                // enough to get a single image.
+                let mut rendered_stream = Box::pin(rendered_source.stream());
+                async move {
+                    let encoded = match rendered_stream.next().await {
+                        Some(image) => spawn_blocking(move || {
+                            stream::timed_encode("image/webp", stream::encode_webp, &image)
+                        })
+                        .map(flatten_join_result)
+                        .await
+                        .map_err(|err| warn!("Error encoding WebP snapshot: {:?}", err))
+                        .ok(),
+                        None => None,
+                    };
+                    let response = match encoded {
+                        Some(image) => {
+                            let mut builder = Response::builder()
+                                .status(200)
+                                .header("Content-Type", "image/webp");
+                            if encode_time_header {
+                                builder =
+                                    builder.header("X-Encode-Time-Ms", image.encode_time_ms());
+                            }
+                            builder.body(hyper::Body::from(image.data))
+                        }
+                        None => Response::builder()
+                            .status(500)
+                            .body(hyper::Body::from("Unable to create snapshot")),
+                    };
+                    Ok::<_, warp::Rejection>(response)
+                }
+            })
+            .boxed()
+    }
+
+    /// Periodically publish the raw temperatures from the camera.
+    async fn create_raw_frame_publisher(&mut self) -> anyhow::Result<()> {
+        let interval = match self.mqtt_config.frame_interval() {
+            Some(interval) => interval,
+            None => return Ok(()),
+        };
+        let frame_state: State<ArcDevice> = State::new(
             self.mqtt_sender.clone(),
-            Arc::clone(&self.hass_device),
             &self.mqtt_config.base_topic,
-            "occupied",
-            true,
-            QoS::AtLeastOnce,
+            &self.mqtt_config.name,
+            "frame",
+            false,
+            QoS::AtMostOnce,
         );
-        if self.mqtt_config.home_assistant.enabled {
+        debug!(
+            topic = frame_state.topic(),
+            ?interval,
+            "Publishing raw frames"
+        );
+        // Like the camera entity, subscribe just long enough to get a single measurement each
+        // interval.
+        let command_channel = self.camera_command_channel.clone();
+        let frame_stream = IntervalStream::new(tokio::time::interval(interval))
+            .filter_map(move |_| {
+                let command_channel = command_channel.clone();
+                async move {
+                    let mut measurement_stream = Self::create_measurement_stream(&command_channel)
+                        .await
+                        .map_err(|err| {
+                            warn!("Unable to subscribe to camera measurements: {:?}", err)
+                        })
+                        .ok()?;
+                    measurement_stream.next().await
+                }
+            })
+            .filter_map(|measurement| async move {
+                serde_json::to_vec(&RawFrame::from(&measurement))
+                    .map(Bytes::from)
+                    .map_err(|err| warn!("Error serializing frame: {:?}", err))
+                    .ok()
+            })
+            .never_error();
+        self.tasks.push(
+            frame_stream
+                .forward(frame_state.bytes_sink())
+                .instrument(info_span!("raw_frames"))
+                .boxed(),
+        );
+        Ok(())
+    }
+
+    /// Periodically publish still images for a Home Assistant camera entity.
+    async fn create_camera_entity(&mut self) -> anyhow::Result<()> {
+        let home_assistant = &self.mqtt_config.home_assistant;
+        let interval = match home_assistant.camera_interval() {
+            Some(interval) if home_assistant.enabled => interval,
+            _ => return Ok(()),
+        };
+        let mut camera = self.sensor_state("camera", false, QoS::AtMostOnce);
+        camera
+            .publish_home_assistant_discovery_with::<CameraImage, _>(
+                &home_assistant.topics,
+                &self.status_topic,
+                |config| config.set_object_id(home_assistant.object_id("camera")),
+            )
+            .await?;
+        // Rendering only happens while there are subscribers, so subscribe just long enough to get
+        // a single image each interval.
+        let rendered_source = self.rendered_source.clone();
+        let image_stream = IntervalStream::new(tokio::time::interval(interval))
+            .filter_map(move |_| {
+                let mut rendered_stream = Box::pin(rendered_source.stream());
+                async move { rendered_stream.next().await }
+            })
+            .then(|image| async move {
+                spawn_blocking(move || stream::encode_jpeg(&image))
+                    .map(flatten_join_result)
+                    .await
+            })
+            .filter_map(|res| async move {
+                res.map_err(|err| warn!("Error encoding camera entity image: {:?}", err))
+                    .ok()
+            })
+            .never_error();
+        self.tasks.push(
+            image_stream
+                .forward(camera.bytes_sink())
+                .instrument(info_span!("camera_entity"))
+                .boxed(),
+        );
+        Ok(())
+    }
+
+    /// Create an occupancy tracker with the given settings and an expected frame duration.
+    async fn create_tracker(
+        &mut self,
+        settings: TrackerSettings,
+        zones: Vec<Zone>,
+        lines: Vec<CountingLine>,
+        frame_rate: f32,
+        (spatial_filter, smoothing): (render::SpatialFilter, Option<f32>),
+        objects_sender: watch::Sender<Vec<TrackedObject>>,
+    ) -> anyhow::Result<()> {
+        let decimation = settings.decimation.get();
+        let mut tracker = Tracker::new(&settings);
+        tracker.set_frame_rate(frame_rate / decimation as f32);
+        if let Some(path) = &settings.background_model {
+            tracker.load_background(path)?;
+        }
+        if let Some(heatmap) = &self.heatmap {
+            tracker.set_heatmap(Arc::clone(heatmap));
+        }
+        let mut count = self.sensor_state("count", true, QoS::AtLeastOnce);
+        let mut occupied = self.sensor_state("occupied", true, QoS::AtLeastOnce);
+        let mut occupied_duration = self.sensor_state("occupied_duration", true, QoS::AtLeastOnce);
+        let mut vacant_duration = self.sensor_state("vacant_duration", true, QoS::AtLeastOnce);
+        let mut objects = self.sensor_state("objects", false, QoS::AtMostOnce);
+        let home_assistant = &self.mqtt_config.home_assistant;
+        if home_assistant.enabled {
+            let expire_after = home_assistant.expire_after();
             count
-                .publish_home_assistant_discovery::<OccupancyCount>(
-                    &self.mqtt_config.home_assistant.topic,
+                .publish_home_assistant_discovery_with::<OccupancyCount, _>(
+                    &home_assistant.topics,
                     &self.status_topic,
+                    |config| {
+                        config.set_expire_after(expire_after);
+                        config.set_object_id(home_assistant.object_id("count"));
+                    },
                 )
                 .await?;
             occupied
-                .publish_home_assistant_discovery::<Occupancy>(
-                    &self.mqtt_config.home_assistant.topic,
+                .publish_home_assistant_discovery_with::<Occupancy, _>(
+                    &home_assistant.topics,
                     &self.status_topic,
+                    |config| {
+                        config.set_expire_after(expire_after);
+                        config.set_object_id(home_assistant.object_id("occupied"));
+                    },
+                )
+                .await?;
+            occupied_duration
+                .publish_home_assistant_discovery_with::<OccupancyDuration, _>(
+                    &home_assistant.topics,
+                    &self.status_topic,
+                    |config| config.set_object_id(home_assistant.object_id("occupied_duration")),
+                )
+                .await?;
+            vacant_duration
+                .publish_home_assistant_discovery_with::<OccupancyDuration, _>(
+                    &home_assistant.topics,
+                    &self.status_topic,
+                    |config| config.set_object_id(home_assistant.object_id("vacant_duration")),
+                )
+                .await?;
+            objects
+                .publish_home_assistant_discovery_with::<TrackedObjects, _>(
+                    &home_assistant.topics,
+                    &self.status_topic,
+                    |config| config.set_object_id(home_assistant.object_id("objects")),
                 )
                 .await?;
         }
         let count_sink = count.sink();
-        let update_count_stream = tracker
-            .count_stream()
-            .map(OccupancyCount::from)
-            .filter_repeated()
+        let counts = self
+            .batched("count", tracker.count_stream().map(OccupancyCount::from))
+            .filter_repeated();
+        let update_count_stream = self
+            .kept_alive(counts)
             .never_error()
             .forward(count_sink)
             .boxed();
         self.tasks.push(update_count_stream);
         let occupied_sink = occupied.sink();
-        let update_occupied_stream = tracker
+        let (on_delay, off_delay) = (settings.occupied_on_delay, settings.occupied_off_delay);
+        let occupied_states = tracker
             .count_stream()
-            .map(Occupancy::from)
-            .filter_repeated()
+            .map(|count| count > 0)
+            .debounce(move |&occupied| if occupied { on_delay } else { off_delay })
+            .map(Occupancy::from);
+        let occupancies = self.batched("occupied", occupied_states).filter_repeated();
+        let update_occupied_stream = self
+            .kept_alive(occupancies)
             .never_error()
             .forward(occupied_sink)
             .boxed();
         self.tasks.push(update_occupied_stream);
-        let measurement_stream = Self::create_measurement_stream(&self.camera_command_channel)
-            .await?
+        let occupied_duration_sink = occupied_duration.sink();
+        let occupied_durations = occupancy_durations(
+            tracker
+                .count_stream()
+                .map(|count| count > 0)
+                .filter_repeated(),
+            settings.duration_interval,
+        )
+        .map(|(occupied_for, _)| OccupancyDuration::from(occupied_for));
+        let update_occupied_duration_stream = self
+            .batched("occupied_duration", occupied_durations)
+            .never_error()
+            .forward(occupied_duration_sink)
+            .boxed();
+        self.tasks.push(update_occupied_duration_stream);
+        let vacant_duration_sink = vacant_duration.sink();
+        let vacant_durations = occupancy_durations(
+            tracker
+                .count_stream()
+                .map(|count| count > 0)
+                .filter_repeated(),
+            settings.duration_interval,
+        )
+        .map(|(_, vacant_for)| OccupancyDuration::from(vacant_for));
+        let update_vacant_duration_stream = self
+            .batched("vacant_duration", vacant_durations)
+            .never_error()
+            .forward(vacant_duration_sink)
+            .boxed();
+        self.tasks.push(update_vacant_duration_stream);
+        let objects_sink = objects.sink();
+        let update_objects_stream = self
+            .batched(
+                "objects",
+                tracker.objects_stream().map(TrackedObjects::from),
+            )
+            .filter_repeated()
+            .never_error()
+            .forward(objects_sink)
+            .boxed();
+        self.tasks.push(update_objects_stream);
+        for zone in zones {
+            self.create_zone_count(&tracker, zone).await?;
+        }
+        for line in lines {
+            self.create_line_counts(&tracker, line).await?;
+        }
+        self.create_background_reset(&tracker).await?;
+        if let Some(limit) = settings.capacity_limit {
+            self.create_capacity_sensor(&tracker, limit, settings.capacity_hysteresis)
+                .await?;
+        }
+        if let Some(threshold) = settings.loitering_threshold {
+            if threshold >= settings.stationary_timeout {
+                warn!(
+                    "loitering_threshold is longer than stationary_timeout, so nobody will be \
+                    counted as loitering"
+                );
+            }
+            self.create_loitering_sensor(&tracker, threshold).await?;
+        }
+        let home_assistant = &self.mqtt_config.home_assistant;
+        if home_assistant.enabled && home_assistant.device_triggers {
+            self.create_device_triggers(&tracker).await?;
+        }
+        if let Some(event_topic) = self.mqtt_config.event_topic.clone() {
+            self.create_transition_events(&tracker, &event_topic);
+        }
+        let forward_objects = tracker
+            .objects_stream()
+            .for_each(move |objects| {
+                // The only receiver is the renderer, so there's nothing to do if it's gone.
+                let _ = objects_sender.send(objects);
+                futures::future::ready(())
+            })
+            .map(Ok)
+            .boxed();
+        self.tasks.push(forward_objects);
+        if let Some(event_log) = &settings.event_log {
+            debug!(path = ?event_log.path, "Logging occupancy events");
+            let event_task = log_occupancy_events(tracker.count_stream(), event_log)?;
+            self.tasks
+                .push(event_task.instrument(info_span!("event_log")).boxed());
+        }
+        let measurement_stream =
+            Self::create_measurement_stream(&self.camera_command_channel).await?;
+        // Smoothing needs to see every frame, so it's applied before decimation.
+        let measurement_stream = filter_measurements(measurement_stream, spatial_filter, smoothing)
+            // Only pass every `decimation`th measurement on to the tracker.
+            .enumerate()
+            .filter_map(move |(index, measurement)| {
+                std::future::ready((index % decimation == 0).then_some(measurement))
+            })
             .instrument(info_span!("tracker_measurements"));
         self.tasks.push(
             measurement_stream
@@ -277,21 +1248,361 @@ impl Pipeline {
         Ok(())
     }
 
-    async fn create_thermometer(&mut self) -> anyhow::Result<()> {
-        info!("Creating thermometer");
-        let unit = self.mqtt_config.home_assistant.unit;
-        let temperature_stream = Self::create_measurement_stream(&self.camera_command_channel)
-            .await?
-            .instrument(info_span!("temperature_measurement"))
-            .map(move |measurement| measurement.temperature.in_unit(&unit));
-        let state = State::new_discoverable(
+    /// Publish the number of people within a zone.
+    async fn create_zone_count(&mut self, tracker: &Tracker, zone: Zone) -> anyhow::Result<()> {
+        let sensor_name = format!("{}_count", zone.name);
+        let mut zone_count = self.sensor_state(&sensor_name, true, QoS::AtLeastOnce);
+        let home_assistant = &self.mqtt_config.home_assistant;
+        if home_assistant.enabled {
+            zone_count
+                .publish_home_assistant_discovery_with::<OccupancyCount, _>(
+                    &home_assistant.topics,
+                    &self.status_topic,
+                    |config| config.set_object_id(home_assistant.object_id(&sensor_name)),
+                )
+                .await?;
+        }
+        let zone_counts = tracker
+            .objects_stream()
+            .map(move |objects| OccupancyCount::from(zone.count(&objects)));
+        let update_zone_stream = self
+            .batched(&sensor_name, zone_counts)
+            .filter_repeated()
+            .never_error()
+            .forward(zone_count.sink())
+            .boxed();
+        self.tasks.push(update_zone_stream);
+        Ok(())
+    }
+
+    /// Publish the total number of people that have crossed a counting line in each direction.
+    ///
+    /// The totals are counted from when r-u-still-there started, so they replace any retained
+    /// totals from before a restart.
+    async fn create_line_counts(
+        &mut self,
+        tracker: &Tracker,
+        line: CountingLine,
+    ) -> anyhow::Result<()> {
+        let entries_name = format!("{}_entries", line.name);
+        let exits_name = format!("{}_exits", line.name);
+        let mut entries = self.sensor_state(&entries_name, true, QoS::AtLeastOnce);
+        let mut exits = self.sensor_state(&exits_name, true, QoS::AtLeastOnce);
+        let home_assistant = &self.mqtt_config.home_assistant;
+        if home_assistant.enabled {
+            entries
+                .publish_home_assistant_discovery_with::<CrossingCount, _>(
+                    &home_assistant.topics,
+                    &self.status_topic,
+                    |config| config.set_object_id(home_assistant.object_id(&entries_name)),
+                )
+                .await?;
+            exits
+                .publish_home_assistant_discovery_with::<CrossingCount, _>(
+                    &home_assistant.topics,
+                    &self.status_topic,
+                    |config| config.set_object_id(home_assistant.object_id(&exits_name)),
+                )
+                .await?;
+        }
+        // Both counters need to see every crossing, so they share one stream of counts.
+        let (counts_sender, counts_receiver) = watch::channel(Default::default());
+        let count_task = line
+            .crossings(tracker.objects_stream())
+            .for_each(move |counts| {
+                // Both receivers live as long as the pipeline, so sending can't fail.
+                let _ = counts_sender.send(counts);
+                futures::future::ready(())
+            })
+            .map(Ok)
+            .boxed();
+        self.tasks.push(count_task);
+        let entry_counts = WatchStream::new(counts_receiver.clone())
+            .map(|counts| CrossingCount::from(counts.entries));
+        let update_entries_stream = self
+            .batched(&entries_name, entry_counts)
+            .filter_repeated()
+            .never_error()
+            .forward(entries.sink())
+            .boxed();
+        self.tasks.push(update_entries_stream);
+        let exit_counts =
+            WatchStream::new(counts_receiver).map(|counts| CrossingCount::from(counts.exits));
+        let update_exits_stream = self
+            .batched(&exits_name, exit_counts)
+            .filter_repeated()
+            .never_error()
+            .forward(exits.sink())
+            .boxed();
+        self.tasks.push(update_exits_stream);
+        Ok(())
+    }
+
+    /// Reset the background model whenever it's requested, either over HTTP or by publishing to
+    /// the `reset_background` MQTT topic.
+    async fn create_background_reset(&mut self, tracker: &Tracker) -> anyhow::Result<()> {
+        let http_tracker = tracker.clone();
+        let background_reset = Arc::clone(&self.background_reset);
+        let http_task = async move {
+            loop {
+                background_reset.notified().await;
+                http_tracker.reset_background();
+            }
+        };
+        self.tasks.push(
+            http_task
+                .map(Ok)
+                .instrument(info_span!("http_background_reset"))
+                .boxed(),
+        );
+        let topic = self.mqtt_config.command_topic("reset_background");
+        debug!(?topic, "Subscribing to background reset commands");
+        let requests = self.mqtt_sender.subscribe(topic, QoS::AtLeastOnce).await?;
+        let mqtt_tracker = tracker.clone();
+        let mqtt_task = BroadcastStream::new(requests).for_each(move |request| {
+            match request {
+                Ok(_) => {
+                    info!("Background reset requested over MQTT");
+                    mqtt_tracker.reset_background();
+                }
+                Err(BroadcastStreamRecvError::Lagged(lag_count)) => {
+                    debug!("Skipped {} background reset requests", lag_count);
+                }
+            }
+            futures::future::ready(())
+        });
+        self.tasks.push(
+            mqtt_task
+                .map(Ok)
+                .instrument(info_span!("mqtt_background_reset"))
+                .boxed(),
+        );
+        Ok(())
+    }
+
+    /// Publish whether there are more than `limit` people present.
+    async fn create_capacity_sensor(
+        &mut self,
+        tracker: &Tracker,
+        limit: usize,
+        hysteresis: usize,
+    ) -> anyhow::Result<()> {
+        let mut capacity = self.sensor_state("over_capacity", true, QoS::AtLeastOnce);
+        let home_assistant = &self.mqtt_config.home_assistant;
+        if home_assistant.enabled {
+            let expire_after = home_assistant.expire_after();
+            capacity
+                .publish_home_assistant_discovery_with::<Capacity, _>(
+                    &home_assistant.topics,
+                    &self.status_topic,
+                    |config| {
+                        config.set_expire_after(expire_after);
+                        config.set_object_id(home_assistant.object_id("over_capacity"));
+                    },
+                )
+                .await?;
+        }
+        let capacities =
+            over_capacity(tracker.count_stream(), limit, hysteresis).map(Capacity::from);
+        let capacities = self.batched("over_capacity", capacities).filter_repeated();
+        let update_capacity_stream = self
+            .kept_alive(capacities)
+            .never_error()
+            .forward(capacity.sink())
+            .boxed();
+        self.tasks.push(update_capacity_stream);
+        Ok(())
+    }
+
+    /// Publish whether anyone has stayed in one spot for longer than `threshold`.
+    async fn create_loitering_sensor(
+        &mut self,
+        tracker: &Tracker,
+        threshold: Duration,
+    ) -> anyhow::Result<()> {
+        let mut loitering_state = self.sensor_state("loitering", true, QoS::AtLeastOnce);
+        let home_assistant = &self.mqtt_config.home_assistant;
+        if home_assistant.enabled {
+            let expire_after = home_assistant.expire_after();
+            loitering_state
+                .publish_home_assistant_discovery_with::<Loitering, _>(
+                    &home_assistant.topics,
+                    &self.status_topic,
+                    |config| {
+                        config.set_expire_after(expire_after);
+                        config.set_object_id(home_assistant.object_id("loitering"));
+                    },
+                )
+                .await?;
+        }
+        let loitering_states = loitering(tracker.objects_stream(), threshold).map(Loitering::from);
+        let loitering_states = self
+            .batched("loitering", loitering_states)
+            .filter_repeated();
+        let update_loitering_stream = self
+            .kept_alive(loitering_states)
+            .never_error()
+            .forward(loitering_state.sink())
+            .boxed();
+        self.tasks.push(update_loitering_stream);
+        Ok(())
+    }
+
+    /// Publish Home Assistant device trigger events each time someone enters or leaves.
+    async fn create_device_triggers(&mut self, tracker: &Tracker) -> anyhow::Result<()> {
+        let mut entered = self.sensor_state("person_entered", false, QoS::AtLeastOnce);
+        let mut exited = self.sensor_state("person_exited", false, QoS::AtLeastOnce);
+        entered
+            .publish_home_assistant_discovery::<PersonEntered>(
+                &self.mqtt_config.home_assistant.topics,
+                &self.status_topic,
+            )
+            .await?;
+        exited
+            .publish_home_assistant_discovery::<PersonExited>(
+                &self.mqtt_config.home_assistant.topics,
+                &self.status_topic,
+            )
+            .await?;
+        // One trigger for each person, the same as the occupancy events.
+        let entered_events = transitions(tracker.count_stream())
+            .filter(|transition| std::future::ready(*transition == Transition::Enter))
+            .map(|_| PersonEntered);
+        self.tasks
+            .push(entered_events.never_error().forward(entered.sink()).boxed());
+        let exited_events = transitions(tracker.count_stream())
+            .filter(|transition| std::future::ready(*transition == Transition::Exit))
+            .map(|_| PersonExited);
+        self.tasks
+            .push(exited_events.never_error().forward(exited.sink()).boxed());
+        Ok(())
+    }
+
+    /// Publish an event to `topic` each time someone enters or leaves.
+    fn create_transition_events(&mut self, tracker: &Tracker, topic: &str) {
+        let event_state: State<ArcDevice> = State::new(
             self.mqtt_sender.clone(),
-            Arc::clone(&self.hass_device),
             &self.mqtt_config.base_topic,
-            "temperature",
-            true,
+            &self.mqtt_config.name,
+            topic,
+            false,
             QoS::AtLeastOnce,
         );
+        debug!(topic = event_state.topic(), "Publishing occupancy events");
+        let events = transitions(tracker.count_stream())
+            .filter_map(|transition| async move {
+                serde_json::to_vec(&transition)
+                    .map(Bytes::from)
+                    .map_err(|err| warn!("Error serializing occupancy event: {:?}", err))
+                    .ok()
+            })
+            .never_error();
+        self.tasks.push(
+            events
+                .forward(event_state.bytes_sink())
+                .instrument(info_span!("occupancy_events"))
+                .boxed(),
+        );
+    }
+
+    /// Create the state for a sensor, applying any configured QoS or retain overrides to the
+    /// sensor's defaults.
+    fn sensor_state(&self, name: &str, retain: bool, qos: QoS) -> State<ArcDevice> {
+        let home_assistant = &self.mqtt_config.home_assistant;
+        State::new_discoverable(
+            self.mqtt_sender.clone(),
+            Arc::clone(&self.hass_device),
+            &self.mqtt_config.base_topic,
+            name,
+            home_assistant.retain_for(name, retain),
+            home_assistant.qos_for(name, qos),
+        )
+    }
+
+    /// Coalesce updates to an MQTT sensor if batching has been configured for it.
+    fn batched<'a, S>(&self, sensor: &str, values: S) -> BoxStream<'a, S::Item>
+    where
+        S: Stream + Send + 'a,
+        S::Item: Send,
+    {
+        match self.mqtt_config.batch_interval_for(sensor) {
+            None => values.boxed(),
+            Some(interval) => {
+                debug!(sensor, ?interval, "Batching MQTT updates");
+                values.latest_every(interval).boxed()
+            }
+        }
+    }
+
+    /// Republish the latest value of a sensor often enough that Home Assistant doesn't expire it,
+    /// if `expire_after` has been configured.
+    fn kept_alive<'a, S>(&self, values: S) -> BoxStream<'a, S::Item>
+    where
+        S: Stream + Send + 'a,
+        S::Item: Clone + Send,
+    {
+        let home_assistant = &self.mqtt_config.home_assistant;
+        match home_assistant.expire_after() {
+            Some(_) if home_assistant.enabled => {
+                let interval = home_assistant.expire_after / 2;
+                debug!(?interval, "Republishing sensor values");
+                values.repeat_after(interval).boxed()
+            }
+            _ => values.boxed(),
+        }
+    }
+
+    async fn create_thermometer(&mut self) -> anyhow::Result<()> {
+        if !self.mqtt_config.thermometer {
+            debug!("Temperature sensors are disabled");
+            self.remove_temperature_sensor("temperature").await?;
+            for statistic in SceneStatistic::ALL {
+                self.remove_temperature_sensor(statistic.sensor_name())
+                    .await?;
+            }
+            return Ok(());
+        }
+        info!("Creating thermometer");
+        let unit = self.mqtt_config.home_assistant.unit;
+        if self.mqtt_config.home_assistant.publish_ambient {
+            let measurements = Self::create_measurement_stream(&self.camera_command_channel)
+                .await?
+                .instrument(info_span!("temperature_measurement"));
+            let temperature_stream = ambient_temperatures(
+                measurements,
+                self.mqtt_config.home_assistant.ambient_average,
+                unit,
+            );
+            self.create_temperature_sensor("temperature", temperature_stream)
+                .await?;
+        } else {
+            self.remove_temperature_sensor("temperature").await?;
+        }
+        // The pixel temperatures summarize the scene the camera is looking at, as opposed to the
+        // temperature of the camera itself.
+        for statistic in SceneStatistic::ALL {
+            let scene_stream = Self::create_measurement_stream(&self.camera_command_channel)
+                .await?
+                .instrument(info_span!("scene_temperature", ?statistic))
+                .map(move |measurement| {
+                    Temperature::Celsius(statistic.compute(&measurement.image)).in_unit(&unit)
+                });
+            self.create_temperature_sensor(statistic.sensor_name(), scene_stream)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Publish a stream of temperatures (in the Home Assistant units) as an MQTT sensor.
+    async fn create_temperature_sensor<S>(
+        &mut self,
+        name: &str,
+        temperatures: S,
+    ) -> anyhow::Result<()>
+    where
+        S: Stream<Item = f32> + Send + 'static,
+    {
+        let state = self.sensor_state(name, true, QoS::AtLeastOnce);
         if self.mqtt_config.home_assistant.enabled {
             let mut config = state
                 .discovery_config::<f32>(&self.status_topic)
@@ -300,19 +1611,26 @@ impl Pipeline {
                 })?;
             config.set_device_class(hass::AnalogSensorClass::Temperature);
             config.set_unit_of_measurement(Some(self.mqtt_config.home_assistant.unit.to_string()));
-            let config_topic = state
-                .discovery_topic::<f32>(&self.mqtt_config.home_assistant.topic)
-                .ok_or_else(|| anyhow!("A discoverable state should have a discovery topic"))?;
-            // Keep this message the same as the debug message in mqtt::state::State::publish_home_assistant_discovery
-            debug!(?config, "Publishing Home Assistant discovery config");
-            self.mqtt_sender
-                .enqueue_publish(config_topic, QoS::AtLeastOnce, &config, true)
-                .await?;
+            config.set_expire_after(self.mqtt_config.home_assistant.expire_after());
+            config.set_object_id(self.mqtt_config.home_assistant.object_id(name));
+            for prefix in &self.mqtt_config.home_assistant.topics {
+                let config_topic = state
+                    .discovery_topic::<f32>(prefix)
+                    .ok_or_else(|| anyhow!("A discoverable state should have a discovery topic"))?;
+                // Keep this message the same as the debug message in mqtt::state::State::publish_home_assistant_discovery
+                debug!(?config, "Publishing Home Assistant discovery config");
+                self.mqtt_sender
+                    .enqueue_publish(config_topic, QoS::AtLeastOnce, &config, true)
+                    .await?;
+            }
         }
         let temperature_sink = state.sink();
+        let deadband = self.mqtt_config.home_assistant.temperature_deadband;
+        let temperatures = self
+            .batched(name, temperatures)
+            .filter_changed_by(|temperature| *temperature, deadband.max(0.0));
         self.tasks.push(
-            temperature_stream
-                .filter_repeated()
+            self.kept_alive(temperatures)
                 .never_error()
                 .forward(temperature_sink)
                 .boxed(),
@@ -320,6 +1638,125 @@ impl Pipeline {
         Ok(())
     }
 
+    /// Remove a disabled temperature sensor from Home Assistant, in case it was published before.
+    async fn remove_temperature_sensor(&mut self, name: &str) -> anyhow::Result<()> {
+        let home_assistant = &self.mqtt_config.home_assistant;
+        if home_assistant.enabled {
+            self.sensor_state(name, true, QoS::AtLeastOnce)
+                .remove_home_assistant_discovery::<f32>(&home_assistant.topics)
+                .await?;
+        }
+        Ok(())
+    }
+
+    // No-op version for when the upload feature isn't enabled.
+    #[cfg(not(feature = "upload"))]
+    async fn create_uploader(&mut self, settings: Option<UploadSettings>) -> anyhow::Result<()> {
+        if settings.is_some_and(|settings| settings.enabled) {
+            warn!("Uploading recorded data is enabled, but upload support has not been enabled.");
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "upload")]
+    async fn create_uploader(&mut self, settings: Option<UploadSettings>) -> anyhow::Result<()> {
+        let settings = match settings {
+            Some(settings) if settings.enabled => settings,
+            _ => return Ok(()),
+        };
+        info!(url = %settings.url, "Uploading recorded data");
+        let measurement_stream = timed_measurements(
+            Self::create_measurement_stream(&self.camera_command_channel)
+                .await?
+                .instrument(info_span!("upload_recording")),
+        );
+        let uploader = crate::upload::Uploader::new(settings);
+        self.tasks.push(uploader.run(measurement_stream).boxed());
+        Ok(())
+    }
+
+    // No-op version for when the mock_camera feature isn't enabled.
+    #[cfg(not(feature = "mock_camera"))]
+    async fn record_ring(
+        &mut self,
+        settings: &CameraSettings,
+        _objects: watch::Receiver<Vec<TrackedObject>>,
+    ) -> anyhow::Result<()> {
+        if settings.record_ring_duration().is_some() {
+            warn!("Buffered recording is enabled, but mock camera support has not been enabled.");
+        }
+        Ok(())
+    }
+
+    /// Keep the most recent camera data in memory, writing it out when SIGUSR1 is received (or
+    /// optionally when the number of people changes).
+    #[cfg(feature = "mock_camera")]
+    async fn record_ring(
+        &mut self,
+        settings: &CameraSettings,
+        objects: watch::Receiver<Vec<TrackedObject>>,
+    ) -> anyhow::Result<()> {
+        let duration = match settings.record_ring_duration() {
+            Some(duration) => duration,
+            None => return Ok(()),
+        };
+        let directory = settings.record_ring_directory();
+        info!(?duration, ?directory, "Buffering recent measurement data");
+        let records = timed_measurements(
+            Self::create_measurement_stream(&self.camera_command_channel)
+                .await?
+                .instrument(info_span!("ring_recording")),
+        )
+        .map(RingEvent::Record);
+        let user_signal = signal(SignalKind::user_defined1())?;
+        let mut triggers = futures::stream::unfold(user_signal, |mut user_signal| async move {
+            user_signal.recv().await.map(|_| ((), user_signal))
+        })
+        .map(|_| RingEvent::Write("SIGUSR1"))
+        .boxed();
+        if settings.record_ring_on_occupancy() {
+            triggers = futures::stream::select(
+                triggers,
+                occupancy_changes(objects).map(|_| RingEvent::Write("occupancy change")),
+            )
+            .boxed();
+        }
+        let ring = crate::recorded_data::RecordingRing::new(duration);
+        let ring_task = futures::stream::select(records, triggers)
+            // The number of writes is kept alongside the ring, as more than one write can happen
+            // within a second.
+            .fold((ring, 0u64), move |(mut ring, mut writes), event| {
+                let directory = directory.clone();
+                async move {
+                    match event {
+                        RingEvent::Record(record) => ring.push(record),
+                        RingEvent::Write(reason) => {
+                            let path = directory.join(format!(
+                                "recording-{}-{}.bin",
+                                time::OffsetDateTime::now_utc().unix_timestamp(),
+                                writes
+                            ));
+                            writes += 1;
+                            info!(reason, ?path, frames = ring.len(), "Writing buffered data");
+                            let written = match ring.to_bincode() {
+                                Ok(data) => tokio::fs::write(&path, data).err_into().await,
+                                Err(err) => Err(err),
+                            };
+                            // Keep buffering even if this write failed.
+                            if let Err(err) = written {
+                                warn!(?path, "Unable to write buffered data: {:?}", err);
+                            }
+                        }
+                    }
+                    (ring, writes)
+                }
+            })
+            .map(|_| Ok(()))
+            .boxed();
+        self.tasks.push(ring_task);
+        Ok(())
+    }
+
     // No-op version for when the mock_camera feature isn't enabled.
     #[cfg(not(feature = "mock_camera"))]
     async fn record_measurements(&mut self, _path: Option<PathBuf>) -> anyhow::Result<()> {
@@ -339,21 +1776,11 @@ impl Pipeline {
                 crate::recorded_data::RecordedData,
                 async_bincode::SyncDestination,
             > = file.into();
-            let measurement_stream = Self::create_measurement_stream(&self.camera_command_channel)
-                .await?
-                .instrument(info_span!("mock_recording"))
-                .scan(
-                    None,
-                    |last_frame_time: &mut Option<std::time::Instant>, measurement: Measurement| {
-                        // Swap in the measurement time for this frame for the previous time. The first
-                        // frame has `None` as the previous time, so it uses 0 for the duration.
-                        let previous_instant = last_frame_time.replace(std::time::Instant::now());
-                        let frame_delay = previous_instant.map_or(Duration::ZERO, |i| i.elapsed());
-                        let timed_measurement =
-                            crate::recorded_data::RecordedData::new(measurement, frame_delay);
-                        std::future::ready(Some(timed_measurement))
-                    },
-                );
+            let measurement_stream = timed_measurements(
+                Self::create_measurement_stream(&self.camera_command_channel)
+                    .await?
+                    .instrument(info_span!("mock_recording")),
+            );
             let recording_task = measurement_stream
                 .never_error()
                 .forward(bincode_sink)
@@ -367,11 +1794,67 @@ impl Pipeline {
     }
 }
 
+/// Events handled by the buffered recording task.
+#[cfg(feature = "mock_camera")]
+enum RingEvent {
+    Record(crate::recorded_data::RecordedData),
+    /// Write the buffered records out, with the reason for doing so.
+    Write(&'static str),
+}
+
+/// Create a stream that yields `()` every time the number of people in `objects` changes.
+#[cfg(feature = "mock_camera")]
+fn occupancy_changes(objects: watch::Receiver<Vec<TrackedObject>>) -> impl Stream<Item = ()> {
+    // The current count is yielded first, as the starting point for count_changes.
+    let counts = futures::stream::unfold((objects, true), |(mut objects, first)| async move {
+        if !first {
+            objects.changed().await.ok()?;
+        }
+        let count = objects
+            .borrow()
+            .iter()
+            .filter(|object| object.person)
+            .count();
+        Some((count, (objects, false)))
+    });
+    crate::occupancy::count_changes(counts).map(|_| ())
+}
+
+/// Pair each measurement with the time since the previous measurement.
+#[cfg(feature = "mock_camera")]
+fn timed_measurements<S>(
+    measurement_stream: S,
+) -> impl Stream<Item = crate::recorded_data::RecordedData>
+where
+    S: Stream<Item = Measurement>,
+{
+    measurement_stream.scan(
+        None,
+        |last_frame_time: &mut Option<std::time::Instant>, measurement: Measurement| {
+            // Swap in the measurement time for this frame for the previous time. The first
+            // frame has `None` as the previous time, so it uses 0 for the duration.
+            let previous_instant = last_frame_time.replace(std::time::Instant::now());
+            let frame_delay = previous_instant.map_or(Duration::ZERO, |i| i.elapsed());
+            let timed_measurement =
+                crate::recorded_data::RecordedData::new(measurement, frame_delay);
+            std::future::ready(Some(timed_measurement))
+        },
+    )
+}
+
 impl Future for Pipeline {
     type Output = anyhow::Result<()>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let mut this = self.project();
+        // The camera and MQTT client are supposed to run until the pipeline is shut down, so if
+        // either of them finish early (even successfully) the pipeline is done.
+        for task in [this.camera_task, this.mqtt_task] {
+            if let Poll::Ready(res) = task.as_mut().poll(cx) {
+                debug!(result = ?res, "Pipeline terminating");
+                return Poll::Ready(res);
+            }
+        }
         Poll::Ready(loop {
             if let Some(res) = ready!(this.tasks.as_mut().poll_next(cx)) {
                 debug!(result = ?res, "Pipeline terminating");
@@ -381,14 +1864,47 @@ impl Future for Pipeline {
     }
 }
 
+/// Reduce the noise in the images of a measurement stream.
+///
+/// The spatial filter is applied to each image first, then an exponential moving average if
+/// `smoothing` is set. If neither is enabled the stream is returned unchanged.
+fn filter_measurements(
+    measurement_stream: MeasurementStream<'static>,
+    spatial_filter: render::SpatialFilter,
+    smoothing: Option<f32>,
+) -> MeasurementStream<'static> {
+    let measurement_stream = match spatial_filter {
+        render::SpatialFilter::None => measurement_stream,
+        spatial_filter => measurement_stream
+            .map(move |measurement| Measurement {
+                image: Arc::new(spatial_filter.apply(&measurement.image)),
+                ..measurement
+            })
+            .boxed(),
+    };
+    let smoothing = match smoothing {
+        None => return measurement_stream,
+        Some(smoothing) => smoothing,
+    };
+    let mut average = ExponentialMovingAverage::new(smoothing);
+    measurement_stream
+        .map(move |measurement| Measurement {
+            image: Arc::new(average.update(ThermalImage::clone(&measurement.image))),
+            ..measurement
+        })
+        .boxed()
+}
+
 fn create_renderer(
     measurement_stream: MeasurementStream<'static>,
     settings: render::RenderSettings,
     frame_rate_limit: Option<Duration>,
-) -> anyhow::Result<(spmc::Sender<BytesImage>, InnerTask)> {
+    objects: watch::Receiver<Vec<TrackedObject>>,
+) -> anyhow::Result<(spmc::Sender<BytesImage>, SharedRenderer, InnerTask)> {
     let renderer = Arc::new(AsyncMutex::new(render::layer::ImageLayers::try_from(
         settings,
     )?));
+    let shared_renderer = Arc::clone(&renderer);
     let rendered_stream = match frame_rate_limit {
         None => measurement_stream,
         Some(limit) => tokio_stream::StreamExt::throttle(measurement_stream, limit).boxed(),
@@ -396,14 +1912,470 @@ fn create_renderer(
     .instrument(info_span!("render_stream"))
     .then(move |measurement| {
         let renderer = Arc::clone(&renderer);
+        let objects = objects.borrow().clone();
         async move {
             let unlocked_renderer = renderer.lock().await;
-            unlocked_renderer.render(measurement).await
+            unlocked_renderer.render(measurement, objects).await
         }
     });
     let rendered_multiplexer = spmc::Sender::default();
     let task = rendered_stream
         .forward(rendered_multiplexer.clone())
         .boxed();
-    Ok((rendered_multiplexer, task))
+    Ok((rendered_multiplexer, shared_renderer, task))
+}
+
+/// Replace the renderer if the render settings in `new` are valid, returning the settings now in
+/// use.
+async fn reload_render_settings(
+    renderer: &SharedRenderer,
+    current: Settings,
+    new: Settings,
+) -> Settings {
+    for setting in current.restart_required_changes(&new) {
+        warn!(
+            setting,
+            "Ignoring change to setting that requires a restart"
+        );
+    }
+    // Smoothing and spatial filtering happen before rendering, so they can't be changed here.
+    let render_settings = render::RenderSettings {
+        smoothing: current.render.smoothing,
+        spatial_filter: current.render.spatial_filter,
+        ..new.render
+    };
+    match render::layer::ImageLayers::try_from(render_settings.clone()) {
+        Ok(layers) => {
+            *renderer.lock().await = layers;
+            info!(?render_settings, "Reloaded render settings");
+            Settings {
+                render: render_settings,
+                ..current
+            }
+        }
+        Err(err) => {
+            warn!(
+                "Invalid render settings, keeping the current ones: {:?}",
+                err
+            );
+            current
+        }
+    }
+}
+
+/// Switch the camera between the idle and full frame rates as stream clients come and go.
+async fn throttle_idle_camera(
+    command_channel: mpsc::Sender<CameraCommand>,
+    subscribers: TreeCount,
+    idle_frame_rate: f32,
+    frame_rate: f32,
+) -> anyhow::Result<()> {
+    loop {
+        subscribers.idle().await;
+        debug!(
+            idle_frame_rate,
+            "No stream clients, reducing camera frame rate"
+        );
+        command_channel.send(CameraCommand::SetFrameRate(idle_frame_rate))?;
+        let count = subscribers.clone().await;
+        debug!(
+            count,
+            frame_rate, "Stream client connected, restoring camera frame rate"
+        );
+        command_channel.send(CameraCommand::SetFrameRate(frame_rate))?;
+    }
+}
+
+/// The ambient temperature from each measurement, averaged over the last `window` measurements.
+///
+/// The die temperature is noisy, so it can be averaged before it's published. This only affects
+/// the published ambient temperature, not the thermal image.
+fn ambient_temperatures(
+    measurements: impl Stream<Item = Measurement>,
+    window: NonZeroU16,
+    unit: TemperatureUnit,
+) -> impl Stream<Item = f32> {
+    let mut average = MovingAverage::new(window);
+    measurements.map(move |measurement| average.update(measurement.temperature).in_unit(&unit))
+}
+
+/// Ping the systemd watchdog every `interval`, as long as camera frames are still arriving.
+///
+/// If no frames have been received since the last ping, the watchdog isn't pinged and systemd
+/// will (eventually) restart the service.
+#[cfg(feature = "systemd")]
+async fn ping_watchdog(
+    mut measurements: MeasurementStream<'static>,
+    interval: Duration,
+) -> anyhow::Result<()> {
+    let mut ticker = tokio::time::interval(interval);
+    // Startup was just completed, so the first ping is always sent.
+    let mut frame_received = true;
+    loop {
+        tokio::select! {
+            measurement = measurements.next() => match measurement {
+                Some(_) => frame_received = true,
+                None => {
+                    warn!("Camera stopped, no longer pinging systemd watchdog");
+                    return Ok(());
+                }
+            },
+            _ = ticker.tick() => {
+                if frame_received {
+                    crate::systemd::notify("WATCHDOG=1")?;
+                } else {
+                    warn!("No camera frames received, skipping systemd watchdog ping");
+                }
+                frame_received = false;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryFrom;
+    use std::num::NonZeroU16;
+    use std::sync::{mpsc, Arc};
+    use std::thread;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use futures::future::{self, FutureExt};
+    use futures::stream::{self, StreamExt};
+    use tokio::sync::{broadcast, watch, Mutex as AsyncMutex};
+
+    use super::{ambient_temperatures, throttle_idle_camera, Pipeline, TaskList};
+    use crate::camera::{CameraCommand, Measurement};
+    use crate::image_buffer::ThermalImage;
+    use crate::mqtt::{MqttClient, MqttSettings};
+    use crate::occupancy::TrackedObject;
+    use crate::render::{self, RenderSettings};
+    #[cfg(feature = "mock_camera")]
+    use crate::settings::Settings;
+    use crate::spmc;
+    use crate::stream::StreamAuth;
+    use crate::temperature::{Temperature, TemperatureUnit};
+
+    /// Create a pipeline without a camera, returning it and a handle to the number of
+    /// measurement subscriptions made.
+    fn bare_pipeline(mqtt_config: MqttSettings) -> (Pipeline, thread::JoinHandle<usize>) {
+        let (camera_command_channel, commands) = mpsc::channel();
+        // Stand in for the camera, answering subscriptions until the pipeline is dropped.
+        let subscriptions = thread::spawn(move || {
+            let (measurements, _) = broadcast::channel(1);
+            let mut count = 0;
+            for command in commands {
+                if let CameraCommand::Subscribe(response) = command {
+                    response.send(measurements.subscribe()).unwrap();
+                    count += 1;
+                }
+            }
+            count
+        });
+        let renderer = render::layer::ImageLayers::try_from(RenderSettings::default()).unwrap();
+        let mqtt_client = MqttClient::new(&mqtt_config).unwrap();
+        let pipeline = Pipeline {
+            camera_command_channel,
+            dropped_frames: Arc::default(),
+            background_reset: Arc::default(),
+            heatmap: None,
+            rendered_source: spmc::Sender::default(),
+            renderer: Arc::new(AsyncMutex::new(renderer)),
+            mqtt_sender: mqtt_client.new_sender(),
+            status_topic: mqtt_client.status_topic().to_string(),
+            hass_device: Pipeline::create_device(&mqtt_config.name, "test".to_string()),
+            mqtt_config,
+            camera_task: future::pending().boxed(),
+            mqtt_task: future::pending().boxed(),
+            tasks: TaskList::new(),
+        };
+        (pipeline, subscriptions)
+    }
+
+    async fn thermometer_subscriptions(source: &str) -> (usize, usize) {
+        let mqtt_config: MqttSettings = toml::from_str(source).unwrap();
+        let (mut pipeline, subscriptions) = bare_pipeline(mqtt_config);
+        pipeline.create_thermometer().await.unwrap();
+        let task_count = pipeline.tasks.len();
+        drop(pipeline);
+        (task_count, subscriptions.join().unwrap())
+    }
+
+    /// The Home Assistant discovery config topics published by the thermometer, and whether each
+    /// one was removed (published with an empty payload).
+    async fn thermometer_discovery(source: &str) -> Vec<(String, bool)> {
+        let mqtt_config: MqttSettings = toml::from_str(source).unwrap();
+        let (mut pipeline, _) = bare_pipeline(mqtt_config);
+        pipeline.create_thermometer().await.unwrap();
+        let mut configs: Vec<_> = pipeline
+            .mqtt_sender
+            .retained_payloads()
+            .await
+            .into_iter()
+            .filter(|(topic, _)| topic.ends_with("/config"))
+            .map(|(topic, payload)| (topic, payload.is_empty()))
+            .collect();
+        configs.sort();
+        configs
+    }
+
+    #[tokio::test]
+    async fn thermometer_enabled() {
+        let source = r#"
+        name = "example"
+        server = "mqtt://127.0.0.1"
+        "#;
+        // The ambient temperature, then the three scene temperatures.
+        assert_eq!(thermometer_subscriptions(source).await, (4, 4));
+    }
+
+    #[tokio::test]
+    async fn ambient_disabled() {
+        let source = r#"
+        name = "example"
+        server = "mqtt://127.0.0.1"
+        [home_assistant]
+        enabled = true
+        publish_ambient = false
+        "#;
+        assert_eq!(thermometer_subscriptions(source).await, (3, 3));
+    }
+
+    #[tokio::test]
+    async fn ambient_disabled_removes_discovery() {
+        let source = r#"
+        name = "example"
+        server = "mqtt://127.0.0.1"
+        [home_assistant]
+        enabled = true
+        publish_ambient = false
+        topic = ["homeassistant", "upstairs"]
+        "#;
+        let configs = thermometer_discovery(source).await;
+        // The three scene temperatures and the ambient temperature, under both prefixes.
+        assert_eq!(configs.len(), 8);
+        let removed: Vec<_> = configs
+            .iter()
+            .filter(|(_, removed)| *removed)
+            .map(|(topic, _)| topic.as_str())
+            .collect();
+        assert_eq!(removed.len(), 2);
+        assert!(removed[0].starts_with("homeassistant/sensor/"));
+        assert!(removed[1].starts_with("upstairs/sensor/"));
+        assert!(removed
+            .iter()
+            .all(|topic| topic.ends_with("_temperature_r-u-still-there/config")));
+    }
+
+    #[tokio::test]
+    async fn thermometer_disabled() {
+        let source = r#"
+        name = "example"
+        server = "mqtt://127.0.0.1"
+        thermometer = false
+        [home_assistant]
+        enabled = true
+        "#;
+        assert_eq!(thermometer_subscriptions(source).await, (0, 0));
+        let configs = thermometer_discovery(source).await;
+        assert_eq!(configs.len(), 4);
+        assert!(configs.iter().all(|(_, removed)| *removed));
+    }
+
+    #[tokio::test]
+    async fn objects_route() {
+        let (sender, receiver) = watch::channel(Vec::new());
+        let route = Pipeline::create_objects_route(receiver);
+        let response = warp::test::request()
+            .path("/api/objects")
+            .reply(&route)
+            .await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.body(), "[]");
+        sender
+            .send(vec![TrackedObject {
+                id: 3,
+                center: [1.0, 2.0],
+                bounding_box: [0, 1, 2, 3],
+                temperature: 30.0,
+                person: true,
+                dwell_time: 12.5,
+            }])
+            .unwrap();
+        let response = warp::test::request()
+            .path("/api/objects")
+            .reply(&route)
+            .await;
+        let objects: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(objects[0]["id"], 3);
+        assert_eq!(objects[0]["dwell_time"], 12.5);
+    }
+
+    /// Ensure the health check stays public when the other routes require authorization.
+    #[tokio::test]
+    async fn public_health_route() {
+        let mqtt_config: MqttSettings = toml::from_str(
+            r#"
+            name = "example"
+            server = "mqtt://127.0.0.1"
+            "#,
+        )
+        .unwrap();
+        let (pipeline, _) = bare_pipeline(mqtt_config);
+        let (_sender, objects) = watch::channel(Vec::new());
+        let route = Pipeline::combine_http_routes(
+            pipeline.create_health_route(),
+            vec![Pipeline::create_objects_route(objects)],
+            Some(StreamAuth::Bearer {
+                token: "hunter2".into(),
+            }),
+        )
+        .unwrap();
+        // There's no MQTT broker to connect to, but the health check still answers.
+        let response = warp::test::request().path("/healthz").reply(&route).await;
+        assert_eq!(response.status(), 503);
+        let response = warp::test::request()
+            .path("/api/objects")
+            .reply(&route)
+            .await;
+        assert_eq!(response.status(), 401);
+        assert_eq!(response.headers()["WWW-Authenticate"], "Bearer");
+        let response = warp::test::request()
+            .path("/api/objects")
+            .header("Authorization", "Bearer hunter2")
+            .reply(&route)
+            .await;
+        assert_eq!(response.status(), 200);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn idle_throttle() {
+        let (command_channel, commands) = mpsc::channel();
+        let rendered: spmc::Sender<u32> = spmc::Sender::default();
+        let task = tokio::spawn(throttle_idle_camera(
+            command_channel,
+            rendered.subscribers(),
+            1.0,
+            10.0,
+        ));
+        let next_frame_rate = || match commands.recv_timeout(Duration::from_secs(5)) {
+            Ok(CameraCommand::SetFrameRate(frame_rate)) => frame_rate,
+            other => panic!("Expected a frame rate change, not {:?}", other),
+        };
+        // Nobody is watching at the start, so the camera is slowed down right away.
+        assert_eq!(next_frame_rate(), 1.0);
+        let client = rendered.stream();
+        assert_eq!(next_frame_rate(), 10.0);
+        drop(client);
+        assert_eq!(next_frame_rate(), 1.0);
+        task.abort();
+    }
+
+    #[tokio::test]
+    async fn ambient_average() {
+        let measurements = [20.0, 22.0, 24.0, 30.0]
+            .iter()
+            .map(|&temperature| Measurement {
+                image: Arc::new(ThermalImage::new(1, 1)),
+                temperature: Temperature::Celsius(temperature),
+                frame_index: 0,
+                timestamp: UNIX_EPOCH,
+            });
+        let published: Vec<f32> = ambient_temperatures(
+            stream::iter(measurements),
+            NonZeroU16::new(2).unwrap(),
+            TemperatureUnit::Celsius,
+        )
+        .collect()
+        .await;
+        assert_eq!(published, vec![20.0, 21.0, 23.0, 27.0]);
+    }
+
+    /// Capture a frame from a test pattern camera in each of the supported formats.
+    #[cfg(feature = "mock_camera")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn capture_once() {
+        let config: Settings = toml::from_str(
+            r#"
+            [camera]
+            kind = "test_pattern"
+            frame_rate = 10
+            width = 4
+            height = 2
+
+            [mqtt]
+            name = "Test"
+            server = "mqtt://127.0.0.1"
+            "#,
+        )
+        .unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let formats: [(&str, &[u8]); 3] = [
+            ("capture.json", b"{"),
+            ("capture.jpg", &[0xFF, 0xD8]),
+            ("capture.png", b"\x89PNG"),
+        ];
+        for (name, magic) in formats {
+            let path = dir.path().join(name);
+            Pipeline::capture_once(config.clone(), path.clone())
+                .await
+                .unwrap();
+            let data = std::fs::read(&path).unwrap();
+            assert!(data.starts_with(magic), "{} has the wrong format", name);
+        }
+        let unsupported = dir.path().join("capture.gif");
+        assert!(Pipeline::capture_once(config, unsupported.clone())
+            .await
+            .is_err());
+        assert!(!unsupported.exists());
+    }
+
+    /// A camera error while calibrating is returned, and nothing is saved.
+    #[cfg(feature = "mock_camera")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn calibrate_camera_error() {
+        use crate::recorded_data::RecordedData;
+        let dir = tempfile::tempdir().unwrap();
+        let recording = dir.path().join("recording.bin");
+        // The recording ends partway through calibrating, which stops the camera.
+        let records: Vec<_> = (0..3)
+            .map(|frame_index| {
+                let measurement = Measurement {
+                    image: Arc::new(ThermalImage::new(4, 2)),
+                    temperature: Temperature::Celsius(20.0),
+                    frame_index,
+                    timestamp: UNIX_EPOCH,
+                };
+                RecordedData::new(measurement, Duration::from_millis(100))
+            })
+            .collect();
+        std::fs::write(&recording, RecordedData::to_bincode(&records).unwrap()).unwrap();
+        let config: Settings = toml::from_str(&format!(
+            r#"
+            [camera]
+            kind = "mock"
+            path = {:?}
+            frame_rate = 10
+            repeat_mode = "none"
+
+            [mqtt]
+            name = "Test"
+            server = "mqtt://127.0.0.1"
+            "#,
+            recording
+        ))
+        .unwrap();
+        let output = dir.path().join("reference.bin");
+        let model = dir.path().join("model.bin");
+        let res = Pipeline::calibrate(
+            config,
+            Duration::from_secs(30),
+            output.clone(),
+            model.clone(),
+        )
+        .await;
+        assert!(res.unwrap_err().is::<crate::camera::EndOfRecording>());
+        assert!(!output.exists());
+        assert!(!model.exists());
+    }
 }
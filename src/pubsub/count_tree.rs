@@ -1,18 +1,23 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
-use futures::task::{AtomicWaker, Context, Poll};
+use futures::task::{Context, Poll, Waker};
 use futures::Future;
 use tracing::{debug, error, trace};
 
 use std::pin::Pin;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 #[derive(Debug)]
 /// The inner data for a [TreeCount]. This should be kept behind an [Arc].
 pub struct InnerTreeCount {
     parent: Option<TreeCount>,
     count: AtomicUsize,
-    waker: AtomicWaker,
+    /// The tasks waiting for the count to change.
+    ///
+    /// Multiple tasks may be waiting on the same node (for example, a [Sender][super::Sender] and
+    /// something waiting for the node to go idle), so every waker is kept instead of just the
+    /// latest one.
+    wakers: Mutex<Vec<Waker>>,
 }
 
 #[derive(Clone, Debug)]
@@ -47,7 +52,7 @@ impl InnerTreeCount {
             error!("tree count has overflowed");
             panic!("tree count has overflowed");
         }
-        self.waker.wake();
+        self.wake_all();
     }
 
     /// Decrement the count of this node and any parent nodes by one.
@@ -64,6 +69,23 @@ impl InnerTreeCount {
             error!("tree count has underflowed");
             panic!("tree count has underflowed");
         }
+        self.wake_all();
+    }
+
+    /// Register a waker to be woken the next time the count changes.
+    fn register(&self, waker: &Waker) {
+        let mut wakers = self.wakers.lock().unwrap();
+        if !wakers.iter().any(|existing| existing.will_wake(waker)) {
+            wakers.push(waker.clone());
+        }
+    }
+
+    /// Wake (and remove) every registered waker.
+    fn wake_all(&self) {
+        let wakers = std::mem::take(&mut *self.wakers.lock().unwrap());
+        for waker in wakers {
+            waker.wake();
+        }
     }
 }
 
@@ -75,7 +97,7 @@ impl TreeCount {
         Self(Arc::new(InnerTreeCount {
             parent: Some(parent),
             count: AtomicUsize::new(0),
-            waker: AtomicWaker::new(),
+            wakers: Mutex::default(),
         }))
     }
 
@@ -90,6 +112,13 @@ impl TreeCount {
     pub fn count(&self) -> usize {
         self.0.count.load(Ordering::Acquire)
     }
+
+    /// Return a [Future] that resolves once there are no outstanding tokens for this node.
+    ///
+    /// This is the opposite of `await`ing the [TreeCount] directly.
+    pub fn idle(&self) -> Idle {
+        Idle(self.clone())
+    }
 }
 
 impl Default for TreeCount {
@@ -97,7 +126,7 @@ impl Default for TreeCount {
         Self(Arc::new(InnerTreeCount {
             parent: None,
             count: AtomicUsize::new(0),
-            waker: AtomicWaker::new(),
+            wakers: Mutex::default(),
         }))
     }
 }
@@ -110,7 +139,7 @@ impl Future for TreeCount {
         if count > 0 {
             return Poll::Ready(count);
         }
-        self.0.waker.register(cx.waker());
+        self.0.register(cx.waker());
         match self.count() {
             0 => Poll::Pending,
             n => Poll::Ready(n),
@@ -118,6 +147,26 @@ impl Future for TreeCount {
     }
 }
 
+/// A [Future] that resolves when a [TreeCount] has no outstanding tokens.
+#[derive(Clone, Debug)]
+pub struct Idle(TreeCount);
+
+impl Future for Idle {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let tree = &self.0;
+        if tree.count() == 0 {
+            return Poll::Ready(());
+        }
+        tree.0.register(cx.waker());
+        match tree.count() {
+            0 => Poll::Ready(()),
+            _ => Poll::Pending,
+        }
+    }
+}
+
 impl CountToken {
     fn new(node: &TreeCount) -> Self {
         let node = Arc::clone(&node.0);
@@ -397,4 +446,57 @@ mod test {
             child_wake,
         );
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn multiple_waiters() {
+        let root = TreeCount::default();
+        let first = root.clone();
+        let second = root.clone();
+        let (first_count, second_count, _token) = tokio::join!(
+            tokio::spawn(async move { timeout(Duration::from_millis(1000), first).await }),
+            tokio::spawn(async move { timeout(Duration::from_millis(1000), second).await }),
+            tokio::spawn(async move {
+                sleep(Duration::from_millis(100)).await;
+                root.get_token()
+            })
+        );
+        assert!(
+            first_count.expect("first wait task panicked").is_ok(),
+            "First waiting task wasn't woken"
+        );
+        assert!(
+            second_count.expect("second wait task panicked").is_ok(),
+            "Second waiting task wasn't woken"
+        );
+    }
+
+    #[tokio::test]
+    async fn idle_no_wait() {
+        let root = TreeCount::default();
+        let idle = timeout(Duration::from_secs(0), root.idle()).await;
+        assert!(idle.is_ok(), "Waiting for an idle tree waited");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn idle_wait() {
+        let root = TreeCount::default();
+        let token = root.get_token();
+        let idle = root.idle();
+        let start_time = Instant::now();
+        let (idle, _) = tokio::join!(
+            tokio::spawn(async move { timeout(Duration::from_millis(1000), idle).await }),
+            tokio::spawn(async move {
+                sleep(Duration::from_millis(100)).await;
+                drop(token);
+            })
+        );
+        assert!(
+            idle.expect("inner tokio task failed").is_ok(),
+            "Idle tree wasn't woken when the last token was dropped"
+        );
+        assert!(
+            start_time.elapsed().as_millis() < 500,
+            "Idle waiting task took too long to wake"
+        );
+    }
 }
@@ -0,0 +1,114 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+use futures::Stream;
+use pin_project::pin_project;
+
+use std::fmt;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// A wrapper around a [Stream] that only yields the most recent item.
+///
+/// Every time this stream is polled, all of the items that are ready from the inner stream are
+/// read, and only the last of them is returned. This means a slow consumer skips straight to the
+/// newest item instead of working through a backlog.
+#[pin_project]
+pub struct LatestStream<S: Stream> {
+    #[pin]
+    stream: S,
+    finished: bool,
+}
+
+impl<S: Stream> LatestStream<S> {
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream,
+            finished: false,
+        }
+    }
+}
+
+impl<S: Stream> fmt::Debug for LatestStream<S>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LatestStream")
+            .field("stream", &self.stream)
+            .field("finished", &self.finished)
+            .finish()
+    }
+}
+
+impl<S: Stream> Stream for LatestStream<S> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        if *this.finished {
+            return Poll::Ready(None);
+        }
+        let mut latest = None;
+        loop {
+            match this.stream.as_mut().poll_next(cx) {
+                Poll::Ready(Some(item)) => latest = Some(item),
+                Poll::Ready(None) => {
+                    // Don't poll the inner stream again once it's done, but make sure to hand out
+                    // the last item if there was one.
+                    *this.finished = true;
+                    return Poll::Ready(latest);
+                }
+                Poll::Pending => {
+                    return match latest {
+                        Some(item) => Poll::Ready(Some(item)),
+                        None => Poll::Pending,
+                    };
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::channel::mpsc;
+    use futures::stream::{self, StreamExt};
+    use futures::FutureExt;
+
+    use super::LatestStream;
+
+    #[test]
+    fn burst_then_pending() {
+        let burst = stream::iter(1..=5).chain(stream::pending());
+        let mut latest = LatestStream::new(burst);
+        assert_eq!(latest.next().now_or_never(), Some(Some(5)));
+        assert_eq!(latest.next().now_or_never(), None);
+    }
+
+    #[test]
+    fn burst_then_end() {
+        let mut latest = LatestStream::new(stream::iter(1..=5));
+        assert_eq!(latest.next().now_or_never(), Some(Some(5)));
+        assert_eq!(latest.next().now_or_never(), Some(None));
+        assert_eq!(latest.next().now_or_never(), Some(None));
+    }
+
+    #[test]
+    fn slow_consumer() {
+        let (sender, receiver) = mpsc::unbounded();
+        let mut latest = LatestStream::new(receiver);
+        assert_eq!(latest.next().now_or_never(), None);
+        sender.unbounded_send(1).unwrap();
+        assert_eq!(latest.next().now_or_never(), Some(Some(1)));
+        // A burst of frames arrives while the consumer is busy.
+        for frame in 2..=10 {
+            sender.unbounded_send(frame).unwrap();
+        }
+        assert_eq!(latest.next().now_or_never(), Some(Some(10)));
+        assert_eq!(latest.next().now_or_never(), None);
+        sender.unbounded_send(11).unwrap();
+        sender.unbounded_send(12).unwrap();
+        sender.close_channel();
+        assert_eq!(latest.next().now_or_never(), Some(Some(12)));
+        assert_eq!(latest.next().now_or_never(), Some(None));
+    }
+}
@@ -1,8 +1,10 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 mod count_tree;
 mod counted_stream;
+mod latest_stream;
 pub mod spmc;
 
 pub use count_tree::{CountToken, TreeCount};
 pub use counted_stream::CountedStream;
+pub use latest_stream::LatestStream;
 pub use spmc::Sender;
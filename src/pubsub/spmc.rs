@@ -8,7 +8,7 @@ use tokio::sync::broadcast;
 use tokio_stream::wrappers::BroadcastStream;
 use tokio_stream::StreamExt;
 
-use super::{CountedStream, TreeCount};
+use super::{CountedStream, LatestStream, TreeCount};
 
 /// A single-producer, multiple consumer channel.
 ///
@@ -47,12 +47,28 @@ impl<T: 'static + Clone + Send> Sender<T> {
         BroadcastStream::new(self.inner.subscribe()).filter_map(Result::ok)
     }
 
+    /// Get the [TreeCount] tracking the subscribers to this `Sender` (and any children).
+    pub fn subscribers(&self) -> TreeCount {
+        self.count.clone()
+    }
+
     /// Create a stream that increments the subscriber count.
     ///
     /// When this stream is dropped, the count is automatically decremented.
     pub fn stream(&self) -> impl Stream<Item = T> {
         CountedStream::new(self.count.get_token(), self.uncounted_stream())
     }
+
+    /// Create a stream that increments the subscriber count, and only yields the most recent item.
+    ///
+    /// If the consumer of this stream can't keep up, older items are skipped instead of being
+    /// buffered. See [LatestStream] for details.
+    pub fn latest_stream(&self) -> impl Stream<Item = T> {
+        CountedStream::new(
+            self.count.get_token(),
+            LatestStream::new(self.uncounted_stream()),
+        )
+    }
 }
 
 impl<T: 'static + Clone + Send> Sink<T> for Sender<T> {
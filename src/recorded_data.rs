@@ -1,7 +1,8 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
+use std::collections::VecDeque;
 use std::io::{Read, Seek, SeekFrom};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
 use bincode::Options;
 use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
@@ -12,6 +13,12 @@ use crate::temperature::TaggedTemperature;
 
 use crate::camera::Measurement;
 
+/// The first field of records in compact formats (like bincode), marking that the frame index and
+/// timestamp are included.
+///
+/// Older records start with the width of the image instead, and no camera is this wide.
+const EXTENDED_MARKER: u32 = u32::MAX;
+
 /// A wrapper around [`Measurement`] data so that it can be serialized to a file.
 #[derive(Clone, Debug, PartialEq)]
 pub(crate) struct RecordedData {
@@ -33,16 +40,82 @@ impl RecordedData {
         let reader_length = reader.seek(SeekFrom::End(0))?;
         // Put the stream back to where it was originally
         reader.seek(SeekFrom::Start(start_position))?;
-        // These are the options async-bincode uses (but skipping the limit).
-        let bincode_options = bincode::options()
-            .with_fixint_encoding()
-            .allow_trailing_bytes();
+        let bincode_options = Self::bincode_options();
         while reader.stream_position()? < reader_length {
             let frame = bincode_options.deserialize_from(reader.by_ref())?;
             measurements.push(frame);
         }
         Ok(measurements)
     }
+
+    /// Serialize a sequence of records in the same format as a recording file.
+    pub(crate) fn to_bincode<'a, I>(records: I) -> anyhow::Result<Vec<u8>>
+    where
+        I: IntoIterator<Item = &'a Self>,
+    {
+        let bincode_options = Self::bincode_options();
+        let mut buffer = Vec::new();
+        for record in records {
+            bincode_options.serialize_into(&mut buffer, record)?;
+        }
+        Ok(buffer)
+    }
+
+    /// These are the options async-bincode uses (but skipping the limit).
+    fn bincode_options() -> impl Options + Copy {
+        bincode::options()
+            .with_fixint_encoding()
+            .allow_trailing_bytes()
+    }
+}
+
+/// A rolling buffer of the most recent records.
+///
+/// Old records are dropped as new ones are added, keeping just enough records to cover the
+/// requested duration.
+#[derive(Clone, Debug)]
+pub(crate) struct RecordingRing {
+    records: VecDeque<RecordedData>,
+    duration: Duration,
+    /// The time between the oldest and newest records.
+    buffered: Duration,
+}
+
+impl RecordingRing {
+    pub(crate) fn new(duration: Duration) -> Self {
+        Self {
+            records: VecDeque::new(),
+            duration,
+            buffered: Duration::ZERO,
+        }
+    }
+
+    pub(crate) fn push(&mut self, record: RecordedData) {
+        if !self.records.is_empty() {
+            self.buffered += record.delay;
+        }
+        self.records.push_back(record);
+        // Drop the oldest record as long as the remaining records still cover the duration.
+        while let Some(second) = self.records.get(1) {
+            match self.buffered.checked_sub(second.delay) {
+                Some(remaining) if remaining >= self.duration => {
+                    self.buffered = remaining;
+                    self.records.pop_front();
+                }
+                _ => break,
+            }
+        }
+    }
+
+    /// The number of records currently buffered.
+    pub(crate) fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Serialize the buffered records in the same format as a recording file.
+    pub(crate) fn to_bincode(&self) -> anyhow::Result<Vec<u8>> {
+        RecordedData::to_bincode(&self.records)
+    }
 }
 
 impl From<RecordedData> for Measurement {
@@ -56,13 +129,22 @@ impl Serialize for RecordedData {
     where
         S: serde::Serializer,
     {
-        let mut flattened = serializer.serialize_struct("MeasurementData", 5)?;
+        // Fields can be left out of human readable formats, but compact formats need a marker to
+        // tell newer records apart from older ones.
+        let extended = !serializer.is_human_readable();
+        let field_count = if extended { 8 } else { 7 };
+        let mut flattened = serializer.serialize_struct("MeasurementData", field_count)?;
+        if extended {
+            flattened.serialize_field("marker", &EXTENDED_MARKER)?;
+        }
         // Serialize the dimensions before the values, so you know how many values there are.
         flattened.serialize_field("width", &self.measurement.image.width())?;
         flattened.serialize_field("height", &self.measurement.image.height())?;
         flattened.serialize_field("values", self.measurement.image.as_raw())?;
         flattened.serialize_field("temperature", &self.measurement.temperature)?;
         flattened.serialize_field("delay", &self.delay)?;
+        flattened.serialize_field("frame_index", &self.measurement.frame_index)?;
+        flattened.serialize_field("timestamp", &self.measurement.timestamp)?;
         flattened.end()
     }
 }
@@ -80,6 +162,8 @@ impl<'de> Deserialize<'de> for RecordedData {
             Values,
             Temperature,
             Delay,
+            FrameIndex,
+            Timestamp,
             Unknown(&'a str),
         }
 
@@ -96,21 +180,40 @@ impl<'de> Deserialize<'de> for RecordedData {
             where
                 V: SeqAccess<'de>,
             {
-                let width: u32 = seq
+                let first: u32 = seq
                     .next_element()?
                     .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+                // Older records don't have the marker, and start with the width.
+                let extended = first == EXTENDED_MARKER;
+                let offset = extended as usize;
+                let width: u32 = if extended {
+                    seq.next_element()?
+                        .ok_or_else(|| de::Error::invalid_length(1, &self))?
+                } else {
+                    first
+                };
                 let height: u32 = seq
                     .next_element()?
-                    .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+                    .ok_or_else(|| de::Error::invalid_length(offset + 1, &self))?;
                 let pixels: Vec<f32> = seq
                     .next_element()?
-                    .ok_or_else(|| de::Error::invalid_length(2, &self))?;
+                    .ok_or_else(|| de::Error::invalid_length(offset + 2, &self))?;
                 let temperature: TaggedTemperature = seq
                     .next_element()?
-                    .ok_or_else(|| de::Error::invalid_length(3, &self))?;
+                    .ok_or_else(|| de::Error::invalid_length(offset + 3, &self))?;
                 let delay = seq
                     .next_element()?
-                    .ok_or_else(|| de::Error::invalid_length(4, &self))?;
+                    .ok_or_else(|| de::Error::invalid_length(offset + 4, &self))?;
+                let (frame_index, timestamp) = if extended {
+                    (
+                        seq.next_element()?
+                            .ok_or_else(|| de::Error::invalid_length(6, &self))?,
+                        seq.next_element()?
+                            .ok_or_else(|| de::Error::invalid_length(7, &self))?,
+                    )
+                } else {
+                    (0, SystemTime::UNIX_EPOCH)
+                };
                 let image: ThermalImage =
                     ThermalImage::from_vec(width as u32, height as u32, pixels)
                         .ok_or_else(|| de::Error::custom("Image buffer was not large enough"))?;
@@ -118,6 +221,8 @@ impl<'de> Deserialize<'de> for RecordedData {
                     measurement: Measurement {
                         image: Arc::new(image),
                         temperature: temperature.into(),
+                        frame_index,
+                        timestamp,
                     },
                     delay,
                 })
@@ -132,6 +237,8 @@ impl<'de> Deserialize<'de> for RecordedData {
                 let mut values = None;
                 let mut temperature = None;
                 let mut delay = None;
+                let mut frame_index = None;
+                let mut timestamp = None;
                 while let Some(key) = map.next_key()? {
                     match key {
                         Field::Width => {
@@ -164,6 +271,18 @@ impl<'de> Deserialize<'de> for RecordedData {
                             }
                             delay = Some(map.next_value()?);
                         }
+                        Field::FrameIndex => {
+                            if frame_index.is_some() {
+                                return Err(de::Error::duplicate_field("frame_index"));
+                            }
+                            frame_index = Some(map.next_value()?);
+                        }
+                        Field::Timestamp => {
+                            if timestamp.is_some() {
+                                return Err(de::Error::duplicate_field("timestamp"));
+                            }
+                            timestamp = Some(map.next_value()?);
+                        }
                         Field::Unknown(_) => {}
                     }
                 }
@@ -187,13 +306,25 @@ impl<'de> Deserialize<'de> for RecordedData {
                     measurement: Measurement {
                         image: Arc::new(image),
                         temperature,
+                        // Older recordings don't have these
+                        frame_index: frame_index.unwrap_or_default(),
+                        timestamp: timestamp.unwrap_or(SystemTime::UNIX_EPOCH),
                     },
                     delay,
                 })
             }
         }
 
-        const FIELDS: &[&str] = &["values", "width", "height", "temperature", "delay"];
+        const FIELDS: &[&str] = &[
+            "marker",
+            "width",
+            "height",
+            "values",
+            "temperature",
+            "delay",
+            "frame_index",
+            "timestamp",
+        ];
         deserializer.deserialize_struct("MeasurementData", FIELDS, DataVisitor)
     }
 }
@@ -201,13 +332,13 @@ impl<'de> Deserialize<'de> for RecordedData {
 #[cfg(test)]
 mod test {
     use std::sync::Arc;
-    use std::time::Duration;
+    use std::time::{Duration, UNIX_EPOCH};
 
-    use serde_test::{assert_tokens, Token};
+    use serde_test::{assert_tokens, Configure, Token};
 
     use crate::camera::Measurement;
     use crate::image_buffer::ThermalImage;
-    use crate::recorded_data::RecordedData;
+    use crate::recorded_data::{RecordedData, RecordingRing};
     use crate::temperature::Temperature;
 
     #[test]
@@ -220,6 +351,8 @@ mod test {
         let measurement = Measurement {
             image: Arc::new(empty_image),
             temperature: Temperature::Celsius(28.0),
+            frame_index: 42,
+            timestamp: UNIX_EPOCH + Duration::from_secs(1_639_451_045),
         };
         let delay = Duration::from_millis(125);
         let record = RecordedData::new(measurement, delay);
@@ -227,7 +360,7 @@ mod test {
             // Start MeasurementData
             Token::Struct {
                 name: "MeasurementData",
-                len: 5,
+                len: 7,
             },
             // width (u32)
             Token::Str("width"),
@@ -267,9 +400,113 @@ mod test {
             Token::U32(delay.subsec_nanos()),
             // End Duration
             Token::StructEnd,
+            // frame_index (u64)
+            Token::Str("frame_index"),
+            Token::U64(42),
+            // timestamp (SystemTime), which is similar to Duration
+            Token::Str("timestamp"),
+            Token::Struct {
+                name: "SystemTime",
+                len: 2,
+            },
+            Token::Str("secs_since_epoch"),
+            Token::U64(1_639_451_045),
+            Token::Str("nanos_since_epoch"),
+            Token::U32(0),
+            Token::StructEnd,
             // End MeasurementData
             Token::StructEnd,
         ]);
-        assert_tokens(&record, &tokens[..]);
+        // This is the human readable layout. Compact formats have a marker before the width.
+        assert_tokens(&record.readable(), &tokens[..]);
+    }
+
+    fn record(n: u64, delay: Duration) -> RecordedData {
+        let measurement = Measurement {
+            image: Arc::new(ThermalImage::from_pixel(4, 2, [n as f32].into())),
+            temperature: Temperature::Celsius(20.0 + n as f32),
+            frame_index: n,
+            timestamp: UNIX_EPOCH + Duration::from_millis(100 * n),
+        };
+        RecordedData::new(measurement, delay)
+    }
+
+    #[test]
+    fn bincode_round_trip() {
+        let records: Vec<RecordedData> = (0..3)
+            .map(|n| record(n, Duration::from_millis(100 * n)))
+            .collect();
+        let encoded = RecordedData::to_bincode(&records).unwrap();
+        let decoded = RecordedData::from_bincode(std::io::Cursor::new(encoded)).unwrap();
+        assert_eq!(decoded, records);
+    }
+
+    #[test]
+    fn bincode_legacy_format() {
+        // A record from before the frame index and timestamp were added.
+        let mut legacy: Vec<u8> = Vec::new();
+        legacy.extend_from_slice(&2u32.to_le_bytes());
+        legacy.extend_from_slice(&1u32.to_le_bytes());
+        legacy.extend_from_slice(&2u64.to_le_bytes());
+        legacy.extend_from_slice(&20f32.to_le_bytes());
+        legacy.extend_from_slice(&21.5f32.to_le_bytes());
+        legacy.extend_from_slice(&0u32.to_le_bytes());
+        legacy.extend_from_slice(&25f32.to_le_bytes());
+        legacy.extend_from_slice(&1u64.to_le_bytes());
+        legacy.extend_from_slice(&0u32.to_le_bytes());
+        // Two of them, to make sure the second one is read from the right place.
+        let legacy = legacy.repeat(2);
+        let decoded = RecordedData::from_bincode(std::io::Cursor::new(legacy)).unwrap();
+        assert_eq!(decoded.len(), 2);
+        for record in decoded {
+            assert_eq!(record.measurement.image.as_raw(), &vec![20.0, 21.5]);
+            assert_eq!(record.measurement.temperature, Temperature::Celsius(25.0));
+            assert_eq!(record.measurement.frame_index, 0);
+            assert_eq!(record.measurement.timestamp, UNIX_EPOCH);
+            assert_eq!(record.delay, Duration::from_secs(1));
+        }
+    }
+
+    #[test]
+    fn toml_without_frame_info() {
+        let source = r#"
+        [[data]]
+        width = 1
+        height = 1
+        values = [20.0]
+        temperature = 25.0
+        delay = { secs = 0, nanos = 100000000 }
+        "#;
+        #[derive(serde::Deserialize)]
+        struct Recording {
+            data: Vec<RecordedData>,
+        }
+        let recording: Recording = toml::from_str(source).unwrap();
+        assert_eq!(recording.data[0].measurement.frame_index, 0);
+        assert_eq!(recording.data[0].measurement.timestamp, UNIX_EPOCH);
+    }
+
+    #[test]
+    fn ring_keeps_duration() {
+        let mut ring = RecordingRing::new(Duration::from_millis(250));
+        let records: Vec<RecordedData> = (0..10)
+            .map(|n| record(n, Duration::from_millis(100)))
+            .collect();
+        for record in records.iter().cloned() {
+            ring.push(record);
+        }
+        // 300ms of records are kept, as dropping one more would only cover 200ms.
+        assert_eq!(ring.len(), 4);
+        let decoded =
+            RecordedData::from_bincode(std::io::Cursor::new(ring.to_bincode().unwrap())).unwrap();
+        assert_eq!(decoded, records[6..]);
+    }
+
+    #[test]
+    fn ring_short_recording() {
+        let mut ring = RecordingRing::new(Duration::from_secs(5));
+        ring.push(record(0, Duration::ZERO));
+        ring.push(record(1, Duration::from_millis(100)));
+        assert_eq!(ring.len(), 2);
     }
 }
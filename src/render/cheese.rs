@@ -16,9 +16,9 @@ use lru::LruCache;
 use tokio::task::spawn_blocking;
 use tracing::trace;
 
-use super::font;
+use super::font::{self, LabelFormat};
 use crate::camera::Measurement;
-use crate::temperature::{Temperature, TemperatureUnit};
+use crate::temperature::Temperature;
 use crate::util::flatten_join_result;
 
 // Just choosing 50, it felt like a good number.
@@ -27,9 +27,9 @@ const CELL_CACHE_SIZE: usize = 50;
 struct InnerRenderer {
     font: Font,
     layout: Layout,
-    // We can use just the temperature as the key as the text color is dependent on the
-    // temperature as well.
-    cache: LruCache<(Temperature, u32), GrayImage>,
+    // We can use just the temperature (and how it's formatted) as the key as the text color is
+    // dependent on the temperature as well.
+    cache: LruCache<(Temperature, u32, LabelFormat), GrayImage>,
 }
 
 impl InnerRenderer {
@@ -42,7 +42,12 @@ impl InnerRenderer {
         }
     }
 
-    fn render_cell(&mut self, temperature: Temperature, grid_size: u32) -> GrayImage {
+    fn render_cell(
+        &mut self,
+        temperature: Temperature,
+        grid_size: u32,
+        format: LabelFormat,
+    ) -> GrayImage {
         // Reset the fontdue context to a known default
         self.layout.reset(&LayoutSettings {
             x: 0.0,
@@ -54,7 +59,7 @@ impl InnerRenderer {
             ..LayoutSettings::default()
         });
         // Add the text we're rendering to the fontdue context
-        let text = format!("{:.2}", &temperature);
+        let text = format.format(&temperature);
         let style = TextStyle::new(&text, font::FONT_SIZE, 0);
         self.layout.append(&[&self.font], &style);
         // Transfer the rasterized glyphs from fontdue onto an image mask. The mask is just the
@@ -69,6 +74,34 @@ impl InnerRenderer {
         }
         mask
     }
+
+    fn render_line(&mut self, text: &str) -> GrayImage {
+        self.layout.reset(&LayoutSettings::default());
+        let style = TextStyle::new(text, font::FONT_SIZE, 0);
+        self.layout.append(&[&self.font], &style);
+        let glyphs = self.layout.glyphs().clone();
+        // The mask is sized to fit the full line height, and out to the right edge of the last
+        // glyph.
+        let width = glyphs
+            .iter()
+            .map(|glyph| glyph.x.max(0.0) as u32 + glyph.width as u32)
+            .max()
+            .unwrap_or(0);
+        let height = self.layout.height().ceil() as u32;
+        let mut mask = GrayImage::new(width, height);
+        for glyph in glyphs.iter() {
+            let (metrics, bitmap) = self.font.rasterize_config(glyph.key);
+            let bitmap = ImageBuffer::from_vec(metrics.width as u32, metrics.height as u32, bitmap)
+                .expect("the provided buffer to be large enough");
+            overlay(
+                &mut mask,
+                &bitmap,
+                glyph.x.max(0.0) as u32,
+                glyph.y.max(0.0) as u32,
+            )
+        }
+        mask
+    }
 }
 
 impl fmt::Debug for InnerRenderer {
@@ -101,7 +134,7 @@ impl font::FontRenderer for FontdueRenderer {
     async fn render(
         &self,
         grid_size: usize,
-        units: TemperatureUnit,
+        format: LabelFormat,
         measurement: Measurement,
     ) -> anyhow::Result<GrayImage> {
         let inner = Arc::clone(&self.inner);
@@ -117,13 +150,15 @@ impl font::FontRenderer for FontdueRenderer {
                 .enumerate_pixels()
                 // Map the temperature in Celsius to whatever the requested unit is.
                 .try_for_each(|(col, row, temperature_pixel)| {
-                    let temperature = Temperature::Celsius(temperature_pixel.0[0]).as_unit(&units);
-                    let mut cached_cell = inner.cache.get(&(temperature, grid_size));
+                    let temperature =
+                        Temperature::Celsius(temperature_pixel.0[0]).as_unit(&format.units);
+                    let key = (temperature, grid_size, format);
+                    let mut cached_cell = inner.cache.get(&key);
                     if cached_cell.is_none() {
                         trace!(?temperature, "cache miss");
-                        let mask = inner.render_cell(temperature, grid_size);
-                        inner.cache.put((temperature, grid_size), mask);
-                        cached_cell = inner.cache.get(&(temperature, grid_size));
+                        let mask = inner.render_cell(temperature, grid_size, format);
+                        inner.cache.put(key, mask);
+                        cached_cell = inner.cache.get(&key);
                     }
                     let cell = cached_cell.unwrap();
                     full_mask.copy_from(cell, col * grid_size, row * grid_size)
@@ -133,4 +168,11 @@ impl font::FontRenderer for FontdueRenderer {
         .map(flatten_join_result)
         .await
     }
+
+    async fn render_line(&self, text: String) -> anyhow::Result<GrayImage> {
+        let inner = Arc::clone(&self.inner);
+        spawn_blocking(move || inner.lock().unwrap().render_line(&text))
+            .await
+            .map_err(anyhow::Error::from)
+    }
 }
@@ -1,9 +1,16 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, Schema, SchemaObject, StringValidation};
+use schemars::JsonSchema;
+use serde::de::{Deserialize, Deserializer, Error as _};
+use serde::ser::{Serialize, Serializer};
 use tracing::trace;
 
+use std::borrow::Cow;
 use std::cmp::Ordering;
 use std::convert::From;
 use std::fmt;
+use std::str::FromStr;
 
 /// A type for colors specifically for finding corresponding colors that have good contrast.
 /// This type uses the WCAG 2.0 definitions of "relative luminance" and "contrast ratio". These
@@ -88,6 +95,60 @@ impl fmt::UpperHex for Color {
     }
 }
 
+impl FromStr for Color {
+    type Err = String;
+
+    /// Parse a color from a hex code, like `#ff8000`. The leading '#' is optional.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        if hex.len() != 6 || !hex.is_ascii() {
+            return Err(format!("'{}' is not a six digit hex color code", s));
+        }
+        let component = |index: usize| {
+            u8::from_str_radix(&hex[index..index + 2], 16)
+                .map_err(|_| format!("'{}' is not a six digit hex color code", s))
+        };
+        Ok(Self::new(component(0)?, component(2)?, component(4)?))
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let hex: Cow<'_, str> = Deserialize::deserialize(deserializer)?;
+        hex.parse().map_err(D::Error::custom)
+    }
+}
+
+impl JsonSchema for Color {
+    fn schema_name() -> String {
+        "Color".to_string()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            string: Some(Box::new(StringValidation {
+                pattern: Some("^#?[0-9A-Fa-f]{6}$".to_string()),
+                ..StringValidation::default()
+            })),
+            ..SchemaObject::default()
+        }
+        .into()
+    }
+}
+
+impl Serialize for Color {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(&format_args!("{:x}", self))
+    }
+}
+
 impl Color {
     pub const BLACK: Self = Self {
         red: u8::MIN,
@@ -232,6 +293,19 @@ mod color_test {
     use super::Color;
     use float_cmp::{assert_approx_eq, F32Margin};
 
+    #[test]
+    fn from_hex() {
+        assert_eq!("#ff8000".parse(), Ok(Color::new(0xff, 0x80, 0x00)));
+        assert_eq!("00FF7f".parse(), Ok(Color::new(0x00, 0xff, 0x7f)));
+        for invalid in ["", "#fff", "#ff80001", "#gg0000", "#ff80é"] {
+            assert!(
+                invalid.parse::<Color>().is_err(),
+                "Parsed invalid color {:?}",
+                invalid
+            );
+        }
+    }
+
     #[test]
     fn black() {
         let black = Color::BLACK;
@@ -270,6 +344,24 @@ mod color_test {
         assert_approx_eq!(f32, Color::WHITE.luminance(), 1.0, F32Margin::default());
     }
 
+    /// Ensure the foreground color is readable against every shade of gray.
+    #[test]
+    fn grayscale_foreground() {
+        for value in u8::MIN..=u8::MAX {
+            let background = Color::new(value, value, value);
+            let foreground = background.foreground_color();
+            let contrast = background.contrast_ratio(&foreground);
+            // WCAG 2.0 AA minimum contrast ratio for normal text
+            assert!(
+                contrast >= 4.5,
+                "Low contrast ({}) for {:x} on {:x}",
+                contrast,
+                foreground,
+                background
+            );
+        }
+    }
+
     mod contrast_ratio {
         use super::Color;
         use float_cmp::{assert_approx_eq, F32Margin};
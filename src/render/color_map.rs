@@ -1,4 +1,5 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
+use std::num::NonZeroU16;
 use std::ops::Deref;
 use std::sync::{Arc, Mutex};
 
@@ -10,19 +11,20 @@ use tracing::{instrument, trace};
 
 use crate::camera::Measurement;
 use crate::image_buffer::ThermalImage;
+use crate::settings::gradient::Gradient;
 use crate::temperature::TemperatureUnit;
 use crate::util::flatten_join_result;
 use crate::util::{Filter, MovingAverage};
 
 use super::settings::{self, RenderSettings};
 
-const DYNAMIC_AVERAGE_NUM: usize = 10;
+const DYNAMIC_AVERAGE_NUM: NonZeroU16 = NonZeroU16::new(10).unwrap();
 
 /// The limits as used by color mappers.
 #[derive(Clone, Debug, PartialEq)]
 enum Limit {
     /// Set the maximum (or minimum) to the largest (or smallest) value in the current image.
-    Dynamic(MovingAverage<f32, DYNAMIC_AVERAGE_NUM>),
+    Dynamic(MovingAverage<f32>),
 
     /// Set the maximum (or minimum) to the given value.
     Static(f32),
@@ -58,14 +60,14 @@ impl Limit {
 
 impl Default for Limit {
     fn default() -> Self {
-        Self::Dynamic(MovingAverage::new())
+        Self::Dynamic(MovingAverage::new(DYNAMIC_AVERAGE_NUM))
     }
 }
 
 impl From<settings::Limit> for Limit {
     fn from(settings_limit: settings::Limit) -> Self {
         match settings_limit {
-            settings::Limit::Dynamic => Self::Dynamic(MovingAverage::new()),
+            settings::Limit::Dynamic => Self::Dynamic(MovingAverage::new(DYNAMIC_AVERAGE_NUM)),
             settings::Limit::Static(temperature) => {
                 Self::Static(temperature.in_unit(&TemperatureUnit::Celsius))
             }
@@ -82,7 +84,8 @@ pub(crate) trait ColorMapper: std::fmt::Debug {
 pub(crate) struct ImageColorMap {
     scale_min: Arc<Mutex<Limit>>,
     scale_max: Arc<Mutex<Limit>>,
-    gradient: colorous::Gradient,
+    gradient: Gradient,
+    reversed: bool,
 }
 
 /// A color mapper using the [`image`] crate.
@@ -90,12 +93,14 @@ impl ImageColorMap {
     pub(crate) fn new(
         scale_min: settings::Limit,
         scale_max: settings::Limit,
-        gradient: colorous::Gradient,
+        gradient: Gradient,
+        reversed: bool,
     ) -> Self {
         Self {
             scale_min: Arc::new(Mutex::new(scale_min.into())),
             scale_max: Arc::new(Mutex::new(scale_max.into())),
             gradient,
+            reversed,
         }
     }
 
@@ -111,7 +116,8 @@ impl Default for ImageColorMap {
         Self::new(
             settings::Limit::default(),
             settings::Limit::default(),
-            colorous::TURBO,
+            Gradient::Turbo,
+            false,
         )
     }
 }
@@ -121,7 +127,8 @@ impl<'a> From<&'a RenderSettings> for ImageColorMap {
         Self::new(
             settings.lower_limit,
             settings.upper_limit,
-            settings.colors.into(),
+            settings.colors.clone(),
+            settings.colors_reversed,
         )
     }
 }
@@ -132,7 +139,8 @@ impl ColorMapper for ImageColorMap {
     async fn render(&self, measurement: Measurement) -> anyhow::Result<RgbaImage> {
         let scale_min = Arc::clone(&self.scale_min);
         let scale_max = Arc::clone(&self.scale_max);
-        let gradient = self.gradient;
+        let gradient = self.gradient.clone();
+        let reversed = self.reversed;
         spawn_blocking(move || {
             // Map the thermal image to an actual RGB image. We're converting to RGBA at the same time
             // as that's what resvg wants.
@@ -163,17 +171,23 @@ impl ColorMapper for ImageColorMap {
                         (lower_limit, *upper_limit)
                     }
                 }
+                // Both limits are static, so the range is fixed
                 (lower_limit, upper_limit) => {
-                    (upper_limit.current_value(), lower_limit.current_value())
+                    (lower_limit.current_value(), upper_limit.current_value())
                 }
             };
             let scale_range = new_max - new_min;
-            // Scale the input temperatures to a value 0-1.0
-            let scaled_values = measurement
-                .image
-                .iter()
-                .map(|temperature| (temperature - new_min) / scale_range);
-            // Use the colorous gradient to map the scaled values into colors
+            // Scale the input temperatures to a value 0-1.0. Temperatures outside of the range are
+            // clamped to the ends of the gradient.
+            let scaled_values = measurement.image.iter().map(|temperature| {
+                let scaled = (temperature - new_min) / scale_range;
+                if reversed {
+                    1.0 - scaled
+                } else {
+                    scaled
+                }
+            });
+            // Use the gradient to map the scaled values into colors
             let mut temperature_colors = image::RgbaImage::new(source_width, source_height);
             for (source, dest) in scaled_values.zip(temperature_colors.pixels_mut()) {
                 let gradient_color =
@@ -196,3 +210,66 @@ impl ColorMapper for ImageColorMap {
         .await
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use crate::camera::Measurement;
+    use crate::image_buffer::ThermalImage;
+    use crate::render::settings::Limit;
+    use crate::settings::gradient::Gradient;
+    use crate::temperature::Temperature;
+
+    use super::{ColorMapper, ImageColorMap};
+
+    fn measurement(temperatures: &[f32]) -> Measurement {
+        let image = ThermalImage::from_raw(temperatures.len() as u32, 1, temperatures.to_vec())
+            .expect("the buffer to be the right size");
+        Measurement {
+            image: Arc::new(image),
+            temperature: Temperature::Celsius(20.0),
+            frame_index: 0,
+            timestamp: std::time::UNIX_EPOCH,
+        }
+    }
+
+    #[tokio::test]
+    async fn static_range() {
+        let color_map = ImageColorMap::new(
+            Limit::Static(Temperature::Celsius(10.0)),
+            Limit::Static(Temperature::Celsius(30.0)),
+            Gradient::Grayscale,
+            false,
+        );
+        let rendered = color_map
+            .render(measurement(&[0.0, 10.0, 20.0, 30.0, 40.0]))
+            .await
+            .unwrap();
+        let values: Vec<u8> = rendered.pixels().map(|pixel| pixel[0]).collect();
+        // Out of range temperatures are clamped to the ends of the range
+        assert_eq!(values, vec![0, 0, 128, 255, 255]);
+        // The same temperature maps to the same color regardless of the rest of the image
+        let rendered = color_map
+            .render(measurement(&[20.0, 21.0, 22.0]))
+            .await
+            .unwrap();
+        assert_eq!(rendered.get_pixel(0, 0)[0], 128);
+    }
+
+    #[tokio::test]
+    async fn reversed() {
+        let color_map = ImageColorMap::new(
+            Limit::Static(Temperature::Celsius(10.0)),
+            Limit::Static(Temperature::Celsius(30.0)),
+            Gradient::Grayscale,
+            true,
+        );
+        let rendered = color_map
+            .render(measurement(&[0.0, 10.0, 15.0, 30.0, 40.0]))
+            .await
+            .unwrap();
+        let values: Vec<u8> = rendered.pixels().map(|pixel| pixel[0]).collect();
+        assert_eq!(values, vec![255, 255, 191, 0, 0]);
+    }
+}
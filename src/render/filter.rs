@@ -0,0 +1,108 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+use image::{ImageBuffer, Luma};
+use imageproc::filter::filter3x3;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::image_buffer::ThermalImage;
+
+/// A 3x3 approximation of a Gaussian kernel.
+const GAUSSIAN_KERNEL: [f32; 9] = [
+    1.0 / 16.0,
+    2.0 / 16.0,
+    1.0 / 16.0,
+    2.0 / 16.0,
+    4.0 / 16.0,
+    2.0 / 16.0,
+    1.0 / 16.0,
+    2.0 / 16.0,
+    1.0 / 16.0,
+];
+
+/// Spatial filters that can be applied to a single thermal image to reduce noise.
+#[derive(Clone, Copy, Debug, Default, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SpatialFilter {
+    /// No filtering.
+    #[default]
+    None,
+
+    /// Replace each pixel with the median of it and its neighbors. Good for removing single
+    /// pixels that are very different from their neighbors ("salt and pepper" noise).
+    Median3,
+
+    /// Blur the image with a 3x3 Gaussian kernel.
+    Gaussian,
+}
+
+impl SpatialFilter {
+    pub(crate) fn apply(&self, image: &ThermalImage) -> ThermalImage {
+        match self {
+            Self::None => image.clone(),
+            Self::Median3 => median3(image),
+            Self::Gaussian => filter3x3(image, &GAUSSIAN_KERNEL),
+        }
+    }
+}
+
+/// A 3x3 median filter, with the pixels on the edges of the image repeated out.
+///
+/// `imageproc` has a median filter, but only for 8-bit images.
+fn median3(image: &ThermalImage) -> ThermalImage {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return image.clone();
+    }
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let mut neighborhood = [0f32; 9];
+        let columns = [x.saturating_sub(1), x, (x + 1).min(width - 1)];
+        let rows = [y.saturating_sub(1), y, (y + 1).min(height - 1)];
+        for (index, (row, column)) in rows
+            .iter()
+            .flat_map(|row| columns.iter().map(move |column| (row, column)))
+            .enumerate()
+        {
+            neighborhood[index] = image.get_pixel(*column, *row)[0];
+        }
+        let (_, median, _) = neighborhood.select_nth_unstable_by(4, f32::total_cmp);
+        Luma([*median])
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use image::ImageBuffer;
+
+    use super::SpatialFilter;
+    use crate::image_buffer::ThermalImage;
+
+    /// A uniform 4x3 image with a single hot pixel.
+    fn hot_pixel_image() -> ThermalImage {
+        let mut image = ImageBuffer::from_pixel(4, 3, image::Luma([20.0]));
+        image.put_pixel(1, 1, image::Luma([80.0]));
+        image
+    }
+
+    #[test]
+    fn none() {
+        let image = hot_pixel_image();
+        assert_eq!(SpatialFilter::None.apply(&image), image);
+    }
+
+    #[test]
+    fn median_removes_outlier() {
+        let filtered = SpatialFilter::Median3.apply(&hot_pixel_image());
+        assert_eq!(filtered.dimensions(), (4, 3));
+        assert!(filtered.pixels().all(|pixel| pixel[0] == 20.0));
+    }
+
+    #[test]
+    fn gaussian_spreads_outlier() {
+        let filtered = SpatialFilter::Gaussian.apply(&hot_pixel_image());
+        assert_eq!(filtered.dimensions(), (4, 3));
+        // The hot pixel keeps a quarter of its difference, and the direct neighbors get an eighth.
+        assert_eq!(filtered.get_pixel(1, 1)[0], 35.0);
+        assert_eq!(filtered.get_pixel(2, 1)[0], 27.5);
+        assert_eq!(filtered.get_pixel(3, 1)[0], 20.0);
+    }
+}
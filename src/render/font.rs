@@ -3,27 +3,85 @@ use async_trait::async_trait;
 use image::GrayImage;
 
 use crate::camera::Measurement;
-use crate::temperature::TemperatureUnit;
+use crate::temperature::{Temperature, TemperatureUnit};
 
 pub(super) const DEJA_VU_SANS: &[u8] = include_bytes!("DejaVuSans-Numbers.ttf");
 pub(super) const FONT_SIZE: f32 = 12.0;
 
+/// How the temperature of each pixel is written out.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub(crate) struct LabelFormat {
+    /// The units the temperatures are displayed in.
+    pub(crate) units: TemperatureUnit,
+    /// The number of digits after the decimal point.
+    pub(crate) precision: usize,
+    /// Add the unit (ex: `°C`) after the value.
+    pub(crate) show_unit: bool,
+}
+
+impl LabelFormat {
+    /// Format a temperature (which should already be in `units`) as a label.
+    pub(crate) fn format(&self, temperature: &Temperature) -> String {
+        if self.show_unit {
+            format!("{:#.*}", self.precision, temperature)
+        } else {
+            format!("{:.*}", self.precision, temperature)
+        }
+    }
+}
+
 #[async_trait]
 pub(crate) trait FontRenderer: std::fmt::Debug {
     /// Render the text for temperatures onto a mask image
     ///
     /// Each temperature in temperatures corresponds to a square `grid_size` pixels wide. The
-    /// temperatures in `temperatures` are `f32` values in Celsius, and should be rendered as the
-    /// units specified in `units`.
+    /// temperatures in `temperatures` are `f32` values in Celsius, and should be rendered as
+    /// described by `format`.
     async fn render(
         &self,
         grid_size: usize,
-        units: TemperatureUnit,
+        format: LabelFormat,
         measurement: Measurement,
     ) -> anyhow::Result<GrayImage>;
+
+    /// Render a single line of text onto a mask image just large enough to hold it.
+    async fn render_line(&self, text: String) -> anyhow::Result<GrayImage>;
 }
 
 /// Create a font renderer based on what has been enabled for this build.
 pub(crate) fn default_renderer() -> Box<dyn FontRenderer + Send + Sync> {
     Box::new(super::cheese::FontdueRenderer::new())
 }
+
+#[cfg(test)]
+mod test {
+    use super::{LabelFormat, Temperature, TemperatureUnit};
+
+    fn label(precision: usize, show_unit: bool) -> LabelFormat {
+        LabelFormat {
+            units: TemperatureUnit::Celsius,
+            precision,
+            show_unit,
+        }
+    }
+
+    #[test]
+    fn precision() {
+        let temperature = Temperature::Celsius(21.456);
+        assert_eq!(label(2, false).format(&temperature), "21.46");
+        assert_eq!(label(1, false).format(&temperature), "21.5");
+        assert_eq!(label(0, false).format(&temperature), "21");
+    }
+
+    #[test]
+    fn unit_suffix() {
+        assert_eq!(
+            label(1, true).format(&Temperature::Celsius(21.456)),
+            "21.5°C"
+        );
+        assert_eq!(
+            label(0, true).format(&Temperature::Fahrenheit(70.6)),
+            "71°F"
+        );
+    }
+}
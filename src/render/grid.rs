@@ -0,0 +1,97 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+use image::{Rgba, RgbaImage};
+
+use super::color::Color;
+
+/// Replace a pixel with a color contrasting with it. Pixels outside of the image are ignored.
+fn contrast_pixel(image: &mut RgbaImage, x: u32, y: u32) {
+    if x < image.width() && y < image.height() {
+        let pixel = image.get_pixel_mut(x, y);
+        let not_mut: &Rgba<u8> = pixel;
+        *pixel = Color::from(not_mut).foreground_color().into();
+    }
+}
+
+/// Draw single pixel lines along the boundaries between camera pixels.
+///
+/// `scale` is the size (in pixels of `image`) of a single camera pixel. The edges of the image
+/// are left alone.
+pub(super) fn draw_grid_lines(image: &mut RgbaImage, scale: u32) {
+    if scale <= 1 {
+        // Every pixel would be a line.
+        return;
+    }
+    let (width, height) = image.dimensions();
+    for x in (scale..width).step_by(scale as usize) {
+        for y in 0..height {
+            contrast_pixel(image, x, y);
+        }
+    }
+    for y in (scale..height).step_by(scale as usize) {
+        for x in 0..width {
+            // Skip the intersections, as they've already been drawn.
+            if x % scale != 0 || x == 0 {
+                contrast_pixel(image, x, y);
+            }
+        }
+    }
+}
+
+/// Draw a cross in the center of the image, with each arm one camera pixel long.
+pub(super) fn draw_crosshair(image: &mut RgbaImage, scale: u32) {
+    let (center_x, center_y) = (image.width() / 2, image.height() / 2);
+    let arm = scale.max(1);
+    for offset in 1..=arm {
+        contrast_pixel(image, center_x.wrapping_sub(offset), center_y);
+        contrast_pixel(image, center_x + offset, center_y);
+        contrast_pixel(image, center_x, center_y.wrapping_sub(offset));
+        contrast_pixel(image, center_x, center_y + offset);
+    }
+    contrast_pixel(image, center_x, center_y);
+}
+
+#[cfg(test)]
+mod test {
+    use image::{Rgba, RgbaImage};
+
+    use super::{draw_crosshair, draw_grid_lines};
+
+    const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+    const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+    #[test]
+    fn grid_lines() {
+        let mut image = RgbaImage::from_pixel(30, 20, BLACK);
+        draw_grid_lines(&mut image, 10);
+        // Lines between the camera pixels
+        assert_eq!(*image.get_pixel(10, 5), WHITE);
+        assert_eq!(*image.get_pixel(20, 15), WHITE);
+        assert_eq!(*image.get_pixel(5, 10), WHITE);
+        // An intersection is drawn once, not flipped back.
+        assert_eq!(*image.get_pixel(10, 10), WHITE);
+        // Not on the edges, or within the camera pixels
+        assert_eq!(*image.get_pixel(0, 5), BLACK);
+        assert_eq!(*image.get_pixel(5, 0), BLACK);
+        assert_eq!(*image.get_pixel(15, 15), BLACK);
+    }
+
+    #[test]
+    fn grid_lines_contrast() {
+        let mut image = RgbaImage::from_pixel(20, 20, WHITE);
+        draw_grid_lines(&mut image, 10);
+        assert_eq!(*image.get_pixel(10, 5), BLACK);
+    }
+
+    #[test]
+    fn crosshair() {
+        let mut image = RgbaImage::from_pixel(40, 40, BLACK);
+        draw_crosshair(&mut image, 5);
+        assert_eq!(*image.get_pixel(20, 20), WHITE);
+        assert_eq!(*image.get_pixel(15, 20), WHITE);
+        assert_eq!(*image.get_pixel(25, 20), WHITE);
+        assert_eq!(*image.get_pixel(20, 15), WHITE);
+        assert_eq!(*image.get_pixel(20, 25), WHITE);
+        assert_eq!(*image.get_pixel(14, 20), BLACK);
+        assert_eq!(*image.get_pixel(21, 21), BLACK);
+    }
+}
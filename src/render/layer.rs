@@ -1,17 +1,27 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 use std::convert::TryFrom;
+use std::panic;
 
 use anyhow::anyhow;
 use bytes::Bytes;
 use futures::future::{self, FutureExt};
-use image::{Pixel, Rgba};
+use image::{imageops, GrayImage, Pixel, Rgba, RgbaImage};
+use time::OffsetDateTime;
+use tokio::task::spawn_blocking;
 
 use crate::camera::Measurement;
 use crate::image_buffer::BytesImage;
+use crate::occupancy::TrackedObject;
+use crate::temperature::TemperatureUnit;
 
 use super::color::Color;
 use super::color_map::{ColorMapper, ImageColorMap};
-use super::font::{default_renderer, FontRenderer};
+use super::font::{default_renderer, FontRenderer, LabelFormat};
+use super::grid::{draw_crosshair, draw_grid_lines};
+use super::orientation::DisplayOrientation;
+use super::outline::OutlineSettings;
+use super::overlay::OverlaySettings;
+use super::padding::Padding;
 use super::resize::{preferred_resizer, Resizer};
 use super::settings::RenderSettings;
 use super::TemperatureDisplay;
@@ -24,44 +34,177 @@ pub(crate) struct ImageLayers {
     display: TemperatureDisplay,
     grid_size: usize,
     display_temperature: TemperatureDisplay,
+    label_precision: usize,
+    label_units: bool,
+    gamma_table: Option<[u8; 256]>,
+    /// The amount and radius of the unsharp mask applied after enlarging the image.
+    sharpen: Option<(f32, f32)>,
+    overlay: Option<OverlaySettings>,
+    /// The units used for the ambient temperature in the overlay.
+    overlay_units: TemperatureUnit,
+    outline: Option<OutlineSettings>,
+    show_grid_lines: bool,
+    show_crosshair: bool,
+    padding: Option<Padding>,
+    orientation: DisplayOrientation,
+}
+
+/// Create a lookup table for applying gamma correction to 8-bit color values.
+fn gamma_table(gamma: f32) -> [u8; 256] {
+    let mut table = [0u8; 256];
+    let exponent = gamma.recip();
+    for (value, corrected) in table.iter_mut().enumerate() {
+        let normalized = value as f32 / u8::MAX as f32;
+        *corrected = (normalized.powf(exponent) * u8::MAX as f32).round() as u8;
+    }
+    table
+}
+
+/// Sharpen an image with an unsharp mask.
+///
+/// `amount` is how much of the difference between the image and a blurred copy of it is added
+/// back, and `sigma` is the radius of the blur. [imageops::unsharpen] adds the absolute difference
+/// (brightening both sides of an edge instead of increasing the contrast), so the mask is
+/// applied here instead. The alpha channel is left alone. This is done on a blocking thread, as
+/// it's a fair amount of work for large images.
+async fn sharpen(image: RgbaImage, amount: f32, sigma: f32) -> RgbaImage {
+    let sharpened = spawn_blocking(move || {
+        let blurred = imageops::blur(&image, sigma);
+        let mut sharpened = image;
+        for (pixel, blurred) in sharpened.pixels_mut().zip(blurred.pixels()) {
+            for (channel, blurred) in pixel.0[..3].iter_mut().zip(&blurred.0[..3]) {
+                let original = *channel as f32;
+                let value = original + amount * (original - *blurred as f32);
+                *channel = value.round().clamp(0.0, u8::MAX as f32) as u8;
+            }
+        }
+        sharpened
+    })
+    .await;
+    match sharpened {
+        Ok(sharpened) => sharpened,
+        Err(join_error) => panic::resume_unwind(join_error.into_panic()),
+    }
+}
+
+/// Blend a text mask onto an image, with the top left corner of the mask at `origin`.
+///
+/// The color of the text is chosen for each pixel to contrast with the image underneath it. Any
+/// part of the mask outside of the image is ignored.
+fn blend_text(image: &mut RgbaImage, mask: &GrayImage, origin: (u32, u32)) {
+    for (x, y, opacity) in mask.enumerate_pixels() {
+        // We only need to modify pixels that have some font data in them, and opacity is the
+        // easy filter for that.
+        if opacity[0] == 0 {
+            continue;
+        }
+        let (x, y) = (origin.0 + x, origin.1 + y);
+        if x >= image.width() || y >= image.height() {
+            continue;
+        }
+        let background = image.get_pixel_mut(x, y);
+        let not_mut: &Rgba<u8> = background;
+        let mut text_color: Rgba<u8> = Color::from(not_mut).foreground_color().into();
+        text_color.channels_mut()[3] = opacity[0];
+        background.blend(&text_color);
+    }
 }
 
 impl ImageLayers {
-    pub(crate) async fn render(&self, measurement: Measurement) -> anyhow::Result<BytesImage> {
+    /// Render a measurement, with `objects` being the objects currently found by the tracker.
+    pub(crate) async fn render(
+        &self,
+        measurement: Measurement,
+        objects: Vec<TrackedObject>,
+    ) -> anyhow::Result<BytesImage> {
+        // Reorienting the thermal image (instead of the rendered one) keeps the text upright, and
+        // the grid lines and outlines lined up with the camera pixels.
+        let (measurement, objects) = if self.orientation.is_identity() {
+            (measurement, objects)
+        } else {
+            self.orientation.apply(measurement, objects)
+        };
         // Cloning the measurement is (comparatively) cheap, as the thermal image is tucked behind
         // an Arc
         // TODO: figure out a way to do the color mapping asynchronously
         let colors = self.color_mapper.render(measurement.clone()).await?;
-        let background_task = self.resizer.enlarge(colors);
+        let background_task = match self.sharpen {
+            Some((amount, sigma)) => self
+                .resizer
+                .enlarge(colors)
+                .then(move |enlarged| sharpen(enlarged, amount, sigma))
+                .boxed(),
+            None => self.resizer.enlarge(colors),
+        };
         let font_task = match self.display_temperature {
             TemperatureDisplay::Disabled => future::ok(None).boxed(),
             // Look at the size of that...
-            TemperatureDisplay::Absolute(unit) => {
+            TemperatureDisplay::Absolute(units) => {
                 let font_renderer = self.font_renderer.as_ref().ok_or_else(|| {
                     anyhow!("Font renderer not created for displayed temperature units")
                 })?;
+                let format = LabelFormat {
+                    units,
+                    precision: self.label_precision,
+                    show_unit: self.label_units,
+                };
                 font_renderer
-                    .render(self.grid_size, unit, measurement.clone())
+                    .render(self.grid_size, format, measurement.clone())
                     .map(|text| Some(text).transpose())
                     .boxed()
             }
         };
-        let (mut background, font_layer_result) = futures::join!(background_task, font_task);
-        let font_layer = font_layer_result?;
+        let overlay_task = match &self.overlay {
+            None => future::ok(None).boxed(),
+            Some(overlay) => {
+                let font_renderer = self
+                    .font_renderer
+                    .as_ref()
+                    .ok_or_else(|| anyhow!("Font renderer not created for overlay"))?;
+                let text = overlay.status_line(
+                    OffsetDateTime::now_utc(),
+                    &measurement,
+                    self.overlay_units,
+                    objects.iter().filter(|object| object.person).count(),
+                );
+                font_renderer
+                    .render_line(text)
+                    .map(|text| Some(text).transpose())
+                    .boxed()
+            }
+        };
+        let (mut background, font_layer_result, overlay_result) =
+            futures::join!(background_task, font_task, overlay_task);
         // Flatten layers
-        if let Some(font_mask) = font_layer {
-            background
-                .pixels_mut()
-                .zip(font_mask.iter())
-                // We only need to modify pixels that have some font data in them, and opacity is
-                // the easy filter for that.
-                .filter(|(_, opacity)| **opacity != 0)
-                .for_each(|(background, text_mask)| {
-                    let not_mut: &Rgba<u8> = background;
-                    let mut text_color: Rgba<u8> = Color::from(not_mut).foreground_color().into();
-                    text_color.channels_mut()[3] = *text_mask;
-                    background.blend(&text_color);
-                });
+        let scale = background.width() / measurement.image.width().max(1);
+        if self.show_grid_lines {
+            draw_grid_lines(&mut background, scale);
+        }
+        if self.show_crosshair {
+            draw_crosshair(&mut background, scale);
+        }
+        if let Some(font_mask) = font_layer_result? {
+            blend_text(&mut background, &font_mask, (0, 0));
+        }
+        if let Some(outline) = &self.outline {
+            outline.draw(&mut background, &objects, scale);
+        }
+        // Padding goes on after everything aligned to the camera pixels, but before the overlay so
+        // that the overlay can be drawn in the padding.
+        if let Some(padding) = &self.padding {
+            background = padding.apply(background);
+        }
+        if let (Some(overlay), Some(overlay_mask)) = (&self.overlay, overlay_result?) {
+            let origin = overlay
+                .corner
+                .position(background.dimensions(), overlay_mask.dimensions());
+            blend_text(&mut background, &overlay_mask, origin);
+        }
+        // Gamma correction is the very last step, and leaves the alpha channel alone
+        if let Some(gamma_table) = &self.gamma_table {
+            for pixel in background.pixels_mut() {
+                pixel.apply_without_alpha(|channel| gamma_table[channel as usize]);
+            }
         }
         let width = background.width();
         let height = background.height();
@@ -75,15 +218,176 @@ impl TryFrom<RenderSettings> for ImageLayers {
     type Error = anyhow::Error;
 
     fn try_from(settings: RenderSettings) -> anyhow::Result<Self> {
-        let font_renderer = settings.units.map(|_| default_renderer());
+        let overlay = Some(settings.overlay).filter(|overlay| !overlay.is_empty());
         let resizer = preferred_resizer(&settings)?;
+        let gamma_table = match settings.gamma {
+            Some(gamma) if gamma.is_normal() && gamma > 0.0 => Some(gamma_table(gamma)),
+            Some(gamma) => return Err(anyhow!("Invalid gamma value {}", gamma)),
+            None => None,
+        };
+        let sharpen = match settings.sharpen {
+            Some(amount) if !(amount.is_normal() && amount > 0.0) => {
+                return Err(anyhow!("Invalid sharpening amount {}", amount))
+            }
+            Some(_) if !(settings.sharpen_radius.is_normal() && settings.sharpen_radius > 0.0) => {
+                return Err(anyhow!(
+                    "Invalid sharpening radius {}",
+                    settings.sharpen_radius
+                ))
+            }
+            Some(amount) => Some((amount, settings.sharpen_radius)),
+            None => None,
+        };
+        // Labels in cells too small to read them are left out.
+        let display_temperature = if settings.grid_size < settings.label_min_grid_size {
+            TemperatureDisplay::Disabled
+        } else {
+            settings.units.into()
+        };
+        let font_renderer = (display_temperature != TemperatureDisplay::Disabled
+            || overlay.is_some())
+        .then(default_renderer);
+        let padding =
+            (settings.target_aspect.is_some() || settings.margin > 0).then_some(Padding {
+                aspect: settings.target_aspect,
+                margin: settings.margin,
+                color: settings.background_color,
+            });
         Ok(Self {
             color_mapper: Box::new(ImageColorMap::from(&settings)),
             resizer,
             font_renderer,
             display: settings.units.into(),
             grid_size: settings.grid_size,
-            display_temperature: settings.units.into(),
+            display_temperature,
+            label_precision: settings.label_precision,
+            label_units: settings.label_units,
+            gamma_table,
+            sharpen,
+            overlay,
+            overlay_units: settings.units.unwrap_or(TemperatureUnit::Celsius),
+            outline: settings.outline,
+            show_grid_lines: settings.show_grid_lines,
+            show_crosshair: settings.show_crosshair,
+            padding,
+            orientation: DisplayOrientation {
+                rotation: settings.display_rotation,
+                flip_horizontal: settings.display_flip_horizontal,
+                flip_vertical: settings.display_flip_vertical,
+            },
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryFrom;
+
+    use image::{Rgba, RgbaImage};
+
+    use super::{gamma_table, sharpen, ImageLayers, RenderSettings, TemperatureDisplay};
+    use crate::temperature::TemperatureUnit;
+
+    #[test]
+    fn unity_gamma() {
+        let table = gamma_table(1.0);
+        for (value, corrected) in table.iter().enumerate() {
+            assert_eq!(value, *corrected as usize);
+        }
+    }
+
+    #[test]
+    fn gamma_endpoints() {
+        for gamma in [0.5, 1.8, 2.2] {
+            let table = gamma_table(gamma);
+            assert_eq!(table[0], 0);
+            assert_eq!(table[255], 255);
+        }
+    }
+
+    #[test]
+    fn gamma_midpoint() {
+        // A gamma larger than 1 brightens mid-tones, smaller than 1 darkens them
+        assert!(gamma_table(2.2)[128] > 128);
+        assert!(gamma_table(0.5)[128] < 128);
+        // (128 / 255) ^ (1 / 2) * 255 = 180.7
+        assert_eq!(gamma_table(2.0)[128], 181);
+    }
+
+    #[test]
+    fn invalid_gamma() {
+        for gamma in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            let settings = RenderSettings {
+                gamma: Some(gamma),
+                ..RenderSettings::default()
+            };
+            assert!(
+                ImageLayers::try_from(settings).is_err(),
+                "Accepted invalid gamma {}",
+                gamma
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_sharpen() {
+        for value in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            let settings = RenderSettings {
+                sharpen: Some(value),
+                ..RenderSettings::default()
+            };
+            assert!(
+                ImageLayers::try_from(settings).is_err(),
+                "Accepted invalid sharpening amount {}",
+                value
+            );
+            let settings = RenderSettings {
+                sharpen: Some(1.0),
+                sharpen_radius: value,
+                ..RenderSettings::default()
+            };
+            assert!(
+                ImageLayers::try_from(settings).is_err(),
+                "Accepted invalid sharpening radius {}",
+                value
+            );
+        }
+        // The radius isn't checked unless sharpening is enabled.
+        let settings = RenderSettings {
+            sharpen_radius: 0.0,
+            ..RenderSettings::default()
+        };
+        assert!(ImageLayers::try_from(settings).is_ok());
+    }
+
+    #[tokio::test]
+    async fn sharpen_edges() {
+        // An edge between dark gray and light gray.
+        let image = RgbaImage::from_fn(8, 8, |x, _| {
+            let value = if x < 4 { 64 } else { 192 };
+            Rgba([value, value, value, 255])
+        });
+        let sharpened = sharpen(image.clone(), 1.0, 1.0).await;
+        assert_eq!(sharpened.dimensions(), image.dimensions());
+        // The contrast across the edge is increased, and alpha is left alone.
+        assert!(sharpened[(3, 4)][0] < 64);
+        assert!(sharpened[(4, 4)][0] > 192);
+        // Away from the edge, nothing changes.
+        assert_eq!(sharpened[(0, 4)], image[(0, 4)]);
+        assert_eq!(sharpened[(7, 4)], image[(7, 4)]);
+        assert!(sharpened.pixels().all(|pixel| pixel[3] == 255));
+    }
+
+    #[test]
+    fn small_grid_labels() {
+        let settings = RenderSettings {
+            grid_size: 20,
+            units: Some(TemperatureUnit::Celsius),
+            label_min_grid_size: 30,
+            ..RenderSettings::default()
+        };
+        let layers = ImageLayers::try_from(settings).unwrap();
+        assert_eq!(layers.display_temperature, TemperatureDisplay::Disabled);
+        assert!(layers.font_renderer.is_none());
+    }
+}
@@ -4,10 +4,18 @@ use crate::temperature::TemperatureUnit;
 
 pub(crate) mod color;
 pub(crate) mod color_map;
+mod filter;
 pub(crate) mod font;
+mod grid;
 pub(crate) mod layer;
+mod orientation;
+mod outline;
+mod overlay;
+mod padding;
 mod resize;
 mod settings;
+pub(crate) use filter::SpatialFilter;
+pub(crate) use resize::ImageResize;
 pub(crate) use settings::RenderSettings;
 
 mod cheese;
@@ -0,0 +1,152 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+use std::sync::Arc;
+
+use image::imageops;
+
+use crate::camera::{Measurement, Rotation};
+use crate::image_buffer::ThermalImage;
+use crate::occupancy::TrackedObject;
+
+/// Rotation and flips applied to the rendered image only.
+///
+/// The camera-level orientation is applied as soon as an image is read, so it affects everything
+/// (including the tracker). This is applied when rendering, so the tracker still sees the image in
+/// the camera orientation. Like the camera orientation, the flips are applied before the rotation.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(super) struct DisplayOrientation {
+    pub(super) rotation: Rotation,
+    pub(super) flip_horizontal: bool,
+    pub(super) flip_vertical: bool,
+}
+
+impl DisplayOrientation {
+    pub(super) fn is_identity(&self) -> bool {
+        self.rotation == Rotation::Zero && !self.flip_horizontal && !self.flip_vertical
+    }
+
+    fn apply_image(&self, image: &ThermalImage) -> ThermalImage {
+        let mut image = image.clone();
+        if self.flip_vertical {
+            imageops::flip_vertical_in_place(&mut image);
+        }
+        if self.flip_horizontal {
+            imageops::flip_horizontal_in_place(&mut image);
+        }
+        match self.rotation {
+            Rotation::Zero => image,
+            Rotation::Ninety => imageops::rotate90(&image),
+            Rotation::OneEighty => {
+                imageops::rotate180_in_place(&mut image);
+                image
+            }
+            Rotation::TwoSeventy => imageops::rotate270(&image),
+        }
+    }
+
+    /// Move a single pixel coordinate in an image `size` pixels large.
+    fn apply_point(&self, (x, y): (u32, u32), (width, height): (u32, u32)) -> (u32, u32) {
+        let x = if self.flip_horizontal {
+            width - 1 - x
+        } else {
+            x
+        };
+        let y = if self.flip_vertical {
+            height - 1 - y
+        } else {
+            y
+        };
+        match self.rotation {
+            Rotation::Zero => (x, y),
+            Rotation::Ninety => (height - 1 - y, x),
+            Rotation::OneEighty => (width - 1 - x, height - 1 - y),
+            Rotation::TwoSeventy => (y, width - 1 - x),
+        }
+    }
+
+    /// Reorient a measurement (and the objects found in it) for display.
+    pub(super) fn apply(
+        &self,
+        measurement: Measurement,
+        mut objects: Vec<TrackedObject>,
+    ) -> (Measurement, Vec<TrackedObject>) {
+        let size = measurement.image.dimensions();
+        for object in objects.iter_mut() {
+            let [min_x, min_y, max_x, max_y] = object.bounding_box;
+            let (x0, y0) = self.apply_point((min_x, min_y), size);
+            let (x1, y1) = self.apply_point((max_x, max_y), size);
+            object.bounding_box = [x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)];
+        }
+        let measurement = Measurement {
+            image: Arc::new(self.apply_image(&measurement.image)),
+            ..measurement
+        };
+        (measurement, objects)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DisplayOrientation, Rotation, ThermalImage};
+
+    /// A 3x2 image, with each pixel numbered from the top left.
+    fn test_image() -> ThermalImage {
+        ThermalImage::from_raw(3, 2, vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0]).unwrap()
+    }
+
+    fn oriented(rotation: Rotation, flip_horizontal: bool) -> DisplayOrientation {
+        DisplayOrientation {
+            rotation,
+            flip_horizontal,
+            flip_vertical: false,
+        }
+    }
+
+    /// Check that moving every pixel coordinate matches how the image itself is moved.
+    fn check_points(orientation: DisplayOrientation) {
+        let image = test_image();
+        let reoriented = orientation.apply_image(&image);
+        for (x, y, pixel) in image.enumerate_pixels() {
+            let (new_x, new_y) = orientation.apply_point((x, y), image.dimensions());
+            assert_eq!(
+                reoriented.get_pixel(new_x, new_y),
+                pixel,
+                "{:?} moved ({}, {}) to the wrong place",
+                orientation,
+                x,
+                y
+            );
+        }
+    }
+
+    #[test]
+    fn identity() {
+        let orientation = DisplayOrientation::default();
+        assert!(orientation.is_identity());
+        assert_eq!(orientation.apply_image(&test_image()), test_image());
+    }
+
+    #[test]
+    fn rotate_ninety() {
+        let image = oriented(Rotation::Ninety, false).apply_image(&test_image());
+        assert_eq!(image.dimensions(), (2, 3));
+        assert_eq!(image.as_raw(), &vec![3.0, 0.0, 4.0, 1.0, 5.0, 2.0]);
+    }
+
+    #[test]
+    fn points_match_image() {
+        for rotation in [
+            Rotation::Zero,
+            Rotation::Ninety,
+            Rotation::OneEighty,
+            Rotation::TwoSeventy,
+        ] {
+            for flip_horizontal in [false, true] {
+                check_points(oriented(rotation, flip_horizontal));
+                check_points(DisplayOrientation {
+                    flip_vertical: true,
+                    ..oriented(rotation, flip_horizontal)
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+use image::{Rgba, RgbaImage};
+use imageproc::drawing::draw_hollow_rect_mut;
+use imageproc::rect::Rect;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::color::Color;
+use crate::occupancy::TrackedObject;
+
+/// Which tracked objects to outline.
+#[derive(Clone, Copy, Debug, Default, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum OutlinedObjects {
+    /// Only objects considered to be people.
+    #[default]
+    Persons,
+
+    /// Every object being tracked, including those not (or not yet) considered people.
+    All,
+}
+
+/// Outlines drawn around the objects found by the tracker.
+#[derive(Clone, Copy, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
+pub(crate) struct OutlineSettings {
+    /// The color of the outlines. Defaults to white.
+    #[serde(default = "OutlineSettings::default_color")]
+    pub(crate) color: Color,
+
+    /// Which objects to outline. Defaults to only people.
+    #[serde(default)]
+    pub(crate) objects: OutlinedObjects,
+}
+
+impl OutlineSettings {
+    fn default_color() -> Color {
+        Color::WHITE
+    }
+
+    /// Draw the bounding box of each selected object onto an image.
+    ///
+    /// `scale` is the size (in pixels of `image`) of a single camera pixel.
+    pub(super) fn draw(&self, image: &mut RgbaImage, objects: &[TrackedObject], scale: u32) {
+        let color: Rgba<u8> = self.color.into();
+        objects
+            .iter()
+            .filter(|object| self.objects == OutlinedObjects::All || object.person)
+            .for_each(|object| {
+                let [min_x, min_y, max_x, max_y] = object.bounding_box;
+                // The bounding box is inclusive, so the outline goes around the outside edges of
+                // the corner pixels.
+                let rect = Rect::at((min_x * scale) as i32, (min_y * scale) as i32)
+                    .of_size((max_x - min_x + 1) * scale, (max_y - min_y + 1) * scale);
+                draw_hollow_rect_mut(image, rect, color);
+            });
+    }
+}
+
+impl Default for OutlineSettings {
+    fn default() -> Self {
+        Self {
+            color: Self::default_color(),
+            objects: OutlinedObjects::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use image::{Rgba, RgbaImage};
+
+    use super::{OutlineSettings, OutlinedObjects};
+    use crate::occupancy::TrackedObject;
+    use crate::render::color::Color;
+
+    fn object(id: u64, bounding_box: [u32; 4], person: bool) -> TrackedObject {
+        TrackedObject {
+            id,
+            center: [0.0, 0.0],
+            bounding_box,
+            temperature: 30.0,
+            person,
+            dwell_time: 0.0,
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let parsed: OutlineSettings = toml::from_str("").unwrap();
+        assert_eq!(parsed, OutlineSettings::default());
+        let parsed: OutlineSettings =
+            toml::from_str("color = \"#ff00ff\"\nobjects = \"all\"").unwrap();
+        assert_eq!(
+            parsed,
+            OutlineSettings {
+                color: Color::new(0xff, 0x00, 0xff),
+                objects: OutlinedObjects::All,
+            }
+        );
+        assert!(toml::from_str::<OutlineSettings>("color = \"magenta\"").is_err());
+    }
+
+    #[test]
+    fn draw() {
+        let black = Rgba([0, 0, 0, 255]);
+        let white = Rgba([255, 255, 255, 255]);
+        let objects = [
+            object(1, [1, 1, 2, 1], true),
+            object(2, [4, 3, 4, 3], false),
+        ];
+        let mut persons_only = RgbaImage::from_pixel(60, 40, black);
+        OutlineSettings::default().draw(&mut persons_only, &objects, 10);
+        // The person is outlined around the outside of the pixels at (1, 1) and (2, 1).
+        assert_eq!(*persons_only.get_pixel(10, 10), white);
+        assert_eq!(*persons_only.get_pixel(29, 19), white);
+        assert_eq!(*persons_only.get_pixel(15, 15), black);
+        assert_eq!(*persons_only.get_pixel(30, 20), black);
+        // The other object isn't.
+        assert_eq!(*persons_only.get_pixel(40, 30), black);
+        let mut all = RgbaImage::from_pixel(60, 40, black);
+        let settings = OutlineSettings {
+            objects: OutlinedObjects::All,
+            ..OutlineSettings::default()
+        };
+        settings.draw(&mut all, &objects, 10);
+        assert_eq!(*all.get_pixel(10, 10), white);
+        assert_eq!(*all.get_pixel(40, 30), white);
+        assert_eq!(*all.get_pixel(49, 39), white);
+    }
+}
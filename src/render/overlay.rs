@@ -0,0 +1,163 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::camera::Measurement;
+use crate::temperature::TemperatureUnit;
+
+/// The space (in pixels) between the overlay and the edges of the image.
+const OVERLAY_MARGIN: u32 = 4;
+
+/// The text placed between each element of the status line.
+const SEPARATOR: &str = " · ";
+
+/// The corner of the image the overlay is drawn in.
+#[derive(Clone, Copy, Debug, Default, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Corner {
+    #[default]
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Corner {
+    /// The position of the top left pixel of an overlay `size` pixels large, when drawn in this
+    /// corner of an image `bounds` pixels large.
+    pub(super) fn position(&self, bounds: (u32, u32), size: (u32, u32)) -> (u32, u32) {
+        let right = bounds.0.saturating_sub(size.0 + OVERLAY_MARGIN);
+        let bottom = bounds.1.saturating_sub(size.1 + OVERLAY_MARGIN);
+        match self {
+            Self::TopLeft => (OVERLAY_MARGIN, OVERLAY_MARGIN),
+            Self::TopRight => (right, OVERLAY_MARGIN),
+            Self::BottomLeft => (OVERLAY_MARGIN, bottom),
+            Self::BottomRight => (right, bottom),
+        }
+    }
+}
+
+/// A line of status information drawn on top of the rendered image.
+///
+/// The bundled font only has digits and a few symbols, so the status line is kept to numbers.
+#[derive(Clone, Copy, Debug, Default, Deserialize, JsonSchema, PartialEq, Serialize)]
+pub(crate) struct OverlaySettings {
+    /// Show the current date and time (in UTC).
+    #[serde(default)]
+    pub(crate) timestamp: bool,
+
+    /// Show the ambient temperature measured by the camera.
+    #[serde(default)]
+    pub(crate) ambient: bool,
+
+    /// Show the number of people currently detected.
+    #[serde(default)]
+    pub(crate) occupancy: bool,
+
+    /// Which corner of the image to draw the overlay in.
+    #[serde(default)]
+    pub(crate) corner: Corner,
+}
+
+impl OverlaySettings {
+    /// Whether there's anything to show in the overlay.
+    pub(crate) fn is_empty(&self) -> bool {
+        !(self.timestamp || self.ambient || self.occupancy)
+    }
+
+    /// Create the text for the status line.
+    pub(super) fn status_line(
+        &self,
+        now: OffsetDateTime,
+        measurement: &Measurement,
+        units: TemperatureUnit,
+        occupancy: usize,
+    ) -> String {
+        let mut elements = Vec::with_capacity(3);
+        if self.timestamp {
+            // There isn't a colon in the font, so periods are used to separate the time instead.
+            elements.push(format!(
+                "{:04}-{:02}-{:02} {:02}.{:02}.{:02}",
+                now.year(),
+                now.month() as u8,
+                now.day(),
+                now.hour(),
+                now.minute(),
+                now.second()
+            ));
+        }
+        if self.ambient {
+            elements.push(format!("{:#.1}", measurement.temperature.as_unit(&units)));
+        }
+        if self.occupancy {
+            elements.push(occupancy.to_string());
+        }
+        elements.join(SEPARATOR)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use image::ImageBuffer;
+    use time::OffsetDateTime;
+
+    use super::{Corner, OverlaySettings};
+    use crate::camera::Measurement;
+    use crate::temperature::{Temperature, TemperatureUnit};
+
+    #[test]
+    fn defaults() {
+        let parsed: OverlaySettings = toml::from_str("").unwrap();
+        assert!(parsed.is_empty());
+        assert_eq!(parsed.corner, Corner::TopLeft);
+        let parsed: OverlaySettings =
+            toml::from_str("occupancy = true\ncorner = \"bottom_right\"").unwrap();
+        assert!(!parsed.is_empty());
+        assert_eq!(parsed.corner, Corner::BottomRight);
+    }
+
+    #[test]
+    fn corner_position() {
+        let bounds = (400, 300);
+        let size = (100, 12);
+        assert_eq!(Corner::TopLeft.position(bounds, size), (4, 4));
+        assert_eq!(Corner::TopRight.position(bounds, size), (296, 4));
+        assert_eq!(Corner::BottomLeft.position(bounds, size), (4, 284));
+        assert_eq!(Corner::BottomRight.position(bounds, size), (296, 284));
+        // Overlays larger than the image are pushed up against the top left.
+        assert_eq!(Corner::BottomRight.position((50, 10), size), (0, 0));
+    }
+
+    #[test]
+    fn status_line() {
+        let measurement = Measurement {
+            image: Arc::new(ImageBuffer::new(8, 8)),
+            temperature: Temperature::Celsius(21.0),
+            frame_index: 0,
+            timestamp: std::time::UNIX_EPOCH,
+        };
+        // 2021-12-14 03:04:05 UTC
+        let now = OffsetDateTime::from_unix_timestamp(1_639_451_045).unwrap();
+        let all = OverlaySettings {
+            timestamp: true,
+            ambient: true,
+            occupancy: true,
+            corner: Corner::default(),
+        };
+        assert_eq!(
+            all.status_line(now, &measurement, TemperatureUnit::Celsius, 2),
+            "2021-12-14 03.04.05 · 21.0°C · 2"
+        );
+        let ambient = OverlaySettings {
+            ambient: true,
+            ..OverlaySettings::default()
+        };
+        assert_eq!(
+            ambient.status_line(now, &measurement, TemperatureUnit::Fahrenheit, 2),
+            "69.8°F"
+        );
+    }
+}
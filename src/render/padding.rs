@@ -0,0 +1,235 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+use std::borrow::Cow;
+use std::str::FromStr;
+
+use image::{Rgba, RgbaImage};
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, Schema, SchemaObject, StringValidation};
+use schemars::JsonSchema;
+use serde::de::{Deserializer, Error as _};
+use serde::ser::{Serialize, Serializer};
+use serde::Deserialize;
+
+use super::color::Color;
+
+/// The ratio of an image's width to its height.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct AspectRatio(f32);
+
+impl AspectRatio {
+    fn new(ratio: f32) -> Result<Self, String> {
+        if ratio.is_normal() && ratio > 0.0 {
+            Ok(Self(ratio))
+        } else {
+            Err(format!("'{}' is not a valid aspect ratio", ratio))
+        }
+    }
+}
+
+impl FromStr for AspectRatio {
+    type Err = String;
+
+    /// Parse an aspect ratio from either a pair of numbers like `16:9`, or a single number like
+    /// `1.5`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("'{}' is not a valid aspect ratio", s);
+        let parse = |number: &str| number.trim().parse::<f32>().map_err(|_| invalid());
+        let ratio = match s.split_once(':') {
+            Some((width, height)) => parse(width)? / parse(height)?,
+            None => parse(s)?,
+        };
+        Self::new(ratio).map_err(|_| invalid())
+    }
+}
+
+impl<'de> Deserialize<'de> for AspectRatio {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr<'a> {
+            Number(f32),
+            #[serde(borrow)]
+            Text(Cow<'a, str>),
+        }
+        match Repr::deserialize(deserializer)? {
+            Repr::Number(ratio) => Self::new(ratio),
+            Repr::Text(text) => text.parse(),
+        }
+        .map_err(D::Error::custom)
+    }
+}
+
+impl JsonSchema for AspectRatio {
+    fn schema_name() -> String {
+        "AspectRatio".to_string()
+    }
+
+    /// Aspect ratios are either a number, or a string like `16:9` (or `1.5`).
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        SchemaObject {
+            instance_type: Some(vec![InstanceType::Number, InstanceType::String].into()),
+            string: Some(Box::new(StringValidation {
+                pattern: Some(r"^\s*[0-9.]+\s*(:\s*[0-9.]+\s*)?$".to_string()),
+                ..StringValidation::default()
+            })),
+            ..SchemaObject::default()
+        }
+        .into()
+    }
+}
+
+impl Serialize for AspectRatio {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_f32(self.0)
+    }
+}
+
+/// Padding added around the rendered image, so that it fits into a fixed size space.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) struct Padding {
+    pub(super) aspect: Option<AspectRatio>,
+    pub(super) margin: u32,
+    pub(super) color: Color,
+}
+
+impl Padding {
+    /// The size of the padded image, and where the original image is placed within it.
+    fn layout(&self, width: u32, height: u32) -> ((u32, u32), (u32, u32)) {
+        let (inner_width, inner_height) = match self.aspect {
+            Some(AspectRatio(ratio)) if (width as f32) < height as f32 * ratio => {
+                // Pillarbox: too narrow, so add space to the sides.
+                ((height as f32 * ratio).round() as u32, height)
+            }
+            Some(AspectRatio(ratio)) => {
+                // Letterbox: too wide, so add space above and below.
+                (width, (width as f32 / ratio).round() as u32)
+            }
+            None => (width, height),
+        };
+        let origin = (
+            (inner_width - width) / 2 + self.margin,
+            (inner_height - height) / 2 + self.margin,
+        );
+        let size = (
+            inner_width + 2 * self.margin,
+            inner_height + 2 * self.margin,
+        );
+        (size, origin)
+    }
+
+    /// Place an image in the center of a new, padded image.
+    pub(super) fn apply(&self, image: RgbaImage) -> RgbaImage {
+        let ((width, height), (x, y)) = self.layout(image.width(), image.height());
+        if (width, height) == image.dimensions() {
+            return image;
+        }
+        let background: Rgba<u8> = self.color.into();
+        let mut padded = RgbaImage::from_pixel(width, height, background);
+        image::imageops::replace(&mut padded, &image, x, y);
+        padded
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use image::{Rgba, RgbaImage};
+
+    use super::{AspectRatio, Color, Padding};
+
+    fn padding(aspect: Option<f32>, margin: u32) -> Padding {
+        Padding {
+            aspect: aspect.map(AspectRatio),
+            margin,
+            color: Color::BLACK,
+        }
+    }
+
+    #[test]
+    fn parse_aspect() {
+        assert_eq!("16:9".parse(), Ok(AspectRatio(16.0 / 9.0)));
+        assert_eq!("4 : 3".parse(), Ok(AspectRatio(4.0 / 3.0)));
+        assert_eq!("1.5".parse(), Ok(AspectRatio(1.5)));
+        for invalid in ["", "16:", "16:0", "0", "-1", "wide", "16:9:4"] {
+            assert!(
+                invalid.parse::<AspectRatio>().is_err(),
+                "Parsed invalid aspect ratio '{}'",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn deserialize_aspect() {
+        #[derive(serde::Deserialize)]
+        struct Wrapper {
+            aspect: AspectRatio,
+        }
+        let parsed: Wrapper = toml::from_str("aspect = \"2:1\"").unwrap();
+        assert_eq!(parsed.aspect, AspectRatio(2.0));
+        let parsed: Wrapper = toml::from_str("aspect = 1.25").unwrap();
+        assert_eq!(parsed.aspect, AspectRatio(1.25));
+        assert!(toml::from_str::<Wrapper>("aspect = 0.0").is_err());
+    }
+
+    #[test]
+    fn pillarbox() {
+        // 8x8 camera into a 16:9 card
+        assert_eq!(
+            padding(Some(16.0 / 9.0), 0).layout(400, 400),
+            ((711, 400), (155, 0))
+        );
+    }
+
+    #[test]
+    fn letterbox() {
+        // 32x24 camera into a square card
+        assert_eq!(
+            padding(Some(1.0), 0).layout(320, 240),
+            ((320, 320), (0, 40))
+        );
+    }
+
+    #[test]
+    fn margin() {
+        assert_eq!(padding(None, 10).layout(320, 240), ((340, 260), (10, 10)));
+        assert_eq!(
+            padding(Some(1.0), 10).layout(320, 240),
+            ((340, 340), (10, 50))
+        );
+    }
+
+    #[test]
+    fn matching_aspect() {
+        assert_eq!(
+            padding(Some(4.0 / 3.0), 0).layout(320, 240),
+            ((320, 240), (0, 0))
+        );
+    }
+
+    #[test]
+    fn apply() {
+        let white = Rgba([255, 255, 255, 255]);
+        let image = RgbaImage::from_pixel(2, 1, white);
+        let padded = Padding {
+            color: Color::new(0x11, 0x22, 0x33),
+            ..padding(Some(1.0), 1)
+        }
+        .apply(image);
+        assert_eq!(padded.dimensions(), (4, 4));
+        let background = Rgba([0x11, 0x22, 0x33, 0xff]);
+        for (x, y, pixel) in padded.enumerate_pixels() {
+            let expected = if y == 1 && (1..=2).contains(&x) {
+                white
+            } else {
+                background
+            };
+            assert_eq!(*pixel, expected, "Unexpected pixel at ({}, {})", x, y);
+        }
+    }
+}
@@ -5,16 +5,19 @@ use std::fmt;
 use std::panic;
 
 use async_trait::async_trait;
-use image::{imageops, RgbaImage};
-use serde::Deserialize;
+use bytes::Bytes;
+use image::{imageops, ImageBuffer, Rgba, RgbaImage};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use tokio::task::spawn_blocking;
 use tracing::{debug, warn};
 
 use super::settings::RenderSettings;
+use crate::image_buffer::BytesImage;
 
 /// Different resizing methods
 
-#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, Deserialize, JsonSchema, PartialEq, Eq, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub(crate) enum Method {
     /// Nearest neighbor sampling.
@@ -154,8 +157,11 @@ impl<'a> TryFrom<&'a RenderSettings> for ImageResize {
             Method::Triangle => imageops::Triangle,
             Method::CatmullRom => imageops::CatmullRom,
             Method::Lanczos3 => imageops::Lanczos3,
-            _ => {
-                return Err(ResizeError::UnsupportedMethod);
+            // imageops doesn't have a Mitchell-Netravali filter, but Catmull-Rom is a close
+            // relative (both are cubic filters from the same family).
+            Method::Mitchell => {
+                warn!("Mitchell scaling is not available without the piston_resize feature, using Catmull-Rom instead");
+                imageops::CatmullRom
             }
         };
         Ok(Self {
@@ -185,6 +191,158 @@ impl Resizer for ImageResize {
     }
 }
 
+impl ImageResize {
+    /// Shrink an image to fit within a square `max_dimension` pixels on each side, keeping the
+    /// aspect ratio.
+    ///
+    /// Images that already fit are returned as-is, they are never enlarged. A triangle filter is
+    /// used, as it's a good fit for shrinking images that are already smooth.
+    pub(crate) fn shrink_to_fit(image: &BytesImage, max_dimension: u32) -> BytesImage {
+        match fit_within(image.dimensions(), max_dimension) {
+            None => image.clone(),
+            Some((width, height)) => {
+                let resized = imageops::resize(image, width, height, imageops::Triangle);
+                let (width, height) = resized.dimensions();
+                ImageBuffer::from_raw(width, height, Bytes::from(resized.into_raw()))
+                    .expect("the resized image to have the right number of pixels")
+            }
+        }
+    }
+}
+
+/// The size to shrink an image to so that it fits within `max_dimension` pixels, or `None` if it
+/// already fits.
+fn fit_within((width, height): (u32, u32), max_dimension: u32) -> Option<(u32, u32)> {
+    let largest = width.max(height);
+    if largest <= max_dimension {
+        return None;
+    }
+    let scale = |dimension: u32| {
+        let scaled =
+            (dimension as u64 * max_dimension as u64 + largest as u64 / 2) / largest as u64;
+        (scaled as u32).max(1)
+    };
+    Some((scale(width), scale(height)))
+}
+
+/// Convert an 8-bit sRGB value to a linear value between 0 and 1.
+fn srgb_to_linear(value: u8) -> f32 {
+    let value = value as f32 / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Convert a linear value between 0 and 1 to an 8-bit sRGB value.
+fn linear_to_srgb(value: f32) -> u8 {
+    let value = value.clamp(0.0, 1.0);
+    let encoded = if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(2.4f32.recip()) - 0.055
+    };
+    (encoded * 255.0).round() as u8
+}
+
+/// A resizer that blends colors in linear light, using [`image::imageops`].
+///
+/// The colors are converted from sRGB to linear values, resized, then converted back to sRGB. This
+/// is how image editors do high quality scaling, and avoids the darkened edges between colors
+/// that blending gamma-encoded values causes. It is quite a bit slower than the other resizers
+/// though. Alpha is resized as-is.
+#[derive(Clone, Debug)]
+pub(crate) struct LinearResize {
+    grid_size: u32,
+    filter_type: imageops::FilterType,
+    /// Lookup table for converting from sRGB to linear values.
+    to_linear: [f32; 256],
+}
+
+impl<'a> TryFrom<&'a RenderSettings> for LinearResize {
+    type Error = ResizeError;
+
+    fn try_from(settings: &'a RenderSettings) -> Result<Self, Self::Error> {
+        let filter_type = match settings.scaling_method {
+            // Nearest neighbor doesn't blend anything, so there's no difference.
+            Method::Triangle => imageops::Triangle,
+            Method::CatmullRom => imageops::CatmullRom,
+            Method::Lanczos3 => imageops::Lanczos3,
+            _ => {
+                return Err(ResizeError::UnsupportedMethod);
+            }
+        };
+        let mut to_linear = [0f32; 256];
+        for (value, linear) in to_linear.iter_mut().enumerate() {
+            *linear = srgb_to_linear(value as u8);
+        }
+        Ok(Self {
+            grid_size: settings.grid_size as u32,
+            filter_type,
+            to_linear,
+        })
+    }
+}
+
+#[async_trait]
+impl Resizer for LinearResize {
+    async fn enlarge(&self, colors: RgbaImage) -> RgbaImage {
+        let new_width = colors.width() * self.grid_size;
+        let new_height = colors.height() * self.grid_size;
+        let filter_type = self.filter_type;
+        let to_linear = self.to_linear;
+        let resized_result = spawn_blocking(move || {
+            let linear: ImageBuffer<Rgba<f32>, Vec<f32>> =
+                ImageBuffer::from_fn(colors.width(), colors.height(), |x, y| {
+                    let [red, green, blue, alpha] = colors.get_pixel(x, y).0;
+                    Rgba([
+                        to_linear[red as usize],
+                        to_linear[green as usize],
+                        to_linear[blue as usize],
+                        alpha as f32 / 255.0,
+                    ])
+                });
+            let resized = imageops::resize(&linear, new_width, new_height, filter_type);
+            RgbaImage::from_fn(new_width, new_height, |x, y| {
+                let [red, green, blue, alpha] = resized.get_pixel(x, y).0;
+                Rgba([
+                    linear_to_srgb(red),
+                    linear_to_srgb(green),
+                    linear_to_srgb(blue),
+                    (alpha.clamp(0.0, 1.0) * 255.0).round() as u8,
+                ])
+            })
+        })
+        .await;
+        match resized_result {
+            Ok(resized) => resized,
+            Err(join_error) => {
+                panic::resume_unwind(join_error.into_panic());
+            }
+        }
+    }
+}
+
+/// Create a [`LinearResize`] if resizing in linear light was requested.
+fn linear_resizer(settings: &RenderSettings) -> Option<Box<dyn Resizer + Send + Sync>> {
+    if !settings.linear_resize {
+        return None;
+    }
+    match LinearResize::try_from(settings) {
+        Ok(resizer) => {
+            debug!(method = ?settings.scaling_method, "Resizing in linear light");
+            Some(Box::new(resizer))
+        }
+        Err(_) => {
+            if settings.scaling_method != Method::Nearest {
+                warn!(method = ?settings.scaling_method, "Resizing in linear light isn't supported for this scaling method");
+            }
+            None
+        }
+    }
+}
+
 #[cfg(feature = "piston_resize")]
 mod piston {
     use std::convert::TryFrom;
@@ -312,7 +470,9 @@ mod piston {
 pub(crate) fn preferred_resizer(
     settings: &RenderSettings,
 ) -> Result<Box<dyn Resizer + Send + Sync>, ResizeError> {
-    if let Ok(resizer) = PointResize::try_from(settings) {
+    if let Some(resizer) = linear_resizer(settings) {
+        Ok(resizer)
+    } else if let Ok(resizer) = PointResize::try_from(settings) {
         debug!(method = ?settings.scaling_method, "Using custom point scaling");
         Ok(Box::new(resizer))
     } else if let Ok(resizer) = piston::PistonResize::try_from(settings) {
@@ -331,8 +491,11 @@ pub(crate) fn preferred_resizer(
 pub(crate) fn preferred_resizer(
     settings: &RenderSettings,
 ) -> Result<Box<dyn Resizer + Send + Sync>, ResizeError> {
-    // Prefer the point resizer, then the imageops resizer
-    if let Ok(resizer) = PointResize::try_from(settings) {
+    // Prefer the linear light resizer (if requested), the point resizer, then the imageops
+    // resizer
+    if let Some(resizer) = linear_resizer(settings) {
+        Ok(resizer)
+    } else if let Ok(resizer) = PointResize::try_from(settings) {
         debug!(method = ?settings.scaling_method, "Using custom point scaling");
         Ok(Box::new(resizer))
     } else if let Ok(resizer) = ImageResize::try_from(settings) {
@@ -343,3 +506,95 @@ pub(crate) fn preferred_resizer(
         Err(ResizeError::UnsupportedMethod)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryFrom;
+
+    use image::{imageops, ImageBuffer, Rgba, RgbaImage};
+
+    use super::{
+        fit_within, linear_to_srgb, srgb_to_linear, ImageResize, LinearResize, Method, Resizer,
+    };
+    use crate::render::RenderSettings;
+
+    #[test]
+    fn mitchell_fallback() {
+        let settings = RenderSettings {
+            scaling_method: Method::Mitchell,
+            ..RenderSettings::default()
+        };
+        let resizer = ImageResize::try_from(&settings).unwrap();
+        assert_eq!(resizer.filter_type, imageops::CatmullRom);
+    }
+
+    #[test]
+    fn fit() {
+        assert_eq!(fit_within((400, 400), 400), None);
+        assert_eq!(fit_within((100, 50), 400), None);
+        assert_eq!(fit_within((800, 600), 400), Some((400, 300)));
+        assert_eq!(fit_within((600, 800), 400), Some((300, 400)));
+        assert_eq!(fit_within((1000, 1), 10), Some((10, 1)));
+    }
+
+    #[test]
+    fn shrink_to_fit() {
+        let image = RgbaImage::from_pixel(80, 40, Rgba([10, 20, 30, 255]));
+        let (width, height) = image.dimensions();
+        let image =
+            ImageBuffer::from_raw(width, height, bytes::Bytes::from(image.into_raw())).unwrap();
+        let shrunk = ImageResize::shrink_to_fit(&image, 20);
+        assert_eq!(shrunk.dimensions(), (20, 10));
+        assert_eq!(shrunk.get_pixel(5, 5).0, [10, 20, 30, 255]);
+        let unchanged = ImageResize::shrink_to_fit(&image, 100);
+        assert_eq!(unchanged.dimensions(), (80, 40));
+    }
+
+    #[test]
+    fn srgb_round_trip() {
+        for value in 0..=255u8 {
+            assert_eq!(linear_to_srgb(srgb_to_linear(value)), value);
+        }
+        assert_eq!(srgb_to_linear(0), 0.0);
+        assert_eq!(srgb_to_linear(255), 1.0);
+        // 50% linear light is a lot brighter than the middle of the sRGB range.
+        assert_eq!(linear_to_srgb(0.5), 188);
+        assert_eq!(linear_to_srgb(-0.5), 0);
+        assert_eq!(linear_to_srgb(1.5), 255);
+    }
+
+    #[test]
+    fn linear_unsupported_method() {
+        let settings = RenderSettings {
+            scaling_method: Method::Nearest,
+            ..RenderSettings::default()
+        };
+        assert!(LinearResize::try_from(&settings).is_err());
+    }
+
+    #[tokio::test]
+    async fn linear_edge() {
+        let settings = RenderSettings {
+            grid_size: 2,
+            scaling_method: Method::Triangle,
+            ..RenderSettings::default()
+        };
+        let resizer = LinearResize::try_from(&settings).unwrap();
+        let mut edge = RgbaImage::from_pixel(2, 1, Rgba([0, 0, 0, 255]));
+        edge.put_pixel(1, 0, Rgba([255, 255, 255, 255]));
+        let resized = resizer.enlarge(edge).await;
+        assert_eq!(resized.dimensions(), (4, 2));
+        let row: Vec<_> = (0..4).map(|x| resized.get_pixel(x, 0).0).collect();
+        // The middle pixels are 25% and 75% of the way from black to white in linear light,
+        // instead of 64 and 191 if they were blended as sRGB values.
+        assert_eq!(
+            row,
+            [
+                [0, 0, 0, 255],
+                [137, 137, 137, 255],
+                [225, 225, 225, 255],
+                [255, 255, 255, 255],
+            ]
+        );
+    }
+}
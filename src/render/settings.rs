@@ -1,14 +1,21 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
-use serde::Deserialize;
+use schemars::JsonSchema;
+use serde::{de, Deserialize, Deserializer, Serialize};
 use structopt::StructOpt;
 
+use crate::camera::Rotation;
 use crate::render::TemperatureDisplay;
 use crate::settings::gradient;
 use crate::temperature::{Temperature, TemperatureUnit};
 
+use super::color::Color;
+use super::filter::SpatialFilter;
+use super::outline::OutlineSettings;
+use super::overlay::OverlaySettings;
+use super::padding::AspectRatio;
 use super::resize::Method;
 
-#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[derive(Clone, Copy, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
 #[serde(untagged)]
 pub(crate) enum Limit {
     /// Set the maximum (or minimum) to the largest (or smallest) value in the current image.
@@ -18,6 +25,14 @@ pub(crate) enum Limit {
     Static(Temperature),
 }
 
+impl Limit {
+    /// Dynamic limits have no representation in the config file, so they're left out when
+    /// serializing.
+    fn is_dynamic(&self) -> bool {
+        matches!(self, Self::Dynamic)
+    }
+}
+
 impl Default for Limit {
     fn default() -> Self {
         Self::Dynamic
@@ -63,7 +78,7 @@ mod from_test {
     }
 }
 
-#[derive(Copy, Clone, Debug, Deserialize, StructOpt)]
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize, StructOpt)]
 pub(crate) struct RenderSettings {
     /// The size (in pixels) each camera pixel should be rendered as.
     #[structopt(short, long, default_value = "50")]
@@ -74,21 +89,155 @@ pub(crate) struct RenderSettings {
     #[serde(default)]
     pub(crate) units: Option<TemperatureUnit>,
 
+    /// The number of digits after the decimal point in the temperature labels.
+    #[structopt(skip = RenderSettings::default_label_precision())]
+    #[serde(default = "RenderSettings::default_label_precision")]
+    pub(crate) label_precision: usize,
+
+    /// Add the unit (ex: `°C`) to the end of each temperature label.
     #[structopt(skip)]
     #[serde(default)]
-    pub(crate) upper_limit: Limit,
+    pub(crate) label_units: bool,
 
+    /// Leave out the temperature labels when `grid_size` is smaller than this.
     #[structopt(skip)]
     #[serde(default)]
+    pub(crate) label_min_grid_size: usize,
+
+    /// The temperature mapped to the top of the color scale.
+    ///
+    /// Warmer temperatures are clamped to the top of the scale.
+    #[structopt(skip)]
+    #[serde(
+        default,
+        alias = "scale_upper",
+        skip_serializing_if = "Limit::is_dynamic"
+    )]
+    pub(crate) upper_limit: Limit,
+
+    /// The temperature mapped to the bottom of the color scale.
+    ///
+    /// Cooler temperatures are clamped to the bottom of the scale.
+    #[structopt(skip)]
+    #[serde(
+        default,
+        alias = "scale_lower",
+        skip_serializing_if = "Limit::is_dynamic"
+    )]
     pub(crate) lower_limit: Limit,
 
     #[structopt(short = "C", long, default_value = "turbo")]
     #[serde(default = "RenderSettings::default_colors")]
     pub(crate) colors: gradient::Gradient,
 
+    /// Flip the color scale, so that the coldest temperatures use the top of the gradient.
+    #[structopt(skip)]
+    #[serde(default)]
+    pub(crate) colors_reversed: bool,
+
     #[structopt(skip)]
     #[serde(default)]
     pub(crate) scaling_method: Method,
+
+    /// Gamma correction applied to the final image.
+    ///
+    /// This is applied to each color channel of the rendered image right before it is encoded, and
+    /// can be used to calibrate the output for a specific display. If not set, no correction is
+    /// applied.
+    #[structopt(skip)]
+    #[serde(default)]
+    pub(crate) gamma: Option<f32>,
+
+    /// Resize the image in linear light instead of in the sRGB color space.
+    ///
+    /// This gives more physically correct blending when a smooth scaling method is used, at the
+    /// cost of extra CPU time. It has no effect with nearest neighbor scaling.
+    #[structopt(skip)]
+    #[serde(default)]
+    pub(crate) linear_resize: bool,
+
+    /// Sharpen the image with an unsharp mask after it's been enlarged.
+    ///
+    /// This is the amount of sharpening, where 1 doubles the contrast of edges. If not set, no
+    /// sharpening is done.
+    #[structopt(skip)]
+    #[serde(default)]
+    pub(crate) sharpen: Option<f32>,
+
+    /// The radius of the unsharp mask used by [`sharpen`][RenderSettings::sharpen].
+    ///
+    /// This is the standard deviation of the blur, in pixels of the rendered image. Larger values
+    /// sharpen coarser details.
+    #[structopt(skip = RenderSettings::default_sharpen_radius())]
+    #[serde(default = "RenderSettings::default_sharpen_radius")]
+    pub(crate) sharpen_radius: f32,
+
+    /// Temporal smoothing applied to thermal images before they're rendered.
+    ///
+    /// This is the weight given to the previous frames in an exponential moving average, from 0
+    /// (no smoothing) up to (but not including) 1. If not set, no smoothing is done.
+    #[structopt(skip)]
+    #[serde(default, deserialize_with = "smoothing")]
+    pub(crate) smoothing: Option<f32>,
+
+    /// A filter applied to each thermal image before it's rendered, to reduce noise.
+    #[structopt(skip)]
+    #[serde(default)]
+    pub(crate) spatial_filter: SpatialFilter,
+
+    /// A status line drawn in a corner of the image.
+    #[structopt(skip)]
+    #[serde(default)]
+    pub(crate) overlay: OverlaySettings,
+
+    /// Outlines drawn around the objects found by the tracker. If not set, no outlines are
+    /// drawn.
+    #[structopt(skip)]
+    #[serde(default)]
+    pub(crate) outline: Option<OutlineSettings>,
+
+    /// Draw thin lines along the boundaries between camera pixels.
+    #[structopt(skip)]
+    #[serde(default)]
+    pub(crate) show_grid_lines: bool,
+
+    /// Draw a small crosshair in the center of the image.
+    #[structopt(skip)]
+    #[serde(default)]
+    pub(crate) show_crosshair: bool,
+
+    /// Pad the rendered image out to this aspect ratio (width divided by height). If not set, the
+    /// image keeps the aspect ratio of the camera.
+    #[structopt(skip)]
+    #[serde(default)]
+    pub(crate) target_aspect: Option<AspectRatio>,
+
+    /// Extra space (in pixels) added around every side of the rendered image.
+    #[structopt(skip)]
+    #[serde(default)]
+    pub(crate) margin: u32,
+
+    /// The color used to fill the padding and margin around the image. Defaults to black.
+    #[structopt(skip = RenderSettings::default_background_color())]
+    #[serde(default = "RenderSettings::default_background_color")]
+    pub(crate) background_color: Color,
+
+    /// Rotate the rendered image, in degrees clockwise.
+    ///
+    /// Unlike the camera rotation, this only affects the rendered image and not the tracker.
+    #[structopt(skip)]
+    #[serde(default)]
+    pub(crate) display_rotation: Rotation,
+
+    /// Mirror the rendered image horizontally, without affecting the tracker.
+    #[structopt(skip)]
+    #[serde(default)]
+    pub(crate) display_flip_horizontal: bool,
+
+    /// Mirror the rendered image vertically, without affecting the tracker.
+    #[structopt(skip)]
+    #[serde(default)]
+    pub(crate) display_flip_vertical: bool,
 }
 
 impl RenderSettings {
@@ -99,6 +248,18 @@ impl RenderSettings {
     fn default_grid_size() -> usize {
         50
     }
+
+    fn default_label_precision() -> usize {
+        2
+    }
+
+    fn default_sharpen_radius() -> f32 {
+        2.0
+    }
+
+    fn default_background_color() -> Color {
+        Color::BLACK
+    }
 }
 
 impl PartialEq for RenderSettings {
@@ -109,6 +270,15 @@ impl PartialEq for RenderSettings {
         if self.units != other.units {
             return false;
         }
+        if self.label_precision != other.label_precision {
+            return false;
+        }
+        if self.label_units != other.label_units {
+            return false;
+        }
+        if self.label_min_grid_size != other.label_min_grid_size {
+            return false;
+        }
         if self.upper_limit != other.upper_limit {
             return false;
         }
@@ -118,6 +288,57 @@ impl PartialEq for RenderSettings {
         if format!("{:?}", self.colors) != format!("{:?}", other.colors) {
             return false;
         }
+        if self.colors_reversed != other.colors_reversed {
+            return false;
+        }
+        if self.gamma != other.gamma {
+            return false;
+        }
+        if self.linear_resize != other.linear_resize {
+            return false;
+        }
+        if self.sharpen != other.sharpen {
+            return false;
+        }
+        if self.sharpen_radius != other.sharpen_radius {
+            return false;
+        }
+        if self.smoothing != other.smoothing {
+            return false;
+        }
+        if self.spatial_filter != other.spatial_filter {
+            return false;
+        }
+        if self.overlay != other.overlay {
+            return false;
+        }
+        if self.outline != other.outline {
+            return false;
+        }
+        if self.show_grid_lines != other.show_grid_lines {
+            return false;
+        }
+        if self.show_crosshair != other.show_crosshair {
+            return false;
+        }
+        if self.target_aspect != other.target_aspect {
+            return false;
+        }
+        if self.margin != other.margin {
+            return false;
+        }
+        if self.background_color != other.background_color {
+            return false;
+        }
+        if self.display_rotation != other.display_rotation {
+            return false;
+        }
+        if self.display_flip_horizontal != other.display_flip_horizontal {
+            return false;
+        }
+        if self.display_flip_vertical != other.display_flip_vertical {
+            return false;
+        }
         true
     }
 }
@@ -127,17 +348,55 @@ impl Default for RenderSettings {
         Self {
             grid_size: Self::default_grid_size(),
             units: None,
+            label_precision: Self::default_label_precision(),
+            label_units: false,
+            label_min_grid_size: 0,
             upper_limit: Limit::default(),
             lower_limit: Limit::default(),
             colors: Self::default_colors(),
+            colors_reversed: false,
             scaling_method: Method::default(),
+            gamma: None,
+            linear_resize: false,
+            sharpen: None,
+            sharpen_radius: Self::default_sharpen_radius(),
+            smoothing: None,
+            spatial_filter: SpatialFilter::default(),
+            overlay: OverlaySettings::default(),
+            outline: None,
+            show_grid_lines: false,
+            show_crosshair: false,
+            target_aspect: None,
+            margin: 0,
+            background_color: Self::default_background_color(),
+            display_rotation: Rotation::default(),
+            display_flip_horizontal: false,
+            display_flip_vertical: false,
         }
     }
 }
 
+/// Ensure the smoothing (if given) is at least 0 and less than 1.
+fn smoothing<'de, D>(deserializer: D) -> Result<Option<f32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<f32>::deserialize(deserializer)? {
+        Some(value) if !(0.0..1.0).contains(&value) => Err(de::Error::invalid_value(
+            de::Unexpected::Float(value.into()),
+            &"a number at least 0 and less than 1",
+        )),
+        smoothing => Ok(smoothing),
+    }
+}
+
 #[cfg(test)]
 mod render_test {
-    use super::{Limit, RenderSettings, TemperatureUnit};
+    use super::{
+        Color, Limit, OutlineSettings, OverlaySettings, RenderSettings, Rotation, SpatialFilter,
+        Temperature, TemperatureUnit,
+    };
+    use crate::render::overlay::Corner;
 
     #[test]
     fn defaults() {
@@ -200,6 +459,199 @@ mod render_test {
         assert_eq!(parsed, expected);
     }
 
+    #[test]
+    fn labels() {
+        let source = r#"
+        units = "fahrenheit"
+        label_precision = 0
+        label_units = true
+        label_min_grid_size = 30
+        "#;
+        let parsed: RenderSettings = toml::from_str(source).unwrap();
+        let expected = RenderSettings {
+            units: Some(TemperatureUnit::Fahrenheit),
+            label_precision: 0,
+            label_units: true,
+            label_min_grid_size: 30,
+            ..RenderSettings::default()
+        };
+        assert_eq!(parsed, expected);
+    }
+
+    #[test]
+    fn gamma() {
+        let parsed: Result<RenderSettings, _> = toml::from_str("gamma = 2.2");
+        assert!(
+            parsed.is_ok(),
+            "Failed to parse gamma: {}",
+            parsed.unwrap_err()
+        );
+        let parsed = parsed.unwrap();
+        let expected = RenderSettings {
+            gamma: Some(2.2),
+            ..RenderSettings::default()
+        };
+        assert_eq!(parsed, expected);
+    }
+
+    #[test]
+    fn sharpen() {
+        let parsed: RenderSettings = toml::from_str("sharpen = 0.5").unwrap();
+        let expected = RenderSettings {
+            sharpen: Some(0.5),
+            sharpen_radius: 2.0,
+            ..RenderSettings::default()
+        };
+        assert_eq!(parsed, expected);
+        let parsed: RenderSettings = toml::from_str("sharpen = 1\nsharpen_radius = 4").unwrap();
+        let expected = RenderSettings {
+            sharpen: Some(1.0),
+            sharpen_radius: 4.0,
+            ..RenderSettings::default()
+        };
+        assert_eq!(parsed, expected);
+    }
+
+    #[test]
+    fn linear_resize() {
+        let parsed: Result<RenderSettings, _> = toml::from_str("linear_resize = true");
+        assert!(
+            parsed.is_ok(),
+            "Failed to parse linear_resize: {}",
+            parsed.unwrap_err()
+        );
+        let parsed = parsed.unwrap();
+        let expected = RenderSettings {
+            linear_resize: true,
+            ..RenderSettings::default()
+        };
+        assert_eq!(parsed, expected);
+    }
+
+    #[test]
+    fn colors_reversed() {
+        let source = r#"
+        colors = "grayscale"
+        colors_reversed = true
+        "#;
+        let parsed: RenderSettings = toml::from_str(source).unwrap();
+        let expected = RenderSettings {
+            colors: crate::settings::gradient::Gradient::Grayscale,
+            colors_reversed: true,
+            ..RenderSettings::default()
+        };
+        assert_eq!(parsed, expected);
+    }
+
+    #[test]
+    fn smoothing() {
+        let parsed: Result<RenderSettings, _> = toml::from_str("smoothing = 0.5");
+        assert!(
+            parsed.is_ok(),
+            "Failed to parse smoothing: {}",
+            parsed.unwrap_err()
+        );
+        let parsed = parsed.unwrap();
+        let expected = RenderSettings {
+            smoothing: Some(0.5),
+            ..RenderSettings::default()
+        };
+        assert_eq!(parsed, expected);
+        for invalid in ["smoothing = 1.0", "smoothing = -0.1", "smoothing = 2"] {
+            assert!(
+                toml::from_str::<RenderSettings>(invalid).is_err(),
+                "'{}' should be invalid",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn spatial_filter() {
+        let parsed: RenderSettings = toml::from_str("").unwrap();
+        assert_eq!(parsed.spatial_filter, SpatialFilter::None);
+        let parsed: RenderSettings = toml::from_str("spatial_filter = \"median3\"").unwrap();
+        assert_eq!(parsed.spatial_filter, SpatialFilter::Median3);
+        let parsed: RenderSettings = toml::from_str("spatial_filter = \"gaussian\"").unwrap();
+        assert_eq!(parsed.spatial_filter, SpatialFilter::Gaussian);
+        let parsed: Result<RenderSettings, _> = toml::from_str("spatial_filter = \"median5\"");
+        assert!(parsed.is_err(), "Parsed an unknown spatial filter");
+    }
+
+    #[test]
+    fn overlay() {
+        let parsed: RenderSettings =
+            toml::from_str("overlay = { timestamp = true, corner = \"top_right\" }").unwrap();
+        let expected = RenderSettings {
+            overlay: OverlaySettings {
+                timestamp: true,
+                corner: Corner::TopRight,
+                ..OverlaySettings::default()
+            },
+            ..RenderSettings::default()
+        };
+        assert_eq!(parsed, expected);
+    }
+
+    #[test]
+    fn outline() {
+        let parsed: RenderSettings = toml::from_str("").unwrap();
+        assert_eq!(parsed.outline, None);
+        let parsed: RenderSettings = toml::from_str("[outline]").unwrap();
+        assert_eq!(parsed.outline, Some(OutlineSettings::default()));
+    }
+
+    #[test]
+    fn grid_lines() {
+        let source = r#"
+        show_grid_lines = true
+        show_crosshair = true
+        "#;
+        let parsed: RenderSettings = toml::from_str(source).unwrap();
+        let expected = RenderSettings {
+            show_grid_lines: true,
+            show_crosshair: true,
+            ..RenderSettings::default()
+        };
+        assert_eq!(parsed, expected);
+    }
+
+    #[test]
+    fn padding() {
+        let source = r##"
+        target_aspect = "16:9"
+        margin = 8
+        background_color = "#202020"
+        "##;
+        let parsed: RenderSettings = toml::from_str(source).unwrap();
+        let expected = RenderSettings {
+            target_aspect: Some("16:9".parse().unwrap()),
+            margin: 8,
+            background_color: Color::new(0x20, 0x20, 0x20),
+            ..RenderSettings::default()
+        };
+        assert_eq!(parsed, expected);
+        let parsed: Result<RenderSettings, _> = toml::from_str("target_aspect = \"16:0\"");
+        assert!(parsed.is_err(), "Parsed an invalid aspect ratio");
+    }
+
+    #[test]
+    fn display_orientation() {
+        let source = r#"
+        display_rotation = 90
+        display_flip_horizontal = true
+        "#;
+        let parsed: RenderSettings = toml::from_str(source).unwrap();
+        let expected = RenderSettings {
+            display_rotation: Rotation::Ninety,
+            display_flip_horizontal: true,
+            ..RenderSettings::default()
+        };
+        assert_eq!(parsed, expected);
+        let parsed: Result<RenderSettings, _> = toml::from_str("display_rotation = 45");
+        assert!(parsed.is_err(), "Parsed an invalid rotation");
+    }
+
     #[test]
     fn static_limit() {
         let parsed: Result<RenderSettings, _> = toml::from_str("upper_limit = 10");
@@ -215,4 +667,25 @@ mod render_test {
         };
         assert_eq!(parsed, expected);
     }
+
+    #[test]
+    fn static_scale() {
+        let source = r#"
+        scale_lower = 15
+        scale_upper = { fahrenheit = 100 }
+        "#;
+        let parsed: Result<RenderSettings, _> = toml::from_str(source);
+        assert!(
+            parsed.is_ok(),
+            "Failed to parse static scale: {}",
+            parsed.unwrap_err()
+        );
+        let parsed = parsed.unwrap();
+        let expected = RenderSettings {
+            lower_limit: Limit::Static(15f32.into()),
+            upper_limit: Limit::Static(Temperature::Fahrenheit(100.0)),
+            ..RenderSettings::default()
+        };
+        assert_eq!(parsed, expected);
+    }
 }
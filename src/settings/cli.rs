@@ -21,7 +21,10 @@ use super::Settings;
 #[structopt(group = ArgGroup::with_name("mjpeg"))]
 #[structopt(group = ArgGroup::with_name("home_assistant"))]
 pub(crate) struct Args {
-    /// Path to a configuration file.
+    /// Path to a configuration file, or `-` to read the configuration from stdin.
+    ///
+    /// If not given, the configuration in the `RUSTILLTHERE_CONFIG_TOML` environment variable is
+    /// used, then `config.toml` in the configuration directory.
     #[structopt(short, long, parse(from_os_str))]
     #[structopt(env = "RUSTILLTHERE_CONFIG")]
     pub(crate) config_path: Option<PathBuf>,
@@ -116,6 +119,48 @@ pub(crate) struct Args {
     #[structopt(long = "no-home-assistant", group = "home_assistant")]
     pub(super) disable_home_assistant: bool,
 
+    /// Check the configuration for errors, then exit.
+    ///
+    /// The exit status is 0 if the configuration is valid, and 5 if it is not.
+    #[structopt(long)]
+    pub(crate) check_config: bool,
+
+    /// When checking the configuration, also make sure the camera and MQTT broker are reachable.
+    #[structopt(long, requires = "check-config")]
+    pub(crate) check_connections: bool,
+
+    /// Print the effective configuration as TOML, then exit.
+    ///
+    /// This is the configuration after the defaults, the config file and the command line
+    /// arguments have all been combined. Secrets (like the MQTT password) are censored.
+    #[structopt(long, conflicts_with = "check-config")]
+    pub(crate) dump_config: bool,
+
+    /// Scan the I2C buses for cameras, print any that are found, then exit.
+    ///
+    /// No configuration is needed for this, and the rest of the program is not started.
+    #[structopt(long)]
+    pub(crate) list_cameras: bool,
+
+    /// Print a JSON Schema describing the configuration file, then exit.
+    ///
+    /// The schema can be used by editors to check and complete configuration files.
+    #[structopt(long)]
+    pub(crate) print_schema: bool,
+
+    /// Capture a single frame from the camera, write it to the `--output` file, then exit.
+    ///
+    /// Only the camera is started, not the MQTT client or the streaming server.
+    #[structopt(long, conflicts_with_all = &["check-config", "dump-config"])]
+    pub(crate) once: bool,
+
+    /// Where `--once` writes the captured frame.
+    ///
+    /// The format is chosen by the file extension: "jpg" or "png" (or "webp" if enabled) for the
+    /// rendered image, or "json" for the raw temperatures.
+    #[structopt(long, parse(from_os_str), default_value = "capture.jpg")]
+    pub(crate) output: PathBuf,
+
     #[cfg(feature = "mock_camera")]
     /// The file to use for mock camera data.
     ///
@@ -135,16 +180,66 @@ pub(crate) struct Args {
         possible_values(crate::camera::RepeatMode::KINDS)
     )]
     pub(crate) mock_repeat_mode: Option<crate::camera::RepeatMode>,
+
+    #[cfg(feature = "mock_camera")]
+    /// How the mock camera paces its frames.
+    ///
+    /// "recorded" replays the recording with its original timing, while "framerate" uses the
+    /// configured frame rate. If not specified, "recorded" is used.
+    #[structopt(
+        long = "playback",
+        possible_values(crate::camera::PlaybackTiming::KINDS)
+    )]
+    pub(crate) mock_playback: Option<crate::camera::PlaybackTiming>,
+
+    #[cfg(feature = "mock_camera")]
+    /// Multiplier for the mock camera playback rate.
+    ///
+    /// For example, 2.0 plays back at double speed. If not specified, 1.0 is used.
+    #[structopt(long = "playback-speed", value_name = "MULTIPLIER")]
+    pub(crate) mock_speed: Option<f32>,
+
+    #[cfg(feature = "mock_camera")]
+    /// Record an empty-room reference for this many seconds, then exit.
+    ///
+    /// The room needs to be empty for the entire capture. The occupancy tracker is trained on the
+    /// captured frames as they arrive, and a message is logged once it has stabilized. Both the
+    /// captured frames and the trained background model are saved.
+    #[structopt(long, value_name = "SECONDS")]
+    pub(crate) calibrate: Option<u64>,
+
+    #[cfg(feature = "mock_camera")]
+    /// The file to write the empty-room reference to when calibrating.
+    ///
+    /// Any existing data will be overwritten.
+    #[structopt(
+        long = "calibration-file",
+        parse(from_os_str),
+        default_value = "empty-room.bin"
+    )]
+    pub(crate) calibration_path: PathBuf,
+
+    #[cfg(feature = "mock_camera")]
+    /// The file to write the trained background model to when calibrating.
+    ///
+    /// This can be loaded later with the `tracker.background_model` setting. Any existing model
+    /// will be overwritten.
+    #[structopt(
+        long = "calibration-model",
+        parse(from_os_str),
+        default_value = "background-model.json"
+    )]
+    pub(crate) calibration_model_path: PathBuf,
 }
 
 /// DRY macro for merging in optional CLI arguments.
 ///
 /// It takes at least 4 arguments. The first is the configuration [table][toml::value::Table] for
-/// the configuration file. The second is either the name of a [toml::Value] variant, `Flag`, or
-/// `Gradient`. When a variant is given, the appropriate value will be inserted into `config`, and
-/// the next argument is the value from the [Args] struct to be inserted. `Gradient` is a special
-/// case where the type of the value is a [crate::settings::Gradient], with the next argument being
-/// the value to be inserted. `Flag` is a special case for boolean flags given as one of two
+/// the configuration file. The second is either the name of a [toml::Value] variant or `Flag`.
+/// When a variant is given, the appropriate value will be inserted into `config`, and the next
+/// argument is the value from the [Args] struct to be inserted. `String` values are converted with
+/// [ToString], so types like [crate::settings::Gradient] are inserted using the name they're
+/// parsed from. `Flag` is a special case for boolean flags given as one of two
 /// command line flags. The next two arguments are the "enabled", then "disabled" flag values on
 /// `Args`.
 ///
@@ -270,9 +365,13 @@ impl Args {
                 "camera",
                 "repeat_mode"
             );
+            merge_arg!(config, String, self.mock_playback, "camera", "playback");
+            merge_arg!(config, Float, self.mock_speed, "camera", "speed");
         }
         // Use the updated table to deserialize from
-        Settings::deserialize(Value::Table(config)).map_err(anyhow::Error::from)
+        let settings = Settings::deserialize(Value::Table(config))?;
+        settings.check_names()?;
+        Ok(settings)
     }
 }
 
@@ -286,8 +385,13 @@ fn empty_to_none(s: &str) -> Option<&str> {
 
 #[cfg(test)]
 mod test {
+    use std::num::NonZeroUsize;
+
+    use structopt::StructOpt;
+
     use crate::camera::{Bus, CameraSettings};
     use crate::mqtt::MqttSettings;
+    use crate::settings::gradient::Gradient;
     use crate::temperature::{Temperature, TemperatureUnit};
 
     use super::{Args, Settings};
@@ -296,22 +400,35 @@ mod test {
         Settings {
             camera: CameraSettings::GridEye {
                 bus: Bus::Number(9),
-                address: amg88::Address::Low,
+                address: 0x68,
                 frame_rate: amg88::FrameRateValue::Fps10,
+                thermistor_interval: NonZeroUsize::new(1).unwrap(),
                 common: Default::default(),
             },
             streams: Default::default(),
             render: Default::default(),
             tracker: Default::default(),
+            zones: Vec::new(),
+            lines: Vec::new(),
             mqtt: MqttSettings {
                 name: "Testing Name".to_string(),
+                client_id: Default::default(),
                 username: Default::default(),
                 password: Default::default(),
                 server: "mqtt://mqtt.invalid".parse().unwrap(),
                 keep_alive: Default::default(),
                 home_assistant: Default::default(),
                 base_topic: MqttSettings::default_base_topic(),
+                batch_interval: None,
+                batch_intervals: Default::default(),
+                frame_interval: None,
+                thermometer: true,
+                event_topic: None,
+                tls_ca_file: None,
+                tls_client_cert: None,
+                tls_client_key: None,
             },
+            upload: None,
         }
     }
 
@@ -490,7 +607,7 @@ mod test {
         let mut expected = expected_config();
         expected.streams.mjpeg.enabled = true;
         expected.render.grid_size = 42;
-        expected.mqtt.home_assistant.topic = "testing_topic".to_string();
+        expected.mqtt.home_assistant.topics = vec!["testing_topic".to_string()];
         let config: Settings = toml::from_str(source)?;
         assert_eq!(config, expected);
         Ok(())
@@ -515,4 +632,36 @@ mod test {
         assert_eq!(config, expected);
         Ok(())
     }
+
+    #[test]
+    fn cli_arg_colors() -> anyhow::Result<()> {
+        let names = [
+            ("viridis", Gradient::Viridis),
+            ("cividis", Gradient::Cividis),
+            ("turbo", Gradient::Turbo),
+            ("red_yellow_blue", Gradient::RedYellowBlue),
+        ];
+        for (name, gradient) in names {
+            let args = Args::from_iter_safe(&[
+                "r-u-still-there",
+                "--camera-kind",
+                "grideye",
+                "--i2c-address",
+                "0x68",
+                "--i2c-bus",
+                "9",
+                "--mqtt-name",
+                "Testing Name",
+                "--mqtt-server",
+                "mqtt://mqtt.invalid",
+                "--colors",
+                name,
+            ])?;
+            let config = args.apply_to_config_str("")?;
+            let mut expected = expected_config();
+            expected.render.colors = gradient;
+            assert_eq!(config, expected);
+        }
+        Ok(())
+    }
 }
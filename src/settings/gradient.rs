@@ -1,14 +1,97 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
+use anyhow::anyhow;
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, Schema, SchemaObject, SubschemaValidation};
+use schemars::JsonSchema;
 use serde::de::{
     value as serde_value, Deserialize, Deserializer, Error, IntoDeserializer, Unexpected,
 };
 
 use std::borrow::Cow;
+use std::convert::TryFrom;
 use std::fmt;
 use std::str::FromStr;
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Serialize)]
-#[serde(rename_all = "snake_case")]
+use crate::render::color::Color;
+
+/// A point along a [`CustomGradient`].
+#[derive(
+    Clone, Copy, Debug, PartialEq, serde::Deserialize, schemars::JsonSchema, serde::Serialize,
+)]
+pub struct ColorStop {
+    /// Where this color is along the gradient, from 0 to 1.
+    pub position: f64,
+    pub color: Color,
+}
+
+/// A gradient defined by a list of colors, with the colors in between them interpolated.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, schemars::JsonSchema, serde::Serialize)]
+#[serde(try_from = "Vec<ColorStop>", into = "Vec<ColorStop>")]
+pub struct CustomGradient(Vec<ColorStop>);
+
+impl TryFrom<Vec<ColorStop>> for CustomGradient {
+    type Error = anyhow::Error;
+
+    fn try_from(stops: Vec<ColorStop>) -> anyhow::Result<Self> {
+        if stops.len() < 2 {
+            return Err(anyhow!("A custom gradient needs at least two color stops"));
+        }
+        if !stops
+            .windows(2)
+            .all(|pair| pair[0].position <= pair[1].position)
+        {
+            return Err(anyhow!(
+                "The positions of custom gradient color stops must be in increasing order"
+            ));
+        }
+        let first = stops[0].position;
+        let last = stops[stops.len() - 1].position;
+        if first != 0.0 || last != 1.0 {
+            return Err(anyhow!(
+                "Custom gradients must start at position 0 and end at position 1 (not {} and {})",
+                first,
+                last
+            ));
+        }
+        Ok(Self(stops))
+    }
+}
+
+impl From<CustomGradient> for Vec<ColorStop> {
+    fn from(gradient: CustomGradient) -> Self {
+        gradient.0
+    }
+}
+
+impl CustomGradient {
+    /// Sample the gradient at position `t`, linearly interpolating the RGB values between the
+    /// closest stops.
+    pub fn eval_continuous(&self, t: f64) -> colorous::Color {
+        let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+        // The stops are validated to cover 0 to 1, so there's always a pair containing t.
+        let (start, end) = self
+            .0
+            .windows(2)
+            .map(|pair| (pair[0], pair[1]))
+            .find(|(_, end)| t <= end.position)
+            .unwrap_or_else(|| (self.0[self.0.len() - 2], self.0[self.0.len() - 1]));
+        let span = end.position - start.position;
+        let fraction = if span > 0.0 {
+            (t - start.position) / span
+        } else {
+            1.0
+        };
+        let interpolate =
+            |from: u8, to: u8| (from as f64 + (to as f64 - from as f64) * fraction).round() as u8;
+        colorous::Color {
+            r: interpolate(start.color.red(), end.color.red()),
+            g: interpolate(start.color.green(), end.color.green()),
+            b: interpolate(start.color.blue(), end.color.blue()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum Gradient {
     Blues,
     BlueGreen,
@@ -17,6 +100,7 @@ pub enum Gradient {
     Cividis,
     Cool,
     Cubehelix,
+    Grayscale,
     Greens,
     GreenBlue,
     Greys,
@@ -48,6 +132,17 @@ pub enum Gradient {
     YellowGreenBlue,
     YellowOrangeBrown,
     YellowOrangeRed,
+
+    /// A list of color stops, like `[{ position = 0.0, color = "#000080" }, ...]`.
+    Custom(CustomGradient),
+}
+
+/// A gradient as written in the config file, either the name of a gradient or a list of stops.
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum GradientSource<'a> {
+    Name(Cow<'a, str>),
+    Stops(CustomGradient),
 }
 
 impl<'de> Deserialize<'de> for Gradient {
@@ -55,7 +150,15 @@ impl<'de> Deserialize<'de> for Gradient {
     where
         D: Deserializer<'de>,
     {
-        let gradient_name: Cow<'_, str> = Deserialize::deserialize(deserializer)?;
+        let gradient_name = match GradientSource::deserialize(deserializer) {
+            Ok(GradientSource::Name(name)) => name,
+            Ok(GradientSource::Stops(gradient)) => return Ok(Gradient::Custom(gradient)),
+            Err(_) => {
+                return Err(D::Error::custom(
+                    "expected a gradient name or a list of valid color stops",
+                ))
+            }
+        };
         let normalized_name = gradient_name
             .to_uppercase()
             .replace(" ", "")
@@ -69,6 +172,7 @@ impl<'de> Deserialize<'de> for Gradient {
             "CIVIDIS" => Ok(Gradient::Cividis),
             "COOL" => Ok(Gradient::Cool),
             "CUBEHELIX" => Ok(Gradient::Cubehelix),
+            "GRAYSCALE" | "GREYSCALE" => Ok(Gradient::Grayscale),
             "GREENS" => Ok(Gradient::Greens),
             "GREENBLUE" => Ok(Gradient::GreenBlue),
             "GREYS" => Ok(Gradient::Greys),
@@ -103,7 +207,7 @@ impl<'de> Deserialize<'de> for Gradient {
             // unknown_variant is the better fit here, but it requires a full list of expected variants
             _ => Err(D::Error::invalid_value(
                 Unexpected::Str(&normalized_name),
-                &"A colorous gradient name",
+                &"A colorous gradient name or grayscale",
             )),
         }
     }
@@ -119,6 +223,7 @@ impl fmt::Display for Gradient {
             Gradient::Cividis => "Cividis",
             Gradient::Cool => "Cool",
             Gradient::Cubehelix => "Cubehelix",
+            Gradient::Grayscale => "Grayscale",
             Gradient::Greens => "Greens",
             Gradient::GreenBlue => "GreenBlue",
             Gradient::Greys => "Greys",
@@ -150,14 +255,137 @@ impl fmt::Display for Gradient {
             Gradient::YellowGreenBlue => "YellowGreenBlue",
             Gradient::YellowOrangeBrown => "YellowOrangeBrown",
             Gradient::YellowOrangeRed => "YellowOrangeRed",
+            Gradient::Custom(_) => "Custom",
         };
         write!(f, "{}", s)
     }
 }
 
-impl From<Gradient> for colorous::Gradient {
-    fn from(gradient_enum: Gradient) -> Self {
-        match gradient_enum {
+impl JsonSchema for Gradient {
+    fn schema_name() -> String {
+        "Gradient".to_string()
+    }
+
+    /// Gradients are either one of the [named gradients][Gradient::NAMED] (in snake case, the
+    /// same as they're serialized), or a list of color stops.
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        let names = SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            enum_values: Some(
+                Self::NAMED
+                    .iter()
+                    .map(|gradient| {
+                        serde_json::to_value(gradient).expect("gradient names are strings")
+                    })
+                    .collect(),
+            ),
+            ..SchemaObject::default()
+        };
+        SchemaObject {
+            subschemas: Some(Box::new(SubschemaValidation {
+                any_of: Some(vec![names.into(), gen.subschema_for::<CustomGradient>()]),
+                ..SubschemaValidation::default()
+            })),
+            ..SchemaObject::default()
+        }
+        .into()
+    }
+}
+
+impl serde::Serialize for Gradient {
+    /// Serialize a gradient the same way it's written in the config file, either as a name or a
+    /// list of stops.
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            Gradient::Custom(gradient) => gradient.serialize(serializer),
+            named => {
+                // Names are written in snake case, like in the example config.
+                let mut name = String::new();
+                for (index, c) in named.to_string().chars().enumerate() {
+                    if c.is_ascii_uppercase() && index > 0 {
+                        name.push('_');
+                    }
+                    name.push(c.to_ascii_lowercase());
+                }
+                serializer.serialize_str(&name)
+            }
+        }
+    }
+}
+
+impl Gradient {
+    /// Every gradient that can be given by name.
+    // Like the `match` in `deserialize`, this needs to be kept up to date by hand.
+    pub const NAMED: &'static [Gradient] = &[
+        Gradient::Blues,
+        Gradient::BlueGreen,
+        Gradient::BluePurple,
+        Gradient::BrownGreen,
+        Gradient::Cividis,
+        Gradient::Cool,
+        Gradient::Cubehelix,
+        Gradient::Grayscale,
+        Gradient::Greens,
+        Gradient::GreenBlue,
+        Gradient::Greys,
+        Gradient::Inferno,
+        Gradient::Magma,
+        Gradient::Oranges,
+        Gradient::OrangeRed,
+        Gradient::PinkGreen,
+        Gradient::Plasma,
+        Gradient::Purples,
+        Gradient::PurpleBlue,
+        Gradient::PurpleBlueGreen,
+        Gradient::PurpleGreen,
+        Gradient::PurpleOrange,
+        Gradient::PurpleRed,
+        Gradient::Rainbow,
+        Gradient::Reds,
+        Gradient::RedBlue,
+        Gradient::RedGrey,
+        Gradient::RedPurple,
+        Gradient::RedYellowBlue,
+        Gradient::RedYellowGreen,
+        Gradient::Sinebow,
+        Gradient::Spectral,
+        Gradient::Turbo,
+        Gradient::Viridis,
+        Gradient::Warm,
+        Gradient::YellowGreen,
+        Gradient::YellowGreenBlue,
+        Gradient::YellowOrangeBrown,
+        Gradient::YellowOrangeRed,
+    ];
+
+    /// Sample the gradient at position `t`, where `t` is between 0 and 1 (inclusive).
+    pub fn eval_continuous(&self, t: f64) -> colorous::Color {
+        if let Gradient::Custom(gradient) = self {
+            return gradient.eval_continuous(t);
+        }
+        match colorous::Gradient::try_from(self.clone()) {
+            Ok(gradient) => gradient.eval_continuous(t),
+            Err(_) => {
+                // Grayscale is the only gradient not provided by colorous
+                let value = (t.clamp(0.0, 1.0) * u8::MAX as f64).round() as u8;
+                colorous::Color {
+                    r: value,
+                    g: value,
+                    b: value,
+                }
+            }
+        }
+    }
+}
+
+impl TryFrom<Gradient> for colorous::Gradient {
+    type Error = anyhow::Error;
+
+    fn try_from(gradient_enum: Gradient) -> anyhow::Result<Self> {
+        Ok(match gradient_enum {
             Gradient::Blues => colorous::BLUES,
             Gradient::BlueGreen => colorous::BLUE_GREEN,
             Gradient::BluePurple => colorous::BLUE_PURPLE,
@@ -196,7 +424,15 @@ impl From<Gradient> for colorous::Gradient {
             Gradient::YellowGreenBlue => colorous::YELLOW_GREEN_BLUE,
             Gradient::YellowOrangeBrown => colorous::YELLOW_ORANGE_BROWN,
             Gradient::YellowOrangeRed => colorous::YELLOW_ORANGE_RED,
-        }
+            Gradient::Grayscale => {
+                return Err(anyhow!("There is no colorous equivalent for grayscale"))
+            }
+            Gradient::Custom(_) => {
+                return Err(anyhow!(
+                    "There is no colorous equivalent for custom gradients"
+                ))
+            }
+        })
     }
 }
 
@@ -210,6 +446,8 @@ impl FromStr for Gradient {
 
 #[cfg(test)]
 mod test {
+    use std::convert::TryFrom;
+
     use super::Gradient;
 
     fn parse_str(gradient_str: &str) -> Result<Gradient, serde_json::Error> {
@@ -233,7 +471,10 @@ mod test {
         assert_eq!(parsed, expected_variant);
         // Comparing the Debug format for colorous
         assert_eq!(
-            format!("{:?}", colorous::Gradient::from(parsed)),
+            format!(
+                "{:?}",
+                colorous::Gradient::try_from(parsed.clone()).unwrap()
+            ),
             format!("{:?}", expected_colorous)
         );
         parsed
@@ -286,6 +527,100 @@ mod test {
         );
     }
 
+    #[test]
+    fn perceptually_uniform() {
+        check_parse("cividis", Gradient::Cividis, colorous::CIVIDIS);
+        check_parse("viridis", Gradient::Viridis, colorous::VIRIDIS);
+        check_parse("Turbo", Gradient::Turbo, colorous::TURBO);
+        check_parse("inferno", Gradient::Inferno, colorous::INFERNO);
+        check_parse("magma", Gradient::Magma, colorous::MAGMA);
+        check_parse("plasma", Gradient::Plasma, colorous::PLASMA);
+    }
+
+    #[test]
+    fn grayscale() {
+        for name in ["grayscale", "greyscale", "Gray Scale", "GREY_SCALE"] {
+            let parsed = parse_str(name);
+            assert!(parsed.is_ok(), "Failed to parse {}", name);
+            assert_eq!(parsed.unwrap(), Gradient::Grayscale);
+        }
+        assert!(colorous::Gradient::try_from(Gradient::Grayscale).is_err());
+    }
+
+    #[test]
+    fn grayscale_eval() {
+        let gradient = Gradient::Grayscale;
+        assert_eq!(gradient.eval_continuous(0.0).as_array(), [0, 0, 0]);
+        assert_eq!(gradient.eval_continuous(0.5).as_array(), [128, 128, 128]);
+        assert_eq!(gradient.eval_continuous(1.0).as_array(), [255, 255, 255]);
+        // Out of range values are clamped
+        assert_eq!(gradient.eval_continuous(-1.0).as_array(), [0, 0, 0]);
+        assert_eq!(gradient.eval_continuous(2.0).as_array(), [255, 255, 255]);
+    }
+
+    #[test]
+    fn named_round_trip() {
+        for gradient in Gradient::NAMED {
+            let name = serde_json::to_value(gradient).unwrap();
+            let parsed: Gradient = serde_json::from_value(name.clone()).unwrap();
+            assert_eq!(&parsed, gradient, "{} didn't parse back to itself", name);
+        }
+    }
+
+    #[test]
+    fn custom_stops() {
+        let source = r##"
+        colors = [
+            { position = 0.0, color = "#000000" },
+            { position = 0.25, color = "#ff0000" },
+            { position = 1.0, color = "#ff00ff" },
+        ]
+        "##;
+        #[derive(serde::Deserialize)]
+        struct Wrapper {
+            colors: Gradient,
+        }
+        let gradient = toml::from_str::<Wrapper>(source).unwrap().colors;
+        assert!(matches!(gradient, Gradient::Custom(_)));
+        assert_eq!(gradient.eval_continuous(0.0).as_array(), [0, 0, 0]);
+        assert_eq!(gradient.eval_continuous(0.125).as_array(), [128, 0, 0]);
+        assert_eq!(gradient.eval_continuous(0.25).as_array(), [255, 0, 0]);
+        assert_eq!(gradient.eval_continuous(0.625).as_array(), [255, 0, 128]);
+        assert_eq!(gradient.eval_continuous(1.0).as_array(), [255, 0, 255]);
+        // Out of range values are clamped
+        assert_eq!(gradient.eval_continuous(-1.0).as_array(), [0, 0, 0]);
+        assert_eq!(gradient.eval_continuous(2.0).as_array(), [255, 0, 255]);
+        assert!(colorous::Gradient::try_from(gradient).is_err());
+    }
+
+    #[test]
+    fn invalid_custom_stops() {
+        let parse = |stops: &[(f64, &str)]| {
+            let stops: Vec<_> = stops
+                .iter()
+                .map(|(position, color)| serde_json::json!({"position": position, "color": color}))
+                .collect();
+            serde_json::from_value::<Gradient>(serde_json::Value::from(stops))
+        };
+        assert!(parse(&[(0.0, "#000000"), (1.0, "#ffffff")]).is_ok());
+        // Only one stop
+        assert!(parse(&[(0.0, "#000000")]).is_err());
+        // Not starting at 0
+        assert!(parse(&[(0.1, "#000000"), (1.0, "#ffffff")]).is_err());
+        // Not ending at 1
+        assert!(parse(&[(0.0, "#000000"), (0.9, "#ffffff")]).is_err());
+        // Out of order
+        assert!(parse(&[
+            (0.0, "#000000"),
+            (0.6, "#ff0000"),
+            (0.4, "#00ff00"),
+            (1.0, "#ffffff")
+        ])
+        .is_err());
+        // Invalid color
+        assert!(parse(&[(0.0, "black"), (1.0, "#ffffff")]).is_err());
+    }
+
     #[test]
     fn bad_gradient() {
         let parsed = parse_str("Not A Gradient");
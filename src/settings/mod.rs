@@ -1,17 +1,24 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
-use serde::Deserialize;
+use std::collections::HashSet;
+
+use anyhow::anyhow;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 
 mod cli;
 pub(crate) mod gradient;
+mod source;
 
 use crate::camera::CameraSettings;
 use crate::mqtt::MqttSettings;
-use crate::occupancy::TrackerSettings;
+use crate::occupancy::{CountingLine, TrackerSettings, Zone};
 use crate::render::RenderSettings;
 use crate::stream::StreamSettings;
+use crate::upload::UploadSettings;
 pub(crate) use cli::Args;
+pub(crate) use source::{ConfigSource, CONFIG_TOML_VAR};
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Clone, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
 pub(crate) struct Settings {
     /// Camera-specific settings.
     pub(crate) camera: CameraSettings,
@@ -28,6 +35,245 @@ pub(crate) struct Settings {
     #[serde(default)]
     pub(crate) tracker: TrackerSettings,
 
+    /// Areas of the camera's view with their own occupancy counts.
+    #[serde(default, deserialize_with = "crate::occupancy::unique_zones")]
+    pub(crate) zones: Vec<Zone>,
+
+    /// Lines across the camera's view, counting the people that cross them.
+    #[serde(default, deserialize_with = "crate::occupancy::unique_lines")]
+    pub(crate) lines: Vec<CountingLine>,
+
     /// MQTT server connection settings.
     pub(crate) mqtt: MqttSettings,
+
+    /// Settings for uploading recorded camera data to a remote server.
+    #[serde(default)]
+    pub(crate) upload: Option<UploadSettings>,
+}
+
+impl Settings {
+    /// The names of the settings that differ from `other`, but can't be changed without
+    /// restarting.
+    ///
+    /// Most of the render settings can be changed while running, the exceptions being the
+    /// smoothing and spatial filter (as they're applied before the measurements are shared with
+    /// the tracker).
+    pub(crate) fn restart_required_changes(&self, other: &Self) -> Vec<&'static str> {
+        let mut changes = Vec::new();
+        if self.camera != other.camera {
+            changes.push("camera");
+        }
+        if self.streams != other.streams {
+            changes.push("streams");
+        }
+        if self.render.smoothing != other.render.smoothing {
+            changes.push("render.smoothing");
+        }
+        if self.render.spatial_filter != other.render.spatial_filter {
+            changes.push("render.spatial_filter");
+        }
+        if self.tracker != other.tracker {
+            changes.push("tracker");
+        }
+        if self.zones != other.zones {
+            changes.push("zones");
+        }
+        if self.lines != other.lines {
+            changes.push("lines");
+        }
+        if self.mqtt != other.mqtt {
+            changes.push("mqtt");
+        }
+        if self.upload != other.upload {
+            changes.push("upload");
+        }
+        changes
+    }
+
+    /// Check that no name is used by both a zone and a counting line.
+    ///
+    /// Zone and line names are each checked for duplicates when they're parsed, but as they share
+    /// the same MQTT topic and entity namespace, a name also can't be reused between them.
+    pub(crate) fn check_names(&self) -> anyhow::Result<()> {
+        let zone_names: HashSet<&str> = self.zones.iter().map(|zone| zone.name.as_str()).collect();
+        match self
+            .lines
+            .iter()
+            .find(|line| zone_names.contains(line.name.as_str()))
+        {
+            Some(line) => Err(anyhow!(
+                "The name '{}' is used by both a zone and a line",
+                line.name
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Write these settings out as TOML, in the same format as the config file.
+    ///
+    /// Secrets (like the MQTT password) are censored.
+    pub(crate) fn to_toml(&self) -> anyhow::Result<String> {
+        // The TOML serializer doesn't support enum variants with values (like temperatures), so
+        // the settings are converted to JSON first, where those are maps. Converting to a TOML
+        // Value also sorts the plain values ahead of the tables, which TOML requires.
+        let mut json = serde_json::to_value(self)?;
+        remove_nulls(&mut json);
+        let value = toml::Value::try_from(json)?;
+        Ok(toml::to_string_pretty(&value)?)
+    }
+}
+
+/// Remove the unset (null) values from a JSON value, as TOML has no way to represent them.
+fn remove_nulls(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            let null_keys: Vec<String> = map
+                .iter()
+                .filter(|(_, value)| value.is_null())
+                .map(|(key, _)| key.clone())
+                .collect();
+            for key in null_keys {
+                map.remove(&key);
+            }
+            map.values_mut().for_each(remove_nulls);
+        }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(remove_nulls),
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Settings;
+
+    const BASE_CONFIG: &str = r#"
+        [camera]
+        kind = "grideye"
+        bus = 1
+        address = 0x68
+
+        [mqtt]
+        name = "Test"
+        server = "mqtt://127.0.0.1"
+    "#;
+
+    #[test]
+    fn shared_zone_and_line_name() {
+        let zone = "[[zones]]\nname = \"door\"\nx = 0\ny = 0\nwidth = 2\nheight = 8\n";
+        let line = "[[lines]]\nname = \"door\"\nstart = [0, 4]\nend = [8, 4]\n";
+        let zone_only: Settings = toml::from_str(&format!("{}\n{}", BASE_CONFIG, zone)).unwrap();
+        assert!(zone_only.check_names().is_ok());
+        let both: Settings =
+            toml::from_str(&format!("{}\n{}\n{}", BASE_CONFIG, zone, line)).unwrap();
+        assert!(both.check_names().is_err());
+    }
+
+    #[test]
+    fn restart_required_changes() {
+        let base: Settings = toml::from_str(BASE_CONFIG).unwrap();
+        assert!(base.restart_required_changes(&base.clone()).is_empty());
+        let render_only: Settings = toml::from_str(&format!(
+            "{}\n[render]\ngrid_size = 20\ncolors = \"magma\"\nunits = \"celsius\"",
+            BASE_CONFIG
+        ))
+        .unwrap();
+        assert!(base.restart_required_changes(&render_only).is_empty());
+        let mixed: Settings = toml::from_str(&format!(
+            "{}\n[render]\nsmoothing = 0.5\n[tracker]\nuse_kalman = true",
+            BASE_CONFIG.replace("0x68", "0x69")
+        ))
+        .unwrap();
+        assert_eq!(
+            base.restart_required_changes(&mixed),
+            ["camera", "render.smoothing", "tracker"]
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let config = r##"
+            [camera]
+            kind = "grideye"
+            bus = 1
+            address = 0x68
+            frame_rate = 10
+            rotation = 90
+            dead_pixels = [[1, 2]]
+
+            [streams]
+            port = 9090
+            [streams.mjpeg]
+            frame_rate_limit = 5.0
+
+            [render]
+            grid_size = 20
+            units = "fahrenheit"
+            upper_limit = 30.0
+            colors = [
+                { position = 0.0, color = "#000080" },
+                { position = 1.0, color = "#ffff00" },
+            ]
+            scaling_method = "catmull_rom"
+
+            [tracker]
+            threshold = { dynamic = 4.0 }
+            stationary_timeout = 60
+            background_model_parameters = { learning_rate = 0.01 }
+
+            [[zones]]
+            name = "door"
+            x = 0
+            y = 0
+            width = 2
+            height = 8
+
+            [mqtt]
+            name = "Test"
+            server = "mqtts://127.0.0.1"
+            batch_interval = 2.5
+            [mqtt.home_assistant]
+            enabled = true
+        "##;
+        let parsed: Settings = toml::from_str(config).unwrap();
+        let dumped = parsed.to_toml().unwrap();
+        let reparsed: Settings = toml::from_str(&dumped).unwrap();
+        assert_eq!(parsed, reparsed);
+        // The scaling method isn't part of the render settings comparison.
+        assert_eq!(parsed.render.scaling_method, reparsed.render.scaling_method);
+    }
+
+    #[test]
+    fn dump_censors_password() {
+        let config = format!("{}password = \"hunter2\"\n", BASE_CONFIG);
+        let parsed: Settings = toml::from_str(&config).unwrap();
+        let dumped = parsed.to_toml().unwrap();
+        assert!(!dumped.contains("hunter2"));
+        let url_password = BASE_CONFIG.replace("mqtt://", "mqtt://user:hunter2@");
+        let parsed: Settings = toml::from_str(&url_password).unwrap();
+        let dumped = parsed.to_toml().unwrap();
+        assert!(!dumped.contains("hunter2"));
+    }
+
+    #[test]
+    fn schema() {
+        let schema = schemars::schema_for!(Settings);
+        let object = schema.schema.object.as_ref().unwrap();
+        assert!(object.required.contains("camera"));
+        assert!(object.required.contains("mqtt"));
+        let schema = serde_json::to_string(&schema).unwrap();
+        for value in [
+            "grideye",
+            "mlx90640",
+            "fahrenheit",
+            "nearest",
+            "blue_green",
+            "topic",
+        ] {
+            assert!(
+                schema.contains(&format!("\"{}\"", value)),
+                "{} missing from the schema",
+                value
+            );
+        }
+    }
 }
@@ -0,0 +1,180 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+use std::fmt;
+use std::fs::read_to_string;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context as _};
+
+/// The environment variable that can hold an entire configuration file.
+pub(crate) const CONFIG_TOML_VAR: &str = "RUSTILLTHERE_CONFIG_TOML";
+
+/// Where the configuration file is read from.
+///
+/// The command line arguments are applied on top of whatever is read from here.
+#[derive(Clone, PartialEq)]
+pub(crate) enum ConfigSource {
+    /// A file, either given with `--config-path` or found in the configuration directory.
+    File(PathBuf),
+
+    /// Standard input, when `--config-path -` is given.
+    Stdin,
+
+    /// The contents of the `RUSTILLTHERE_CONFIG_TOML` environment variable.
+    Environment(String),
+
+    /// There is no configuration file, so only the defaults (and the command line arguments)
+    /// are used.
+    Defaults,
+}
+
+impl ConfigSource {
+    /// Decide where the configuration should be read from.
+    ///
+    /// The priority (from greatest to least) is:
+    /// 1. A path given with `--config-path` (or `-` for standard input).
+    /// 2. An entire configuration in `RUSTILLTHERE_CONFIG_TOML`.
+    /// 3. `config.toml` within `config_dir`.
+    /// 4. The defaults.
+    pub(crate) fn find(
+        config_path: Option<&Path>,
+        config_toml: Option<String>,
+        config_dir: &Path,
+    ) -> anyhow::Result<Self> {
+        if let Some(path) = config_path {
+            return if path == Path::new("-") {
+                Ok(Self::Stdin)
+            } else if path.exists() {
+                Ok(Self::File(path.to_path_buf()))
+            } else {
+                Err(anyhow!("Non-existant config file given: {:?}", path))
+            };
+        }
+        if let Some(config_toml) = config_toml {
+            return Ok(Self::Environment(config_toml));
+        }
+        // Only supporting TOML
+        let path = config_dir.join("config.toml");
+        Ok(if path.exists() {
+            Self::File(path)
+        } else {
+            Self::Defaults
+        })
+    }
+
+    /// Read the configuration file, with `stdin` being used for [ConfigSource::Stdin].
+    pub(crate) fn read<R: Read>(&self, mut stdin: R) -> anyhow::Result<String> {
+        match self {
+            Self::File(path) => read_to_string(path)
+                .with_context(|| format!("Unable to read config file {}", path.display())),
+            Self::Stdin => {
+                let mut config = String::new();
+                stdin
+                    .read_to_string(&mut config)
+                    .context("Unable to read the configuration from stdin")?;
+                Ok(config)
+            }
+            Self::Environment(config) => Ok(config.clone()),
+            Self::Defaults => Ok(String::new()),
+        }
+    }
+
+    /// Whether the configuration can be read again when reloading.
+    ///
+    /// Standard input can only be read once.
+    pub(crate) fn is_reloadable(&self) -> bool {
+        !matches!(self, Self::Stdin)
+    }
+}
+
+impl fmt::Debug for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File(path) => f.debug_tuple("File").field(path).finish(),
+            Self::Stdin => f.write_str("Stdin"),
+            // The configuration can have passwords in it, so the contents are left out.
+            Self::Environment(_) => write!(f, "Environment({})", CONFIG_TOML_VAR),
+            Self::Defaults => f.write_str("Defaults"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::io;
+    use std::path::{Path, PathBuf};
+
+    use super::ConfigSource;
+
+    const FILE_CONFIG: &str = "[camera]\nkind = \"grideye\"\n";
+    const ENV_CONFIG: &str = "[camera]\nkind = \"mlx90640\"\n";
+
+    fn config_dir() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("config.toml"), FILE_CONFIG).unwrap();
+        dir
+    }
+
+    #[test]
+    fn cli_path_first() {
+        let dir = config_dir();
+        let other = dir.path().join("other.toml");
+        fs::write(&other, "").unwrap();
+        let source =
+            ConfigSource::find(Some(&other), Some(ENV_CONFIG.to_string()), dir.path()).unwrap();
+        assert_eq!(source, ConfigSource::File(other));
+    }
+
+    #[test]
+    fn stdin() {
+        let dir = config_dir();
+        let source = ConfigSource::find(
+            Some(Path::new("-")),
+            Some(ENV_CONFIG.to_string()),
+            dir.path(),
+        )
+        .unwrap();
+        assert_eq!(source, ConfigSource::Stdin);
+        assert!(!source.is_reloadable());
+        let config = source.read(FILE_CONFIG.as_bytes()).unwrap();
+        assert_eq!(config, FILE_CONFIG);
+    }
+
+    #[test]
+    fn missing_cli_path() {
+        let dir = config_dir();
+        let missing = dir.path().join("missing.toml");
+        assert!(ConfigSource::find(Some(&missing), None, dir.path()).is_err());
+    }
+
+    #[test]
+    fn environment_before_file() {
+        let dir = config_dir();
+        let source = ConfigSource::find(None, Some(ENV_CONFIG.to_string()), dir.path()).unwrap();
+        assert_eq!(source, ConfigSource::Environment(ENV_CONFIG.to_string()));
+        assert!(source.is_reloadable());
+        assert_eq!(source.read(io::empty()).unwrap(), ENV_CONFIG);
+    }
+
+    #[test]
+    fn file() {
+        let dir = config_dir();
+        let source = ConfigSource::find(None, None, dir.path()).unwrap();
+        assert_eq!(source, ConfigSource::File(dir.path().join("config.toml")));
+        assert_eq!(source.read(io::empty()).unwrap(), FILE_CONFIG);
+    }
+
+    #[test]
+    fn defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = ConfigSource::find(None, None, dir.path()).unwrap();
+        assert_eq!(source, ConfigSource::Defaults);
+        assert_eq!(source.read(io::empty()).unwrap(), "");
+        let missing = PathBuf::from("/nonexistent/r-u-still-there");
+        assert_eq!(
+            ConfigSource::find(None, None, &missing).unwrap(),
+            ConfigSource::Defaults
+        );
+    }
+}
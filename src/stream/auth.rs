@@ -0,0 +1,103 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+use http::{Response, StatusCode};
+use tracing::warn;
+use warp::filters::BoxedFilter;
+use warp::{Filter, Rejection};
+
+use super::settings::StreamAuth;
+
+/// The rejection for requests without the required credentials.
+#[derive(Debug)]
+struct Unauthorized(&'static str);
+
+impl warp::reject::Reject for Unauthorized {}
+
+/// A filter that rejects requests that don't have the required credentials.
+///
+/// If there are no credentials required, every request is allowed. Rejected requests are turned
+/// into `401 Unauthorized` responses by [`recover_unauthorized`].
+pub(crate) fn authorized(auth: Option<StreamAuth>) -> BoxedFilter<()> {
+    warp::header::optional::<String>("authorization")
+        .and_then(move |authorization: Option<String>| {
+            let result = match &auth {
+                Some(auth) if !auth.is_authorized(authorization.as_deref()) => {
+                    warn!("Rejecting unauthorized HTTP request");
+                    Err(warp::reject::custom(Unauthorized(auth.challenge())))
+                }
+                _ => Ok(()),
+            };
+            futures::future::ready(result)
+        })
+        .untuple_one()
+        .boxed()
+}
+
+/// Respond to requests rejected by [`authorized`], passing any other rejections through.
+pub(crate) async fn recover_unauthorized(
+    rejection: Rejection,
+) -> Result<Response<hyper::Body>, Rejection> {
+    match rejection.find::<Unauthorized>() {
+        Some(Unauthorized(challenge)) => Response::builder()
+            .status(StatusCode::UNAUTHORIZED)
+            .header("WWW-Authenticate", *challenge)
+            .body(hyper::Body::from("Unauthorized"))
+            .map_err(|err| {
+                warn!("Unable to create unauthorized response: {:?}", err);
+                rejection
+            }),
+        None => Err(rejection),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{authorized, recover_unauthorized};
+    use crate::stream::settings::StreamAuth;
+    use warp::Filter;
+
+    fn route(auth: Option<StreamAuth>) -> impl warp::Filter<Extract = (impl warp::Reply,)> + Clone {
+        authorized(auth)
+            .and(warp::path("protected"))
+            .map(|| "OK")
+            .recover(recover_unauthorized)
+    }
+
+    #[tokio::test]
+    async fn no_auth() {
+        let response = warp::test::request()
+            .path("/protected")
+            .reply(&route(None))
+            .await;
+        assert_eq!(response.status(), 200);
+    }
+
+    #[tokio::test]
+    async fn bearer_auth() {
+        let route = route(Some(StreamAuth::Bearer {
+            token: "hunter2".into(),
+        }));
+        let response = warp::test::request().path("/protected").reply(&route).await;
+        assert_eq!(response.status(), 401);
+        assert_eq!(response.headers()["WWW-Authenticate"], "Bearer");
+        let response = warp::test::request()
+            .path("/protected")
+            .header("Authorization", "Bearer hunter2")
+            .reply(&route)
+            .await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.body(), "OK");
+    }
+
+    #[tokio::test]
+    async fn authorized_not_found() {
+        let route = route(Some(StreamAuth::Bearer {
+            token: "hunter2".into(),
+        }));
+        let response = warp::test::request()
+            .path("/missing")
+            .header("Authorization", "Bearer hunter2")
+            .reply(&route)
+            .await;
+        assert_eq!(response.status(), 404);
+    }
+}
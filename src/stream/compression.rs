@@ -0,0 +1,140 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+use http::header::{HeaderValue, VARY};
+use http::Response;
+use warp::filters::BoxedFilter;
+use warp::{Filter, Rejection, Reply};
+
+/// The type of the JSON API routes.
+type Route = BoxedFilter<(Result<Response<hyper::Body>, http::Error>,)>;
+
+/// Check if an `Accept-Encoding` header allows a content encoding.
+///
+/// Encodings with a quality of zero are explicitly refused, and `*` matches any encoding.
+fn accepts_encoding(header: &str, encoding: &str) -> bool {
+    header.split(',').any(|item| {
+        let mut parts = item.split(';').map(str::trim);
+        let name = parts.next().unwrap_or_default();
+        let refused = parts.any(|param| {
+            param
+                .strip_prefix("q=")
+                .and_then(|quality| quality.parse::<f32>().ok())
+                == Some(0.0)
+        });
+        (name.eq_ignore_ascii_case(encoding) || name == "*") && !refused
+    })
+}
+
+/// A filter that only matches requests accepting the given encoding.
+fn accepts(encoding: &'static str) -> BoxedFilter<()> {
+    warp::header::optional::<String>("accept-encoding")
+        .and_then(move |header: Option<String>| async move {
+            if matches!(header, Some(header) if accepts_encoding(&header, encoding)) {
+                Ok(())
+            } else {
+                Err::<_, Rejection>(warp::reject())
+            }
+        })
+        .untuple_one()
+        .boxed()
+}
+
+/// Compress the responses from a route if the client accepts gzip or deflate.
+///
+/// This is meant for the text (JSON) routes; the images are already compressed, so there's
+/// little to gain by compressing them again.
+pub(crate) fn compressed(route: Route) -> Route {
+    let gzip = accepts("gzip")
+        .and(route.clone())
+        .with(warp::compression::gzip())
+        .map(|reply| Ok(Reply::into_response(reply)));
+    let deflate = accepts("deflate")
+        .and(route.clone())
+        .with(warp::compression::deflate())
+        .map(|reply| Ok(Reply::into_response(reply)));
+    gzip.or(deflate)
+        .unify()
+        .or(route)
+        .unify()
+        .map(|response: Result<Response<hyper::Body>, http::Error>| {
+            response.map(|mut response| {
+                response
+                    .headers_mut()
+                    .insert(VARY, HeaderValue::from_static("accept-encoding"));
+                response
+            })
+        })
+        .boxed()
+}
+
+#[cfg(test)]
+mod test {
+    use http::Response;
+    use warp::Filter;
+
+    use super::{accepts_encoding, compressed};
+
+    #[test]
+    fn parse_accept_encoding() {
+        assert!(accepts_encoding("gzip", "gzip"));
+        assert!(accepts_encoding("deflate, gzip;q=1.0, *;q=0.5", "gzip"));
+        assert!(accepts_encoding("br, GZIP", "gzip"));
+        assert!(accepts_encoding("*", "deflate"));
+        assert!(!accepts_encoding("br", "gzip"));
+        assert!(!accepts_encoding("gzip;q=0", "gzip"));
+        assert!(!accepts_encoding("identity", "gzip"));
+    }
+
+    fn route() -> super::Route {
+        compressed(
+            warp::path("json")
+                .map(|| {
+                    Response::builder()
+                        .header("Content-Type", "application/json")
+                        .body(hyper::Body::from("[1, 2, 3]"))
+                })
+                .boxed(),
+        )
+    }
+
+    #[tokio::test]
+    async fn gzip() {
+        let response = warp::test::request()
+            .path("/json")
+            .header("Accept-Encoding", "gzip, deflate")
+            .reply(&route())
+            .await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()["Content-Encoding"], "gzip");
+        assert_eq!(response.headers()["Vary"], "accept-encoding");
+        assert_ne!(response.body(), "[1, 2, 3]");
+    }
+
+    #[tokio::test]
+    async fn deflate() {
+        let response = warp::test::request()
+            .path("/json")
+            .header("Accept-Encoding", "deflate")
+            .reply(&route())
+            .await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()["Content-Encoding"], "deflate");
+    }
+
+    #[tokio::test]
+    async fn uncompressed() {
+        let response = warp::test::request().path("/json").reply(&route()).await;
+        assert_eq!(response.status(), 200);
+        assert!(!response.headers().contains_key("Content-Encoding"));
+        assert_eq!(response.body(), "[1, 2, 3]");
+    }
+
+    #[tokio::test]
+    async fn not_found() {
+        let response = warp::test::request()
+            .path("/missing")
+            .header("Accept-Encoding", "gzip")
+            .reply(&route())
+            .await;
+        assert_eq!(response.status(), 404);
+    }
+}
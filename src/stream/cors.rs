@@ -0,0 +1,70 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+use warp::cors::Builder;
+
+/// Create the CORS configuration for the HTTP server, if any origins are allowed.
+///
+/// `*` allows any origin, otherwise only the listed origins are allowed. Preflight requests are
+/// answered before any authorization is checked, as browsers never include credentials with
+/// them.
+pub(crate) fn cors(allowed_origins: &[String]) -> Option<Builder> {
+    if allowed_origins.is_empty() {
+        return None;
+    }
+    let builder = warp::cors()
+        .allow_methods(["GET", "POST"])
+        .allow_headers(["Authorization"])
+        .expose_headers(["X-Dropped-Frames", "X-Encode-Time-Ms"]);
+    let builder = if allowed_origins.iter().any(|origin| origin == "*") {
+        builder.allow_any_origin()
+    } else {
+        builder.allow_origins(allowed_origins.iter().map(String::as_str))
+    };
+    Some(builder)
+}
+
+#[cfg(test)]
+mod test {
+    use super::cors;
+    use warp::Filter;
+
+    async fn preflight(allowed_origins: &[&str], origin: &str) -> http::Response<bytes::Bytes> {
+        let allowed_origins: Vec<String> = allowed_origins.iter().map(|s| s.to_string()).collect();
+        let route = warp::any()
+            .map(|| "OK")
+            .with(cors(&allowed_origins).unwrap());
+        warp::test::request()
+            .method("OPTIONS")
+            .header("Origin", origin)
+            .header("Access-Control-Request-Method", "GET")
+            .reply(&route)
+            .await
+    }
+
+    #[test]
+    fn disabled() {
+        assert!(cors(&[]).is_none());
+    }
+
+    #[tokio::test]
+    async fn any_origin() {
+        let response = preflight(&["*"], "https://example.com").await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            response.headers()["Access-Control-Allow-Origin"],
+            "https://example.com"
+        );
+    }
+
+    #[tokio::test]
+    async fn listed_origins() {
+        let allowed = ["https://example.com", "http://localhost:8123"];
+        let response = preflight(&allowed, "http://localhost:8123").await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            response.headers()["Access-Control-Allow-Origin"],
+            "http://localhost:8123"
+        );
+        let response = preflight(&allowed, "https://example.org").await;
+        assert_eq!(response.status(), 403);
+    }
+}
@@ -0,0 +1,120 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//! A TCP server streaming the raw camera measurements to every connected client.
+//!
+//! Each message is a little-endian `u32` with the length of the payload, followed by the payload
+//! itself: a bincode encoded struct (with fixed size integers) of these fields, in order:
+//!
+//! * `index` (`u64`): The camera's index for the frame. It increases by one for every image read
+//!   from the camera, so a gap means frames were skipped.
+//! * `width` and `height` (`u32`): The dimensions of the image.
+//! * `values` (`u64` length, then that many `f32`): The pixel temperatures in Celsius, from left
+//!   to right, top to bottom.
+//! * `temperature`: The ambient temperature, as a `u32` tag (0 for Celsius, 1 for Fahrenheit)
+//!   followed by an `f32`.
+//!
+//! The `values` and `temperature` fields use the same layout as recorded camera data.
+use bytes::{BufMut, Bytes, BytesMut};
+use futures::{Stream, StreamExt};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+use tracing::{debug, info_span, warn};
+use tracing_futures::Instrument;
+
+use crate::camera::Measurement;
+use crate::pubsub::spmc;
+
+/// A single frame sent to clients.
+#[derive(Clone, Debug)]
+struct FeedFrame<'a>(&'a Measurement);
+
+impl<'a> Serialize for FeedFrame<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut frame = serializer.serialize_struct("FeedFrame", 5)?;
+        frame.serialize_field("index", &self.0.frame_index)?;
+        frame.serialize_field("width", &self.0.image.width())?;
+        frame.serialize_field("height", &self.0.image.height())?;
+        frame.serialize_field("values", self.0.image.as_raw())?;
+        frame.serialize_field("temperature", &self.0.temperature)?;
+        frame.end()
+    }
+}
+
+/// Encode a measurement as a length-prefixed message.
+pub(crate) fn encode_frame(measurement: &Measurement) -> anyhow::Result<Bytes> {
+    let payload = bincode::serialize(&FeedFrame(measurement))?;
+    let mut message = BytesMut::with_capacity(payload.len() + 4);
+    message.put_u32_le(payload.len() as u32);
+    message.put_slice(&payload);
+    Ok(message.freeze())
+}
+
+/// Encode each measurement.
+pub(crate) fn encode_frames<S>(measurements: S) -> impl Stream<Item = Bytes>
+where
+    S: Stream<Item = Measurement>,
+{
+    measurements.filter_map(|measurement| async move {
+        encode_frame(&measurement)
+            .map_err(|err| warn!("Error encoding frame: {:?}", err))
+            .ok()
+    })
+}
+
+/// Accept connections, sending every frame from `frames` to each client until it disconnects.
+pub(crate) async fn serve(
+    listener: TcpListener,
+    frames: spmc::Sender<Bytes>,
+) -> anyhow::Result<()> {
+    loop {
+        let (mut socket, peer) = listener.accept().await?;
+        debug!(?peer, "Frame feed client connected");
+        let mut client_frames = Box::pin(frames.stream());
+        let client_task = async move {
+            while let Some(frame) = client_frames.next().await {
+                if let Err(err) = socket.write_all(&frame).await {
+                    debug!("Frame feed client disconnected: {}", err);
+                    break;
+                }
+            }
+        };
+        tokio::spawn(client_task.instrument(info_span!("frame_feed_client", ?peer)));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use image::ImageBuffer;
+
+    use super::encode_frame;
+    use crate::camera::Measurement;
+    use crate::temperature::Temperature;
+
+    #[test]
+    fn message_layout() {
+        let measurement = Measurement {
+            image: Arc::new(ImageBuffer::from_raw(2, 1, vec![20.0, 21.5]).unwrap()),
+            temperature: Temperature::Fahrenheit(70.0),
+            frame_index: 7,
+            timestamp: std::time::UNIX_EPOCH,
+        };
+        let message = encode_frame(&measurement).unwrap();
+        let mut expected: Vec<u8> = Vec::new();
+        // Length prefix
+        expected.extend_from_slice(&(8u32 + 4 + 4 + 8 + 8 + 4 + 4).to_le_bytes());
+        expected.extend_from_slice(&7u64.to_le_bytes());
+        expected.extend_from_slice(&2u32.to_le_bytes());
+        expected.extend_from_slice(&1u32.to_le_bytes());
+        expected.extend_from_slice(&2u64.to_le_bytes());
+        expected.extend_from_slice(&20f32.to_le_bytes());
+        expected.extend_from_slice(&21.5f32.to_le_bytes());
+        expected.extend_from_slice(&1u32.to_le_bytes());
+        expected.extend_from_slice(&70f32.to_le_bytes());
+        assert_eq!(message.as_ref(), expected.as_slice());
+    }
+}
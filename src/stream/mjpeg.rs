@@ -11,27 +11,42 @@ use std::pin::Pin;
 use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
 
+use super::EncodedImage;
 use crate::spmc::Sender;
 
-type StreamBox = Arc<Mutex<dyn Stream<Item = Bytes> + Send + Sync + Unpin>>;
+type StreamBox = Arc<Mutex<dyn Stream<Item = EncodedImage> + Send + Sync + Unpin>>;
 
 #[pin_project]
 #[derive(Clone)]
 pub(crate) struct MjpegStream {
     boundary: String,
+    image_type: &'static str,
+    content_length: bool,
+    encode_time_header: bool,
     #[pin]
     sender: Sender<Bytes>,
     render_stream: StreamBox,
-    temp_image: Option<Bytes>,
+    temp_image: Option<EncodedImage>,
 }
 
 impl MjpegStream {
-    pub(crate) fn new(render_source: &Sender<Bytes>) -> Self {
-        // TODO: randomize boundary
-        let boundary = "mjpeg_rs_boundary".to_string();
+    /// Create a new stream, where each image has the `image_type` MIME type.
+    ///
+    /// Each image is preceded by `boundary`, and if `content_length` is set, a `Content-Length`
+    /// header. If `encode_time_header` is set, an `X-Encode-Time-Ms` header is also added.
+    pub(crate) fn new(
+        render_source: &Sender<EncodedImage>,
+        image_type: &'static str,
+        boundary: String,
+        content_length: bool,
+        encode_time_header: bool,
+    ) -> Self {
         debug!(%boundary, "creating new MJPEG encoder");
         Self {
             boundary,
+            image_type,
+            content_length,
+            encode_time_header,
             sender: render_source.new_child(),
             render_stream: Arc::new(Mutex::new(render_source.uncounted_stream())),
             temp_image: None,
@@ -39,9 +54,9 @@ impl MjpegStream {
     }
 
     pub(crate) fn body(&self) -> Body {
-        // The only kind of error BroadcastStream can send is when a receiver is lagged. In that
-        // case just continue reading as the next recv() will work.
-        let jpeg_stream = self.sender.stream();
+        // Slow clients (like phone browsers) skip to the newest frame instead of building up a
+        // backlog that would hold up the encoder.
+        let jpeg_stream = self.sender.latest_stream();
         let result_stream = jpeg_stream.map(Result::<Bytes, hyper::http::Error>::Ok);
         info!("creating new MJPEG stream for client");
         Body::wrap_stream(result_stream)
@@ -51,13 +66,27 @@ impl MjpegStream {
         format!("multipart/x-mixed-replace; boundary={}", self.boundary)
     }
 
-    fn send_image(&mut self, jpeg_buf: Bytes) -> anyhow::Result<()> {
+    /// The headers (including the boundary) preceding an image.
+    fn part_header(&self, image: &EncodedImage) -> Bytes {
+        let mut header = format!(
+            "\r\n--{}\r\nContent-Type: {}\r\n",
+            self.boundary, self.image_type
+        );
+        if self.content_length {
+            header.push_str(&format!("Content-Length: {}\r\n", image.data.len()));
+        }
+        if self.encode_time_header {
+            header.push_str(&format!("X-Encode-Time-Ms: {}\r\n", image.encode_time_ms()));
+        }
+        header.push_str("\r\n");
+        Bytes::from(header)
+    }
+
+    fn send_image(&mut self, image: EncodedImage) -> anyhow::Result<()> {
         let span = debug_span!("send_mjpeg_image");
         let _enter = span.enter();
-        let header = Bytes::from(format!(
-            "\r\n--{}\r\nContent-Type: image/jpeg\r\n\r\n",
-            self.boundary
-        ));
+        let header = self.part_header(&image);
+        let jpeg_buf = image.data;
         // TODO: this is doing some extra copies.
         let total_length = header.len() + jpeg_buf.len();
         trace!(total_size = total_length, "total frame data length");
@@ -96,15 +125,15 @@ impl Future for MjpegStream {
     }
 }
 
-impl Sink<Bytes> for MjpegStream {
+impl Sink<EncodedImage> for MjpegStream {
     type Error = anyhow::Error;
 
     fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
         self.project().sender.poll_ready(cx)
     }
 
-    fn start_send(mut self: Pin<&mut Self>, buf: Bytes) -> Result<(), Self::Error> {
-        self.send_image(buf)
+    fn start_send(mut self: Pin<&mut Self>, image: EncodedImage) -> Result<(), Self::Error> {
+        self.send_image(image)
     }
 
     fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
@@ -115,3 +144,46 @@ impl Sink<Bytes> for MjpegStream {
         self.project().sender.poll_close(cx)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+
+    use std::time::Duration;
+
+    use super::MjpegStream;
+    use crate::spmc::Sender;
+    use crate::stream::EncodedImage;
+
+    #[test]
+    fn part_header() {
+        let source = Sender::<EncodedImage>::default();
+        let image = EncodedImage {
+            data: Bytes::from(vec![0; 1234]),
+            encode_time: Duration::from_micros(12345),
+        };
+        let stream = MjpegStream::new(&source, "image/jpeg", "frame".to_string(), true, false);
+        assert_eq!(
+            stream.part_header(&image),
+            Bytes::from_static(
+                b"\r\n--frame\r\nContent-Type: image/jpeg\r\nContent-Length: 1234\r\n\r\n"
+            )
+        );
+        assert_eq!(
+            stream.content_type(),
+            "multipart/x-mixed-replace; boundary=frame"
+        );
+        let stream = MjpegStream::new(&source, "image/webp", "frame".to_string(), false, false);
+        assert_eq!(
+            stream.part_header(&image),
+            Bytes::from_static(b"\r\n--frame\r\nContent-Type: image/webp\r\n\r\n")
+        );
+        let stream = MjpegStream::new(&source, "image/jpeg", "frame".to_string(), false, true);
+        assert_eq!(
+            stream.part_header(&image),
+            Bytes::from_static(
+                b"\r\n--frame\r\nContent-Type: image/jpeg\r\nX-Encode-Time-Ms: 12.3\r\n\r\n"
+            )
+        );
+    }
+}
@@ -1,8 +1,98 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
+use bytes::{BufMut, Bytes, BytesMut};
+use image::codecs::png::PngEncoder;
+use image::ColorType;
+use tracing::{debug, debug_span, field, trace};
+
+use std::time::{Duration, Instant};
+
+use crate::image_buffer::BytesImage;
+
+mod auth;
+#[cfg(feature = "compression")]
+mod compression;
+mod cors;
+#[cfg(feature = "frame_feed")]
+pub(crate) mod frame_feed;
 mod jpeg;
 mod mjpeg;
 mod settings;
+#[cfg(target_os = "linux")]
+mod v4l2;
+#[cfg(feature = "webp")]
+mod webp;
 
+#[cfg(feature = "webp")]
+pub(crate) use self::webp::encode_webp;
+pub(crate) use auth::{authorized, recover_unauthorized};
+#[cfg(feature = "compression")]
+pub(crate) use compression::compressed;
+pub(crate) use cors::cors;
 pub(crate) use jpeg::encode_jpeg;
 pub(crate) use mjpeg::MjpegStream;
-pub(crate) use settings::StreamSettings;
+pub(crate) use settings::{Encoder, FrameFeedSettings, StreamAuth, StreamSettings, V4l2Settings};
+#[cfg(target_os = "linux")]
+pub(crate) use v4l2::V4l2Output;
+
+/// An encoded image, along with how long it took to encode.
+#[derive(Clone, Debug)]
+pub(crate) struct EncodedImage {
+    pub(crate) data: Bytes,
+    pub(crate) encode_time: Duration,
+}
+
+impl EncodedImage {
+    /// The encoding time in milliseconds, as used for the `X-Encode-Time-Ms` header.
+    pub(crate) fn encode_time_ms(&self) -> String {
+        format!("{:.1}", self.encode_time.as_secs_f32() * 1000.0)
+    }
+}
+
+/// Encode an image, keeping track of how long the encoding took.
+///
+/// The time is also recorded (in milliseconds) as `elapsed_ms` in a `debug` span, which makes it
+/// easier to tell if the encoder is what's keeping a slow device from keeping up.
+pub(crate) fn timed_encode(
+    format: &str,
+    encode: Encoder,
+    image: &BytesImage,
+) -> anyhow::Result<EncodedImage> {
+    let span = debug_span!("encode_image", format, elapsed_ms = field::Empty);
+    let _enter = span.enter();
+    let start = Instant::now();
+    let data = encode(image)?;
+    let encode_time = start.elapsed();
+    span.record("elapsed_ms", &(encode_time.as_secs_f64() * 1000.0));
+    debug!(size = data.len(), "encoded image");
+    Ok(EncodedImage { data, encode_time })
+}
+
+/// Encode an image as a PNG.
+pub(crate) fn encode_png(image: &BytesImage) -> anyhow::Result<Bytes> {
+    trace!("encoding PNG image");
+    let mut png_buf = BytesMut::new().writer();
+    // BytesImage is defined to be RGBA.
+    PngEncoder::new(&mut png_buf).encode(image, image.width(), image.height(), ColorType::Rgba8)?;
+    Ok(png_buf.into_inner().freeze())
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+
+    use crate::image_buffer::BytesImage;
+
+    use super::encode_png;
+
+    #[test]
+    fn png_round_trip() {
+        let pixels: Vec<u8> = (0..(3 * 2 * 4)).collect();
+        let image = BytesImage::from_raw(3, 2, Bytes::from(pixels.clone())).unwrap();
+        let png = encode_png(&image).unwrap();
+        let decoded = image::load_from_memory_with_format(&png, image::ImageFormat::Png)
+            .unwrap()
+            .into_rgba8();
+        assert_eq!(decoded.dimensions(), (3, 2));
+        assert_eq!(decoded.into_raw(), pixels);
+    }
+}
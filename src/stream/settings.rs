@@ -1,11 +1,20 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
+use bytes::Bytes;
 use num_integer::Integer;
-use serde::Deserialize;
+use schemars::JsonSchema;
+use serde::de::{Deserializer, Error as _, Unexpected};
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+
+use crate::image_buffer::BytesImage;
+use crate::mqtt::ExternalValue;
 
 use std::net;
+use std::num::NonZeroU32;
+use std::path::PathBuf;
 use std::time::Duration;
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Clone, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
 pub(crate) struct StreamSettings {
     /// The address to bind the server to. Defaults to `127.0.0.1`.
     #[serde(default = "StreamSettings::default_address")]
@@ -18,14 +27,44 @@ pub(crate) struct StreamSettings {
     /// MJPEG-specific settings.
     #[serde(default)]
     pub(crate) mjpeg: MjpegSettings,
+
+    /// The frame rate to poll the camera at when there are no stream clients connected.
+    ///
+    /// The occupancy tracker only sees the frames the camera produces, so while idle it runs at
+    /// this frame rate as well, and this shouldn't be set too low. If not set, the camera is always
+    /// polled at its configured frame rate.
+    #[serde(default)]
+    pub(crate) idle_fps: Option<f32>,
+
+    /// Settings for writing the rendered images to a V4L2 (Video4Linux) device.
+    #[serde(default)]
+    pub(crate) v4l2: Option<V4l2Settings>,
+
+    /// Settings for streaming the raw camera measurements over TCP.
+    #[serde(default)]
+    pub(crate) frame_feed: Option<FrameFeedSettings>,
+
+    /// The credentials required to use the HTTP server. If not set, anyone able to connect to
+    /// the server can use it.
+    #[serde(default)]
+    pub(crate) auth: Option<StreamAuth>,
+
+    /// The origins (like `https://dashboard.example.com`) allowed to use the HTTP server from a
+    /// web browser, with `*` allowing any origin. By default cross-origin requests are not
+    /// allowed.
+    #[serde(default, deserialize_with = "deserialize_origins")]
+    pub(crate) cors_allowed_origins: Vec<String>,
+
+    /// Whether or not to add an `X-Encode-Time-Ms` header with how long it took to encode images
+    /// to snapshot responses and each frame of the MJPEG stream.
+    #[serde(default)]
+    pub(crate) encode_time_header: bool,
 }
 
 impl StreamSettings {
     /// Test if any streams are enabled.
     pub(crate) fn any_streams_enabled(&self) -> bool {
-        // Right now there's only MJPEG streams, so this isn't a very useful check, but it'll be
-        // useful later.
-        self.mjpeg.enabled
+        self.mjpeg.enabled || self.v4l2.is_some() || self.frame_feed.is_some()
     }
 
     /// Test if any streams that require the HTTP server are enabled.
@@ -42,24 +81,36 @@ impl StreamSettings {
         9000u16
     }
 
+    /// The frame rate to use when there are no stream clients, if `idle_fps` is set to a valid
+    /// frame rate.
+    pub(crate) fn idle_frame_rate(&self) -> Option<f32> {
+        self.idle_fps.filter(|fps| fps.is_normal() && *fps > 0.0)
+    }
+
     /// Out of the enabled streams, if they are frame rate limited, find the greatest common
     /// denominator for them. And return it as the delay betwwen frames. If there are no frame rate
-    /// limits, or the GCD is 1, `None` is returned.
+    /// limits, or the GCD is a millisecond or less, `None` is returned.
+    ///
+    /// Frame rates below 1 FPS (like 0.5 FPS) are supported, and result in delays longer than a
+    /// second.
     pub(crate) fn common_frame_rate(&self) -> Option<Duration> {
-        let mut rates: Vec<u64> = Vec::new();
-        if self.mjpeg.enabled && self.mjpeg.frame_rate_limit.is_some() {
-            let rate = self.mjpeg.frame_rate_limit.unwrap();
-            let millis_delay = rate.recip() * 1000.0;
-            rates.push(millis_delay as u64);
-        }
-        rates.into_iter().reduce(|a, b| a.gcd(&b)).and_then(|gcd| {
-            // If the GCD is 1, there's no point in reducing the frame rate
-            if gcd == 1 {
-                None
-            } else {
-                Some(Duration::from_millis(gcd))
+        let mut intervals: Vec<u64> = Vec::new();
+        if self.mjpeg.enabled {
+            if let Some(interval) = self.mjpeg.frame_rate_limit.and_then(frame_interval) {
+                intervals.push(interval.as_micros() as u64);
             }
-        })
+        }
+        intervals
+            .into_iter()
+            .reduce(|a, b| a.gcd(&b))
+            .and_then(|gcd| {
+                // If the GCD is too small, there's no point in reducing the frame rate
+                if gcd <= 1000 {
+                    None
+                } else {
+                    Some(Duration::from_micros(gcd))
+                }
+            })
     }
 }
 
@@ -78,11 +129,77 @@ impl Default for StreamSettings {
             address: Self::default_address(),
             port: Self::default_port(),
             mjpeg: MjpegSettings::default(),
+            idle_fps: None,
+            v4l2: None,
+            frame_feed: None,
+            auth: None,
+            cors_allowed_origins: Vec::new(),
+            encode_time_header: false,
         }
     }
 }
 
-#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+/// The kinds of authentication supported by the HTTP server.
+#[derive(Clone, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(untagged)]
+pub(crate) enum StreamAuth {
+    /// An HTTP bearer token, given as `Authorization: Bearer <token>`.
+    Bearer { token: ExternalValue },
+
+    /// HTTP Basic authentication, which browsers and most stream players support.
+    Basic {
+        username: String,
+        password: ExternalValue,
+    },
+}
+
+impl StreamAuth {
+    /// Check the value of an `Authorization` header against these credentials.
+    pub(crate) fn is_authorized(&self, authorization: Option<&str>) -> bool {
+        let authorization = match authorization.and_then(|header| header.split_once(' ')) {
+            Some((scheme, credentials)) => (scheme.to_ascii_lowercase(), credentials.trim()),
+            None => return false,
+        };
+        match (self, authorization) {
+            (Self::Bearer { token }, (scheme, given)) if scheme == "bearer" => {
+                constant_time_eq(given, &token.0)
+            }
+            (Self::Basic { username, password }, (scheme, given)) if scheme == "basic" => {
+                base64::decode(given)
+                    .ok()
+                    .and_then(|decoded| String::from_utf8(decoded).ok())
+                    .and_then(|decoded| {
+                        decoded
+                            .split_once(':')
+                            .map(|(given_username, given_password)| {
+                                // Both are always checked, so the timing doesn't reveal which
+                                // was wrong.
+                                constant_time_eq(given_username, username)
+                                    & constant_time_eq(given_password, &password.0)
+                            })
+                    })
+                    .unwrap_or(false)
+            }
+            _ => false,
+        }
+    }
+
+    /// The value of the `WWW-Authenticate` header sent with unauthorized responses.
+    pub(crate) fn challenge(&self) -> &'static str {
+        match self {
+            Self::Bearer { .. } => "Bearer",
+            Self::Basic { .. } => "Basic realm=\"r-u-still-there\", charset=\"UTF-8\"",
+        }
+    }
+}
+
+/// Compare two credentials in constant time, so that the time taken doesn't leak how much of a
+/// guess was correct.
+fn constant_time_eq(given: &str, expected: &str) -> bool {
+    given.as_bytes().ct_eq(expected.as_bytes()).into()
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
 pub(crate) struct MjpegSettings {
     /// Whether or not the MJPEG video stream should be enabled.
     #[serde(default = "MjpegSettings::default_enabled")]
@@ -91,12 +208,125 @@ pub(crate) struct MjpegSettings {
     /// A frame rate limit to apply to just the MJPEG stream.
     #[serde(default)]
     pub(crate) frame_rate_limit: Option<f32>,
+
+    /// The maximum frame rate for JPEG encoding.
+    ///
+    /// Unlike `frame_rate_limit`, this only throttles the JPEG encoder, not the rendering of
+    /// frames. If both are set, the lower of the two rates is the effective limit.
+    #[serde(default)]
+    pub(crate) max_fps: Option<f32>,
+
+    /// The largest width or height (in pixels) for frames of the stream.
+    ///
+    /// Larger frames are shrunk to fit (keeping their aspect ratio) before they're encoded, but
+    /// smaller frames are never enlarged. This doesn't change the size of the rendered images
+    /// used elsewhere. If not set, frames are sent at the rendered size.
+    #[serde(default)]
+    pub(crate) max_dimension: Option<NonZeroU32>,
+
+    /// The image format used for each frame of the stream.
+    #[serde(default)]
+    pub(crate) format: StreamFormat,
+
+    /// The boundary marker between each frame of the stream.
+    ///
+    /// It must be between 1 and 70 characters long, and can only use the characters allowed by
+    /// RFC 2046.
+    #[serde(
+        default = "MjpegSettings::default_boundary",
+        deserialize_with = "deserialize_boundary"
+    )]
+    pub(crate) boundary: String,
+
+    /// Whether or not each frame includes a `Content-Length` header. Some players (like VLC)
+    /// require it. Defaults to `true`.
+    #[serde(default = "MjpegSettings::default_content_length")]
+    pub(crate) content_length: bool,
 }
 
 impl MjpegSettings {
     fn default_enabled() -> bool {
         true
     }
+
+    fn default_boundary() -> String {
+        "mjpeg_rs_boundary".to_string()
+    }
+
+    fn default_content_length() -> bool {
+        true
+    }
+
+    /// The minimum delay between encoded frames, if `max_fps` is set.
+    pub(crate) fn encoder_delay(&self) -> Option<Duration> {
+        self.max_fps.and_then(frame_interval)
+    }
+}
+
+/// Deserialize a multipart boundary, making sure it's valid according to RFC 2046.
+fn deserialize_boundary<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    // The characters allowed in a boundary, besides alphanumerics. Spaces are also allowed, just
+    // not as the last character.
+    const SPECIALS: &str = "'()+_,-./:=? ";
+    let boundary = String::deserialize(deserializer)?;
+    if boundary.is_empty() || boundary.len() > 70 {
+        Err(D::Error::invalid_length(
+            boundary.len(),
+            &"between 1 and 70 characters",
+        ))
+    } else if boundary.ends_with(' ')
+        || !boundary
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || SPECIALS.contains(c))
+    {
+        Err(D::Error::invalid_value(
+            Unexpected::Str(&boundary),
+            &"a multipart boundary",
+        ))
+    } else {
+        Ok(boundary)
+    }
+}
+
+/// Deserialize a list of origins for CORS, making sure each one is either `*` or a valid origin.
+fn deserialize_origins<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let origins = Vec::<String>::deserialize(deserializer)?;
+    origins
+        .into_iter()
+        .map(|origin| {
+            if origin == "*" {
+                return Ok(origin);
+            }
+            let parsed = url::Url::parse(&origin)
+                .ok()
+                .map(|url| url.origin())
+                .filter(url::Origin::is_tuple)
+                .map(|parsed| parsed.ascii_serialization());
+            // Origins only have a scheme, host, and port, but allow a trailing slash.
+            match parsed {
+                Some(parsed) if parsed == origin.trim_end_matches('/') => Ok(parsed),
+                _ => Err(D::Error::invalid_value(
+                    Unexpected::Str(&origin),
+                    &"an origin like 'https://example.com'",
+                )),
+            }
+        })
+        .collect()
+}
+
+/// Convert a frame rate into the delay between frames, if it is a valid (positive) frame rate.
+fn frame_interval(fps: f32) -> Option<Duration> {
+    if fps.is_normal() && fps > 0.0 {
+        Some(Duration::from_secs_f32(fps.recip()))
+    } else {
+        None
+    }
 }
 
 impl Default for MjpegSettings {
@@ -104,14 +334,113 @@ impl Default for MjpegSettings {
         Self {
             enabled: Self::default_enabled(),
             frame_rate_limit: None,
+            max_fps: None,
+            max_dimension: None,
+            format: StreamFormat::default(),
+            boundary: Self::default_boundary(),
+            content_length: Self::default_content_length(),
+        }
+    }
+}
+
+pub(crate) type Encoder = fn(&BytesImage) -> anyhow::Result<Bytes>;
+
+/// The image format for each frame of a multipart stream.
+#[derive(Clone, Copy, Debug, Default, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum StreamFormat {
+    /// A series of JPEG images (a.k.a. MJPEG).
+    #[default]
+    MotionJpeg,
+
+    /// A series of WebP images. Requires the `webp` feature.
+    MotionWebp,
+}
+
+impl StreamFormat {
+    /// The MIME type of each frame.
+    pub(crate) fn content_type(&self) -> &'static str {
+        match self {
+            Self::MotionJpeg => "image/jpeg",
+            Self::MotionWebp => "image/webp",
         }
     }
+
+    /// The function used to encode each frame, if support for this format has been enabled.
+    pub(crate) fn encoder(&self) -> anyhow::Result<Encoder> {
+        match self {
+            Self::MotionJpeg => Ok(super::encode_jpeg),
+            #[cfg(feature = "webp")]
+            Self::MotionWebp => Ok(super::encode_webp),
+            #[cfg(not(feature = "webp"))]
+            Self::MotionWebp => Err(anyhow::anyhow!(
+                "WebP streams require r-u-still-there to be built with the 'webp' feature"
+            )),
+        }
+    }
+}
+
+/// Settings for writing rendered images to a V4L2 output device.
+///
+/// This is mainly useful with the `v4l2loopback` kernel module, so that the thermal images show up
+/// as a normal webcam for other programs. Only supported on Linux.
+#[derive(Clone, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
+pub(crate) struct V4l2Settings {
+    /// The path to the device, like `/dev/video0`.
+    pub(crate) device: PathBuf,
+
+    /// The pixel format to write to the device.
+    #[serde(default)]
+    pub(crate) pixel_format: V4l2PixelFormat,
+}
+
+/// The pixel formats that can be written to a V4L2 device.
+#[derive(Clone, Copy, Debug, Default, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum V4l2PixelFormat {
+    /// Packed YUV 4:2:2, supported by pretty much every program that uses webcams.
+    #[default]
+    Yuyv,
+
+    /// Packed 8-bit RGB. This avoids the color conversion, but fewer programs support it.
+    Rgb24,
+}
+
+/// Settings for the TCP server streaming raw camera measurements.
+///
+/// This server is separate from the HTTP server, and requires the `frame_feed` feature.
+#[derive(Clone, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
+pub(crate) struct FrameFeedSettings {
+    /// The address to bind the server to. Defaults to `127.0.0.1`.
+    #[serde(default = "StreamSettings::default_address")]
+    pub(crate) address: net::IpAddr,
+
+    /// The port to bind the server to. Defaults to `9001`.
+    #[serde(default = "FrameFeedSettings::default_port")]
+    pub(crate) port: u16,
+}
+
+impl FrameFeedSettings {
+    fn default_port() -> u16 {
+        9001u16
+    }
+
+    #[cfg(feature = "frame_feed")]
+    pub(crate) fn socket_address(&self) -> net::SocketAddr {
+        net::SocketAddr::from((self.address, self.port))
+    }
 }
 
 #[cfg(test)]
 mod stream_test {
-    use super::{MjpegSettings, StreamSettings};
+    use super::{
+        FrameFeedSettings, MjpegSettings, StreamAuth, StreamFormat, StreamSettings,
+        V4l2PixelFormat, V4l2Settings,
+    };
     use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+    use std::num::NonZeroU32;
+    use std::path::PathBuf;
+    use std::time::Duration;
 
     #[test]
     fn default_settings() {
@@ -245,6 +574,113 @@ mod stream_test {
         assert_eq!(parsed, expected);
     }
 
+    #[test]
+    fn mjpeg_max_fps() {
+        let parsed: Result<StreamSettings, _> = toml::from_str("mjpeg.max_fps = 4.0");
+        assert!(parsed.is_ok(), "Failed to parse MJPEG max FPS");
+        let parsed = parsed.unwrap();
+        let expected = StreamSettings {
+            mjpeg: MjpegSettings {
+                max_fps: Some(4.0),
+                ..MjpegSettings::default()
+            },
+            ..StreamSettings::default()
+        };
+        assert_eq!(parsed, expected);
+        assert_eq!(
+            parsed.mjpeg.encoder_delay(),
+            Some(Duration::from_millis(250))
+        );
+        assert_eq!(MjpegSettings::default().encoder_delay(), None);
+    }
+
+    #[test]
+    fn mjpeg_max_dimension() {
+        let parsed: StreamSettings = toml::from_str("mjpeg.max_dimension = 320").unwrap();
+        assert_eq!(parsed.mjpeg.max_dimension, NonZeroU32::new(320));
+        assert!(toml::from_str::<StreamSettings>("mjpeg.max_dimension = 0").is_err());
+    }
+
+    #[test]
+    fn idle_fps() {
+        let parsed: Result<StreamSettings, _> = toml::from_str("idle_fps = 1.0");
+        assert!(parsed.is_ok(), "Failed to parse idle FPS");
+        let parsed = parsed.unwrap();
+        let expected = StreamSettings {
+            idle_fps: Some(1.0),
+            ..StreamSettings::default()
+        };
+        assert_eq!(parsed, expected);
+        assert_eq!(parsed.idle_frame_rate(), Some(1.0));
+        assert_eq!(StreamSettings::default().idle_frame_rate(), None);
+        let zero = StreamSettings {
+            idle_fps: Some(0.0),
+            ..StreamSettings::default()
+        };
+        assert_eq!(zero.idle_frame_rate(), None);
+    }
+
+    #[test]
+    fn mjpeg_format() {
+        let parsed: StreamSettings = toml::from_str("").unwrap();
+        assert_eq!(parsed.mjpeg.format, StreamFormat::MotionJpeg);
+        let parsed: StreamSettings = toml::from_str("mjpeg.format = \"motion_webp\"").unwrap();
+        assert_eq!(parsed.mjpeg.format, StreamFormat::MotionWebp);
+        assert_eq!(parsed.mjpeg.format.content_type(), "image/webp");
+        assert_eq!(
+            parsed.mjpeg.format.encoder().is_ok(),
+            cfg!(feature = "webp")
+        );
+        let parsed: Result<StreamSettings, _> = toml::from_str("mjpeg.format = \"h264\"");
+        assert!(parsed.is_err(), "Parsed an unknown stream format");
+    }
+
+    #[test]
+    fn v4l2_device() {
+        let source = r#"
+        [v4l2]
+        device = "/dev/video2"
+        "#;
+        let parsed: StreamSettings = toml::from_str(source).unwrap();
+        let expected = StreamSettings {
+            v4l2: Some(V4l2Settings {
+                device: PathBuf::from("/dev/video2"),
+                pixel_format: V4l2PixelFormat::Yuyv,
+            }),
+            ..StreamSettings::default()
+        };
+        assert_eq!(parsed, expected);
+        assert!(parsed.any_streams_enabled());
+        let parsed: StreamSettings =
+            toml::from_str("v4l2 = { device = \"/dev/video2\", pixel_format = \"rgb24\" }")
+                .unwrap();
+        assert_eq!(parsed.v4l2.unwrap().pixel_format, V4l2PixelFormat::Rgb24);
+        let parsed: Result<StreamSettings, _> = toml::from_str("v4l2 = {}");
+        assert!(parsed.is_err(), "Parsed V4L2 settings without a device");
+    }
+
+    #[test]
+    fn frame_feed() {
+        let source = r#"
+        mjpeg.enabled = false
+        [frame_feed]
+        address = "0.0.0.0"
+        "#;
+        let parsed: StreamSettings = toml::from_str(source).unwrap();
+        let expected = FrameFeedSettings {
+            address: IpAddr::from(Ipv4Addr::new(0, 0, 0, 0)),
+            port: 9001,
+        };
+        assert_eq!(parsed.frame_feed, Some(expected));
+        assert!(parsed.any_streams_enabled());
+        assert!(!parsed.http_streams_enabled());
+        #[cfg(feature = "frame_feed")]
+        assert_eq!(
+            parsed.frame_feed.unwrap().socket_address(),
+            "0.0.0.0:9001".parse().unwrap()
+        );
+    }
+
     #[test]
     fn mjpeg_invalid() {
         let parsed: Result<StreamSettings, _> = toml::from_str("mjpeg.enabled = \"foo\"");
@@ -253,4 +689,98 @@ mod stream_test {
             "Incorrectly parsed bad MJPEG configuration"
         );
     }
+
+    #[test]
+    fn common_frame_rate() {
+        let parsed: StreamSettings = toml::from_str("").unwrap();
+        assert_eq!(parsed.common_frame_rate(), None);
+        let parsed: StreamSettings = toml::from_str("mjpeg.frame_rate_limit = 4").unwrap();
+        assert_eq!(parsed.common_frame_rate(), Some(Duration::from_millis(250)));
+        // Frame rates below 1 FPS are allowed
+        let parsed: StreamSettings = toml::from_str("mjpeg.frame_rate_limit = 0.5").unwrap();
+        assert_eq!(parsed.common_frame_rate(), Some(Duration::from_secs(2)));
+        let parsed: StreamSettings = toml::from_str("mjpeg.frame_rate_limit = 0").unwrap();
+        assert_eq!(parsed.common_frame_rate(), None);
+        let parsed: StreamSettings =
+            toml::from_str("mjpeg = { enabled = false, frame_rate_limit = 0.5 }").unwrap();
+        assert_eq!(parsed.common_frame_rate(), None);
+    }
+
+    #[test]
+    fn mjpeg_boundary() {
+        let parsed: StreamSettings = toml::from_str("").unwrap();
+        assert_eq!(parsed.mjpeg.boundary, "mjpeg_rs_boundary");
+        assert!(parsed.mjpeg.content_length);
+        let parsed: StreamSettings =
+            toml::from_str("mjpeg = { boundary = \"frame\", content_length = false }").unwrap();
+        assert_eq!(parsed.mjpeg.boundary, "frame");
+        assert!(!parsed.mjpeg.content_length);
+        for invalid in ["", "trailing space ", "no\\r\\nnewlines", &"a".repeat(71)] {
+            let source = format!("mjpeg.boundary = \"{}\"", invalid);
+            let parsed: Result<StreamSettings, _> = toml::from_str(&source);
+            assert!(parsed.is_err(), "Parsed invalid boundary {:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn bearer_auth() {
+        let parsed: StreamSettings = toml::from_str("auth = { token = \"hunter2\" }").unwrap();
+        let auth = parsed.auth.unwrap();
+        assert_eq!(
+            auth,
+            StreamAuth::Bearer {
+                token: "hunter2".into()
+            }
+        );
+        assert_eq!(auth.challenge(), "Bearer");
+        assert!(auth.is_authorized(Some("Bearer hunter2")));
+        assert!(auth.is_authorized(Some("bearer hunter2")));
+        assert!(!auth.is_authorized(Some("Bearer hunter3")));
+        assert!(!auth.is_authorized(Some("hunter2")));
+        assert!(!auth.is_authorized(None));
+    }
+
+    #[test]
+    fn basic_auth() {
+        let source = "auth = { username = \"Cthon98\", password = \"hunter2\" }";
+        let parsed: StreamSettings = toml::from_str(source).unwrap();
+        let auth = parsed.auth.unwrap();
+        assert_eq!(
+            auth,
+            StreamAuth::Basic {
+                username: "Cthon98".to_string(),
+                password: "hunter2".into()
+            }
+        );
+        // "Cthon98:hunter2"
+        assert!(auth.is_authorized(Some("Basic Q3Rob245ODpodW50ZXIy")));
+        // "AzureDiamond:hunter2"
+        assert!(!auth.is_authorized(Some("Basic QXp1cmVEaWFtb25kOmh1bnRlcjI=")));
+        assert!(!auth.is_authorized(Some("Basic not base64")));
+        assert!(!auth.is_authorized(Some("Bearer hunter2")));
+        assert!(!auth.is_authorized(None));
+        let parsed: Result<StreamSettings, _> = toml::from_str("auth = { username = \"foo\" }");
+        assert!(parsed.is_err(), "Parsed basic auth without a password");
+    }
+
+    #[test]
+    fn cors_allowed_origins() {
+        let parsed: StreamSettings = toml::from_str("").unwrap();
+        assert!(parsed.cors_allowed_origins.is_empty());
+        let source = r#"cors_allowed_origins = ["*", "https://example.com/", "http://[::1]:8123"]"#;
+        let parsed: StreamSettings = toml::from_str(source).unwrap();
+        assert_eq!(
+            parsed.cors_allowed_origins,
+            ["*", "https://example.com", "http://[::1]:8123"]
+        );
+        for invalid in [
+            "example.com",
+            "https://example.com/dashboard",
+            "data:text/plain,",
+        ] {
+            let source = format!("cors_allowed_origins = [\"{}\"]", invalid);
+            let parsed: Result<StreamSettings, _> = toml::from_str(&source);
+            assert!(parsed.is_err(), "Parsed invalid origin {:?}", invalid);
+        }
+    }
 }
@@ -0,0 +1,266 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+use anyhow::{anyhow, Context as _};
+use tracing::{debug, info};
+
+use super::settings::V4l2PixelFormat;
+use crate::image_buffer::BytesImage;
+
+// The subset of the V4L2 API needed to configure the output format of a device. See
+// linux/videodev2.h for the full definitions (the structures are named v4l2_pix_format and
+// v4l2_format there).
+const V4L2_BUF_TYPE_VIDEO_OUTPUT: u32 = 2;
+const V4L2_FIELD_NONE: u32 = 1;
+const V4L2_COLORSPACE_SRGB: u32 = 8;
+
+/// Build a V4L2 FourCC code.
+const fn fourcc(code: &[u8; 4]) -> u32 {
+    (code[0] as u32) | (code[1] as u32) << 8 | (code[2] as u32) << 16 | (code[3] as u32) << 24
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct PixFormat {
+    width: u32,
+    height: u32,
+    pixelformat: u32,
+    field: u32,
+    bytesperline: u32,
+    sizeimage: u32,
+    colorspace: u32,
+    private: u32,
+    flags: u32,
+    ycbcr_enc: u32,
+    quantization: u32,
+    xfer_func: u32,
+}
+
+#[repr(C)]
+union FormatUnion {
+    pix: PixFormat,
+    raw_data: [u8; 200],
+    // Some of the other members of this union have pointers, so the alignment (and the size of
+    // the whole structure) depends on the pointer size.
+    _align: *const std::ffi::c_void,
+}
+
+#[repr(C)]
+struct Format {
+    buf_type: u32,
+    fmt: FormatUnion,
+}
+
+mod ioctl {
+    nix::ioctl_readwrite!(vidioc_s_fmt, b'V', 5, super::Format);
+}
+
+impl V4l2PixelFormat {
+    fn fourcc(&self) -> u32 {
+        match self {
+            Self::Yuyv => fourcc(b"YUYV"),
+            Self::Rgb24 => fourcc(b"RGB3"),
+        }
+    }
+
+    /// The width of the device format for frames of the given width.
+    ///
+    /// YUYV packs pixels in pairs, so odd widths are rounded up to the next even width (and the
+    /// last pixel in each row is repeated, see [`rgba_to_yuyv`]).
+    fn device_width(&self, width: u32) -> u32 {
+        match self {
+            Self::Yuyv => width + width % 2,
+            Self::Rgb24 => width,
+        }
+    }
+
+    fn bytes_per_line(&self, width: u32) -> u32 {
+        match self {
+            Self::Yuyv => width * 2,
+            Self::Rgb24 => width * 3,
+        }
+    }
+}
+
+/// A V4L2 output device, like one created by the `v4l2loopback` kernel module.
+///
+/// The format of the device is set when the first frame is written, and set again whenever the
+/// size of the frames changes.
+pub(crate) struct V4l2Output {
+    device: File,
+    pixel_format: V4l2PixelFormat,
+    frame_size: Option<(u32, u32)>,
+    buffer: Vec<u8>,
+}
+
+impl V4l2Output {
+    pub(crate) fn open(path: &Path, pixel_format: V4l2PixelFormat) -> anyhow::Result<Self> {
+        let device = OpenOptions::new()
+            .write(true)
+            .open(path)
+            .with_context(|| format!("Unable to open V4L2 device {}", path.display()))?;
+        Ok(Self {
+            device,
+            pixel_format,
+            frame_size: None,
+            buffer: Vec::new(),
+        })
+    }
+
+    fn set_format(&mut self, width: u32, height: u32) -> anyhow::Result<()> {
+        let device_width = self.pixel_format.device_width(width);
+        let bytes_per_line = self.pixel_format.bytes_per_line(device_width);
+        let pix = PixFormat {
+            width: device_width,
+            height,
+            pixelformat: self.pixel_format.fourcc(),
+            field: V4L2_FIELD_NONE,
+            bytesperline: bytes_per_line,
+            sizeimage: bytes_per_line * height,
+            colorspace: V4L2_COLORSPACE_SRGB,
+            ..PixFormat::default()
+        };
+        let mut format = Format {
+            buf_type: V4L2_BUF_TYPE_VIDEO_OUTPUT,
+            fmt: FormatUnion { raw_data: [0; 200] },
+        };
+        format.fmt.pix = pix;
+        // Safe as the format structure matches the kernel's layout, and the kernel only accesses
+        // it for the duration of the call.
+        unsafe { ioctl::vidioc_s_fmt(self.device.as_raw_fd(), &mut format) }
+            .context("Unable to set the V4L2 device format")?;
+        debug!(
+            width = device_width,
+            height,
+            pixel_format = ?self.pixel_format,
+            "Configured V4L2 output format"
+        );
+        self.frame_size = Some((width, height));
+        Ok(())
+    }
+
+    /// Write a single frame to the device.
+    pub(crate) fn write_frame(&mut self, image: &BytesImage) -> anyhow::Result<()> {
+        let size = image.dimensions();
+        match self.frame_size {
+            None => self.set_format(size.0, size.1)?,
+            Some(frame_size) if frame_size != size => {
+                // The frame size changes when the settings are reloaded.
+                info!(
+                    previous = ?frame_size,
+                    new = ?size,
+                    "Frame size changed, reconfiguring the V4L2 output"
+                );
+                self.set_format(size.0, size.1)?;
+            }
+            Some(_) => (),
+        }
+        self.buffer.clear();
+        match self.pixel_format {
+            V4l2PixelFormat::Yuyv => rgba_to_yuyv(image, &mut self.buffer),
+            V4l2PixelFormat::Rgb24 => self.buffer.extend(
+                image
+                    .pixels()
+                    .flat_map(|pixel| [pixel[0], pixel[1], pixel[2]]),
+            ),
+        }
+        self.device
+            .write_all(&self.buffer)
+            .map_err(|err| anyhow!("Unable to write frame to V4L2 device: {}", err))
+    }
+}
+
+/// Convert an RGB color to limited range BT.601 YCbCr.
+fn rgb_to_ycbcr(red: u8, green: u8, blue: u8) -> (f32, f32, f32) {
+    let (red, green, blue) = (red as f32, green as f32, blue as f32);
+    let luma = 16.0 + (65.481 * red + 128.553 * green + 24.966 * blue) / 255.0;
+    let blue_diff = 128.0 + (-37.797 * red - 74.203 * green + 112.0 * blue) / 255.0;
+    let red_diff = 128.0 + (112.0 * red - 93.786 * green - 18.214 * blue) / 255.0;
+    (luma, blue_diff, red_diff)
+}
+
+/// Convert an RGBA image to packed YUYV (4:2:2), appending to the given buffer.
+///
+/// The chroma for each pair of pixels is averaged. If the width is odd, the last pixel is paired
+/// with itself.
+fn rgba_to_yuyv(image: &BytesImage, buffer: &mut Vec<u8>) {
+    let width = image.width();
+    for y in 0..image.height() {
+        for x in (0..width).step_by(2) {
+            let left = image.get_pixel(x, y);
+            let right = image.get_pixel((x + 1).min(width - 1), y);
+            let (left_luma, left_blue, left_red) = rgb_to_ycbcr(left[0], left[1], left[2]);
+            let (right_luma, right_blue, right_red) = rgb_to_ycbcr(right[0], right[1], right[2]);
+            buffer.extend([
+                left_luma.round() as u8,
+                ((left_blue + right_blue) / 2.0).round() as u8,
+                right_luma.round() as u8,
+                ((left_red + right_red) / 2.0).round() as u8,
+            ]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+    use image::ImageBuffer;
+
+    use super::{fourcc, rgba_to_yuyv, Format};
+    use crate::image_buffer::BytesImage;
+    use crate::stream::settings::V4l2PixelFormat;
+
+    #[test]
+    fn format_size() {
+        let expected = if cfg!(target_pointer_width = "64") {
+            208
+        } else {
+            204
+        };
+        assert_eq!(std::mem::size_of::<Format>(), expected);
+    }
+
+    #[test]
+    fn fourcc_order() {
+        // From videodev2.h
+        assert_eq!(fourcc(b"YUYV"), 0x5659_5559);
+    }
+
+    #[test]
+    fn yuyv_conversion() {
+        let pixels: Vec<u8> = [
+            [0, 0, 0, 255],
+            [255, 255, 255, 255],
+            [255, 0, 0, 255],
+            [255, 0, 0, 255],
+        ]
+        .concat();
+        let image: BytesImage = ImageBuffer::from_raw(4, 1, Bytes::from(pixels)).unwrap();
+        let mut buffer = Vec::new();
+        rgba_to_yuyv(&image, &mut buffer);
+        // Black and white are the limits of the luma range, with neutral chroma.
+        assert_eq!(buffer[..4], [16, 128, 235, 128]);
+        // Pure red
+        assert_eq!(buffer[4..], [81, 90, 81, 240]);
+    }
+
+    #[test]
+    fn yuyv_odd_width() {
+        let image: BytesImage =
+            ImageBuffer::from_raw(3, 2, Bytes::from(vec![255; 3 * 2 * 4])).unwrap();
+        let mut buffer = Vec::new();
+        rgba_to_yuyv(&image, &mut buffer);
+        // The device format is rounded up to an even width, so the frame fills it exactly.
+        let format = V4l2PixelFormat::Yuyv;
+        let device_width = format.device_width(image.width());
+        assert_eq!(device_width, 4);
+        assert_eq!(
+            buffer.len() as u32,
+            format.bytes_per_line(device_width) * image.height()
+        );
+        assert_eq!(V4l2PixelFormat::Rgb24.device_width(3), 3);
+    }
+}
@@ -0,0 +1,19 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+use anyhow::anyhow;
+use bytes::Bytes;
+use tracing::trace;
+
+use crate::image_buffer::BytesImage;
+
+/// The same quality as is used for JPEG encoding.
+const WEBP_QUALITY: f32 = 75.0;
+
+pub(crate) fn encode_webp(image: &BytesImage) -> anyhow::Result<Bytes> {
+    trace!("encoding WebP image");
+    // BytesImage is defined to be RGBA.
+    let encoder = ::webp::Encoder::from_rgba(image, image.width(), image.height());
+    let encoded = encoder
+        .encode_simple(false, WEBP_QUALITY)
+        .map_err(|err| anyhow!("Error encoding WebP image: {:?}", err))?;
+    Ok(Bytes::copy_from_slice(&encoded))
+}
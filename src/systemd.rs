@@ -0,0 +1,103 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//! Notifications to systemd about the state of the service.
+//!
+//! This is a minimal implementation of the [`sd_notify`] protocol: a datagram is sent to the Unix
+//! socket given in `NOTIFY_SOCKET` (if it's set).
+//!
+//! [`sd_notify`]: https://www.freedesktop.org/software/systemd/man/sd_notify.html
+use std::env;
+use std::ffi::OsStr;
+use std::io;
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::time::Duration;
+
+/// Send a state string (like `READY=1`) to systemd.
+///
+/// Returns `false` if systemd isn't listening for notifications.
+pub(crate) fn notify(state: &str) -> io::Result<bool> {
+    let socket_path = match env::var_os("NOTIFY_SOCKET") {
+        Some(path) if !path.is_empty() => path,
+        _ => return Ok(false),
+    };
+    let address = socket_address(&socket_path)?;
+    let socket = UnixDatagram::unbound()?;
+    socket.send_to_addr(state.as_bytes(), &address)?;
+    Ok(true)
+}
+
+/// Convert the value of `NOTIFY_SOCKET` to a socket address.
+///
+/// Names starting with `@` are in the abstract namespace, everything else is a path.
+fn socket_address(socket_path: &OsStr) -> io::Result<SocketAddr> {
+    match socket_path.as_bytes().split_first() {
+        Some((b'@', name)) => SocketAddr::from_abstract_name(name),
+        _ => SocketAddr::from_pathname(socket_path),
+    }
+}
+
+/// How often systemd expects to be pinged with `WATCHDOG=1`, if the watchdog is enabled for this
+/// process.
+pub(crate) fn watchdog_interval() -> Option<Duration> {
+    parse_watchdog(
+        env::var("WATCHDOG_USEC").ok().as_deref(),
+        env::var("WATCHDOG_PID").ok().as_deref(),
+        std::process::id(),
+    )
+}
+
+fn parse_watchdog(usec: Option<&str>, pid: Option<&str>, own_pid: u32) -> Option<Duration> {
+    // WATCHDOG_PID is optional, but if it's set the watchdog is only for that process.
+    if let Some(pid) = pid {
+        if pid.parse::<u32>().ok()? != own_pid {
+            return None;
+        }
+    }
+    let usec: u64 = usec?.parse().ok()?;
+    if usec == 0 {
+        None
+    } else {
+        Some(Duration::from_micros(usec))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::ffi::OsStr;
+    use std::os::linux::net::SocketAddrExt;
+    use std::path::Path;
+    use std::time::Duration;
+
+    use super::{parse_watchdog, socket_address};
+
+    #[test]
+    fn watchdog() {
+        assert_eq!(
+            parse_watchdog(Some("5000000"), None, 42),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(
+            parse_watchdog(Some("5000000"), Some("42"), 42),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(parse_watchdog(Some("5000000"), Some("7"), 42), None);
+        assert_eq!(parse_watchdog(Some("0"), None, 42), None);
+        assert_eq!(parse_watchdog(Some("soon"), None, 42), None);
+        assert_eq!(parse_watchdog(None, None, 42), None);
+    }
+
+    #[test]
+    fn address() {
+        let address = socket_address(OsStr::new("/run/systemd/notify")).unwrap();
+        assert_eq!(
+            address.as_pathname(),
+            Some(Path::new("/run/systemd/notify"))
+        );
+        let address = socket_address(OsStr::new("@/org/freedesktop/systemd1/notify")).unwrap();
+        assert_eq!(
+            address.as_abstract_name(),
+            Some(&b"/org/freedesktop/systemd1/notify"[..])
+        );
+    }
+}
@@ -5,15 +5,19 @@ use std::convert::Into;
 use std::fmt;
 use std::hash::Hash;
 use std::mem::discriminant;
+use std::ops::{Add, AddAssign, Sub, SubAssign};
 use std::str::FromStr;
 
 use num_traits::Float;
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::mqtt::{home_assistant as hass, DiscoveryValue};
 use crate::util::{Average, AverageMut};
 
-#[derive(Clone, Copy, Debug, Deserialize, Hash, PartialEq, Eq, Serialize)]
+#[derive(Clone, Copy, Debug, Deserialize, JsonSchema, Hash, PartialEq, Eq, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum TemperatureUnit {
     #[serde(alias = "C")]
@@ -167,6 +171,25 @@ where
 
 impl<T> cmp::Eq for Temperature<T> where T: Float {}
 
+impl<T> cmp::PartialOrd<Self> for Temperature<T>
+where
+    T: Float,
+{
+    /// Temperatures are ordered by their value in Celsius, so temperatures in different units can
+    /// be compared directly.
+    ///
+    /// As temperatures in different units are never equal (see [PartialEq]), the same temperature
+    /// in different units is ordered with Celsius first.
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        let is_fahrenheit = |temperature: &Self| matches!(temperature, Self::Fahrenheit(_));
+        Some(
+            self.in_celsius()
+                .partial_cmp(&other.in_celsius())?
+                .then_with(|| is_fahrenheit(self).cmp(&is_fahrenheit(other))),
+        )
+    }
+}
+
 impl<T> Hash for Temperature<T>
 where
     T: Float,
@@ -237,6 +260,104 @@ where
     }
 }
 
+/// The difference between two [`Temperature`]s.
+///
+/// There's no offset when converting a difference between units, only a change in scale, so a
+/// 10°C difference is an 18°F difference.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TemperatureDelta<T = f32>
+where
+    T: Float,
+{
+    Celsius(T),
+    Fahrenheit(T),
+}
+
+impl<T> TemperatureDelta<T>
+where
+    T: Float,
+{
+    fn new(unit: TemperatureUnit, value: T) -> Self {
+        match unit {
+            TemperatureUnit::Celsius => Self::Celsius(value),
+            TemperatureUnit::Fahrenheit => Self::Fahrenheit(value),
+        }
+    }
+
+    pub fn value(&self) -> T {
+        match self {
+            Self::Celsius(c) => *c,
+            Self::Fahrenheit(f) => *f,
+        }
+    }
+
+    /// Get this difference in the unit specified.
+    pub fn in_unit(&self, unit: &TemperatureUnit) -> T {
+        let scale = T::from(1.8).expect("1.8 to be able to be represented by a float");
+        match (self, unit) {
+            (Self::Celsius(c), TemperatureUnit::Fahrenheit) => *c * scale,
+            (Self::Fahrenheit(f), TemperatureUnit::Celsius) => *f / scale,
+            _ => self.value(),
+        }
+    }
+}
+
+/// Subtracting two temperatures gives the difference between them, in the units of the left hand
+/// side.
+impl<T> Sub for Temperature<T>
+where
+    T: Float,
+{
+    type Output = TemperatureDelta<T>;
+
+    fn sub(self, rhs: Self) -> TemperatureDelta<T> {
+        TemperatureDelta::new(self.unit(), self.value() - rhs.in_unit(&self.unit()))
+    }
+}
+
+/// A difference can be added to a temperature, with the result in the units of the temperature.
+impl<T> Add<TemperatureDelta<T>> for Temperature<T>
+where
+    T: Float,
+{
+    type Output = Self;
+
+    fn add(self, rhs: TemperatureDelta<T>) -> Self {
+        Self::new(self.unit(), self.value() + rhs.in_unit(&self.unit()))
+    }
+}
+
+/// A difference can be subtracted from a temperature, with the result in the units of the
+/// temperature.
+impl<T> Sub<TemperatureDelta<T>> for Temperature<T>
+where
+    T: Float,
+{
+    type Output = Self;
+
+    fn sub(self, rhs: TemperatureDelta<T>) -> Self {
+        Self::new(self.unit(), self.value() - rhs.in_unit(&self.unit()))
+    }
+}
+
+impl<T> AddAssign<TemperatureDelta<T>> for Temperature<T>
+where
+    T: Float,
+{
+    fn add_assign(&mut self, rhs: TemperatureDelta<T>) {
+        *self = *self + rhs;
+    }
+}
+
+impl<T> SubAssign<TemperatureDelta<T>> for Temperature<T>
+where
+    T: Float,
+{
+    fn sub_assign(&mut self, rhs: TemperatureDelta<T>) {
+        *self = *self - rhs;
+    }
+}
+
 impl<T, Div> Average<Div> for Temperature<T>
 where
     T: Float,
@@ -265,19 +386,17 @@ where
     Div: Into<T> + Copy,
 {
     fn add_assign(&mut self, rhs: &Self) {
-        let new_value = self.value() + rhs.in_unit(&self.unit());
-        *self = Self::new(self.unit(), new_value)
+        *self = Average::<Div>::add(self, rhs);
     }
 
     fn sub_assign(&mut self, rhs: &Self) {
-        let new_value = self.value() - rhs.in_unit(&self.unit());
-        *self = Self::new(self.unit(), new_value)
+        *self = Average::<Div>::sub(self, rhs);
     }
 }
 
 // This little dance is to avoid manually implementing Deserialize on Temperature ourselves so that
 // it can accept either a raw number or a map of a unit to a number.
-#[derive(Copy, Clone, Debug, Deserialize)]
+#[derive(Copy, Clone, Debug, Deserialize, JsonSchema)]
 #[serde(untagged)]
 enum UntaggedTemperature<T>
 where
@@ -287,7 +406,7 @@ where
     Wrapped(TaggedTemperature<T>),
 }
 
-#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[derive(Clone, Copy, Debug, Deserialize, JsonSchema, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum TaggedTemperature<T = f32>
 where
@@ -300,6 +419,21 @@ where
     Fahrenheit(T),
 }
 
+impl<T> JsonSchema for Temperature<T>
+where
+    T: Float + JsonSchema,
+{
+    fn schema_name() -> String {
+        format!("Temperature_for_{}", T::schema_name())
+    }
+
+    /// Temperatures are deserialized through [`UntaggedTemperature`], so they have the same
+    /// schema: either a number (in Celsius) or a map of the unit to a number.
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        UntaggedTemperature::<T>::json_schema(gen)
+    }
+}
+
 impl<T> From<UntaggedTemperature<T>> for Temperature<T>
 where
     T: Float,
@@ -343,7 +477,7 @@ mod test {
 
     use crate::temperature::TemperatureUnit;
 
-    use super::Temperature;
+    use super::{Temperature, TemperatureDelta};
     use float_cmp::{assert_approx_eq, F32Margin};
 
     #[test]
@@ -398,6 +532,57 @@ mod test {
         );
     }
 
+    #[test]
+    fn ordering() {
+        assert!(Temperature::Celsius(20.0) < Temperature::Celsius(21.0));
+        assert!(Temperature::Fahrenheit(70.0) < Temperature::Celsius(22.0));
+        assert!(Temperature::Celsius(20.0) < Temperature::Fahrenheit(69.0));
+        assert!(Temperature::Fahrenheit(-40.0) <= Temperature::Fahrenheit(-40.0));
+    }
+
+    #[test]
+    fn ordering_consistent_with_eq() {
+        let celsius = Temperature::Celsius(-40.0f32);
+        let fahrenheit = Temperature::Fahrenheit(-40.0f32);
+        assert_ne!(celsius, fahrenheit);
+        assert!(celsius < fahrenheit);
+        assert!(fahrenheit > celsius);
+        assert_eq!(
+            celsius.partial_cmp(&celsius),
+            Some(std::cmp::Ordering::Equal)
+        );
+    }
+
+    #[test]
+    fn difference() {
+        let difference = Temperature::Fahrenheit(212.0f32) - Temperature::Celsius(100.0);
+        assert!(matches!(difference, TemperatureDelta::Fahrenheit(_)));
+        assert_approx_eq!(f32, difference.value(), 0.0, F32Margin::default());
+        let difference = Temperature::Celsius(30.0f32) - Temperature::Fahrenheit(50.0);
+        assert_eq!(difference, TemperatureDelta::Celsius(20.0));
+        // Differences are only scaled when converted, not offset.
+        assert_approx_eq!(
+            f32,
+            difference.in_unit(&TemperatureUnit::Fahrenheit),
+            36.0,
+            F32Margin::default()
+        );
+    }
+
+    #[test]
+    fn add_difference() {
+        let sum = Temperature::Celsius(10.0f32) + TemperatureDelta::Fahrenheit(18.0);
+        assert_eq!(sum.unit(), TemperatureUnit::Celsius);
+        assert_approx_eq!(f32, sum.value(), 20.0, F32Margin::default());
+        let mut temperature = Temperature::Fahrenheit(32.0f32);
+        temperature += TemperatureDelta::Celsius(10.0);
+        assert_approx_eq!(f32, temperature.value(), 50.0, F32Margin::default());
+        temperature -= TemperatureDelta::Celsius(5.0);
+        assert_approx_eq!(f32, temperature.value(), 41.0, F32Margin::default());
+        let lower = Temperature::Celsius(20.0f32) - TemperatureDelta::Celsius(5.0);
+        assert_eq!(lower, Temperature::Celsius(15.0));
+    }
+
     #[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
     struct TemperatureTest<T>
     where
@@ -0,0 +1,10 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+mod settings;
+#[cfg(feature = "upload")]
+mod sigv4;
+#[cfg(feature = "upload")]
+mod uploader;
+
+pub(crate) use settings::UploadSettings;
+#[cfg(feature = "upload")]
+pub(crate) use uploader::Uploader;
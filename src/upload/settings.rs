@@ -0,0 +1,212 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+use std::num::NonZeroUsize;
+use std::time::Duration;
+
+use schemars::JsonSchema;
+use serde::{de, Deserialize, Deserializer, Serialize};
+use url::Url;
+
+use crate::mqtt::ExternalValue;
+
+/// Settings for uploading recorded camera data to a remote server.
+#[derive(Clone, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
+pub(crate) struct UploadSettings {
+    /// Whether or not uploading is enabled.
+    #[serde(default)]
+    pub(crate) enabled: bool,
+
+    /// The URL objects are uploaded under.
+    ///
+    /// Each chunk of recorded data is uploaded with an HTTP `PUT` to a new object under this URL.
+    /// For S3-compatible stores, this should be a path-style URL including the bucket (and an
+    /// optional prefix).
+    pub(crate) url: Url,
+
+    /// How to authenticate with the server.
+    #[serde(default)]
+    pub(crate) auth: UploadAuth,
+
+    /// The maximum time between uploads, in seconds. It must be greater than 0.
+    #[schemars(with = "u64")]
+    #[serde(
+        default = "UploadSettings::default_interval",
+        serialize_with = "serde_with::As::<serde_with::DurationSeconds<u64>>::serialize",
+        deserialize_with = "non_zero_seconds"
+    )]
+    pub(crate) interval: Duration,
+
+    /// The maximum number of frames in a single upload.
+    #[serde(default = "UploadSettings::default_chunk_size")]
+    pub(crate) chunk_size: NonZeroUsize,
+
+    /// How many times to retry a failed upload before giving up on that chunk.
+    #[serde(default = "UploadSettings::default_retries")]
+    pub(crate) retries: u32,
+}
+
+/// Ensure a number of seconds isn't 0.
+fn non_zero_seconds<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match u64::deserialize(deserializer)? {
+        0 => Err(de::Error::invalid_value(
+            de::Unexpected::Unsigned(0),
+            &"a number of seconds greater than 0",
+        )),
+        seconds => Ok(Duration::from_secs(seconds)),
+    }
+}
+
+impl UploadSettings {
+    const fn default_interval() -> Duration {
+        // Five minutes
+        Duration::from_secs(60 * 5)
+    }
+
+    fn default_chunk_size() -> NonZeroUsize {
+        // Five minutes at 10 FPS
+        NonZeroUsize::new(3000).unwrap()
+    }
+
+    const fn default_retries() -> u32 {
+        3
+    }
+}
+
+/// The kinds of authentication supported for uploads.
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub(crate) enum UploadAuth {
+    /// No authentication.
+    #[default]
+    None,
+
+    /// HTTP Basic authentication.
+    Basic {
+        username: String,
+        password: ExternalValue,
+    },
+
+    /// An HTTP bearer token.
+    Bearer { token: ExternalValue },
+
+    /// AWS Signature Version 4, as used by S3 and S3-compatible object stores.
+    S3 {
+        access_key: String,
+        secret_key: ExternalValue,
+        #[serde(default = "UploadAuth::default_region")]
+        region: String,
+    },
+}
+
+impl UploadAuth {
+    fn default_region() -> String {
+        "us-east-1".to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::num::NonZeroUsize;
+    use std::time::Duration;
+
+    use super::{UploadAuth, UploadSettings};
+
+    #[test]
+    fn defaults() -> anyhow::Result<()> {
+        let source = r#"
+        url = "https://example.com/recordings"
+        "#;
+        let parsed: UploadSettings = toml::from_str(source)?;
+        let expected = UploadSettings {
+            enabled: false,
+            url: "https://example.com/recordings".parse()?,
+            auth: UploadAuth::None,
+            interval: Duration::from_secs(300),
+            chunk_size: NonZeroUsize::new(3000).unwrap(),
+            retries: 3,
+        };
+        assert_eq!(parsed, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn basic_auth() -> anyhow::Result<()> {
+        let source = r#"
+        enabled = true
+        url = "https://example.com/recordings"
+        auth = { kind = "basic", username = "user", password = "hunter2" }
+        interval = 60
+        chunk_size = 600
+        retries = 0
+        "#;
+        let parsed: UploadSettings = toml::from_str(source)?;
+        let expected = UploadSettings {
+            enabled: true,
+            url: "https://example.com/recordings".parse()?,
+            auth: UploadAuth::Basic {
+                username: "user".to_string(),
+                password: "hunter2".into(),
+            },
+            interval: Duration::from_secs(60),
+            chunk_size: NonZeroUsize::new(600).unwrap(),
+            retries: 0,
+        };
+        assert_eq!(parsed, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn s3_auth() -> anyhow::Result<()> {
+        let source = r#"
+        url = "https://s3.example.com/bucket"
+        [auth]
+        kind = "s3"
+        access_key = "AKIDEXAMPLE"
+        secret_key = "secret"
+        "#;
+        let parsed: UploadSettings = toml::from_str(source)?;
+        assert_eq!(
+            parsed.auth,
+            UploadAuth::S3 {
+                access_key: "AKIDEXAMPLE".to_string(),
+                secret_key: "secret".into(),
+                region: "us-east-1".to_string(),
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn zero_chunk_size() {
+        let source = r#"
+        url = "https://example.com/recordings"
+        chunk_size = 0
+        "#;
+        let parsed: Result<UploadSettings, _> = toml::from_str(source);
+        assert!(parsed.is_err());
+    }
+
+    #[test]
+    fn zero_interval() {
+        let source = r#"
+        url = "https://example.com/recordings"
+        interval = 0
+        "#;
+        let parsed: Result<UploadSettings, _> = toml::from_str(source);
+        assert!(parsed.is_err());
+    }
+
+    #[test]
+    fn serialize_interval() -> anyhow::Result<()> {
+        let source = r#"
+        url = "https://example.com/recordings"
+        interval = 60
+        "#;
+        let parsed: UploadSettings = toml::from_str(source)?;
+        let serialized = toml::Value::try_from(&parsed)?;
+        assert_eq!(serialized.get("interval"), Some(&toml::Value::Integer(60)));
+        Ok(())
+    }
+}
@@ -0,0 +1,200 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Context as _};
+use bytes::Bytes;
+use futures::stream::{Stream, StreamExt};
+use hyper::client::HttpConnector;
+use hyper::{header, Body, Client, Method, Request};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use tokio::time::{sleep, sleep_until, Instant};
+use tracing::{debug, info, warn};
+use url::Url;
+
+use crate::recorded_data::RecordedData;
+
+use super::settings::{UploadAuth, UploadSettings};
+use super::sigv4;
+
+/// Uploads chunks of recorded camera data to a remote server.
+#[derive(Clone)]
+pub(crate) struct Uploader {
+    client: Client<HttpsConnector<HttpConnector>>,
+    settings: Arc<UploadSettings>,
+}
+
+impl Uploader {
+    pub(crate) fn new(settings: UploadSettings) -> Self {
+        let connector = HttpsConnectorBuilder::new()
+            .with_webpki_roots()
+            .https_or_http()
+            .enable_http1()
+            .build();
+        Self {
+            client: Client::builder().build(connector),
+            settings: Arc::new(settings),
+        }
+    }
+
+    /// Collect records into chunks and upload them until the stream ends.
+    ///
+    /// A chunk is uploaded once it has `chunk_size` frames in it, or `interval` has passed since
+    /// the last upload, whichever comes first. Uploads happen in the background so that a slow
+    /// server doesn't hold up the collection of new frames.
+    pub(crate) async fn run<S>(self, records: S) -> anyhow::Result<()>
+    where
+        S: Stream<Item = RecordedData>,
+    {
+        let chunk_size = self.settings.chunk_size.get();
+        let interval = self.settings.interval;
+        let mut records = Box::pin(records);
+        let mut chunk: Vec<RecordedData> = Vec::with_capacity(chunk_size);
+        let mut chunk_start = SystemTime::now();
+        let mut deadline = Instant::now() + interval;
+        loop {
+            let stream_done = tokio::select! {
+                record = records.next() => match record {
+                    Some(record) => {
+                        if chunk.is_empty() {
+                            chunk_start = SystemTime::now();
+                        }
+                        chunk.push(record);
+                        if chunk.len() < chunk_size {
+                            continue;
+                        }
+                        false
+                    }
+                    None => true,
+                },
+                _ = sleep_until(deadline) => false,
+            };
+            deadline = Instant::now() + interval;
+            if !chunk.is_empty() {
+                let body = Bytes::from(RecordedData::to_bincode(&chunk)?);
+                let name = object_name(chunk_start);
+                debug!(frames = chunk.len(), %name, "Uploading recorded data");
+                chunk.clear();
+                tokio::spawn(self.clone().upload(name, body));
+            }
+            if stream_done {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Upload an object, retrying with an increasing delay on failure.
+    async fn upload(self, name: String, body: Bytes) {
+        let url = match self.object_url(&name) {
+            Ok(url) => url,
+            Err(err) => {
+                warn!("Unable to create upload URL: {:?}", err);
+                return;
+            }
+        };
+        for attempt in 0..=self.settings.retries {
+            if attempt > 0 {
+                sleep(Duration::from_secs(1 << attempt.min(6))).await;
+            }
+            match self.put(&url, body.clone()).await {
+                Ok(()) => {
+                    info!(%url, "Uploaded recorded data");
+                    return;
+                }
+                Err(err) => warn!(%url, attempt, "Error uploading recorded data: {:?}", err),
+            }
+        }
+        warn!(%url, "Giving up on uploading recorded data");
+    }
+
+    fn object_url(&self, name: &str) -> anyhow::Result<Url> {
+        let mut url = self.settings.url.clone();
+        url.path_segments_mut()
+            .map_err(|_| anyhow!("The upload URL cannot be used as a base URL"))?
+            .pop_if_empty()
+            .push(name);
+        Ok(url)
+    }
+
+    async fn put(&self, url: &Url, body: Bytes) -> anyhow::Result<()> {
+        let mut request = Request::builder()
+            .method(Method::PUT)
+            .uri(url.as_str())
+            .header(header::CONTENT_TYPE, "application/octet-stream");
+        match &self.settings.auth {
+            UploadAuth::None => (),
+            UploadAuth::Basic { username, password } => {
+                let credentials = base64::encode(format!("{}:{}", username, password.0));
+                request = request.header(header::AUTHORIZATION, format!("Basic {}", credentials));
+            }
+            UploadAuth::Bearer { token } => {
+                request = request.header(header::AUTHORIZATION, format!("Bearer {}", token.0));
+            }
+            UploadAuth::S3 {
+                access_key,
+                secret_key,
+                region,
+            } => {
+                let signed = sigv4::sign_put(
+                    url,
+                    &body,
+                    SystemTime::now(),
+                    access_key,
+                    &secret_key.0,
+                    region,
+                );
+                request = request
+                    .header(header::HOST, sigv4::host_header(url))
+                    .header("x-amz-content-sha256", signed.content_sha256)
+                    .header("x-amz-date", signed.date)
+                    .header(header::AUTHORIZATION, signed.authorization);
+            }
+        }
+        let request = request
+            .body(Body::from(body))
+            .context("Error building upload request")?;
+        let response = self.client.request(request).await?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(anyhow!("Server responded with {}", response.status()))
+        }
+    }
+}
+
+/// The name for an object starting at the given time.
+fn object_name(start: SystemTime) -> String {
+    let millis = start
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis());
+    format!("{}.bin", millis)
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::{object_name, Uploader};
+
+    #[test]
+    fn names() {
+        let start = UNIX_EPOCH + Duration::from_millis(1_440_938_160_123);
+        assert_eq!(object_name(start), "1440938160123.bin");
+    }
+
+    #[tokio::test]
+    async fn object_urls() -> anyhow::Result<()> {
+        for base in &[
+            "https://example.com/recordings",
+            "https://example.com/recordings/",
+        ] {
+            let settings = toml::from_str(&format!("url = \"{}\"", base))?;
+            let uploader = Uploader::new(settings);
+            assert_eq!(
+                uploader.object_url("1.bin")?.as_str(),
+                "https://example.com/recordings/1.bin"
+            );
+        }
+        Ok(())
+    }
+}
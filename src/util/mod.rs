@@ -8,7 +8,7 @@ use std::panic;
 use num_traits::Num;
 use tokio::task::JoinError;
 
-pub use moving_average::{Average, AverageMut, Filter, MovingAverage};
+pub use moving_average::{Average, AverageMut, ExponentialMovingAverage, Filter, MovingAverage};
 pub use stream::StreamExt;
 
 /// Parse an unsigned integer from a base-10 or base-16 string representation.
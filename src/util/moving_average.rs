@@ -1,6 +1,7 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 use std::collections::VecDeque;
 use std::convert;
+use std::num::NonZeroU16;
 use std::ops;
 use std::time::Duration;
 use std::vec::Vec;
@@ -247,34 +248,33 @@ pub trait Filter<T> {
 
 /// A moving average where all samples are weighted identically.
 #[derive(Clone, Debug)]
-pub struct MovingAverage<T, const N: usize> {
+pub struct MovingAverage<T> {
+    /// The number of samples averaged together.
+    window: usize,
     frames: VecDeque<T>,
     // Possibly a premature optimization
     sums: Option<T>,
 }
 
-impl<T, const N: usize> MovingAverage<T, N> {
-    pub fn new() -> Self {
+impl<T> MovingAverage<T> {
+    /// Create a moving average of the last `window` samples.
+    pub fn new(window: NonZeroU16) -> Self {
+        let window = usize::from(window.get());
         Self {
-            frames: VecDeque::with_capacity(N),
+            window,
+            frames: VecDeque::with_capacity(window),
             sums: None,
         }
     }
 }
 
-impl<T, const N: usize> Default for MovingAverage<T, N> {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-impl<T, const N: usize> Filter<T> for MovingAverage<T, N>
+impl<T> Filter<T> for MovingAverage<T>
 where
     T: AverageMut<u16> + Clone,
 {
     fn push(&mut self, new_value: T) {
         // Always check to see if we need to pop first to keep the queue from getting too big
-        if self.frames.len() >= N {
+        if self.frames.len() >= self.window {
             if let Some(old_frame) = self.frames.pop_front() {
                 if let Some(sums) = &mut self.sums {
                     sums.sub_assign(&old_frame);
@@ -291,12 +291,51 @@ where
     }
 
     fn current_value(&self) -> Option<T> {
+        // The window is at most u16::MAX, so the number of frames always fits.
         let num_frames = self.frames.len() as u16;
         self.sums.as_ref().map(|sums| sums.clone().div(&num_frames))
     }
 }
 
-impl<T, const N: usize> PartialEq for MovingAverage<T, N>
+/// An exponentially weighted moving average.
+///
+/// `smoothing` is the weight given to the previous average when a new sample is added, so 0 is no
+/// smoothing at all, and values closer to 1 smooth more (and respond to changes slower).
+#[derive(Clone, Debug)]
+pub struct ExponentialMovingAverage<T> {
+    /// The reciprocal of the weight given to new samples.
+    divisor: f32,
+    average: Option<T>,
+}
+
+impl<T> ExponentialMovingAverage<T> {
+    pub fn new(smoothing: f32) -> Self {
+        Self {
+            divisor: (1.0 - smoothing).recip(),
+            average: None,
+        }
+    }
+}
+
+impl<T> Filter<T> for ExponentialMovingAverage<T>
+where
+    T: Average<f32> + Clone,
+{
+    fn push(&mut self, new_value: T) {
+        let new_average = match self.average.take() {
+            // average + (new - average) * (1 - smoothing)
+            Some(average) => average.add(&new_value.sub(&average).div(&self.divisor)),
+            None => new_value,
+        };
+        self.average = Some(new_average);
+    }
+
+    fn current_value(&self) -> Option<T> {
+        self.average.clone()
+    }
+}
+
+impl<T> PartialEq for MovingAverage<T>
 where
     T: PartialEq,
 {
@@ -305,4 +344,33 @@ where
     }
 }
 
-impl<T, const N: usize> Eq for MovingAverage<T, N> where T: Eq {}
+impl<T> Eq for MovingAverage<T> where T: Eq {}
+
+#[cfg(test)]
+mod test {
+    use float_cmp::assert_approx_eq;
+
+    use super::{ExponentialMovingAverage, Filter};
+
+    #[test]
+    fn exponential_moving_average() {
+        let mut average = ExponentialMovingAverage::new(0.75);
+        assert_eq!(average.current_value(), None);
+        // The first sample is taken as is.
+        assert_approx_eq!(f32, average.update(8.0), 8.0);
+        // Then each new sample only contributes a quarter.
+        assert_approx_eq!(f32, average.update(0.0), 6.0);
+        assert_approx_eq!(f32, average.update(10.0), 7.0);
+        // Containers are averaged element-wise.
+        let mut average = ExponentialMovingAverage::new(0.5);
+        average.push(vec![0.0f32, 4.0]);
+        assert_eq!(average.update(vec![2.0, 0.0]), vec![1.0, 2.0]);
+    }
+
+    #[test]
+    fn no_smoothing() {
+        let mut average = ExponentialMovingAverage::new(0.0);
+        average.push(5.0f32);
+        assert_approx_eq!(f32, average.update(-3.0), -3.0);
+    }
+}
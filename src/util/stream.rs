@@ -1,11 +1,14 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 //! [`Stream`][futures::Stream] extensions.
 use std::marker::PhantomData;
+use std::ops::Sub;
 use std::pin::Pin;
 use std::task::{Context, Poll};
+use std::time::Duration;
 
-use futures::{ready, Stream};
+use futures::{ready, Future, Stream};
 use pin_project::pin_project;
+use tokio::time::{sleep, Sleep};
 
 pub trait StreamExt: Stream {
     fn filter_repeated(self) -> FilterRepeated<Self>
@@ -16,12 +19,63 @@ pub trait StreamExt: Stream {
         FilterRepeated::new(self)
     }
 
+    /// Only yield items whose key has changed by at least `delta` since the last yielded item.
+    ///
+    /// The first item is always yielded. Keys are compared with the key of the last *yielded*
+    /// item, so a slow drift is still passed on once it adds up to `delta`. Items with the same
+    /// key as the last yielded item are never yielded, so with a `delta` of zero this behaves like
+    /// [`filter_repeated`][StreamExt::filter_repeated] (but on the key).
+    fn filter_changed_by<K, F>(self, key: F, delta: K) -> FilterChangedBy<Self, K, F>
+    where
+        Self: Sized,
+        K: Copy + PartialOrd + Sub<Output = K>,
+        F: FnMut(&Self::Item) -> K,
+    {
+        FilterChangedBy::new(self, key, delta)
+    }
+
     fn never_error<E>(self) -> OkStream<Self, E>
     where
         Self: Sized,
     {
         OkStream::new(self)
     }
+
+    /// Only yield the latest item at most once every `interval`.
+    ///
+    /// If nothing has been yielded within the last `interval`, an item is yielded immediately.
+    /// Otherwise items are coalesced, with only the most recent one being yielded once the interval
+    /// has passed.
+    fn latest_every(self, interval: Duration) -> LatestEvery<Self>
+    where
+        Self: Sized,
+    {
+        LatestEvery::new(self, interval)
+    }
+
+    /// Yield the most recent item again if no new item has been yielded within `interval`.
+    fn repeat_after(self, interval: Duration) -> RepeatAfter<Self>
+    where
+        Self: Sized,
+        Self::Item: Clone,
+    {
+        RepeatAfter::new(self, interval)
+    }
+
+    /// Only yield an item once it has been repeated for a period of time.
+    ///
+    /// The first item is yielded immediately. After that, an item that differs from the last
+    /// yielded item is only yielded once no other value has been seen for `delay(&item)`. If the
+    /// stream goes back to the last yielded value before then, nothing is yielded. Repeated items
+    /// are never yielded.
+    fn debounce<F>(self, delay: F) -> Debounce<Self, F>
+    where
+        Self: Sized,
+        Self::Item: PartialEq + Clone,
+        F: FnMut(&Self::Item) -> Duration,
+    {
+        Debounce::new(self, delay)
+    }
 }
 
 impl<St: Stream> StreamExt for St {}
@@ -69,6 +123,61 @@ where
     }
 }
 
+#[pin_project]
+#[derive(Debug)]
+pub struct FilterChangedBy<St, K, F> {
+    #[pin]
+    stream: St,
+    key: F,
+    delta: K,
+    last_key: Option<K>,
+}
+
+impl<St, K, F> FilterChangedBy<St, K, F> {
+    fn new(stream: St, key: F, delta: K) -> Self {
+        Self {
+            stream,
+            key,
+            delta,
+            last_key: None,
+        }
+    }
+}
+
+impl<St, K, F> Stream for FilterChangedBy<St, K, F>
+where
+    St: Stream,
+    K: Copy + PartialOrd + Sub<Output = K>,
+    F: FnMut(&St::Item) -> K,
+{
+    type Item = St::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        Poll::Ready(loop {
+            match ready!(this.stream.as_mut().poll_next(cx)) {
+                Some(next_item) => {
+                    let next_key = (this.key)(&next_item);
+                    let changed = this.last_key.is_none_or(|last_key| {
+                        // Subtracting in this order keeps unsigned keys from underflowing.
+                        let difference = if next_key > last_key {
+                            next_key - last_key
+                        } else {
+                            last_key - next_key
+                        };
+                        next_key != last_key && difference >= *this.delta
+                    });
+                    if changed {
+                        *this.last_key = Some(next_key);
+                        break Some(next_item);
+                    }
+                }
+                None => break None,
+            }
+        })
+    }
+}
+
 #[pin_project]
 #[derive(Debug)]
 pub struct OkStream<St: Stream, E> {
@@ -94,11 +203,194 @@ impl<St: Stream, E> Stream for OkStream<St, E> {
     }
 }
 
+#[pin_project]
+#[derive(Debug)]
+pub struct LatestEvery<St: Stream> {
+    #[pin]
+    stream: St,
+    #[pin]
+    delay: Option<Sleep>,
+    interval: Duration,
+    latest: Option<St::Item>,
+    stream_done: bool,
+}
+
+impl<St: Stream> LatestEvery<St> {
+    fn new(stream: St, interval: Duration) -> Self {
+        Self {
+            stream,
+            delay: None,
+            interval,
+            latest: None,
+            stream_done: false,
+        }
+    }
+}
+
+impl<St: Stream> Stream for LatestEvery<St> {
+    type Item = St::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        // Pull in everything that's ready, keeping only the newest item.
+        while !*this.stream_done {
+            match this.stream.as_mut().poll_next(cx) {
+                Poll::Ready(Some(item)) => *this.latest = Some(item),
+                Poll::Ready(None) => *this.stream_done = true,
+                Poll::Pending => break,
+            }
+        }
+        if this.latest.is_none() {
+            return if *this.stream_done {
+                Poll::Ready(None)
+            } else {
+                Poll::Pending
+            };
+        }
+        if let Some(delay) = this.delay.as_mut().as_pin_mut() {
+            ready!(delay.poll(cx));
+        }
+        this.delay.set(Some(sleep(*this.interval)));
+        Poll::Ready(this.latest.take())
+    }
+}
+
+#[pin_project]
+#[derive(Debug)]
+pub struct RepeatAfter<St: Stream> {
+    #[pin]
+    stream: St,
+    #[pin]
+    delay: Option<Sleep>,
+    interval: Duration,
+    latest: Option<St::Item>,
+}
+
+impl<St: Stream> RepeatAfter<St> {
+    fn new(stream: St, interval: Duration) -> Self {
+        Self {
+            stream,
+            delay: None,
+            interval,
+            latest: None,
+        }
+    }
+}
+
+impl<St> Stream for RepeatAfter<St>
+where
+    St: Stream,
+    St::Item: Clone,
+{
+    type Item = St::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        match this.stream.poll_next(cx) {
+            Poll::Ready(Some(item)) => {
+                *this.latest = Some(item.clone());
+                this.delay.set(Some(sleep(*this.interval)));
+                return Poll::Ready(Some(item));
+            }
+            Poll::Ready(None) => return Poll::Ready(None),
+            Poll::Pending => (),
+        }
+        // There's only a delay once an item has been seen.
+        match this.delay.as_mut().as_pin_mut() {
+            None => Poll::Pending,
+            Some(delay) => {
+                ready!(delay.poll(cx));
+                this.delay.set(Some(sleep(*this.interval)));
+                Poll::Ready(this.latest.clone())
+            }
+        }
+    }
+}
+
+#[pin_project]
+#[derive(Debug)]
+pub struct Debounce<St: Stream, F> {
+    #[pin]
+    stream: St,
+    #[pin]
+    timer: Option<Sleep>,
+    delay: F,
+    current: Option<St::Item>,
+    pending: Option<St::Item>,
+    stream_done: bool,
+}
+
+impl<St: Stream, F> Debounce<St, F> {
+    fn new(stream: St, delay: F) -> Self {
+        Self {
+            stream,
+            timer: None,
+            delay,
+            current: None,
+            pending: None,
+            stream_done: false,
+        }
+    }
+}
+
+impl<St, F> Stream for Debounce<St, F>
+where
+    St: Stream,
+    St::Item: PartialEq + Clone,
+    F: FnMut(&St::Item) -> Duration,
+{
+    type Item = St::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        while !*this.stream_done {
+            match this.stream.as_mut().poll_next(cx) {
+                Poll::Ready(Some(item)) => {
+                    if this.current.as_ref() == Some(&item) {
+                        // Back to the current value before the delay passed
+                        *this.pending = None;
+                        this.timer.set(None);
+                    } else if this.pending.as_ref() != Some(&item) {
+                        let delay = if this.current.is_some() {
+                            (this.delay)(&item)
+                        } else {
+                            Duration::ZERO
+                        };
+                        if delay.is_zero() {
+                            *this.pending = None;
+                            this.timer.set(None);
+                            *this.current = Some(item.clone());
+                            return Poll::Ready(Some(item));
+                        }
+                        *this.pending = Some(item);
+                        this.timer.set(Some(sleep(delay)));
+                    }
+                }
+                Poll::Ready(None) => *this.stream_done = true,
+                Poll::Pending => break,
+            }
+        }
+        match this.timer.as_mut().as_pin_mut() {
+            Some(timer) => {
+                ready!(timer.poll(cx));
+                this.timer.set(None);
+                *this.current = this.pending.take();
+                Poll::Ready(this.current.clone())
+            }
+            None if *this.stream_done => Poll::Ready(None),
+            None => Poll::Pending,
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::convert::Infallible;
+    use std::time::Duration;
 
     use futures::stream::{self, StreamExt as _};
+    use futures::FutureExt;
+    use tokio::time::Instant;
 
     use super::StreamExt;
 
@@ -126,6 +418,45 @@ mod test {
         assert_eq!(v, vec![0, 1, 0, 1]);
     }
 
+    /// Ensure the first item is passed through, no matter what it is.
+    #[tokio::test]
+    async fn filter_changed_by_first_item() {
+        let s = stream::iter([f32::MAX]);
+        let v = s.filter_changed_by(|t| *t, 0.5).collect::<Vec<_>>().await;
+        assert_eq!(v, vec![f32::MAX]);
+    }
+
+    /// Ensure small changes are filtered out, but a slow drift still adds up.
+    #[tokio::test]
+    async fn filter_changed_by_monotonic_drift() {
+        let s = stream::iter(0..10u32);
+        let v = s.filter_changed_by(|n| *n, 3).collect::<Vec<_>>().await;
+        assert_eq!(v, vec![0, 3, 6, 9]);
+        let s = stream::iter((0..10u32).rev());
+        let v = s.filter_changed_by(|n| *n, 3).collect::<Vec<_>>().await;
+        assert_eq!(v, vec![9, 6, 3, 0]);
+    }
+
+    /// Ensure a spike is passed through, as is the return to the original value.
+    #[tokio::test]
+    async fn filter_changed_by_spike() {
+        let s = stream::iter([20.0, 20.05, 25.0, 20.05, 19.95]);
+        let v = s.filter_changed_by(|t| *t, 0.25).collect::<Vec<_>>().await;
+        assert_eq!(v, vec![20.0, 25.0, 20.05]);
+    }
+
+    /// Ensure a delta of zero only filters out repeated keys.
+    #[tokio::test]
+    async fn filter_changed_by_zero() {
+        let s = stream::iter([(1, 'a'), (1, 'b'), (2, 'c'), (2, 'd'), (1, 'e')]);
+        let v = s
+            .filter_changed_by(|(n, _)| *n, 0)
+            .map(|(_, c)| c)
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(v, vec!['a', 'c', 'e']);
+    }
+
     #[tokio::test]
     async fn never_error() {
         let st = stream::iter(0..5);
@@ -138,4 +469,156 @@ mod test {
             assert_eq!(actual.unwrap(), expected);
         }
     }
+
+    /// Ensure that a burst of items is coalesced into just the latest item.
+    #[tokio::test]
+    async fn latest_every_coalesces() {
+        let st = stream::iter(0..5);
+        let v = st
+            .latest_every(Duration::from_millis(100))
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(v, vec![4]);
+    }
+
+    /// Ensure that items are spaced out by at least the interval.
+    #[tokio::test]
+    async fn latest_every_interval() {
+        let interval = Duration::from_millis(200);
+        let st = stream::iter(0..3).then(|n| async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            n
+        });
+        let start = Instant::now();
+        let times = st
+            .latest_every(interval)
+            .map(|n| (n, start.elapsed()))
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(times.len(), 2);
+        assert_eq!(times[0].0, 0);
+        assert_eq!(times[1].0, 2);
+        assert!(times[1].1 - times[0].1 >= interval);
+    }
+
+    /// Create a stream of the items sent to the returned sender.
+    fn channel<T>() -> (
+        tokio::sync::mpsc::UnboundedSender<T>,
+        tokio_stream::wrappers::UnboundedReceiverStream<T>,
+    ) {
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        (
+            sender,
+            tokio_stream::wrappers::UnboundedReceiverStream::new(receiver),
+        )
+    }
+
+    /// Ensure the latest item is repeated while waiting for a new one.
+    #[tokio::test(start_paused = true)]
+    async fn repeat_after() {
+        let (sender, st) = channel();
+        let st = st.repeat_after(Duration::from_millis(100));
+        tokio::pin!(st);
+        sender.send(0).unwrap();
+        assert_eq!(st.next().now_or_never(), Some(Some(0)));
+        tokio::time::advance(Duration::from_millis(99)).await;
+        assert_eq!(st.next().now_or_never(), None);
+        tokio::time::advance(Duration::from_millis(1)).await;
+        assert_eq!(st.next().now_or_never(), Some(Some(0)));
+        tokio::time::advance(Duration::from_millis(100)).await;
+        assert_eq!(st.next().now_or_never(), Some(Some(0)));
+        // A new item resets the timer.
+        tokio::time::advance(Duration::from_millis(50)).await;
+        sender.send(1).unwrap();
+        assert_eq!(st.next().now_or_never(), Some(Some(1)));
+        tokio::time::advance(Duration::from_millis(99)).await;
+        assert_eq!(st.next().now_or_never(), None);
+        drop(sender);
+        assert_eq!(st.next().now_or_never(), Some(None));
+    }
+
+    /// Ensure nothing is repeated before the first item.
+    #[tokio::test(start_paused = true)]
+    async fn repeat_after_empty() {
+        let (sender, st) = channel::<u32>();
+        let st = st.repeat_after(Duration::from_millis(100));
+        tokio::pin!(st);
+        assert_eq!(st.next().now_or_never(), None);
+        tokio::time::advance(Duration::from_millis(250)).await;
+        assert_eq!(st.next().now_or_never(), None);
+        drop(sender);
+        assert_eq!(st.next().now_or_never(), Some(None));
+    }
+
+    /// Ensure a brief change is ignored, but a longer one is passed on.
+    #[tokio::test(start_paused = true)]
+    async fn debounce() {
+        let (sender, st) = channel();
+        let st = st.debounce(|_| Duration::from_millis(200));
+        tokio::pin!(st);
+        sender.send(false).unwrap();
+        assert_eq!(st.next().now_or_never(), Some(Some(false)));
+        sender.send(true).unwrap();
+        assert_eq!(st.next().now_or_never(), None);
+        tokio::time::advance(Duration::from_millis(50)).await;
+        // Going back to the last value cancels the change.
+        sender.send(false).unwrap();
+        assert_eq!(st.next().now_or_never(), None);
+        tokio::time::advance(Duration::from_millis(50)).await;
+        sender.send(true).unwrap();
+        assert_eq!(st.next().now_or_never(), None);
+        tokio::time::advance(Duration::from_millis(199)).await;
+        assert_eq!(st.next().now_or_never(), None);
+        tokio::time::advance(Duration::from_millis(1)).await;
+        assert_eq!(st.next().now_or_never(), Some(Some(true)));
+        // Repeats aren't passed on.
+        sender.send(true).unwrap();
+        drop(sender);
+        assert_eq!(st.next().await, None);
+    }
+
+    /// Ensure each value can have its own delay.
+    #[tokio::test(start_paused = true)]
+    async fn debounce_asymmetric() {
+        let (sender, st) = channel();
+        let st = st.debounce(|occupied| {
+            if *occupied {
+                Duration::ZERO
+            } else {
+                Duration::from_millis(200)
+            }
+        });
+        tokio::pin!(st);
+        sender.send(true).unwrap();
+        assert_eq!(st.next().now_or_never(), Some(Some(true)));
+        sender.send(false).unwrap();
+        assert_eq!(st.next().now_or_never(), None);
+        tokio::time::advance(Duration::from_millis(100)).await;
+        assert_eq!(st.next().now_or_never(), None);
+        sender.send(true).unwrap();
+        assert_eq!(st.next().now_or_never(), None);
+        // Changing to `true` has no delay.
+        sender.send(false).unwrap();
+        assert_eq!(st.next().now_or_never(), None);
+        tokio::time::advance(Duration::from_millis(200)).await;
+        assert_eq!(st.next().now_or_never(), Some(Some(false)));
+        sender.send(true).unwrap();
+        assert_eq!(st.next().now_or_never(), Some(Some(true)));
+    }
+
+    /// Ensure a pending item is still yielded after the stream ends.
+    #[tokio::test(start_paused = true)]
+    async fn debounce_pending_at_end() {
+        let (sender, st) = channel();
+        let st = st.debounce(|_| Duration::from_millis(50));
+        tokio::pin!(st);
+        sender.send(false).unwrap();
+        sender.send(true).unwrap();
+        drop(sender);
+        assert_eq!(st.next().now_or_never(), Some(Some(false)));
+        assert_eq!(st.next().now_or_never(), None);
+        tokio::time::advance(Duration::from_millis(50)).await;
+        assert_eq!(st.next().now_or_never(), Some(Some(true)));
+        assert_eq!(st.next().now_or_never(), Some(None));
+    }
 }